@@ -0,0 +1,308 @@
+//! Throughput of `get` and `put` per eviction policy, for `Cache` and for `AsyncCache` persisting to an
+//! in-memory `AOF`, of a get-heavy workload with the default and a fast hasher, of reading a large hot value cloned or shared through `Arc`, of persisting large values as
+//! `Vec<u8>` or shared `Bytes`, of preloading entries with `warm_from_snapshot`, of compressing the large values of `AOF` with `compress_values_over` and,
+//! with the `mmap` feature, of appending to `AOF` against `MmapAOF`.
+//!
+//! Run with `cargo bench --features bench-internals`, adding `mmap` for the appends, and `slab` to compare
+//! the storage of the entries in a slab to the default `HashMap`.
+
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use rand::Rng;
+use sine_cache::cache::{AsyncCache, AsyncCacheBuilder, Cache};
+use sine_cache::config::{AsyncCacheConfig, CacheConfig, CacheSyncConfig, CustomCacheConfig, EvictionAOFConfig, EvictionAsyncConfig, NoEvictionAsyncConfig};
+use sine_cache::eviction_policies::lru::LRU;
+
+/// Capacity of the caches, a tenth of the keys so that puts evict.
+const CAPACITY: usize = 10_000;
+const KEYS: u64 = 100_000;
+const OPS: usize = 1_000_000;
+/// Reads for every put of `bench_get_heavy`.
+const GETS_PER_PUT: usize = 9;
+/// Size of the hot value read by `bench_hot_key`.
+const HOT_VALUE_BYTES: usize = 64 * 1024;
+const HOT_OPS: usize = 100_000;
+/// Size of the values put by `bench_large_values`.
+const LARGE_VALUE_BYTES: usize = 1024 * 1024;
+const LARGE_OPS: usize = 100;
+/// Values put by `bench_compress_values_over`, one in `MIXED_LARGE_EVERY` of `MIXED_LARGE_BYTES`, the others
+/// of `MIXED_SMALL_BYTES`.
+const MIXED_OPS: usize = 10_000;
+const MIXED_LARGE_EVERY: usize = 10;
+const MIXED_LARGE_BYTES: usize = 64 * 1024;
+const MIXED_SMALL_BYTES: usize = 32;
+/// Number of records appended by `bench_append`.
+#[cfg(all(feature = "mmap", unix))]
+const APPEND_OPS: usize = 100_000;
+
+fn sync_config(policy: &str) -> CacheSyncConfig<u64> {
+    let config = CacheConfig { max_size: CAPACITY };
+    match policy {
+        "LRU" => CacheSyncConfig::LRU(config),
+        "LFU" => CacheSyncConfig::LFU(config),
+        "FIFO" => CacheSyncConfig::FIFO(config),
+        "ApproxLFU" => CacheSyncConfig::ApproxLFU(config),
+        "SampledLRU" => CacheSyncConfig::SampledLRU(config),
+        _ => CacheSyncConfig::NoEviction,
+    }
+}
+
+fn async_config(policy: &str) -> AsyncCacheConfig<u64> {
+    let config = EvictionAsyncConfig { max_size: CAPACITY, aof_config: None };
+    match policy {
+        "LRU" => AsyncCacheConfig::LRU(config),
+        "LFU" => AsyncCacheConfig::LFU(config),
+        "FIFO" => AsyncCacheConfig::FIFO(config),
+        "ApproxLFU" => AsyncCacheConfig::ApproxLFU(config),
+        "SampledLRU" => AsyncCacheConfig::SampledLRU(config),
+        _ => AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig { aof_config: None }),
+    }
+}
+
+fn report(name: &str, ops: usize, elapsed: Duration) {
+    println!("{:<40} {:>12.0} ops/s", name, ops as f64 / elapsed.as_secs_f64());
+}
+
+fn bench_sync(policy: &str, keys: &[u64]) {
+    let mut cache = Cache::new(sync_config(policy));
+    let start = Instant::now();
+    for key in keys {
+        cache.put(*key, *key);
+    }
+    report(&format!("Cache<{}>::put", policy), keys.len(), start.elapsed());
+
+    let start = Instant::now();
+    let mut hits = 0;
+    for key in keys {
+        hits += cache.get(key).is_some() as usize;
+    }
+    report(&format!("Cache<{}>::get ({} hits)", policy, hits), keys.len(), start.elapsed());
+}
+
+/// Multiplicative hash of `u64` keys, standing in for a fast non DoS-resistant hasher such as `ahash`.
+#[derive(Default)]
+struct MultiplyHasher(u64);
+
+impl Hasher for MultiplyHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(*byte as u64);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = (self.0 ^ value).wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(26);
+    }
+}
+
+type MultiplyState = BuildHasherDefault<MultiplyHasher>;
+
+/// Reads `GETS_PER_PUT` keys for every put from an `LRU` cache, hashing keys with the default SipHash and with
+/// `MultiplyHasher` in both the cache and the policy.
+fn bench_get_heavy(keys: &[u64]) {
+    let mut cache: Cache<u64, u64> = Cache::new(sync_config("LRU"));
+    let start = Instant::now();
+    get_heavy(&mut cache, keys);
+    report("Cache<LRU>::get heavy (SipHash)", keys.len(), start.elapsed());
+
+    let policy: LRU<u64, MultiplyState> = LRU::with_hasher(MultiplyState::default());
+    let config = CacheSyncConfig::Custom(CustomCacheConfig { max_size: CAPACITY, policy: Box::new(policy) });
+    let mut cache: Cache<u64, u64, MultiplyState> = Cache::with_hasher(config, MultiplyState::default());
+    let start = Instant::now();
+    get_heavy(&mut cache, keys);
+    report("Cache<LRU>::get heavy (multiply)", keys.len(), start.elapsed());
+}
+
+fn get_heavy<S: std::hash::BuildHasher>(cache: &mut Cache<u64, u64, S>, keys: &[u64]) {
+    for (i, key) in keys.iter().enumerate() {
+        match i % (GETS_PER_PUT + 1) {
+            0 => cache.put(*key, *key),
+            _ => {
+                cache.get(key);
+            },
+        }
+    }
+}
+
+async fn bench_async(policy: &str, keys: &[u64]) {
+    let cache: AsyncCache<u64, u64> = AsyncCacheBuilder::new(async_config(policy)).in_memory_aof().build().await;
+    let start = Instant::now();
+    for key in keys {
+        cache.put(*key, *key).await;
+    }
+    report(&format!("AsyncCache<{}>::put", policy), keys.len(), start.elapsed());
+
+    let start = Instant::now();
+    for key in keys {
+        cache.get(key).await;
+    }
+    report(&format!("AsyncCache<{}>::get", policy), keys.len(), start.elapsed());
+}
+
+async fn bench_hot_key() {
+    let cache: AsyncCache<u64, Vec<u8>> = AsyncCacheBuilder::new(async_config("LRU")).build_without_aof().await;
+    let start = Instant::now();
+    for _ in 0..HOT_OPS {
+        cache.get_or_insert_with(0, || async { vec![0; HOT_VALUE_BYTES] }).await;
+    }
+    report("AsyncCache::get_or_insert_with (hot)", HOT_OPS, start.elapsed());
+
+    let cache: AsyncCache<u64, Arc<Vec<u8>>> = AsyncCacheBuilder::new(async_config("LRU")).build_without_aof().await;
+    let start = Instant::now();
+    for _ in 0..HOT_OPS {
+        cache.get_or_insert_arc(0, || async { vec![0; HOT_VALUE_BYTES] }).await;
+    }
+    report("AsyncCache::get_or_insert_arc (hot)", HOT_OPS, start.elapsed());
+}
+
+/// Puts large values persisted to an in-memory `AOF`: a `Vec<u8>` is cloned for the record, while the record of
+/// a `Bytes` shares its buffer with the cache.
+async fn bench_large_values() {
+    let value = vec![7; LARGE_VALUE_BYTES];
+    let cache: AsyncCache<u64, Vec<u8>> = AsyncCacheBuilder::new(async_config("LRU")).in_memory_aof().build().await;
+    let start = Instant::now();
+    for key in 0..LARGE_OPS as u64 {
+        cache.put(key % 4, value.clone()).await;
+    }
+    report("AsyncCache<Vec<u8>>::put (1 MiB)", LARGE_OPS, start.elapsed());
+
+    let value = Bytes::from(value);
+    let cache: AsyncCache<u64, Bytes> = AsyncCacheBuilder::new(async_config("LRU")).in_memory_aof().build().await;
+    let start = Instant::now();
+    for key in 0..LARGE_OPS as u64 {
+        cache.put(key % 4, value.clone()).await;
+    }
+    report("AsyncCache<Bytes>::put (1 MiB)", LARGE_OPS, start.elapsed());
+}
+
+/// Preloads `CAPACITY` entries with a `put` each, then all at once with `warm_from_snapshot`.
+async fn bench_warm_from_snapshot() {
+    let entries: Vec<(u64, u64)> = (0..CAPACITY as u64).map(|key| (key, key)).collect();
+    let cache: AsyncCache<u64, u64> = AsyncCacheBuilder::new(async_config("LRU")).in_memory_aof().build().await;
+    let start = Instant::now();
+    for (key, value) in entries.iter() {
+        cache.put(*key, *value).await;
+    }
+    report("AsyncCache::put (warm-up)", CAPACITY, start.elapsed());
+
+    let cache: AsyncCache<u64, u64> = AsyncCacheBuilder::new(async_config("LRU")).in_memory_aof().build().await;
+    let start = Instant::now();
+    cache.warm_from_snapshot(entries).await;
+    report("AsyncCache::warm_from_snapshot", CAPACITY, start.elapsed());
+}
+
+/// Run-length encoding, standing in for a compression: it shrinks the repetitive large values and doubles the
+/// small ones.
+fn run_length_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded: Vec<u8> = Vec::new();
+    for byte in bytes {
+        match encoded.len() {
+            len if len > 0 && encoded[len - 1] == *byte && encoded[len - 2] < u8::MAX => encoded[len - 2] += 1,
+            _ => encoded.extend([1, *byte]),
+        }
+    }
+    encoded
+}
+
+fn run_length_decode(bytes: &[u8]) -> Vec<u8> {
+    bytes.chunks(2).flat_map(|x| std::iter::repeat(x[1]).take(x[0] as usize)).collect()
+}
+
+/// Puts a mix of small and large values to the file of `AOF` without transform, with every value run-length
+/// encoded, and with only the values over `MIXED_SMALL_BYTES` encoded, then reports the size of the file and
+/// the time to replay it. Values are kept decoded in memory, so `get` takes the same time in all three.
+async fn bench_compress_values_over() {
+    let cache_name = "bench_compress_values_over";
+    let file = format!("./{}.dat", cache_name);
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from("."),
+            cache_name: String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false,
+        }),
+        max_size: MIXED_OPS,
+    });
+    let value = |key: usize| match key % MIXED_LARGE_EVERY {
+        0 => vec![(key % 251) as u8; MIXED_LARGE_BYTES],
+        _ => (0..MIXED_SMALL_BYTES).map(|x| (key + x) as u8).collect(),
+    };
+    for (name, transform, over) in [("none", false, None), ("all", true, None), ("over", true, Some(MIXED_SMALL_BYTES))] {
+        let _ = std::fs::remove_file(&file);
+        let builder = || match transform {
+            true => AsyncCacheBuilder::new(config()).value_transform(run_length_encode, run_length_decode).compress_values_over(over),
+            false => AsyncCacheBuilder::new(config()),
+        };
+        let cache: AsyncCache<u64, Vec<u8>> = builder().build().await;
+        let start = Instant::now();
+        for key in 0..MIXED_OPS {
+            cache.put(key as u64, value(key)).await;
+        }
+        report(&format!("AsyncCache::put (transform {})", name), MIXED_OPS, start.elapsed());
+        drop(cache);
+
+        println!("{:<40} {:>12} bytes", format!("AOF (transform {})", name), std::fs::metadata(&file).unwrap().len());
+        let start = Instant::now();
+        let cache: AsyncCache<u64, Vec<u8>> = builder().build().await;
+        report(&format!("AsyncCache::build (transform {})", name), MIXED_OPS, start.elapsed());
+        assert_eq!(cache.get(&0).await, Some(value(0)));
+    }
+    let _ = std::fs::remove_file(&file);
+}
+
+/// Appends records one at a time to the file of `AOF`, with a `write_all` and a flush each, and to `MmapAOF`,
+/// which copies them into the mapping and syncs them to disk once at the end.
+#[cfg(all(feature = "mmap", unix))]
+async fn bench_append() {
+    use sine_cache::aof::AOF;
+    use sine_cache::common::{AOFRecord, Operation};
+    use sine_cache::mmap_aof::MmapAOF;
+
+    let record = |key: u64| AOFRecord { key, value: Some(key), operation: Operation::Put, expires_at: None, cache_id: None, version: None, origin_ts: None };
+    let (file, mmap_file) = ("bench_append.dat", "bench_append.mmap");
+    let _ = std::fs::remove_file(file);
+    let _ = std::fs::remove_file(mmap_file);
+
+    let aof = AOF::new(file.to_string()).await;
+    let start = Instant::now();
+    for key in 0..APPEND_OPS as u64 {
+        aof.on_event(record(key), true).await.unwrap();
+    }
+    report("AOF::on_event", APPEND_OPS, start.elapsed());
+
+    let mut aof = MmapAOF::open(mmap_file, 1 << 20).unwrap();
+    let start = Instant::now();
+    for key in 0..APPEND_OPS as u64 {
+        aof.append(&[record(key)]).await.unwrap();
+    }
+    aof.sync().unwrap();
+    report("MmapAOF::append", APPEND_OPS, start.elapsed());
+
+    let _ = std::fs::remove_file(file);
+    let _ = std::fs::remove_file(mmap_file);
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    // skewed towards low keys, so that frequency based policies have something to keep.
+    let keys: Vec<u64> = (0..OPS).map(|_| (rng.gen::<f64>().powi(3) * KEYS as f64) as u64).collect();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    for policy in ["LRU", "SampledLRU", "LFU", "FIFO", "ApproxLFU", "NoEviction"] {
+        bench_sync(policy, &keys);
+        runtime.block_on(bench_async(policy, &keys));
+    }
+    bench_get_heavy(&keys);
+    runtime.block_on(bench_hot_key());
+    runtime.block_on(bench_large_values());
+    runtime.block_on(bench_warm_from_snapshot());
+    runtime.block_on(bench_compress_values_over());
+    #[cfg(all(feature = "mmap", unix))]
+    runtime.block_on(bench_append());
+}
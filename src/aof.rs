@@ -1,196 +1,831 @@
 //! Contains code for AOF for persisting data.
 
-use std::collections::VecDeque;
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use bytes::Bytes;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
 use tokio::sync::Mutex;
 
-use crate::common::{AOFRecord, Operation};
+use crate::common::{sleep_unless_stopped, AOFRecord, AofStats, FlushHealth, Operation, RepairReport};
+use crate::config::FlushStrategy;
+use crate::error::CacheError;
+
+/// Bit set in the operation byte of a record which is followed by an 8 byte expiry.
+const EXPIRY_FLAG: u8 = 0x80;
+
+/// Bit set in the operation byte of a record which is tagged with a 2 byte cache id.
+const CACHE_ID_FLAG: u8 = 0x40;
+
+/// Bit set in the operation byte of a record which carries an 8 byte version of the value.
+const VERSION_FLAG: u8 = 0x20;
+
+/// Bit set in the operation byte of a record whose value is written as raw bytes instead of JSON.
+const RAW_VALUE_FLAG: u8 = 0x10;
+
+/// Bit set in the operation byte of a record whose value bytes went through `ValueTransform::encode`.
+const TRANSFORMED_FLAG: u8 = 0x08;
+
+/// Operation code in the operation byte of a record which is followed by an extension byte, holding the actual
+/// operation code in its low 4 bits and more flags in the others. The flags of the operation byte keep their meaning.
+/// Operations whose code does not fit in the operation byte, i.e. from this one on, are always written this way.
+const EXTENDED_OPERATION: u8 = 0x07;
+
+/// Bit set in the extension byte of a record which carries an 8 byte origin timestamp, after the version.
+const ORIGIN_TS_FLAG: u8 = 0x80;
+
+/// Bits of the actual operation code in the extension byte.
+const EXTENDED_OPERATION_MASK: u8 = 0x0f;
+
+/// Function transforming bytes, see `ValueTransform`.
+pub type TransformFn = Arc<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+/// Transforms the bytes of values written to `AOF` and back while reading, e.g. to encrypt or compress them.
+/// Framing, keys and the other fields of records are written as they are.
+#[derive(Clone)]
+pub struct ValueTransform {
+    /// Applied to the serialized value before it is written.
+    pub encode: TransformFn,
+    /// Inverse of `encode`, applied to the bytes read before they are deserialized.
+    pub decode: TransformFn,
+    /// Only values whose serialized size exceeds it are transformed, e.g. compressed, while the others are
+    /// written as they are. `None` transforms every value. Whether a value was transformed is kept in its
+    /// record, so the threshold can be changed between restarts.
+    pub transform_over: Option<usize>,
+}
+
+/// Default limit of the size of the key and the value of a record read from `AOF`, see
+/// `AOFIterator::set_max_record_bytes`.
+pub const DEFAULT_MAX_RECORD_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Returns the bytes of `Vec<u8>` and `Bytes` values, which are written as they are. As JSON, every byte would
+/// be an element of an array of numbers, several times bigger and slower to parse.
+fn raw_value_bytes<V: 'static>(value: &V) -> Option<&[u8]> {
+    let value = value as &dyn Any;
+    value.downcast_ref::<Vec<u8>>().map(|x| x.as_slice())
+        .or_else(|| value.downcast_ref::<Bytes>().map(|x| x.as_ref()))
+}
+
+/// Reverse of `raw_value_bytes`. Gives the bytes back if `V` is neither `Vec<u8>` nor `Bytes`.
+fn value_from_raw_bytes<V: 'static>(bytes: Vec<u8>) -> Result<V, Vec<u8>> {
+    let boxed: Box<dyn Any> = match TypeId::of::<V>() == TypeId::of::<Bytes>() {
+        // takes over the buffer without copying it.
+        true => Box::new(Bytes::from(bytes)),
+        false => Box::new(bytes),
+    };
+    boxed.downcast::<V>()
+        .map(|x| *x)
+        .map_err(|x| *x.downcast::<Vec<u8>>().unwrap())
+}
+
+/// Decoded record, or the bytes of the key or the value which could not be decoded.
+pub(crate) type DecodeResult<K, V> = Result<AOFRecord<K, V>, (Vec<u8>, CacheError)>;
 
 /// This struct represents an Append-only File (AOF) for persistent storage
 pub struct AOF {
     filedir: String,
     writer: Mutex<File>,
+    value_transform: Option<ValueTransform>,
 }
 
 impl AOF {
     /// Opens an existing AOF file or creates a new one at the specified path
     pub async fn new(filedir: String) -> Self {
-        return Self {
-            writer: Mutex::new(OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&filedir)
-                .await
-                .expect(&format!("Error in opening aof {} file", filedir))),
+        match Self::try_new(filedir.clone()).await {
+            Ok(aof) => aof,
+            Err(e) => panic!("Error in opening aof {} file: {}", filedir, e),
+        }
+    }
+
+    /// Same as `new`, but returns the error instead of panicking if the file can not be opened.
+    pub async fn try_new(filedir: String) -> io::Result<Self> {
+        let writer = OpenOptions::new().create(true).append(true).open(&filedir).await?;
+        Ok(Self {
+            writer: Mutex::new(writer),
             filedir: filedir,
-        };
+            value_transform: None,
+        })
+    }
+
+    /// Transforms the values of the records written from now on and of the records read, see `ValueTransform`.
+    /// Records written before without transform are still read as they are.
+    pub fn set_value_transform(&mut self, value_transform: Option<ValueTransform>) {
+        self.value_transform = value_transform;
     }
 
     async fn object_to_bytes<O: Serialize>(obj: &O) -> Vec<u8> {
         serde_json::to_vec(obj).unwrap()
     }
 
-    async fn to_single_record_bytes<K: Serialize, V: Serialize>(
+    #[allow(clippy::too_many_arguments)]
+    async fn to_single_record_bytes<K: Serialize, V: Serialize + 'static>(
+        &self,
+        operation: Operation,
+        key: &K,
+        value: &Option<V>,
+        expires_at: Option<u64>,
+        cache_id: Option<u16>,
+        version: Option<u64>,
+        origin_ts: Option<u64>,
+    ) -> Vec<u8> {
+        Self::encode_record(self.value_transform.as_ref(), operation, key, value, expires_at, cache_id, version, origin_ts).await
+    }
+
+    /// Encodes a record as it is written to the file.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn encode_record<K: Serialize, V: Serialize + 'static>(
+        value_transform: Option<&ValueTransform>,
         operation: Operation,
         key: &K,
         value: &Option<V>,
+        expires_at: Option<u64>,
+        cache_id: Option<u16>,
+        version: Option<u64>,
+        origin_ts: Option<u64>,
     ) -> Vec<u8> {
         let key_bytes = Self::object_to_bytes(key).await;
-        let operation_byte_size = operation.to_int().to_le_bytes();
+        let mut operation_byte = operation.to_int() as u8;
+        // the extension byte is only written when needed, so that other records are read by older versions.
+        let extension_byte = match origin_ts {
+            Some(_) => Some(operation_byte | ORIGIN_TS_FLAG),
+            None => (operation_byte >= EXTENDED_OPERATION).then_some(operation_byte),
+        };
+        if extension_byte.is_some() {
+            operation_byte = EXTENDED_OPERATION;
+        }
+        if expires_at.is_some() {
+            operation_byte |= EXPIRY_FLAG;
+        }
+        if cache_id.is_some() {
+            operation_byte |= CACHE_ID_FLAG;
+        }
+        if version.is_some() {
+            operation_byte |= VERSION_FLAG;
+        }
+        let raw_value = value.as_ref().and_then(raw_value_bytes);
+        if raw_value.is_some() {
+            operation_byte |= RAW_VALUE_FLAG;
+        }
+        let value_bytes = match (value.as_ref(), raw_value) {
+            (None, _) => None,
+            (Some(_), Some(raw_value)) => Some(Cow::Borrowed(raw_value)),
+            (Some(value), None) => Some(Cow::Owned(Self::object_to_bytes(value).await)),
+        };
+        let transform = value_transform.filter(|transform| {
+            value_bytes.as_ref().is_some_and(|x| transform.transform_over.map_or(true, |over| x.len() > over))
+        });
+        if transform.is_some() {
+            operation_byte |= TRANSFORMED_FLAG;
+        }
         let key_bytes_size = (key_bytes.len() as u32).to_le_bytes();
         let mut bytes = vec![];
-        bytes.extend(operation_byte_size);
+        bytes.push(operation_byte);
+        if let Some(extension_byte) = extension_byte {
+            bytes.push(extension_byte);
+        }
+        if let Some(cache_id) = cache_id {
+            bytes.extend(cache_id.to_le_bytes());
+        }
+        if let Some(expires_at) = expires_at {
+            bytes.extend(expires_at.to_le_bytes());
+        }
+        if let Some(version) = version {
+            bytes.extend(version.to_le_bytes());
+        }
+        if let Some(origin_ts) = origin_ts {
+            bytes.extend(origin_ts.to_le_bytes());
+        }
         bytes.extend(key_bytes_size);
         bytes.extend(key_bytes);
-        if value.is_some() {
-            let value_bytes = Self::object_to_bytes(value.as_ref().unwrap()).await;
+        if let Some(value_bytes) = value_bytes {
+            let value_bytes = match transform {
+                Some(transform) => Cow::Owned((transform.encode)(&value_bytes)),
+                None => value_bytes,
+            };
             bytes.extend((value_bytes.len() as u64).to_le_bytes());
-            bytes.extend(value_bytes);
+            bytes.extend_from_slice(&value_bytes);
+        } else if operation == Operation::Put {
+            // readers expect a value after `Put`, an empty one is read back as `None`.
+            bytes.extend(0u64.to_le_bytes());
         };
         bytes
     }
 
-    pub async fn on_event<K, V>(&self, r: AOFRecord<K, V>, flush: bool)
+    /// Encodes a `PolicyState` record holding `state`, see `EvictionPolicy::export_state_bytes`. The state is
+    /// written as a raw value with a `null` key, so that it goes through `value_transform` like other values.
+    pub(crate) async fn encode_policy_state(value_transform: Option<&ValueTransform>, cache_id: Option<u16>, state: Vec<u8>) -> Vec<u8> {
+        Self::encode_record(value_transform, Operation::PolicyState, &(), &Some(state), None, cache_id, None, None).await
+    }
+
+    /// Appends the record, flushing it to the file if `flush` is `true`. Returns the error if writing fails,
+    /// see `write_bytes`.
+    pub async fn on_event<K, V>(&self, r: AOFRecord<K, V>, flush: bool) -> io::Result<()>
     where
         for<'de> K: Deserialize<'de> + Serialize,
-        for<'de> V: Deserialize<'de> + Serialize,
+        for<'de> V: Deserialize<'de> + Serialize + 'static,
     {
-        let mut gaurd = self.writer.lock().await;
-        gaurd
-            .write_all(&Self::to_single_record_bytes(r.operation, &r.key, &r.value).await)
-            .await
-            .unwrap();
-        if flush {
-            gaurd.flush().await.unwrap();
-        }
+        let bytes = self.to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await;
+        self.write_bytes(&bytes, flush).await
     }
 
-    pub async fn on_event_multi<K, V>(&self, records: Vec<AOFRecord<K, V>>, flush: bool)
+    /// Same as `on_event` for several records, appended in one write.
+    pub async fn on_event_multi<K, V>(&self, records: Vec<AOFRecord<K, V>>, flush: bool) -> io::Result<()>
     where
         for<'de> K: Deserialize<'de> + Serialize,
-        for<'de> V: Deserialize<'de> + Serialize,
+        for<'de> V: Deserialize<'de> + Serialize + 'static,
     {
         let mut bytes = vec![];
         for r in records {
-            bytes.extend(Self::to_single_record_bytes(r.operation, &r.key, &r.value).await)
+            bytes.extend(self.to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await)
         }
+        self.write_bytes(&bytes, flush).await
+    }
+
+    /// Appends already encoded records. If writing fails, the file is cut back to its length before, so that
+    /// the records written in part are not left in front of the ones of a retry.
+    async fn write_bytes(&self, bytes: &[u8], flush: bool) -> io::Result<()> {
         let mut gaurd = self.writer.lock().await;
-        gaurd.write_all(&bytes).await.unwrap();
-        if flush {
-            gaurd.flush().await.unwrap();
+        // completes the writes not flushed yet, so that the length includes them.
+        gaurd.flush().await?;
+        let len = gaurd.metadata().await?.len();
+        let mut result = gaurd.write_all(bytes).await;
+        if result.is_ok() && flush {
+            result = gaurd.flush().await;
+        }
+        if result.is_err() {
+            let _ = gaurd.set_len(len).await;
         }
+        result
     }
 
-    pub async fn flush(&mut self) {
-        self.writer.lock().await.flush().await.unwrap();
+    /// Flushes the records written so far to the file.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.lock().await.flush().await
+    }
+
+    /// Waits until the written records are on the disk.
+    async fn sync(&self) -> io::Result<()> {
+        self.writer.lock().await.sync_data().await
+    }
+
+    /// Re-opens the file for appending, e.g. after it was replaced by another process.
+    async fn reopen(&self) -> io::Result<()> {
+        let mut gaurd = self.writer.lock().await;
+        gaurd.flush().await?;
+        *gaurd = OpenOptions::new().create(true).append(true).open(&self.filedir).await?;
+        Ok(())
+    }
+
+    /// Size of the file in bytes.
+    pub async fn file_bytes(&self) -> io::Result<u64> {
+        Ok(tokio::fs::metadata(&self.filedir).await?.len())
     }
 
     pub async fn into_iter(&self) -> io::Result<AOFIterator> {
-        let reader = File::open(&self.filedir).await?;
-        Ok(AOFIterator { reader })
+        AOFIterator::open(&self.filedir, None, self.value_transform.clone()).await
+    }
+
+    /// Returns an iterator over the records tagged with `cache_id` only.
+    pub async fn into_iter_for(&self, cache_id: u16) -> io::Result<AOFIterator> {
+        AOFIterator::open(&self.filedir, Some(cache_id), self.value_transform.clone()).await
+    }
+
+    /// Opens the file at `path` for reading only, e.g. for tools which audit or export `AOF`. Unlike `new`, the
+    /// file is neither created nor opened for appending, so it can be read safely while a cache writes to it.
+    pub async fn open_reader(path: &str) -> Result<AOFIterator, CacheError> {
+        Ok(AOFIterator::open(path, None, None).await?)
+    }
+
+    /// Truncates the file at `path` after its last valid record, e.g. after reading it failed with
+    /// `CacheError::Corruption`, so that a cache can start with the records before. The records are checked
+    /// without being deserialized and their size is only limited by the file. It must not be written to
+    /// meanwhile.
+    pub async fn repair(path: &str) -> Result<RepairReport, CacheError> {
+        let mut iter = AOFIterator::open(path, None, None).await?;
+        iter.set_max_record_bytes(u64::MAX);
+        let mut records_kept = 0;
+        loop {
+            match iter.next_raw().await {
+                Ok(Some(_)) => records_kept += 1,
+                Ok(None) | Err(CacheError::Corruption { .. }) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let valid_bytes = iter.bytes_read();
+        let bytes_discarded = iter.total_bytes().await? - valid_bytes;
+        if bytes_discarded > 0 {
+            let file = OpenOptions::new().write(true).open(path).await?;
+            file.set_len(valid_bytes).await?;
+            file.sync_all().await?;
+        }
+        Ok(RepairReport { records_kept, bytes_discarded })
+    }
+}
+
+/// Version of the on-disk layout recorded in `AofManifest`.
+pub const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+/// Human-readable description of the files of a cache, written next to them as `<cache_name>.manifest.json`
+/// with `AsyncCacheBuilder::write_manifest`. If it exists, the cache checks that its eviction policy matches on
+/// start.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AofManifest {
+    /// Version of the layout, `MANIFEST_FORMAT_VERSION` when written.
+    pub format_version: u32,
+    /// Name of the eviction policy, see `AsyncCacheConfig::policy_name`.
+    pub policy: String,
+    /// Maximum number of entries, `0` for `NoEviction`.
+    pub max_size: usize,
+    /// Names of the files holding the records in the folder of the manifest, in the order they are replayed.
+    /// A cache writes a single file, its `cache_name` with the configured extension.
+    pub segments: Vec<String>,
+    /// Time the manifest was first written, in unix milliseconds. It is kept when the manifest is rewritten.
+    pub created_at: u64,
+}
+
+impl AofManifest {
+    /// Path of the manifest of `cache_name` in `folder`.
+    pub fn path(folder: &str, cache_name: &str) -> String {
+        format!("{}/{}.manifest.json", folder, cache_name)
+    }
+
+    /// Reads the manifest at `path`, `None` if there is none.
+    pub async fn read(path: &str) -> Result<Option<Self>, CacheError> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CacheError::Io(e)),
+        }
+    }
+
+    /// Writes the manifest to `path`, replacing the previous one atomically.
+    pub async fn write(&self, path: &str) -> Result<(), CacheError> {
+        let tmp_path = format!("{}.tmp", path);
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
+
+/// Source of the bytes read by `AOFIterator`.
+enum AofReader {
+    File(File),
+    /// Committed records of a memory-mapped file, see `MmapAOF::into_iter`.
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(std::io::Cursor<crate::mmap_aof::MappedBytes>),
+    /// Bytes of `MemoryAOF`.
+    Memory(std::io::Cursor<Vec<u8>>),
+}
+
+impl AofReader {
+    async fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.read_exact(buf).await.map(|_| ()),
+            #[cfg(all(feature = "mmap", unix))]
+            Self::Mapped(cursor) => std::io::Read::read_exact(cursor, buf),
+            Self::Memory(cursor) => std::io::Read::read_exact(cursor, buf),
+        }
+    }
+
+    /// Number of bytes which can be read.
+    async fn len(&self) -> io::Result<u64> {
+        match self {
+            Self::File(file) => Ok(file.metadata().await?.len()),
+            #[cfg(all(feature = "mmap", unix))]
+            Self::Mapped(cursor) => Ok(cursor.get_ref().as_ref().len() as u64),
+            Self::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+        }
     }
 }
 
 /// Iterator which helps in iterating all the recorded options one by one.
 pub struct AOFIterator {
-    reader: File,
+    reader: AofReader,
+    /// If set, records of other caches are skipped without being deserialized.
+    cache_id: Option<u16>,
+    bytes_read: u64,
+    /// Size of the file when last looked up, to tell lengths past its end apart without a lookup per record.
+    file_bytes: u64,
+    max_record_bytes: u64,
+    value_transform: Option<ValueTransform>,
+    /// State of the last `PolicyState` record read, see `take_policy_state`.
+    policy_state: Option<Vec<u8>>,
 }
 
 impl AOFIterator {
+    async fn open(filedir: &str, cache_id: Option<u16>, value_transform: Option<ValueTransform>) -> io::Result<Self> {
+        let reader = AofReader::File(File::open(filedir).await?);
+        let file_bytes = reader.len().await?;
+        Ok(AOFIterator { reader, cache_id, bytes_read: 0, file_bytes, max_record_bytes: DEFAULT_MAX_RECORD_BYTES, value_transform, policy_state: None })
+    }
+
+    /// Iterator over the committed records of a memory-mapped file.
+    #[cfg(all(feature = "mmap", unix))]
+    pub(crate) fn from_mapped(bytes: crate::mmap_aof::MappedBytes, value_transform: Option<ValueTransform>) -> Self {
+        let file_bytes = bytes.as_ref().len() as u64;
+        let reader = AofReader::Mapped(std::io::Cursor::new(bytes));
+        AOFIterator { reader, cache_id: None, bytes_read: 0, file_bytes, max_record_bytes: DEFAULT_MAX_RECORD_BYTES, value_transform, policy_state: None }
+    }
+
+    /// Iterator over the bytes of `MemoryAOF`.
+    fn from_memory(bytes: Vec<u8>) -> Self {
+        let file_bytes = bytes.len() as u64;
+        let reader = AofReader::Memory(std::io::Cursor::new(bytes));
+        AOFIterator { reader, cache_id: None, bytes_read: 0, file_bytes, max_record_bytes: DEFAULT_MAX_RECORD_BYTES, value_transform: None, policy_state: None }
+    }
+
+    /// Limits the size of the key and the value of a record together, `DEFAULT_MAX_RECORD_BYTES` by default.
+    /// Bigger lengths are reported as `CacheError::Corruption` instead of being allocated, as a corrupted
+    /// length could ask for any amount of memory.
+    pub fn set_max_record_bytes(&mut self, max_record_bytes: u64) {
+        self.max_record_bytes = max_record_bytes;
+    }
+
+    /// Sets the inverse of the transform the values were written with, see `AOF::set_value_transform`.
+    /// Transformed values can not be decoded without it.
+    pub fn set_value_transform(&mut self, value_transform: Option<ValueTransform>) {
+        self.value_transform = value_transform;
+    }
+
+    /// Reads the records as `AOFRecord<K, V>`, see `AofRecords`.
+    pub fn records<K, V>(self) -> AofRecords<K, V> {
+        AofRecords { bytes_read: self.bytes_read, iter: Some(self), _marker: PhantomData }
+    }
+
+    /// Size of the file in bytes.
+    pub async fn total_bytes(&self) -> io::Result<u64> {
+        self.reader.len().await
+    }
+
+    /// Number of bytes of the complete records read so far, including the skipped records of other caches.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// State of the eviction policy of the last `PolicyState` record read since the last call, see
+    /// `EvictionPolicy::export_state_bytes`. `PolicyState` records are not returned as records, the state is
+    /// taken after every record instead, so that it is imported in the order it was written.
+    pub(crate) fn take_policy_state(&mut self) -> Option<Vec<u8>> {
+        self.policy_state.take()
+    }
+
     /// Next record in the sequence.
+    ///
+    /// Bytes which are not a valid record, e.g. a record cut short by a crash, are returned as an error of kind
+    /// `InvalidData` wrapping `CacheError::Corruption`. Reading should stop there.
     pub async fn next<K, V>(&mut self) -> io::Result<Option<AOFRecord<K, V>>>
     where
         for<'de> K: Deserialize<'de> + Serialize,
-        for<'de> V: Deserialize<'de> + Serialize,
+        for<'de> V: Deserialize<'de> + Serialize + 'static,
     {
+        match self.next_decoded().await {
+            Ok(Some(Ok(record))) => Ok(Some(record)),
+            Ok(Some(Err((_, e)))) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            Ok(None) => Ok(None),
+            Err(CacheError::Io(e)) => Err(e),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    /// Same as `next`, but a record which can not be decoded is returned as an error with its bytes, so that
+    /// the caller can skip it and go on with the next one.
+    pub(crate) async fn next_decoded<K, V>(&mut self) -> Result<Option<DecodeResult<K, V>>, CacheError>
+    where
+        for<'de> K: Deserialize<'de> + Serialize,
+        for<'de> V: Deserialize<'de> + Serialize + 'static,
+    {
+        loop {
+            let Some(RawRecord { cache_id: record_cache_id, operation, expires_at, version, origin_ts, key: key_buf, value: value_buf, raw_value, transformed }) = self.next_raw().await? else {
+                return Ok(None);
+            };
+            if self.cache_id.is_some() && self.cache_id != record_cache_id {
+                continue;
+            }
+            if operation == Operation::PolicyState {
+                self.policy_state = match (value_buf, transformed, self.value_transform.as_ref()) {
+                    (Some(value_buf), true, Some(transform)) => Some((transform.decode)(&value_buf)),
+                    // can not be decoded without transform, the order of replay is kept instead.
+                    (_, true, None) => None,
+                    (value_buf, _, _) => value_buf,
+                };
+                continue;
+            }
+            let key: K = match serde_json::from_slice(&key_buf) {
+                Ok(key) => key,
+                Err(e) => return Ok(Some(Err((key_buf, CacheError::Serialize(e))))),
+            };
+            let value_buf = match (value_buf, transformed, self.value_transform.as_ref()) {
+                (Some(value_buf), true, Some(transform)) => Some((transform.decode)(&value_buf)),
+                (Some(value_buf), true, None) => {
+                    let e = CacheError::Serialize(serde::de::Error::custom("value is transformed, but there is no transform to decode it."));
+                    return Ok(Some(Err((value_buf, e))));
+                },
+                (value_buf, _, _) => value_buf,
+            };
+            let value = match value_buf {
+                // written for a `Put` without value, JSON is never empty.
+                Some(value_buf) if value_buf.is_empty() && !raw_value => None,
+                Some(value_buf) if raw_value => match value_from_raw_bytes(value_buf) {
+                    Ok(value) => Some(value),
+                    Err(value_buf) => {
+                        let e = CacheError::Serialize(serde::de::Error::custom("raw bytes value can only be read as `Vec<u8>`."));
+                        return Ok(Some(Err((value_buf, e))));
+                    }
+                },
+                Some(value_buf) => match serde_json::from_slice(&value_buf) {
+                    Ok(value) => Some(value),
+                    Err(e) => return Ok(Some(Err((value_buf, CacheError::Serialize(e))))),
+                },
+                None => None,
+            };
+            return Ok(Some(Ok(AOFRecord {
+                key,
+                value,
+                operation,
+                expires_at,
+                cache_id: record_cache_id,
+                version,
+                origin_ts,
+            })));
+        }
+    }
+
+    /// Reads the next record without deserializing key and value.
+    async fn next_raw(&mut self) -> Result<Option<RawRecord>, CacheError> {
         let mut ops_int_bytes = [0u8; 1];
         if self.reader.read_exact(&mut ops_int_bytes).await.is_err() {
             return Ok(None);
         };
         let ops_int = u8::from_le_bytes(ops_int_bytes);
-        let operation = Operation::from_int(ops_int);
+        let mut operation_int = ops_int & !(EXPIRY_FLAG | CACHE_ID_FLAG | VERSION_FLAG | RAW_VALUE_FLAG | TRANSFORMED_FLAG);
+        let mut extension_int = 0;
+        let extended = operation_int == EXTENDED_OPERATION;
+        if extended {
+            let mut extension_buf = [0u8; 1];
+            self.read_exact(&mut extension_buf).await?;
+            extension_int = extension_buf[0];
+            operation_int = extension_int & EXTENDED_OPERATION_MASK;
+        }
+        let Ok(operation) = Operation::from_int(operation_int) else {
+            return Err(self.corruption(format!("invalid operation byte {}", ops_int)));
+        };
+        let mut cache_id = None;
+        if ops_int & CACHE_ID_FLAG != 0 {
+            let mut cache_id_buf = [0u8; 2];
+            self.read_exact(&mut cache_id_buf).await?;
+            cache_id = Some(u16::from_le_bytes(cache_id_buf));
+        }
+        let mut expires_at = None;
+        if ops_int & EXPIRY_FLAG != 0 {
+            let mut expires_at_buf = [0u8; 8];
+            self.read_exact(&mut expires_at_buf).await?;
+            expires_at = Some(u64::from_le_bytes(expires_at_buf));
+        }
+        let mut version = None;
+        if ops_int & VERSION_FLAG != 0 {
+            let mut version_buf = [0u8; 8];
+            self.read_exact(&mut version_buf).await?;
+            version = Some(u64::from_le_bytes(version_buf));
+        }
+        let mut origin_ts = None;
+        if extension_int & ORIGIN_TS_FLAG != 0 {
+            let mut origin_ts_buf = [0u8; 8];
+            self.read_exact(&mut origin_ts_buf).await?;
+            origin_ts = Some(u64::from_le_bytes(origin_ts_buf));
+        }
+        let header_bytes = 1
+            + u64::from(extended)
+            + cache_id.map_or(0, |_| 2)
+            + expires_at.map_or(0, |_| 8)
+            + version.map_or(0, |_| 8)
+            + origin_ts.map_or(0, |_| 8);
         let mut key_size_buf = [0u8; 4];
-        self.reader.read_exact(&mut key_size_buf).await?;
-        let key_size = u32::from_le_bytes(key_size_buf);
+        self.read_exact(&mut key_size_buf).await?;
+        let key_size = u32::from_le_bytes(key_size_buf) as u64;
+        self.check_length(key_size, header_bytes + 4).await?;
         let mut key_buf = vec![0u8; key_size as usize];
-        self.reader.read_exact(&mut key_buf).await?;
-        let key: K = serde_json::from_slice(&key_buf)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.read_exact(&mut key_buf).await?;
         let value;
-        if let Operation::Put = operation {
+        if let Operation::Put | Operation::PolicyState = operation {
             let mut value_size_buf = [0u8; 8];
-            self.reader.read_exact(&mut value_size_buf).await?;
+            self.read_exact(&mut value_size_buf).await?;
             let value_size = u64::from_le_bytes(value_size_buf);
+            self.check_length(key_size.saturating_add(value_size), header_bytes + 4 + 8).await?;
             let mut value_buf = vec![0u8; value_size as usize];
-            self.reader.read_exact(&mut value_buf).await?;
-            value = Some(
-                serde_json::from_slice(&value_buf)
-                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
-            );
+            self.read_exact(&mut value_buf).await?;
+            value = Some(value_buf);
         } else {
             value = None
         }
-        return Ok(Some(AOFRecord {
-            key,
-            value,
-            operation,
-        }));
+        self.bytes_read += header_bytes
+            + 4
+            + key_buf.len() as u64
+            + value.as_ref().map_or(0, |v| 8 + v.len() as u64);
+        Ok(Some(RawRecord { cache_id, operation, expires_at, version, origin_ts, key: key_buf, value, raw_value: ops_int & RAW_VALUE_FLAG != 0, transformed: ops_int & TRANSFORMED_FLAG != 0 }))
+    }
+
+    /// Reads bytes of the current record, a record cut short by the end of the file is a corruption.
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), CacheError> {
+        match self.reader.read_exact(buf).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(self.corruption(String::from("truncated record"))),
+            Err(e) => Err(CacheError::Io(e)),
+        }
+    }
+
+    /// Checks the length of key and value of the current record, of which `header_bytes` have been read, before
+    /// it is allocated.
+    async fn check_length(&mut self, length: u64, header_bytes: u64) -> Result<(), CacheError> {
+        if length > self.max_record_bytes {
+            return Err(self.corruption(format!("record of {} bytes is bigger than the limit of {} bytes", length, self.max_record_bytes)));
+        }
+        let end = self.bytes_read + header_bytes + length;
+        if end > self.file_bytes {
+            // the file may have grown since it was opened.
+            self.file_bytes = self.reader.len().await?;
+            if end > self.file_bytes {
+                return Err(self.corruption(format!("record of {} bytes goes past the end of the file", length)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Corruption of the record being read.
+    fn corruption(&self, detail: String) -> CacheError {
+        CacheError::Corruption { offset: self.bytes_read, detail }
     }
 }
 
-/// This struct is a facade to use `AOF`. 
-/// 
-pub struct AOFSubscriber<K, V>
+/// Typed records of `AOF`, returned by `AOFIterator::records`.
+///
+/// A record whose key or value can not be decoded is returned as `CacheError::Serialize` and reading goes on
+/// with the next one. Bytes which are not a valid record, e.g. cut short by a crash, end the records after
+/// returning `CacheError::Corruption`, as the records after them can not be located.
+pub struct AofRecords<K, V> {
+    /// `None` once the end of the file or invalid bytes have been reached.
+    iter: Option<AOFIterator>,
+    bytes_read: u64,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> AofRecords<K, V>
 where
     for<'de> K: Deserialize<'de> + Serialize,
-    for<'de> V: Deserialize<'de> + Serialize,
+    for<'de> V: Deserialize<'de> + Serialize + 'static,
 {
+    /// Next record, `None` at the end.
+    pub async fn next(&mut self) -> Option<Result<AOFRecord<K, V>, CacheError>> {
+        let iter = self.iter.as_mut()?;
+        let result = iter.next_decoded().await;
+        self.bytes_read = iter.bytes_read();
+        match result {
+            Ok(Some(record)) => Some(record.map_err(|(_, e)| e)),
+            Ok(None) => {
+                self.iter = None;
+                None
+            },
+            Err(e) => {
+                self.iter = None;
+                Some(Err(e))
+            },
+        }
+    }
+
+    /// Number of bytes of the complete records read so far, the offset of invalid bytes once they have been
+    /// returned as `CacheError::Corruption`.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+}
+
+/// Record read from `AOF` with key and value not deserialized yet.
+struct RawRecord {
+    cache_id: Option<u16>,
+    operation: Operation,
+    expires_at: Option<u64>,
+    version: Option<u64>,
+    origin_ts: Option<u64>,
+    key: Vec<u8>,
+    value: Option<Vec<u8>>,
+    /// Whether `value` holds raw bytes rather than JSON.
+    raw_value: bool,
+    /// Whether `value` has to be decoded with `ValueTransform::decode` first.
+    transformed: bool,
+}
+
+/// This struct is a facade to use `AOF`. 
+/// 
+pub struct AOFSubscriber<K, V> {
     aof: Option<AOF>,
     pub flush_time: Option<u32>,
+    /// Fraction of `flush_time` by which every periodic flush is moved randomly either way, `0.0` by default.
+    pub flush_jitter: f64,
+    /// Replaces the fixed `flush_time` of the periodic flush if set, see `FlushStrategy`.
+    pub flush_strategy: Option<FlushStrategy>,
     unwritten_inmemory_records: Mutex<VecDeque<AOFRecord<K, V>>>,
+    health: std::sync::Mutex<FlushHealth>,
+    /// Held for the whole flush, so that once a flush returns, the records taken by a concurrent one are
+    /// on disk as well.
+    flush_lock: Mutex<()>,
+    /// Records replayed from the file plus the ones recorded since, see `aof_stats`.
+    records: AtomicU64,
 }
 
 impl<K, V> AOFSubscriber<K, V>
 where
     for<'de> K: Deserialize<'de> + Serialize,
-    for<'de> V: Deserialize<'de> + Serialize,
+    for<'de> V: Deserialize<'de> + Serialize + 'static,
 {
     pub async fn new(
         filedir: Option<String>,
         cache_name: Option<String>,
         flush_time: Option<u32>,
     ) -> Self {
+        let path = format!("{}/{}.dat", filedir.as_ref().unwrap(), cache_name.as_ref().unwrap());
+        match Self::try_new(filedir, cache_name, flush_time).await {
+            Ok(subscriber) => subscriber,
+            Err(e) => panic!("Error in opening aof {} file: {}", path, e),
+        }
+    }
+
+    /// Same as `new`, but returns the error instead of panicking if the file can not be opened.
+    pub async fn try_new(
+        filedir: Option<String>,
+        cache_name: Option<String>,
+        flush_time: Option<u32>,
+    ) -> io::Result<Self> {
+        Self::try_with_extension(filedir, cache_name, "dat", flush_time).await
+    }
+
+    /// Same as `try_new`, writing to `{filedir}/{cache_name}.{extension}` instead of the `.dat` file.
+    pub async fn try_with_extension(
+        filedir: Option<String>,
+        cache_name: Option<String>,
+        extension: &str,
+        flush_time: Option<u32>,
+    ) -> io::Result<Self> {
         if !Path::new(filedir.as_ref().unwrap()).exists() {
             let _ = tokio::fs::create_dir_all(filedir.as_ref().unwrap()).await;
         };
-        Self {
+        Ok(Self {
             aof: if filedir.as_ref().is_some() {
                 Some(
-                    AOF::new(format!("{}/{}.dat", filedir.unwrap(), cache_name.unwrap())).await,
+                    AOF::try_new(format!("{}/{}.{}", filedir.unwrap(), cache_name.unwrap(), extension)).await?,
                 )
             } else {
                 None
             },
             flush_time: flush_time,
+            flush_jitter: 0.0,
+            flush_strategy: None,
             unwritten_inmemory_records: Mutex::new(VecDeque::new()),
+            health: std::sync::Mutex::new(FlushHealth::default()),
+            flush_lock: Mutex::new(()),
+            records: AtomicU64::new(0),
+        })
+    }
+
+    /// Transforms the values written to and read from the file, see `AOF::set_value_transform`.
+    pub fn set_value_transform(&mut self, value_transform: Option<ValueTransform>) {
+        if let Some(aof) = self.aof.as_mut() {
+            aof.set_value_transform(value_transform);
         }
     }
 
     pub async fn on_event(&self, r: AOFRecord<K, V>) {
         if self.aof.as_ref().is_some() {
+            self.records.fetch_add(1, Ordering::Relaxed);
             if self.flush_time.is_some() {
                 self.unwritten_inmemory_records.lock().await.push_back(r);
             } else {
-                self.aof
-                    .as_ref()
-                    .unwrap()
-                    .on_event(r, true)
-                    .await;
+                let bytes = self.aof.as_ref().unwrap().to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await;
+                let result = self.aof.as_ref().unwrap().write_bytes(&bytes, true).await;
+                self.health.lock().unwrap().record(&result);
+            }
+        }
+    }
+
+    /// Same as `on_event` for several records, taking the lock of pending records or writing to the file once.
+    pub async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        if self.aof.as_ref().is_some() {
+            self.records.fetch_add(records.len() as u64, Ordering::Relaxed);
+            if self.flush_time.is_some() {
+                self.unwritten_inmemory_records.lock().await.extend(records);
+            } else {
+                let mut bytes = vec![];
+                for r in records {
+                    bytes.extend(self.aof.as_ref().unwrap().to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await);
+                }
+                let result = self.aof.as_ref().unwrap().write_bytes(&bytes, true).await;
+                self.health.lock().unwrap().record(&result);
             }
         }
     }
@@ -207,13 +842,102 @@ where
 
     /// Flushes the in memory data to disk and empties in memory. Call this function carefully as it does
     /// not check whether it is ok to call this or not. For e.g. in case of no flush time or no AOF, it must not be called.
+    ///
+    /// If writing fails, the records are kept in memory to be retried by the next flush and the error is
+    /// reported by `flush_health`.
     pub async fn flush_to_disk(&self) {
-        let records = self.get_current_records_and_empty_it().await;
-        self.aof
-            .as_ref()
-            .unwrap()
-            .on_event_multi(records, true)
-            .await;
+        let _ = self.try_flush_to_disk().await;
+    }
+
+    /// Same as `flush_to_disk`, returning the error of writing.
+    async fn try_flush_to_disk(&self) -> io::Result<()> {
+        self.write_pending_and(&[], false).await
+    }
+
+    /// Writes the pending records followed by the encoded records `extra` in one go, waiting until they are on
+    /// the disk if `sync`. If writing fails, the pending records are kept in memory to be retried, `extra` is not.
+    async fn write_pending_and(&self, extra: &[u8], sync: bool) -> io::Result<()> {
+        let aof = self.aof.as_ref().unwrap();
+        let _flush_gaurd = self.flush_lock.lock().await;
+        let pending = self.get_current_records_and_empty_it().await;
+        let mut bytes = vec![];
+        for r in pending.iter() {
+            bytes.extend(aof.to_single_record_bytes(r.operation.clone(), &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await);
+        }
+        bytes.extend_from_slice(extra);
+        let mut result = aof.write_bytes(&bytes, true).await;
+        if result.is_ok() && sync {
+            result = aof.sync().await;
+        }
+        if result.is_err() {
+            // back to the front, so that the order is kept with the records added meanwhile.
+            let mut records_guard = self.unwritten_inmemory_records.lock().await;
+            for r in pending.into_iter().rev() {
+                records_guard.push_front(r);
+            }
+        }
+        self.health.lock().unwrap().record(&result);
+        result
+    }
+
+    /// Writes `records` right away in one go, after the pending records, and waits until they are on the
+    /// disk. Used for the records which must be persisted together or not at all.
+    pub async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()> {
+        let Some(aof) = self.aof.as_ref() else {
+            return Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."));
+        };
+        let mut bytes = vec![];
+        for r in records.iter() {
+            bytes.extend(aof.to_single_record_bytes(r.operation.clone(), &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await);
+        }
+        self.write_pending_and(&bytes, true).await?;
+        self.records.fetch_add(records.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Writes a `PolicyState` record with `state` right away, after the pending records, and waits until it
+    /// is on the disk.
+    pub async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        let Some(aof) = self.aof.as_ref() else {
+            return Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."));
+        };
+        let bytes = AOF::encode_policy_state(aof.value_transform.as_ref(), None, state).await;
+        self.write_pending_and(&bytes, true).await?;
+        self.records.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Size of the file and approximate number of records in it: the ones counted by `set_approx_records`
+    /// after replay plus the ones recorded since, including those still waiting for the periodic flush.
+    pub async fn aof_stats(&self) -> io::Result<AofStats> {
+        let Some(aof) = self.aof.as_ref() else {
+            return Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."));
+        };
+        Ok(AofStats { file_bytes: aof.file_bytes().await?, approx_records: self.records.load(Ordering::Relaxed) })
+    }
+
+    /// Sets the number of records in the file, e.g. after they have been counted while replaying.
+    pub fn set_approx_records(&self, records: u64) {
+        self.records.store(records, Ordering::Relaxed);
+    }
+
+    /// Re-opens the file, e.g. after it was replaced by another process, and writes the pending records
+    /// to it so that everything recorded so far is in the file.
+    pub async fn reopen(&self) -> io::Result<()> {
+        let Some(aof) = self.aof.as_ref() else {
+            return Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."));
+        };
+        aof.reopen().await?;
+        if self.flush_time.is_some() {
+            self.try_flush_to_disk().await?;
+        }
+        Ok(())
+    }
+
+    /// Result of the last write to disk and the number of records waiting to be flushed.
+    pub async fn flush_health(&self) -> FlushHealth {
+        let pending = self.unwritten_inmemory_records.lock().await.len();
+        FlushHealth { pending, ..self.health.lock().unwrap().clone() }
     }
 
     pub async fn into_iter(&self) -> io::Result<AOFIterator> {
@@ -225,22 +949,372 @@ where
     }
 }
 
-use async_recursion::async_recursion;
-
 /// Flushes periodically to disk.
-#[async_recursion]
 pub async fn periodic_flush<K, V>(aof_subscriber: Arc<AOFSubscriber<K, V>>)
 where
     for<'de> K: Deserialize<'de> + Serialize + Send + Sync,
-    for<'de> V: Deserialize<'de> + Serialize + Send + Sync,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
+{
+    // the sender is dropped right away, so it is never stopped.
+    periodic_flush_until_stopped(aof_subscriber, tokio::sync::watch::channel(false).1).await;
+}
+
+/// Same as `periodic_flush`, returning once `stop` is signalled between two flushes, see `BackgroundTasks`.
+pub(crate) async fn periodic_flush_until_stopped<K, V>(aof_subscriber: Arc<AOFSubscriber<K, V>>, mut stop: tokio::sync::watch::Receiver<bool>)
+where
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
 {
-    if aof_subscriber.flush_time.as_ref().is_none() {
+    let Some(flush_time) = aof_subscriber.flush_time else {
         return;
+    };
+    let flush_time = match aof_subscriber.flush_strategy {
+        Some(FlushStrategy::Adaptive { min, .. }) => return adaptive_flush(aof_subscriber, min, stop).await,
+        Some(FlushStrategy::Fixed(interval)) => u32::try_from(interval.as_millis()).unwrap_or(u32::MAX),
+        None => flush_time,
+    };
+    while sleep_unless_stopped(jittered_interval(flush_time, aof_subscriber.flush_jitter), &mut stop).await {
+        aof_subscriber.flush_to_disk().await;
+    }
+}
+
+/// Flushes to disk once the time since the last flush reaches the interval of `FlushStrategy::Adaptive` for
+/// the records pending then, checked every `min`, so that a burst of writes shortens the current wait as well.
+async fn adaptive_flush<K, V>(aof_subscriber: Arc<AOFSubscriber<K, V>>, min: tokio::time::Duration, mut stop: tokio::sync::watch::Receiver<bool>)
+where
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
+{
+    let Some(flush_strategy) = aof_subscriber.flush_strategy else {
+        return;
+    };
+    let mut waited = tokio::time::Duration::ZERO;
+    while sleep_unless_stopped(min, &mut stop).await {
+        waited += min;
+        let pending = aof_subscriber.unwritten_inmemory_records.lock().await.len();
+        if waited >= flush_strategy.interval(pending) {
+            aof_subscriber.flush_to_disk().await;
+            waited = tokio::time::Duration::ZERO;
+        }
+    }
+}
+
+/// `interval` milliseconds moved randomly by up to `jitter` of it either way, recomputed on every call so that
+/// caches with the same `flush_time` do not keep flushing at the same moment.
+pub(crate) fn jittered_interval(interval: u32, jitter: f64) -> tokio::time::Duration {
+    let shift = if jitter > 0.0 {
+        rand::thread_rng().gen_range(-jitter..=jitter)
+    } else {
+        0.0
+    };
+    tokio::time::Duration::from_millis((interval as f64 * (1.0 + shift)).round() as u64)
+}
+
+/// Shares a single `AOF` between several caches to reduce the number of open files.
+///
+/// Every cache registers itself with a unique `cache_id` through `register`, all its records are tagged
+/// with that id and while replaying, only records with the same id are read back. Keys and values of
+/// different caches may have different types.
+///
+/// Caches with their own file (default) are not affected by this.
+pub struct AofMultiplexer {
+    aof: AOF,
+    flush_time: Option<u32>,
+    unwritten_inmemory_bytes: Mutex<UnwrittenBytes>,
+    cache_ids: std::sync::Mutex<HashSet<u16>>,
+    health: std::sync::Mutex<FlushHealth>,
+    /// Held for the whole flush, see `AOFSubscriber`.
+    flush_lock: Mutex<()>,
+}
+
+/// Encoded records waiting for the next periodic flush of `AofMultiplexer`.
+#[derive(Default)]
+struct UnwrittenBytes {
+    bytes: Vec<u8>,
+    records: usize,
+}
+
+impl AofMultiplexer {
+    /// Creates the shared `AOF` as `{filedir}/{name}.dat`.
+    ///
+    /// `flush_time` works same as for a single cache: records are flushed to disk periodically after every
+    /// `flush_time` milliseconds, in case of `None` every record is flushed right away.
+    ///
+    /// Panics if `flush_time` is zero.
+    pub async fn new(filedir: String, name: String, flush_time: Option<u32>) -> Arc<Self> {
+        if flush_time.is_some_and(|x| x == 0) {
+            panic!("flush time must be greater than zero.");
+        }
+        if !Path::new(&filedir).exists() {
+            let _ = tokio::fs::create_dir_all(&filedir).await;
+        };
+        let instance = Arc::new(Self {
+            aof: AOF::new(format!("{}/{}.dat", filedir, name)).await,
+            flush_time,
+            unwritten_inmemory_bytes: Mutex::new(UnwrittenBytes::default()),
+            cache_ids: std::sync::Mutex::new(HashSet::new()),
+            health: std::sync::Mutex::new(FlushHealth::default()),
+            flush_lock: Mutex::new(()),
+        });
+        if let Some(flush_time) = flush_time {
+            let multiplexer = Arc::downgrade(&instance);
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(flush_time as u64)).await;
+                    match multiplexer.upgrade() {
+                        Some(multiplexer) => multiplexer.flush_to_disk().await,
+                        None => break,
+                    }
+                }
+            });
+        }
+        instance
+    }
+
+    /// Registers a cache with the given id and returns the subscriber which writes its records.
+    ///
+    /// Panics if `cache_id` is already registered. The id is released when the subscriber is dropped.
+    pub fn register<K, V>(self: &Arc<Self>, cache_id: u16) -> MultiplexedAOFSubscriber<K, V> {
+        if !self.cache_ids.lock().unwrap().insert(cache_id) {
+            panic!("cache id {} is already registered.", cache_id);
+        }
+        MultiplexedAOFSubscriber {
+            multiplexer: self.clone(),
+            cache_id,
+            records: AtomicU64::new(0),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Writes the records which have not been written to disk yet.
+    ///
+    /// If writing fails, the records are kept in memory to be retried by the next flush and the error is
+    /// reported by `flush_health`.
+    pub async fn flush_to_disk(&self) {
+        let _ = self.try_flush_to_disk().await;
+    }
+
+    /// Same as `flush_to_disk`, returning the error of writing.
+    async fn try_flush_to_disk(&self) -> io::Result<()> {
+        self.write_pending_and(&[], false).await
+    }
+
+    /// Writes the pending records followed by `bytes`, see `AOFSubscriber::write_pending_and`.
+    async fn write_pending_and(&self, bytes: &[u8], sync: bool) -> io::Result<()> {
+        let _flush_gaurd = self.flush_lock.lock().await;
+        let mut unwritten = std::mem::take(&mut *self.unwritten_inmemory_bytes.lock().await);
+        let pending_len = unwritten.bytes.len();
+        unwritten.bytes.extend_from_slice(bytes);
+        let mut result = self.aof.write_bytes(&unwritten.bytes, true).await;
+        if result.is_ok() && sync {
+            result = self.aof.sync().await;
+        }
+        if result.is_err() {
+            unwritten.bytes.truncate(pending_len);
+            let mut gaurd = self.unwritten_inmemory_bytes.lock().await;
+            let newer = std::mem::replace(&mut *gaurd, unwritten);
+            gaurd.bytes.extend(newer.bytes);
+            gaurd.records += newer.records;
+        }
+        self.health.lock().unwrap().record(&result);
+        result
+    }
+
+    /// Re-opens the shared file and writes the pending records of all the caches to it, see
+    /// `AOFSubscriber::reopen`.
+    pub async fn reopen(&self) -> io::Result<()> {
+        self.aof.reopen().await?;
+        if self.flush_time.is_some() {
+            self.try_flush_to_disk().await?;
+        }
+        Ok(())
+    }
+
+    /// Result of the last write to disk and the number of records of all the caches waiting to be flushed.
+    pub async fn flush_health(&self) -> FlushHealth {
+        let pending = self.unwritten_inmemory_bytes.lock().await.records;
+        FlushHealth { pending, ..self.health.lock().unwrap().clone() }
+    }
+}
+
+impl Drop for AofMultiplexer {
+    fn drop(&mut self) {
+        // best effort to not lose the records buffered since the last periodic flush.
+        let bytes = std::mem::take(&mut self.unwritten_inmemory_bytes.get_mut().bytes);
+        if !bytes.is_empty() {
+            if let Ok(mut file) = std::fs::OpenOptions::new().append(true).open(&self.aof.filedir) {
+                let _ = std::io::Write::write_all(&mut file, &bytes);
+            }
+        }
     }
-    tokio::time::sleep(tokio::time::Duration::from_millis(
-        aof_subscriber.flush_time.unwrap() as u64,
-    ))
-    .await;
-    aof_subscriber.flush_to_disk().await;
-    periodic_flush(aof_subscriber).await;
 }
+
+/// Writes records of a single cache to the `AofMultiplexer` it was registered with.
+pub struct MultiplexedAOFSubscriber<K, V> {
+    multiplexer: Arc<AofMultiplexer>,
+    cache_id: u16,
+    /// Records of this cache in the shared `AOF`, see `AOFSubscriber::aof_stats`.
+    records: AtomicU64,
+    _phantom: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> MultiplexedAOFSubscriber<K, V>
+where
+    for<'de> K: Deserialize<'de> + Serialize,
+    for<'de> V: Deserialize<'de> + Serialize + 'static,
+{
+    /// Id with which the cache was registered.
+    pub fn cache_id(&self) -> u16 {
+        self.cache_id
+    }
+
+    pub async fn on_event(&self, r: AOFRecord<K, V>) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        let bytes = self.multiplexer.aof.to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, Some(self.cache_id), r.version, r.origin_ts).await;
+        if self.multiplexer.flush_time.is_some() {
+            let mut unwritten = self.multiplexer.unwritten_inmemory_bytes.lock().await;
+            unwritten.bytes.extend(bytes);
+            unwritten.records += 1;
+        } else {
+            let result = self.multiplexer.aof.write_bytes(&bytes, true).await;
+            self.multiplexer.health.lock().unwrap().record(&result);
+        }
+    }
+
+    /// Same as `on_event` for several records, see `AOFSubscriber::on_event_multi`.
+    pub async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        let count = records.len();
+        self.records.fetch_add(count as u64, Ordering::Relaxed);
+        let mut bytes = vec![];
+        for r in records {
+            bytes.extend(self.multiplexer.aof.to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, Some(self.cache_id), r.version, r.origin_ts).await);
+        }
+        if self.multiplexer.flush_time.is_some() {
+            let mut unwritten = self.multiplexer.unwritten_inmemory_bytes.lock().await;
+            unwritten.bytes.extend(bytes);
+            unwritten.records += count;
+        } else {
+            let result = self.multiplexer.aof.write_bytes(&bytes, true).await;
+            self.multiplexer.health.lock().unwrap().record(&result);
+        }
+    }
+
+    /// Writes `records` right away in one go and waits until they are on the disk, see
+    /// `AOFSubscriber::on_events_synced`.
+    pub async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()> {
+        let count = records.len();
+        let mut bytes = vec![];
+        for r in records {
+            bytes.extend(self.multiplexer.aof.to_single_record_bytes(r.operation, &r.key, &r.value, r.expires_at, Some(self.cache_id), r.version, r.origin_ts).await);
+        }
+        self.multiplexer.write_pending_and(&bytes, true).await?;
+        self.records.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Writes a `PolicyState` record with `state` tagged with the id of the cache right away, see
+    /// `AOFSubscriber::on_policy_state`.
+    pub async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        let bytes = AOF::encode_policy_state(self.multiplexer.aof.value_transform.as_ref(), Some(self.cache_id), state).await;
+        self.multiplexer.write_pending_and(&bytes, true).await?;
+        self.records.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Size of the shared file and approximate number of records of this cache in it, see
+    /// `AOFSubscriber::aof_stats`.
+    pub async fn aof_stats(&self) -> io::Result<AofStats> {
+        Ok(AofStats {
+            file_bytes: self.multiplexer.aof.file_bytes().await?,
+            approx_records: self.records.load(Ordering::Relaxed)
+        })
+    }
+
+    /// See `AOFSubscriber::set_approx_records`.
+    pub fn set_approx_records(&self, records: u64) {
+        self.records.store(records, Ordering::Relaxed);
+    }
+
+    /// Re-opens the shared `AOF`, see `AofMultiplexer::reopen`.
+    pub async fn reopen(&self) -> io::Result<()> {
+        self.multiplexer.reopen().await
+    }
+
+    /// Health of the shared `AOF`, see `AofMultiplexer::flush_health`.
+    pub async fn flush_health(&self) -> FlushHealth {
+        self.multiplexer.flush_health().await
+    }
+
+    pub async fn into_iter(&self) -> io::Result<AOFIterator> {
+        self.multiplexer.aof.into_iter_for(self.cache_id).await
+    }
+}
+
+impl<K, V> Drop for MultiplexedAOFSubscriber<K, V> {
+    fn drop(&mut self) {
+        self.multiplexer.cache_ids.lock().unwrap().remove(&self.cache_id);
+    }
+}
+
+/// `AOF` which keeps its records in memory instead of a file, so that persistence and replay can be tested
+/// without touching the disk or waiting for flushes, see `AsyncCacheBuilder::memory_aof`. Records are encoded
+/// like in a file and appended as soon as they are recorded.
+///
+/// Clones share the same bytes, so a cache built with a clone replays the records of a cache dropped before.
+#[derive(Clone, Default)]
+pub struct MemoryAOF {
+    bytes: Arc<Mutex<Vec<u8>>>,
+    records: Arc<AtomicU64>,
+}
+
+impl MemoryAOF {
+    /// Creates an empty `MemoryAOF`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the bytes recorded so far, in the format of the file of `AOF`.
+    pub async fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().await.clone()
+    }
+
+    /// Iterator over the records recorded so far. Records recorded afterwards are not read by it.
+    pub async fn into_iter(&self) -> AOFIterator {
+        AOFIterator::from_memory(self.bytes().await)
+    }
+
+    /// Encodes the records and appends them to the bytes in memory.
+    pub async fn on_event_multi<K, V>(&self, records: Vec<AOFRecord<K, V>>)
+    where
+        for<'de> K: Deserialize<'de> + Serialize,
+        for<'de> V: Deserialize<'de> + Serialize + 'static,
+    {
+        let mut bytes = vec![];
+        for r in records.iter() {
+            bytes.extend(AOF::encode_record(None, r.operation.clone(), &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await);
+        }
+        self.records.fetch_add(records.len() as u64, Ordering::Relaxed);
+        self.bytes.lock().await.extend(bytes);
+    }
+
+    /// Appends the policy state, see `AOFSubscriber::on_policy_state`.
+    pub async fn on_policy_state(&self, state: Vec<u8>) {
+        let bytes = AOF::encode_policy_state(None, None, state).await;
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.bytes.lock().await.extend(bytes);
+    }
+
+    /// Size of the bytes in memory and approximate number of records in them, see `AOFSubscriber::aof_stats`.
+    pub async fn aof_stats(&self) -> AofStats {
+        AofStats {
+            file_bytes: self.bytes.lock().await.len() as u64,
+            approx_records: self.records.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets the approximate number of records, see `AOFSubscriber::set_approx_records`.
+    pub fn set_approx_records(&self, records: u64) {
+        self.records.store(records, Ordering::Relaxed);
+    }
+}
+
@@ -1,30 +1,131 @@
 //! Code of `Cache` and `AsyncCache` struct which provides functionalities of caching.
 
-use std::collections::HashMap;
-use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Weak};
+use std::borrow::{Borrow, Cow};
+use std::hash::BuildHasher;
+use std::ops::Index;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::sync::Mutex;
 
-use crate::{cache_events::CacheEventSubscriber, common::{AOFRecord, CacheEntry, Operation}, config::{AsyncCacheConfig, CacheSyncConfig}, eviction_policies::common::EvictionPolicy};
+use crate::{aof::{AOFIterator, AofManifest, AofMultiplexer, MemoryAOF, ValueTransform, DEFAULT_MAX_RECORD_BYTES, MANIFEST_FORMAT_VERSION}, cache_events::{CacheEventSubscriber, PersistFilter}, error::{CacheError, CacheFull, CasError, ConfigError, LoaderTimeout}, common::{instant_to_unix_millis, sleep_unless_stopped, BackgroundTasks, unix_millis_to_instant, AOFRecord, AofStats, CacheEntry, CacheStats, CacheOp, Clock, EvictionExplanation, SystemClock, FlushHealth, Lookup, LookupCounts, MemoryBudget, OpResult, Operation, ReplayProgress, StatsStream, UtilizationEvent}, config::{AofFailureMode, AsyncCacheConfig, CacheSyncConfig, FlushStrategy, Watermarks, MAX_PREALLOCATED_KEYS}, eviction_policies::common::EvictionPolicy};
 
 /// This struct, `Cache<K, V, P>`, implements a generic in-memory cache. It utilizes a `HashMap` to store key-value pairs and allows customization of the eviction policy through the `P` generic type, which must implement the `EvictionPolicy<K>` trait.
 /// 
 /// This is basic Cache to use. For using cache with persistence with append only files or using in async env,
 /// please use `AsyncCache`
 /// 
+/// `S` is the hasher of the internal `HashMap`, `RandomState` (SipHash) by default. For trusted keys a faster
+/// hasher can be plugged in via `Cache::with_hasher`.
+/// 
+
+
+/// Storage of the entries of `Cache`, a slab with the `slab` feature, see `slab::SlabMap`.
+#[cfg(not(feature = "slab"))]
+type Entries<K, V, S> = HashMap<K, CacheEntry<V>, S>;
+#[cfg(feature = "slab")]
+type Entries<K, V, S> = crate::slab::SlabMap<K, V, S>;
+
+/// Mutable references to the entries of distinct `keys`, `None` if any key is absent.
+#[cfg(not(feature = "slab"))]
+fn entries_get_many_mut<'a, K, V, S, const N: usize>(
+    entries: &'a mut Entries<K, V, S>,
+    keys: [&K; N],
+) -> Option<[&'a mut CacheEntry<V>; N]>
+where
+    K: Eq + std::hash::Hash,
+    S: BuildHasher,
+{
+    let mut pointers = [std::ptr::null_mut::<CacheEntry<V>>(); N];
+    for (pointer, key) in pointers.iter_mut().zip(keys) {
+        *pointer = entries.get_mut(key)?;
+    }
+    // SAFETY: the keys are distinct, so the pointers are to distinct entries. `HashMap::get_mut` borrows the
+    // map but not the buckets of the other entries, so the pointers taken before stay valid, and the entries
+    // are neither moved nor removed while `entries` is borrowed by the returned references.
+    Some(pointers.map(|pointer| unsafe { &mut *pointer }))
+}
+#[cfg(feature = "slab")]
+fn entries_get_many_mut<'a, K, V, S, const N: usize>(
+    entries: &'a mut Entries<K, V, S>,
+    keys: [&K; N],
+) -> Option<[&'a mut CacheEntry<V>; N]>
+where
+    K: Eq + std::hash::Hash,
+    S: BuildHasher,
+{
+    entries.get_many_mut(keys)
+}
+
+/// Iterator over the entries of consumed `Entries`.
+#[cfg(not(feature = "slab"))]
+type EntriesIntoIter<K, V> = std::collections::hash_map::IntoIter<K, CacheEntry<V>>;
+#[cfg(feature = "slab")]
+type EntriesIntoIter<K, V> = crate::slab::IntoIter<K, V>;
 
+/// Keys evicted recently, without their values, see `Cache::set_ghost_capacity`.
+struct GhostKeys<K> {
+    /// Keys in order of eviction, the oldest first.
+    order: VecDeque<K>,
+    keys: HashSet<K>,
+    capacity: usize,
+    /// Misses of `get` on keys in `keys`.
+    would_have_hit: u64,
+}
 
-pub struct Cache<K, V>
+pub struct Cache<K, V, S = RandomState>
 where
     K: Eq + std::hash::Hash + Clone ,
 {
     /// The maximum size of the cache in number of entries.
     max_size: usize,
 
-    /// The internal HashMap storing key-value pairs with associated cache entries.
-    cache: HashMap<K, CacheEntry<V>>,
+    /// The internal HashMap storing key-value pairs with associated cache entries, or a slab with the `slab`
+    /// feature.
+    cache: Entries<K, V, S>,
 
     /// The eviction policy instance used by the cache to determine eviction behavior.
     eviction_policy: Box<dyn EvictionPolicy<K> + Send>,
+
+    /// Computes weight of entries passed to `EvictionPolicy::on_set_weighted`.
+    weigher: Option<Weigher<K, V>>,
+
+    /// Version assigned to the last written entry, versions are unique within the cache.
+    last_version: u64,
+
+    /// Estimated heap bytes of an entry added by `estimated_bytes` when there is no weigher.
+    entry_overhead: usize,
+
+    /// Maximum estimated bytes of all the entries, see `set_max_memory`.
+    max_bytes: Option<u64>,
+
+    /// Estimated bytes of all the entries, only kept up to date when `max_bytes` is set.
+    bytes: u64,
+
+    /// Source of the current time for expiry, see `set_clock`.
+    clock: Box<dyn Clock>,
+
+    /// Keys of the entries with an expiry, ordered by `(expires_at, version)`, so that expired entries are
+    /// found without scanning the whole cache.
+    expiries: BTreeMap<(Instant, u64), K>,
+
+    /// Keys skipped by eviction, see `pin`.
+    pinned: HashSet<K>,
+
+    /// Recently evicted keys to count misses a bigger cache would have hit, see `set_ghost_capacity`.
+    ghost: Option<GhostKeys<K>>,
+
+    /// Maps keys to their canonical form before they are looked up or inserted, see `set_key_normalizer`.
+    key_normalizer: Option<KeyNormalizer<K>>,
+
+    /// Lookups of `get` by outcome, see `lookup_counts`.
+    lookups: LookupCounts,
 }
 
 impl<K, V> Cache<K, V>
@@ -36,236 +137,3308 @@ where
     /// This function constructs a new cache with the provided `config`.
     /// 
     pub fn new(config: CacheSyncConfig<K>) -> Self {
+        Self::with_hasher(config, RandomState::new())
+    }
+
+    /// Creates a new `Cache` instance pre-populated with the given entries.
+    ///
+    /// Entries are inserted with `put` in iteration order, so evictions happen and the eviction policy
+    /// ends up in the same state as if the entries had been inserted one by one.
+    ///
+    pub fn from_entries<I: IntoIterator<Item = (K, V)>>(config: CacheSyncConfig<K>, entries: I) -> Self {
+        let mut cache = Self::new(config);
+        cache.put_many(entries);
+        cache
+    }
+
+    /// Creates a new `Cache` instance with the memory, clock, ghost and key options of `AsyncCacheBuilder`.
+    fn with_options(
+        config: CacheSyncConfig<K>,
+        weigher: Option<Weigher<K, V>>,
+        entry_overhead: usize,
+        max_memory: Option<MemoryBudget>,
+        clock: Option<Box<dyn Clock>>,
+        ghost_capacity: usize,
+        key_normalizer: Option<KeyNormalizer<K>>,
+    ) -> Self {
+        let mut cache = Cache {
+            weigher,
+            entry_overhead,
+            clock: clock.unwrap_or_else(|| Box::new(SystemClock)),
+            key_normalizer,
+            ..Self::new(config)
+        };
+        if let Some(max_memory) = max_memory {
+            // resolved by `AsyncCacheBuilder::option_errors` before.
+            let _ = cache.set_max_memory(max_memory);
+        }
+        cache.set_ghost_capacity(ghost_capacity);
+        cache
+    }
+}
+
+impl<K, V, S> Cache<K, V, S>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    S: BuildHasher,
+{
+    /// Creates a new `Cache` instance which uses `hash_builder` to hash keys.
+    ///
+    /// Built-in eviction policies keep using the default hasher, to use a custom hasher in them as well,
+    /// construct the policy with its `with_hasher` and pass it through `CacheSyncConfig::Custom`.
+    /// 
+    pub fn with_hasher(config: CacheSyncConfig<K>, hash_builder: S) -> Self {
         let max_size = config.get_config().max_size;
         let policy_type = config.get_policy_type();
         Cache {
-            cache: HashMap::new(),
+            cache: Entries::with_hasher(hash_builder),
             max_size,
-            eviction_policy: policy_type.create_policy()
+            eviction_policy: policy_type.create_policy(max_size),
+            weigher: None,
+            last_version: 0,
+            entry_overhead: 0,
+            max_bytes: None,
+            bytes: 0,
+            clock: Box::new(SystemClock),
+            expiries: BTreeMap::new(),
+            pinned: HashSet::new(),
+            ghost: None,
+            key_normalizer: None,
+            lookups: LookupCounts::default(),
         }
     }
 }
 
-impl<K, V> Cache<K, V>
+impl<K, V, S> Cache<K, V, S>
 where
-    K: Eq + std::hash::Hash + Clone + std::fmt::Debug
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
+    S: BuildHasher,
 {
+    /// Sets the function which computes the weight of an entry on every insert. The weight is passed to the
+    /// eviction policy through `EvictionPolicy::on_set_weighted` instead of calling `on_set`.
+    ///
+    /// Built-in policies ignore weights, this is meant for size or cost aware custom policies.
+    pub fn set_weigher<F>(&mut self, weigher: F)
+    where
+        F: Fn(&K, &V) -> usize + Send + 'static,
+    {
+        self.weigher = Some(Box::new(weigher));
+        self.recount_bytes();
+    }
+
+    /// Sets the function which maps keys to their canonical form, e.g. lowercases them for a case-insensitive
+    /// cache, applied by every method taking a `K` or `&K`, e.g. `get`, `put`, `set_ttl`, `compare_and_swap` or
+    /// `pin`, before hashing, so that keys it maps to the same key are equal. The eviction policy only sees the
+    /// normalized keys. Lookups of a borrowed form of the key, e.g. `get_borrowed`, are not normalized.
+    ///
+    /// Keys already in the cache are not normalized, so set it on an empty cache. It must be idempotent and, with
+    /// `AOF`, stay the same across restarts, as replayed keys are normalized again.
+    pub fn set_key_normalizer<F>(&mut self, key_normalizer: F)
+    where
+        F: Fn(&K) -> K + Send + 'static,
+    {
+        self.key_normalizer = Some(Box::new(key_normalizer));
+    }
+
+    /// Returns the canonical form of `key`, see `set_key_normalizer`, `key` itself if there is no normalizer.
+    pub fn normalize_key(&self, key: K) -> K {
+        match self.key_normalizer.as_ref() {
+            Some(key_normalizer) => key_normalizer(&key),
+            None => key,
+        }
+    }
+
+    /// Same as `normalize_key`, borrowing `key` if there is no normalizer.
+    fn normalized<'a>(&self, key: &'a K) -> Cow<'a, K> {
+        match self.key_normalizer.as_ref() {
+            Some(key_normalizer) => Cow::Owned(key_normalizer(key)),
+            None => Cow::Borrowed(key),
+        }
+    }
+
+    /// Sets the estimated bytes an entry owns on the heap, e.g. the average length of string values, added to
+    /// the inline size of every entry by `estimated_bytes` when there is no weigher. `0` by default.
+    pub fn set_entry_overhead(&mut self, entry_overhead: usize) {
+        self.entry_overhead = entry_overhead;
+        self.recount_bytes();
+    }
+
+    /// Limits the estimated memory of the entries, see `estimated_bytes`, on top of `max_size`. Inserts evict
+    /// until the entries fit into the budget again, the inserted entry being kept unless the eviction policy
+    /// picks it. `NoEviction` never evicts, so the budget is not enforced there.
+    ///
+    /// `MemoryBudget::PercentOfSystem` is resolved to bytes here, once. Entries are weighed when inserted and
+    /// removed, so values changed in place through `get_mut` should keep their weight.
+    ///
+    /// Fails without changing anything if the budget is invalid or total system memory can not be read, see
+    /// `MemoryBudget::resolve`.
+    pub fn set_max_memory(&mut self, max_memory: MemoryBudget) -> Result<(), ConfigError> {
+        self.max_bytes = Some(max_memory.resolve()?);
+        self.recount_bytes();
+        self.evict_over_budget(None);
+        Ok(())
+    }
+
+    /// Remembers the last `capacity` evicted keys, without their values, to count the misses of `get` on them
+    /// in `would_have_hit`, i.e. the misses a bigger cache would have hit, e.g. to decide whether to increase
+    /// `max_size`. `0` (default) remembers nothing.
+    ///
+    /// Forgets the keys remembered so far and resets `would_have_hit`. A key evicted again keeps its place
+    /// among the remembered keys, so the oldest evictions are forgotten first.
+    pub fn set_ghost_capacity(&mut self, capacity: usize) {
+        self.ghost = (capacity > 0).then(|| GhostKeys {
+            order: VecDeque::with_capacity(capacity.min(MAX_PREALLOCATED_KEYS)),
+            keys: HashSet::new(),
+            capacity,
+            would_have_hit: 0,
+        });
+    }
+
+    /// Number of misses of `get` on keys evicted recently, see `set_ghost_capacity`. Always `0` without it.
+    pub fn would_have_hit(&self) -> u64 {
+        self.ghost.as_ref().map_or(0, |x| x.would_have_hit)
+    }
+
+    /// Number of lookups of `get` which hit, missed an absent key or missed a key whose entry had expired.
+    /// Lookups which don't count as an access, e.g. `peek`, are not counted.
+    pub fn lookup_counts(&self) -> LookupCounts {
+        self.lookups
+    }
+
+    /// Sets the source of the current time used for expiry, `SystemClock` by default. A `MockClock` lets tests
+    /// check expiry without sleeping.
+    ///
+    /// Expiry persisted in `AOF` is converted with the system time, so a clock far from it only suits caches
+    /// without `AOF`.
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Returns the memory budget in bytes, see `set_max_memory`. `None` if there is none.
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    /// Estimates the memory used by the entries, for capacity planning.
+    ///
+    /// With a weigher, see `set_weigher`, it is the sum of the weights of all the entries, so the weigher
+    /// should return bytes. Otherwise it is the inline size of a key and an entry (`std::mem::size_of`) plus
+    /// `entry_overhead` for every entry.
+    ///
+    /// This is an approximation: heap memory owned by keys and values is only accounted for by the weigher
+    /// or the overhead, and the spare capacity of the internal `HashMap` and the eviction policy is ignored.
+    /// Expired entries which have not been removed yet are counted.
+    pub fn estimated_bytes(&self) -> usize {
+        self.cache.iter().map(|(key, entry)| self.entry_bytes(key, &entry.value)).sum()
+    }
+
+    /// Estimated bytes of a single entry, see `estimated_bytes`.
+    fn entry_bytes(&self, key: &K, value: &V) -> usize {
+        match self.weigher.as_ref() {
+            Some(weigher) => weigher(key, value),
+            None => std::mem::size_of::<K>() + std::mem::size_of::<CacheEntry<V>>() + self.entry_overhead,
+        }
+    }
+
+    /// Recomputes `bytes` from scratch if there is a memory budget, e.g. after the weigher has changed.
+    fn recount_bytes(&mut self) {
+        if self.max_bytes.is_some() {
+            self.bytes = self.estimated_bytes() as u64;
+        }
+    }
+
+    /// Accounts for an entry being inserted (`added`) or removed if there is a memory budget.
+    fn track_bytes(&mut self, key: &K, value: &V, added: bool) {
+        if self.max_bytes.is_some() {
+            let bytes = self.entry_bytes(key, value) as u64;
+            self.bytes = if added { self.bytes + bytes } else { self.bytes.saturating_sub(bytes) };
+        }
+    }
+
+    /// Evicts until the entries fit into the memory budget, or the eviction policy has no victim. `inserted` is
+    /// only evicted if the policy picks it.
+    fn evict_over_budget(&mut self, inserted: Option<&K>) {
+        let Some(max_bytes) = self.max_bytes else { return };
+        while self.bytes > max_bytes {
+            let Some(evicted) = self.evict_unpinned() else { break };
+            self.remove_evicted(&evicted);
+            if inserted == Some(&evicted) {
+                break;
+            }
+        }
+    }
+
+    /// Removes the entry of a key which the eviction policy has evicted.
+    fn remove_evicted(&mut self, evicted: &K) {
+        if let Some(entry) = self.cache.remove(evicted) {
+            self.track_bytes(evicted, &entry.value, false);
+            self.unindex_expiry(&entry);
+            if let Some(ghost) = self.ghost.as_mut() {
+                if ghost.keys.insert(evicted.clone()) {
+                    ghost.order.push_back(evicted.clone());
+                }
+                if ghost.order.len() > ghost.capacity {
+                    let oldest = ghost.order.pop_front().unwrap();
+                    ghost.keys.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    /// Counts a lookup of `key` by `get` in `lookup_counts` after its expired entry, if any, was removed, which
+    /// `expired` tells. A miss on a key evicted recently is counted in `would_have_hit` as well.
+    fn note_lookup<Q>(&mut self, key: &Q, expired: bool)
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        if self.cache.contains_key(key) {
+            self.lookups.hits += 1;
+            return;
+        }
+        match expired {
+            true => self.lookups.misses_expired += 1,
+            false => self.lookups.misses_absent += 1,
+        }
+        if let Some(ghost) = self.ghost.as_mut() {
+            if ghost.keys.contains(key) {
+                ghost.would_have_hit += 1;
+            }
+        }
+    }
+
+    /// Evicts until an entry of `key` with `value` fits into `max_size` and the memory budget, see `try_put`.
+    /// Returns whether it fits.
+    fn make_room(&mut self, key: &K, value: &V) -> bool {
+        // `max_size` of `NoEviction` is `0`, as there is no limit.
+        if self.max_size > 0 && self.cache.len() >= self.max_size && !self.cache.contains_key(key) {
+            let Some(evicted) = self.evict_unpinned() else { return false };
+            self.remove_evicted(&evicted);
+        }
+        let Some(max_bytes) = self.max_bytes else { return true };
+        let needed = self.entry_bytes(key, value) as u64;
+        loop {
+            // the previous value of the key is replaced, so its bytes are freed.
+            let freed = self.cache.get(key).map_or(0, |x| self.entry_bytes(key, &x.value) as u64);
+            if self.bytes.saturating_sub(freed) + needed <= max_bytes {
+                return true;
+            }
+            let Some(evicted) = self.evict_unpinned() else { return false };
+            self.remove_evicted(&evicted);
+        }
+    }
+
+    /// Evicts the next victim of the eviction policy which is not pinned. Pinned victims are tracked again as
+    /// newly set keys. `None` if the policy has no victim left or only pinned ones.
+    fn evict_unpinned(&mut self) -> Option<K> {
+        if self.pinned.is_empty() {
+            return self.eviction_policy.evict();
+        }
+        let mut skipped = vec![];
+        let mut victim = None;
+        // every pinned key is skipped at most once, so that the loop ends when all the keys are pinned.
+        for _ in 0..=self.pinned.len() {
+            match self.eviction_policy.evict() {
+                Some(key) if self.pinned.contains(&key) => skipped.push(key),
+                key => {
+                    victim = key;
+                    break;
+                },
+            }
+        }
+        for key in skipped {
+            let weight = self.weigher.as_ref().zip(self.cache.get(&key)).map(|(weigher, entry)| weigher(&key, &entry.value));
+            match weight {
+                Some(weight) => self.eviction_policy.on_set_weighted(key, weight),
+                None => self.eviction_policy.on_set(key),
+            }
+        }
+        victim
+    }
+
+    /// Pins the key, so that the eviction policy skips its entry, e.g. for configuration which must never be
+    /// evicted. Pinned entries still expire and can be removed. The pin is kept on the key, also while it is
+    /// absent, until `unpin`. Returns whether the key was not pinned already.
+    ///
+    /// Pinned entries still count towards `max_size` and the memory budget, so if there are not enough unpinned
+    /// entries to evict, the cache grows past them.
+    pub fn pin(&mut self, key: &K) -> bool {
+        let key = self.normalized(key).into_owned();
+        self.pinned.insert(key)
+    }
+
+    /// Unpins the key, see `pin`. Returns whether it was pinned.
+    pub fn unpin(&mut self, key: &K) -> bool {
+        let normalized = self.normalized(key);
+        self.pinned.remove(normalized.as_ref())
+    }
+
+    /// Returns whether the key is pinned, see `pin`.
+    pub fn is_pinned(&self, key: &K) -> bool {
+        self.pinned.contains(self.normalized(key).as_ref())
+    }
+
     /// Retrieves the value associated with the given key from the cache.
 
     /// This function attempts to retrieve the value for the provided `key`. It checks if the key exists in the cache, and if so, calls the eviction policy's `on_get` method. If the key is found, an immuatable reference to the value is returned. Otherwise, `None` is returned.
 
     pub fn get(&mut self, key: &K) -> Option<&V>
     {
-        self.eviction_policy.on_get(key);
+        self.get_opts(key, true)
+    }
+
+    /// Same as `get`, but the eviction policy's `on_get` is called only if `record_access` is `true`, so that
+    /// e.g. housekeeping reads don't count as uses of the entries.
+    pub fn get_opts(&mut self, key: &K, record_access: bool) -> Option<&V>
+    {
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        let expired = self.remove_if_expired(key);
+        if record_access {
+            self.eviction_policy.on_get(key);
+            self.note_lookup(key, expired);
+        }
         self.cache.get(key).map(|x| &x.value)
     }
 
+    /// Retrieves the value without counting as an access, same as `get_opts(key, false)`.
+    pub fn peek(&mut self, key: &K) -> Option<&V>
+    {
+        self.get_opts(key, false)
+    }
+
     /// Retrieves mutable pointer to the value associated with the given key from the cache.
 
     /// This function attempts to retrieve the value for the provided `key`. It checks if the key exists in the cache, and if so, calls the eviction policy's `on_get` method. If the key is found, an muatable reference to the value is returned. Otherwise, `None` is returned.
 
     pub fn get_mut(&mut self, key: &K) -> Option<&mut V>
     {
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        self.remove_if_expired(key);
         self.eviction_policy.on_get(key);
         self.cache.get_mut(key).map(|x| &mut x.value)
     }
 
-    /// Inserts a new key-value pair into the cache.
-
-    /// This function inserts a new key-value pair into the cache. It checks if the cache is at its maximum size, and if necessary, evicts an entry using the eviction policy. The new key-value pair is then inserted into the cache along with a `CacheEntry` and the eviction policy's `on_set` method is called.
-    /// 
-
-    pub fn put(&mut self, key: K, value: V) {
-        if self.cache.len() >= self.max_size && !self.contains_key(&key){
-            if let Some(evicted) = self.eviction_policy.evict() {
-                self.cache.remove(&evicted);
+    /// Retrieves mutable references to the values of several distinct keys at once, e.g. to update related
+    /// entries together. Returns `None` if any key is absent (or expired) or if a key is given more than once,
+    /// as the references would alias, without counting any access then. Otherwise the eviction policy's
+    /// `on_get` is called once for every key, in order.
+    pub fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let normalized = keys.map(|key| self.normalized(key));
+        let keys: [&K; N] = std::array::from_fn(|i| normalized[i].as_ref());
+        for i in 0..N {
+            if keys[..i].contains(&keys[i]) {
+                return None;
             }
         }
-        match self.cache.get_mut(&key) {
-            Some(v) => {
-                v.value = value;
-            },
-            None => {
-                self.cache.insert(key.clone(), CacheEntry::new(value));
-            }
-        };
+        for key in keys {
+            self.remove_if_expired(key);
+        }
+        if keys.iter().any(|key| !self.cache.contains_key(*key)) {
+            return None;
+        }
+        for key in keys {
+            self.eviction_policy.on_get(key);
+        }
+        let entries = entries_get_many_mut(&mut self.cache, keys)?;
+        Some(entries.map(|entry| &mut entry.value))
+    }
 
-        self.eviction_policy.on_set(key);
+    /// Same as `get_mut`, but without calling the eviction policy's `on_get`, e.g. to update bookkeeping in a
+    /// value which should not protect it from eviction.
+    pub fn get_mut_no_touch(&mut self, key: &K) -> Option<&mut V>
+    {
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        self.remove_if_expired(key);
+        self.cache.get_mut(key).map(|x| &mut x.value)
     }
 
-    /// Removes the entry with the given key from the cache.
+    /// Returns a clone of the value, or `V::default()` if the key is absent. Nothing is inserted.
+    ///
+    /// Unlike `get`, the eviction policy's `on_get` is only called on a hit.
+    pub fn get_or_default(&mut self, key: &K) -> V
+    where
+        V: Default + Clone,
+    {
+        let normalized = self.normalized(key);
+        self.get_borrowed(normalized.as_ref()).cloned().unwrap_or_default()
+    }
 
-    /// This function removes the entry associated with the provided `key` from the cache. It removes the entry if it exists. If an entry is removed, the eviction policy's `remove` method is called.
+    /// Returns a mutable reference to the value, inserting `V::default()` first if the key is absent, e.g. to
+    /// update counters in place. A hit counts as an access, an insert works like `put`.
+    pub fn entry_or_default(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        self.get_or_insert_with_mut(key, V::default)
+    }
 
-    pub fn remove(&mut self, key: &K) {
-        self.cache.remove(key);
-        self.eviction_policy.remove(key.clone());
+    /// Returns a mutable reference to the value, inserting the value returned by `f` first if the key is absent
+    /// (or expired), e.g. to append to a `Vec` stored as the value. `f` is only called on a miss. A hit counts as
+    /// an access, an insert works like `put`, evicting if needed.
+    pub fn get_or_insert_with_mut(&mut self, key: K, f: impl FnOnce() -> V) -> &mut V {
+        let key = self.normalize_key(key);
+        let expired = self.remove_if_expired(&key);
+        self.note_lookup(&key, expired);
+        if self.cache.contains_key(&key) {
+            self.eviction_policy.on_get(&key);
+        } else {
+            self.put(key.clone(), f());
+        }
+        &mut self.cache.get_mut(&key).unwrap().value
     }
 
-    ///Checks if key is already in cache.
-    /// 
-    /// This does not account for access.
+    /// Inserts a new key-value pair into the cache.
+
+    /// This function inserts a new key-value pair into the cache. It checks if the cache is at its maximum size, and if necessary, evicts an entry using the eviction policy. The new key-value pair is then inserted into the cache along with a `CacheEntry` and the eviction policy's `on_set` method is called.
     /// 
 
-    pub fn contains_key(&self, key: &K) -> bool {
-        return self.cache.contains_key(&key);
+    pub fn put(&mut self, key: K, value: V) {
+        let _ = self.put_entry(key, CacheEntry::new(value));
     }
 
-    ///Returns the current size of the cache. The number of keys in the cache at the moment.
-    pub fn size(&self) -> usize {
-        return self.cache.len();
+    /// Same as `put`, but fails instead of growing past `max_size` or the memory budget if no entry can be
+    /// evicted to make room, e.g. because all of them are pinned, so that capacity is a hard limit. The key and
+    /// the value are given back in `CacheFull`. Entries evicted before it fails stay evicted.
+    ///
+    /// Returns the previous value of the key.
+    pub fn try_put(&mut self, key: K, value: V) -> Result<Option<V>, CacheFull<K, V>> {
+        let key = self.normalize_key(key);
+        if !self.make_room(&key, &value) {
+            return Err(CacheFull { key, value });
+        }
+        Ok(self.put_entry(key, CacheEntry::new(value)))
     }
 
-    /// Returns a raw pointer to the value associated with the given key.
+    /// Inserts a new key-value pair into the cache which expires after `ttl`.
     ///
-    /// Returns a raw pointer to the value associated with the given key, if it exists
-    /// in the cache. This method is unsafe due to potential dangling pointers and should
-    /// only be used in environments where it is safe to manage raw pointers manually.
-    fn get_raw(&mut self, key: &K) -> Option<*const V> {
-        self.get(key).map(|x| x as *const V)
-    }
-}
+    /// Works like `put`, but once `ttl` has passed the entry is treated as absent. Expired entries are removed
+    /// lazily when accessed or explicitly through `purge_expired`, until then they still occupy space.
 
+    pub fn put_with_ttl(&mut self, key: K, value: V, ttl: Duration) {
+        let entry = self.entry_with_ttl(value, ttl);
+        let _ = self.put_entry(key, entry);
+    }
 
-/// A more advanced cache exposing `async` functions, suitable for concurrent environments.
-/// 
-/// It uses `Mutex` around `Cache` to provide synchronization.
-/// 
-/// `AOF` related configurations can be passed in `new()` method to persist data to restart the cache
-/// from the same point where it was stopped or crashed. Although some data may be lost, please go through
-/// `AsyncCacheConfig` for more info.
-/// 
+    /// Creates an entry which expires after `ttl` on the clock of the cache, never if `ttl` is too long to be
+    /// represented as an `Instant`.
+    fn entry_with_ttl(&self, value: V, ttl: Duration) -> CacheEntry<V> {
+        CacheEntry { value, expires_at: self.clock.now().checked_add(ttl), version: 0, origin_ts: None }
+    }
 
-pub struct AsyncCache<K, V>
-where
-    for<'de> K: Eq + std::hash::Hash + Clone + Deserialize<'de> + Serialize + Send + Sync,
-    for<'de> V: Deserialize<'de> + Serialize + Send + Sync,
-{
-    cache: Mutex<Cache<K, V>>,
-    persist_read_ops: Option<bool>,
-    subscriber_manager: CacheEventSubscriber<K, V>
-}
+    /// Returns the time left until the entry expires, `None` if the key is absent, has expired or never expires.
+    ///
+    /// This does not account for access.
+    pub fn get_ttl(&self, key: &K) -> Option<Duration> {
+        let now = self.clock.now();
+        self.cache.get(self.normalized(key).as_ref())
+            .filter(|x| !x.is_expired_at(now))?
+            .expires_at
+            .map(|x| x.saturating_duration_since(now))
+    }
 
-impl<K, V> AsyncCache <K, V>
-where
-    for<'de> K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + Deserialize<'de> + Serialize + 'static,
-    for<'de> V: Clone + Deserialize<'de> + Serialize + Send + Sync + 'static
-{
-    /// Creates a new `AsyncCache` instance based on configurations.
-    /// 
-    /// In case of `AOF`, if given `cache_name` already exists in persistent files, it goes through all the
-    /// operations sequentially and performs those on the newly created instance to get the latest cache.
-    /// 
-    /// Data may be lost in case of `flush_time` being not `None` for the last `flush_time` milliseconds before
-    /// crash or stop.
-    /// 
-    /// Changing `EvictionPolicy` may load different keys as no meta data regarding policy, flushtime etc
-    /// is persisted.
+    /// Makes the entry expire after `ttl`, or never in case of `None` or a `ttl` too long to be represented as
+    /// an `Instant`, keeping its value and version. Returns whether the key was present, an expired entry is
+    /// removed instead.
     ///
-    /// In case of `NoEviction` and `read heavy` cache, using `flush_time = None` with `persist_read_ops = false`
-    /// i.e. flush on every write but reads will not be persisted remove may be useful as `writes` 
-    /// speed will be slow but `reads` will become faster.
-    /// 
-    /// In case of eviction policies, setting `flush_time` as `None` is *NOT RECOMMENDED* as it will make it as slow
-    /// as disk io.
-    /// 
-    pub async fn new(config: AsyncCacheConfig<K>) -> Self {
-        let instance = Self {
-            persist_read_ops: config.persist_read_ops(),
-            subscriber_manager: match config.get_aof_config() {
-                Some(v) => CacheEventSubscriber::new(Some(v.0), Some(v.1), v.2).await,
-                None => CacheEventSubscriber::new(None, None, None).await
-            },
-            cache: Mutex::new(Cache::new(config.get_sync_config()))
+    /// This does not account for access.
+    pub fn set_ttl(&mut self, key: &K, ttl: Option<Duration>) -> bool {
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        self.remove_if_expired(key);
+        let now = self.clock.now();
+        let Some(entry) = self.cache.get_mut(key) else {
+            return false;
         };
-        // performing operations sequentially as per `AOF`.
-        let mut gaurd = instance.cache.lock().await;
-        if let Ok(mut iter) = instance.subscriber_manager.into_iter().await {
-            while let Ok(Some(record)) = iter.next().await {
-                match record.operation {
-                    Operation::Get => {
-                        let _ = gaurd.get(&record.key);
-                    },
-                    Operation::Put => gaurd.put(record.key, record.value.unwrap()),
-                    Operation::Remove => gaurd.remove(&record.key)
-                }
-            }
+        if let Some(expires_at) = entry.expires_at {
+            self.expiries.remove(&(expires_at, entry.version));
         }
-        drop(gaurd);
-        instance
+        entry.expires_at = ttl.and_then(|x| now.checked_add(x));
+        if let Some(expires_at) = entry.expires_at {
+            self.expiries.insert((expires_at, entry.version), key.clone());
+        }
+        true
     }
 
-    /// Retrieves the value associated with the given key from the cache.
+    /// Inserts all the entries with `put` in iteration order, reserving room for them up front.
     ///
-    /// Asynchronously retrieves the value associated with the provided `key` from the cache.
-    /// Returns `None` if the key is not found.
-    
+    /// For LRU and FIFO the first entries are evicted first, so if there are more entries than `max_size`
+    /// only the last ones are kept.
+    pub fn put_many<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        let entries = entries.into_iter();
+        self.reserve(entries.size_hint().0.min(MAX_PREALLOCATED_KEYS));
+        for (key, value) in entries {
+            self.put(key, value);
+        }
+    }
 
-    pub async fn get(&self, key: &K) -> Option<V>
-    {
-        let mut guard = self.cache.lock().await;
-        let value = guard.get(key).cloned();
-        if self.persist_read_ops.as_ref().is_some_and(|x| x.clone()) {
-            self.subscriber_manager.on_event(AOFRecord {
-                key: key.clone(),
-                value: None,
-                operation: crate::common::Operation::Get
-            }).await;
-        };
-        drop(guard);
-        value
+    /// Inserts a new key-value pair into the cache and returns the previous value of the key.
+    ///
+    /// Works like `put`, eviction happens only if the key is new. An expired previous value is not returned.
+    pub fn replace(&mut self, key: K, value: V) -> Option<V> {
+        self.put_entry(key, CacheEntry::new(value))
     }
 
-    /// Retrieves a reference to the value associated with the given key from the cache.
+    /// Writes `value` only if the key is present, never inserting, e.g. to refresh a value without bringing
+    /// back one removed meanwhile. Returns the previous value, `None` if the key is absent (or expired) and
+    /// nothing was written. The value is written like `put`, i.e. without expiry.
+    pub fn replace_if_present(&mut self, key: &K, value: V) -> Option<V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.put_entry(key.clone(), CacheEntry::new(value))
+    }
+
+    /// Retrieves the value along with its version, to be passed to `compare_and_swap` later.
     ///
-    /// Asynchronously retrieves a reference to the value associated with the provided `key` from the cache.
-    /// Returns `None` if the key is not found.
+    /// Counts as an access like `get`.
+    pub fn get_with_version(&mut self, key: &K) -> Option<(&V, u64)> {
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        self.remove_if_expired(key);
+        self.eviction_policy.on_get(key);
+        self.cache.get(key).map(|x| (&x.value, x.version))
+    }
+
+    /// Writes `value` only if the current version of the key is `expected_version` and returns the new version.
     ///
-    /// **Safety Note:** This method returns a reference that may become invalid in a multithreaded environment
-    /// due to potential concurrent modifications. Use with caution in single-threaded environments only.
-    
-    pub async fn get_ref(&self, key: &K) -> Option<&V>
+    /// Every write assigns the entry a new version, greater than all the versions assigned before in this cache.
+    /// Absent (or expired) keys have version `0`, so `expected_version = 0` inserts only if the key is absent.
+    /// The value is written like `put`, i.e. without expiry.
+    pub fn compare_and_swap(&mut self, key: K, expected_version: u64, value: V) -> Result<u64, CasError> {
+        let key = self.normalize_key(key);
+        self.remove_if_expired(&key);
+        let current = self.cache.get(&key).map_or(0, |x| x.version);
+        if current != expected_version {
+            return Err(CasError::VersionMismatch { expected: expected_version, current });
+        }
+        let _ = self.put_entry(key, CacheEntry::new(value));
+        Ok(self.last_version)
+    }
+
+    /// Removes the key only if its current value equals `expected`, e.g. to invalidate a value without deleting
+    /// the one another writer just refreshed. Returns whether the key was removed, expired keys are not.
+    pub fn compare_and_remove(&mut self, key: &K, expected: &V) -> bool
+    where
+        V: PartialEq,
     {
-        let mut gaurd = self.cache.lock().await;
-        let val = gaurd.get_raw(key).map(|x| unsafe{x.as_ref()}).flatten();
-        if self.persist_read_ops.as_ref().is_some_and(|x| x.clone()) {
-            self.subscriber_manager.on_event(AOFRecord {
-                key: key.clone(),
-                value: None,
-                operation: crate::common::Operation::Get
-            }).await;
-        };
-        drop(gaurd);
-        val
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        self.remove_if_expired(key);
+        if !self.cache.get(key).is_some_and(|x| x.value == *expected) {
+            return false;
+        }
+        self.remove_borrowed(key);
+        true
     }
 
-    /// Inserts a new key-value pair into the cache.
+    /// Writes `value` only if `timestamp` is greater than the timestamp of the current value, i.e. last writer
+    /// wins, e.g. while merging writes of several replicas. Returns whether the value was written.
+    ///
+    /// The timestamp is given by the origin of the value and kept in the entry. Absent (or expired) keys and
+    /// values written by the other methods, which have no timestamp, are always overwritten. On a tie the current
+    /// value is kept. The value is written like `put`, i.e. without expiry.
+    pub fn put_if_newer(&mut self, key: K, value: V, timestamp: u64) -> bool {
+        let key = self.normalize_key(key);
+        self.remove_if_expired(&key);
+        if self.cache.get(&key).and_then(|x| x.origin_ts).is_some_and(|x| x >= timestamp) {
+            return false;
+        }
+        let entry = CacheEntry { origin_ts: Some(timestamp), ..CacheEntry::new(value) };
+        let _ = self.put_entry(key, entry);
+        true
+    }
+
+    /// Inserts the entry with a new version, evicting if needed. Overwriting a key replaces its expiry as well.
+    ///
+    /// Returns the previous value if it had not expired.
+    fn put_entry(&mut self, key: K, mut entry: CacheEntry<V>) -> Option<V> {
+        self.last_version += 1;
+        entry.version = self.last_version;
+        self.insert_entry(key, entry)
+    }
+
+    /// Inserts the entry keeping its version, e.g. while replaying `AOF`.
+    fn restore_entry(&mut self, key: K, entry: CacheEntry<V>) {
+        self.last_version = self.last_version.max(entry.version);
+        let _ = self.insert_entry(key, entry);
+    }
+
+    /// Inserts the entry as it is, evicting if needed.
+    fn insert_entry(&mut self, key: K, entry: CacheEntry<V>) -> Option<V> {
+        let key = self.normalize_key(key);
+        if self.cache.len() >= self.max_size && !self.cache.contains_key(&key){
+            if let Some(evicted) = self.evict_unpinned() {
+                self.remove_evicted(&evicted);
+            }
+        }
+        let weight = self.weigher.as_ref().map(|weigher| weigher(&key, &entry.value));
+        self.track_bytes(&key, &entry.value, true);
+        let expiry = entry.expires_at.map(|x| (x, entry.version));
+        let previous = self.cache.insert(key.clone(), entry);
+        if let Some(previous) = previous.as_ref() {
+            self.track_bytes(&key, &previous.value, false);
+            self.unindex_expiry(previous);
+        }
+        if let Some(expiry) = expiry {
+            self.expiries.insert(expiry, key.clone());
+        }
+
+        match weight {
+            Some(weight) => self.eviction_policy.on_set_weighted(key.clone(), weight),
+            None => self.eviction_policy.on_set(key.clone()),
+        }
+        self.evict_over_budget(Some(&key));
+        previous.filter(|x| !x.is_expired_at(self.clock.now())).map(|x| x.value)
+    }
+
+    /// Evicts up to `n` entries picked by the eviction policy, skipping pinned ones, and returns their keys, e.g.
+    /// to make room ahead of a burst of inserts. Fewer are evicted if the policy runs out of victims.
+    pub fn evict_n(&mut self, n: usize) -> Vec<K> {
+        let mut evicted = vec![];
+        while evicted.len() < n {
+            let Some(key) = self.evict_unpinned() else { break };
+            self.remove_evicted(&key);
+            evicted.push(key);
+        }
+        evicted
+    }
+
+    /// Removes all the expired entries from the cache and returns their keys.
+    ///
+    /// Expired entries are found through an index ordered by expiry, so only they are visited.
+
+    pub fn purge_expired(&mut self) -> Vec<K> {
+        self.drain_expired().into_iter().map(|(key, _)| key).collect()
+    }
+
+    /// Same as `purge_expired`, but returns the removed entries with their values, e.g. to refresh them from
+    /// the source. Entries evicted for capacity are not included, they never show up here.
+    pub fn drain_expired(&mut self) -> Vec<(K, V)> {
+        let now = self.clock.now();
+        let live = self.expiries.split_off(&(now, u64::MAX));
+        let expired = std::mem::replace(&mut self.expiries, live);
+        let mut drained = Vec::with_capacity(expired.len());
+        for key in expired.into_values() {
+            if let Some(entry) = self.cache.remove(&key) {
+                self.eviction_policy.remove(key.clone());
+                self.track_bytes(&key, &entry.value, false);
+                drained.push((key, entry.value));
+            }
+        }
+        drained
+    }
+
+    /// Removes the entry if it has expired. Returns whether it was removed.
+    fn remove_if_expired<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let now = self.clock.now();
+        if self.cache.get(key).is_some_and(|x| x.is_expired_at(now)) {
+            if let Some((key, entry)) = self.cache.remove_entry(key) {
+                self.track_bytes(&key, &entry.value, false);
+                self.unindex_expiry(&entry);
+                self.eviction_policy.remove(key);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Same as `get`, but takes any borrowed form of the key, e.g. `&str` for `String` keys, so that no
+    /// owned key has to be allocated for the lookup.
+    ///
+    /// Unlike `get`, the eviction policy's `on_get` is only called on a hit, with the key stored in the cache.
+    pub fn get_borrowed<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.get_entry_borrowed(key).map(|(_, value)| value)
+    }
+
+    /// Looks up the entry by a borrowed key, returning the stored key as well.
+    fn get_entry_borrowed<Q>(&mut self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let expired = self.remove_if_expired(key);
+        self.note_lookup(key, expired);
+        let (key, entry) = self.cache.get_key_value(key)?;
+        self.eviction_policy.on_get(key);
+        Some((key, &entry.value))
+    }
+
+    /// Same as `remove`, but takes any borrowed form of the key.
+    pub fn remove_borrowed<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.remove_entry_borrowed(key).map(|(_, value)| value)
+    }
+
+    /// Removes the entry by a borrowed key, returning the stored key as well.
+    fn remove_entry_borrowed<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let (key, removed) = self.cache.remove_entry(key)?;
+        self.track_bytes(&key, &removed.value, false);
+        self.unindex_expiry(&removed);
+        self.eviction_policy.remove(key.clone());
+        Some((key, removed.value))
+    }
+
+    /// Same as `contains_key`, but takes any borrowed form of the key.
+    pub fn contains_key_borrowed<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.cache.get(key).is_some_and(|x| !x.is_expired_at(self.clock.now()))
+    }
+
+    /// Removes the entry with the given key from the cache.
+
+    /// This function removes the entry associated with the provided `key` from the cache. It removes the entry if it exists. If an entry is removed, the eviction policy's `remove` method is called.
+    ///
+    /// Returns the removed value, `None` if the key was not present.
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let normalized = self.normalized(key);
+        self.remove_borrowed(normalized.as_ref())
+    }
+
+    /// Removes all the entries and yields the ones which have not expired, e.g. to hand them over to another
+    /// store at shutdown. The eviction policy is emptied through its `remove`, like `clear`, but the memory is
+    /// kept for reuse.
+    ///
+    /// All the entries are removed even if the iterator is dropped before the end.
+    pub fn drain(&mut self) -> impl Iterator<Item = (K, V)> + '_ {
+        for key in self.cache.keys() {
+            self.eviction_policy.remove(key.clone());
+        }
+        self.bytes = 0;
+        self.expiries.clear();
+        let now = self.clock.now();
+        self.cache.drain()
+            .filter(move |(_, entry)| !entry.is_expired_at(now))
+            .map(|(key, entry)| (key, entry.value))
+    }
+
+    /// Removes all the entries, through the eviction policy's `remove` so that it is emptied as well, and
+    /// releases the memory, see `shrink_to_fit`.
+    pub fn clear(&mut self) {
+        let keys: Vec<K> = self.cache.keys().cloned().collect();
+        for key in keys.iter() {
+            self.remove(key);
+        }
+        self.shrink_to_fit();
+    }
+
+    /// Releases unused capacity of the internal `HashMap` and of the eviction policy, e.g. after a lot of entries
+    /// have been removed.
+    ///
+    /// It may rehash all the entries, so don't call it on the hot path.
+    pub fn shrink_to_fit(&mut self) {
+        self.cache.shrink_to_fit();
+        self.eviction_policy.shrink_to_fit();
+    }
+
+    /// Reserves capacity of the internal `HashMap` and of the eviction policy for at least `additional` more
+    /// entries, like `HashMap::reserve`, e.g. before a bulk load, so that they are not rehashed while growing.
+    ///
+    /// Capacity is never reserved past `max_size`, as further entries evict others.
+    pub fn reserve(&mut self, additional: usize) {
+        let additional = additional.min(self.max_size.saturating_sub(self.cache.len()));
+        self.cache.reserve(additional);
+        self.eviction_policy.reserve(additional);
+    }
+
+    /// Returns the key which would be evicted on the next insert of a new key when the cache is full, without
+    /// evicting it or counting as an access.
+    ///
+    /// `None` if the cache is empty or the eviction policy does not support it, see `EvictionPolicy::peek_victim`.
+    pub fn next_victim(&self) -> Option<&K> {
+        self.eviction_policy.peek_victim()
+    }
+
+    /// Same as `next_victim`, with a short reason from the eviction policy, e.g. `"LRU tail"`, to check that an
+    /// access pattern evicts the keys it is expected to, see `EvictionPolicy::explain`.
+    pub fn explain_next_eviction(&self) -> EvictionExplanation<K> {
+        match self.eviction_policy.explain() {
+            Some((key, reason)) => EvictionExplanation { victim: Some(key), reason },
+            None if self.cache.is_empty() => EvictionExplanation { victim: None, reason: String::from("cache is empty") },
+            None => EvictionExplanation { victim: None, reason: String::from("eviction policy can not tell the next victim") },
+        }
+    }
+
+    /// Returns the number of keys at every access frequency, e.g. to see whether an `LFU` cache is dominated by
+    /// keys accessed once. Read-only, it does not count as an access.
+    ///
+    /// `None` if the eviction policy does not count accesses, see `EvictionPolicy::frequency_histogram`.
+    pub fn frequency_histogram(&self) -> Option<BTreeMap<u64, usize>> {
+        self.eviction_policy.frequency_histogram()
+    }
+
+    /// Returns the keys in eviction order with their access counts, so that the order can be restored with
+    /// `import_policy_state` after restart.
+    ///
+    /// `None` if the eviction policy can not export its state, see `EvictionPolicy::export_state`.
+    pub fn export_policy_state(&self) -> Option<Vec<(K, u64)>> {
+        self.eviction_policy.export_state()
+    }
+
+    /// Seeds the eviction policy with a state returned by `export_policy_state`, instead of the order in which
+    /// the entries were inserted. Keys which are not in the cache are skipped, the ones missing from `state`
+    /// follow in their current order.
+    ///
+    /// Returns `false`, changing nothing, if the eviction policy can not export its state.
+    pub fn import_policy_state(&mut self, state: Vec<(K, u64)>) -> bool {
+        let Some(current) = self.eviction_policy.export_state() else {
+            return false;
+        };
+        let seeded: std::collections::HashSet<&K> = state.iter().map(|(key, _)| key).collect();
+        let missing: Vec<(K, u64)> = current.iter().filter(|(key, _)| !seeded.contains(key)).cloned().collect();
+        for (key, _) in current {
+            self.eviction_policy.remove(key);
+        }
+        let state = state.into_iter().filter(|(key, _)| self.cache.contains_key(key)).chain(missing).collect();
+        self.eviction_policy.import_state(state);
+        true
+    }
+
+    ///Checks if key is already in cache.
+    /// 
+    /// This does not account for access. Expired entries are reported as absent.
+    /// 
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        return self.cache.get(self.normalized(key).as_ref()).is_some_and(|x| !x.is_expired_at(self.clock.now()));
+    }
+
+    ///Returns the current size of the cache. The number of keys in the cache at the moment.
+    ///
+    /// Expired entries which have not been removed yet are counted as well, see `live_size`.
+    pub fn size(&self) -> usize {
+        return self.cache.len();
+    }
+
+    /// Returns the number of entries in the cache, same as `size`.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Whether the cache has no entries. Expired entries which have not been removed yet are counted.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the configured maximum number of entries, `None` for `NoEviction` which has no limit.
+    pub fn max_size(&self) -> Option<usize> {
+        (self.max_size > 0).then_some(self.max_size)
+    }
+
+    /// Fill level of the cache, `size / max_size`, e.g. for autoscaling. It is `0.0` for `NoEviction` which
+    /// has no limit, and may exceed `1.0` when pinned entries grow the cache past `max_size`.
+    pub fn utilization(&self) -> f32 {
+        if self.max_size == 0 {
+            return 0.0;
+        }
+        self.cache.len() as f32 / self.max_size as f32
+    }
+
+    /// Copies the values of `keys`, or of all the entries in case of `None`, into an owned map, e.g. to compute
+    /// an aggregate over values of a single moment. Absent and expired keys are left out and no access is
+    /// counted.
+    ///
+    /// A full snapshot clones every key and value, so it needs as much memory again as the entries.
+    pub fn snapshot(&self, keys: Option<&[K]>) -> HashMap<K, V>
+    where
+        V: Clone,
+    {
+        let now = self.clock.now();
+        let live = |(key, entry): (&K, &CacheEntry<V>)| (!entry.is_expired_at(now)).then(|| (key.clone(), entry.value.clone()));
+        match keys {
+            Some(keys) => keys.iter()
+                .filter_map(|key| self.cache.get_key_value(self.normalized(key).as_ref()))
+                .filter_map(live)
+                .collect(),
+            None => self.cache.iter().filter_map(live).collect(),
+        }
+    }
+
+    /// Whether both caches hold the same keys with equal values, e.g. to check a cache rebuilt from `AOF`
+    /// against a reference one in tests. Eviction order, versions and expiry times are not compared, and
+    /// expired entries which have not been removed yet are ignored.
+    pub fn content_eq<S2: BuildHasher>(&self, other: &Cache<K, V, S2>) -> bool
+    where
+        V: PartialEq,
+    {
+        let (now, other_now) = (self.clock.now(), other.clock.now());
+        self.live_size() == other.live_size()
+            && self.cache.iter()
+                .filter(|(_, entry)| !entry.is_expired_at(now))
+                .all(|(key, entry)| other.cache.get(key).is_some_and(|x| !x.is_expired_at(other_now) && x.value == entry.value))
+    }
+
+    /// Returns the number of entries which have not expired, unlike `size` which counts the expired entries not
+    /// removed yet as well, e.g. for capacity decisions.
+    ///
+    /// Expired entries are counted through the index of `purge_expired`, so it does not scan the whole cache.
+    pub fn live_size(&self) -> usize {
+        let now = self.clock.now();
+        self.cache.len() - self.expiries.range(..(now, u64::MAX)).count()
+    }
+
+    /// Removes the entry from the index of expiries, after it was removed from the cache.
+    fn unindex_expiry(&mut self, entry: &CacheEntry<V>) {
+        if let Some(expires_at) = entry.expires_at {
+            self.expiries.remove(&(expires_at, entry.version));
+        }
+    }
+
+    /// Assigns a new version to the entry, e.g. after it was updated in place, and returns it.
+    fn bump_version(&mut self, key: &K) -> Option<&CacheEntry<V>> {
+        let normalized = self.normalized(key);
+        let key = normalized.as_ref();
+        let entry = self.cache.get_mut(key)?;
+        self.last_version += 1;
+        if let Some(expires_at) = entry.expires_at {
+            self.expiries.remove(&(expires_at, entry.version));
+            self.expiries.insert((expires_at, self.last_version), key.clone());
+        }
+        entry.version = self.last_version;
+        Some(entry)
+    }
+}
+
+/// `cache[&key]` reads the value with `peek` semantics: it is not an access for the eviction policy, and as it
+/// only borrows the cache immutably, an expired entry is treated as absent but not removed. `IndexMut` is not
+/// implemented, use `get_mut` which counts as an access.
+impl<K, V, S> Index<&K> for Cache<K, V, S>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Panics if the key is absent or expired, like indexing a `HashMap`.
+    fn index(&self, key: &K) -> &V {
+        match self.cache.get(self.normalized(key).as_ref()) {
+            Some(entry) if !entry.is_expired_at(self.clock.now()) => &entry.value,
+            _ => panic!("key {:?} is not in the cache", key),
+        }
+    }
+}
+
+/// `cache.extend(entries)` is same as `put_many`, so entries are inserted in iteration order and evicted as
+/// they would be by `put`.
+impl<K, V, S> Extend<(K, V)> for Cache<K, V, S>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        self.put_many(entries);
+    }
+}
+
+/// Consumes the cache into the entries which have not expired, in no particular order.
+impl<K, V, S> IntoIterator for Cache<K, V, S>
+where
+    K: Eq + std::hash::Hash + Clone,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { now: self.clock.now(), entries: self.cache.into_iter() }
+    }
+}
+
+/// Iterator over the entries of a consumed `Cache`, see its `IntoIterator` implementation.
+pub struct IntoIter<K, V> {
+    entries: EntriesIntoIter<K, V>,
+    /// Entries expired at this time are skipped.
+    now: Instant,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let now = self.now;
+        self.entries.by_ref()
+            .find(|(_, entry)| !entry.is_expired_at(now))
+            .map(|(key, entry)| (key, entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.entries.size_hint().1)
+    }
+}
+
+
+/// Function computing the weight of an entry, see `Cache::set_weigher`.
+type Weigher<K, V> = Box<dyn Fn(&K, &V) -> usize + Send>;
+
+/// Function mapping keys to their canonical form, see `Cache::set_key_normalizer`.
+type KeyNormalizer<K> = Box<dyn Fn(&K) -> K + Send>;
+
+/// Key normalizer of `AsyncCacheBuilder::normalize_key`, shared by the cache and `AsyncCache::get_stale`.
+type SharedKeyNormalizer<K> = Arc<dyn Fn(&K) -> K + Send + Sync>;
+
+/// Callback reporting progress of replaying `AOF`, see `AsyncCacheBuilder::replay_progress`.
+type ReplayProgressCallback = Box<dyn Fn(ReplayProgress) + Send + Sync>;
+
+/// Callback receiving the records of `AOF` which could not be decoded, see `AsyncCacheBuilder::on_decode_error`.
+type DecodeErrorCallback = Arc<dyn Fn(&[u8], CacheError) + Send + Sync>;
+/// Caching values owned elsewhere: `Weak` values do not keep them alive and dead entries are removed lazily.
+impl<K, T, S> Cache<K, Weak<T>, S>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
+    S: BuildHasher,
+{
+    /// Retrieves the value and upgrades it. If the value has been dropped by its owners, the dead entry is
+    /// removed and `None` is returned.
+    pub fn get_upgrade(&mut self, key: &K) -> Option<Arc<T>> {
+        let upgraded = self.get(key)?.upgrade();
+        if upgraded.is_none() {
+            self.remove(key);
+        }
+        upgraded
+    }
+
+    /// Removes all the entries whose values have been dropped and returns their keys.
+    ///
+    /// This scans the whole cache, so call it periodically rather than on every operation.
+    pub fn prune_dead(&mut self) -> Vec<K> {
+        let dead: Vec<K> = self.cache.iter()
+            .filter(|(_, entry)| entry.value.strong_count() == 0)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in dead.iter() {
+            self.remove(key);
+        }
+        dead
+    }
+}
+
+/// Negative caching: `None` values mark keys known to be absent, usually inserted with a short ttl so that
+/// nonexistent keys don't hit the backend on every lookup.
+impl<K, V, S> Cache<K, Option<V>, S>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
+    S: BuildHasher,
+{
+    /// Remembers that `key` has no value for `ttl`. It is stored as a regular entry, so it takes a slot and
+    /// can be evicted.
+    pub fn put_absent(&mut self, key: K, ttl: Duration) {
+        self.put_with_ttl(key, None, ttl);
+    }
+
+    /// Looks up the key, telling apart a key known to be absent from a key not cached at all.
+    pub fn lookup(&mut self, key: &K) -> Lookup<&V> {
+        match self.get(key) {
+            Some(Some(value)) => Lookup::Present(value),
+            Some(None) => Lookup::KnownAbsent,
+            None => Lookup::Unknown,
+        }
+    }
+}
+
+/// A more advanced cache exposing `async` functions, suitable for concurrent environments.
+/// 
+/// It uses `Mutex` around `Cache` to provide synchronization.
+/// 
+/// `AOF` related configurations can be passed in `new()` method to persist data to restart the cache
+/// from the same point where it was stopped or crashed. Although some data may be lost, please go through
+/// `AsyncCacheConfig` for more info.
+/// 
+/// `AsyncCache` is a cheap handle to the shared state, so it can be cloned and moved into spawned tasks
+/// instead of being wrapped in an `Arc`.
+
+pub struct AsyncCache<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + Send + Sync,
+    V: Send + Sync,
+{
+    cache: Arc<Mutex<Cache<K, V>>>,
+    persist_read_ops: Option<bool>,
+    subscriber_manager: Arc<CacheEventSubscriber<K, V>>,
+    /// Per key locks of the initializations in progress, so that concurrent callers don't run them twice.
+    in_flight: InFlight<K>,
+    /// Called for the records skipped while replaying `AOF`, aborting the replay instead if `None`.
+    on_decode_error: Option<DecodeErrorCallback>,
+    /// Loads the values missed by `get`, see `AsyncCacheBuilder::loader`.
+    loader: Option<Loader<K, V>>,
+    /// Time `get` waits for the loader, see `AsyncCacheBuilder::loader_timeout`.
+    loader_timeout: Option<Duration>,
+    /// Ttl of the values loaded by `get`, see `AsyncCacheBuilder::loader_ttl`.
+    loader_ttl: Option<Duration>,
+    /// Part of `loader_ttl` left when `get_or_refresh_ahead` refreshes an entry, see
+    /// `AsyncCacheBuilder::refresh_ahead_fraction`.
+    refresh_ahead_fraction: Option<f32>,
+    /// Size limit of records read from `AOF`, see `AsyncCacheBuilder::max_record_bytes`.
+    max_record_bytes: u64,
+    /// File of the eviction policy state, see `AsyncCacheBuilder::persist_policy_state`.
+    policy_state_path: Option<String>,
+    /// Copy of the entries read by `get_stale`, see `AsyncCacheBuilder::stale_reads`.
+    snapshot: Option<Snapshot<K, V>>,
+    /// Normalizer of the cache, so that `get_stale` normalizes keys without locking it.
+    key_normalizer: Option<SharedKeyNormalizer<K>>,
+    /// Name reported in stats and errors, see `AsyncCacheBuilder::name`.
+    name: Option<Arc<str>>,
+    /// Housekeeping tasks, stopped by `shutdown`.
+    tasks: Arc<BackgroundTasks>,
+}
+
+/// Clones share the same cache, `AOF` and background tasks, like clones of an `Arc`.
+impl<K, V> Clone for AsyncCache<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + Send + Sync,
+    V: Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            persist_read_ops: self.persist_read_ops,
+            subscriber_manager: self.subscriber_manager.clone(),
+            in_flight: self.in_flight.clone(),
+            on_decode_error: self.on_decode_error.clone(),
+            loader: self.loader.clone(),
+            loader_timeout: self.loader_timeout,
+            loader_ttl: self.loader_ttl,
+            refresh_ahead_fraction: self.refresh_ahead_fraction,
+            max_record_bytes: self.max_record_bytes,
+            policy_state_path: self.policy_state_path.clone(),
+            snapshot: self.snapshot.clone(),
+            key_normalizer: self.key_normalizer.clone(),
+            name: self.name.clone(),
+            tasks: self.tasks.clone(),
+        }
+    }
+}
+
+/// Reference to a value of `AsyncCache` returned by `AsyncCache::get_ref`, which keeps the cache locked until
+/// it is dropped.
+pub struct ValueRef<'a, V>(tokio::sync::MappedMutexGuard<'a, V>);
+
+impl<V> std::ops::Deref for ValueRef<'_, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.0
+    }
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for ValueRef<'_, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// Read-only handle to an `AsyncCache`, returned by `AsyncCache::reader`, for the parts of an application which
+/// must not write to the cache. It shares the cache of the handle it was created from and is cheaply `Clone`.
+pub struct CacheReader<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + Send + Sync,
+    V: Send + Sync,
+{
+    cache: AsyncCache<K, V>,
+}
+
+impl<K, V> Clone for CacheReader<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + Send + Sync,
+    V: Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self { cache: self.cache.clone() }
+    }
+}
+
+impl<K, V> CacheReader<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static
+{
+    /// Retrieves the value, see `AsyncCache::get`. The access counts for eviction and is recorded in `AOF` with
+    /// `persist_read_ops` like any read, a configured loader still fills a miss.
+    pub async fn get(&self, key: &K) -> Option<V> {
+        self.cache.get(key).await
+    }
+
+    /// Retrieves the value without counting as an access, see `AsyncCache::peek`.
+    pub async fn peek(&self, key: &K) -> Option<V> {
+        self.cache.peek(key).await
+    }
+
+    /// Whether the key is present and not expired, see `AsyncCache::contains_key`.
+    pub async fn contains_key(&self, key: &K) -> bool {
+        self.cache.contains_key(key).await
+    }
+
+    /// Number of entries in the cache, see `AsyncCache::size`.
+    pub async fn size(&self) -> usize {
+        self.cache.size().await
+    }
+}
+
+/// Locks of keys being initialized, see `AsyncCache::get_or_try_insert_with`.
+type InFlight<K> = Arc<std::sync::Mutex<HashMap<K, Arc<Mutex<()>>>>>;
+
+/// Key and number of records announced by the `BatchBegin` of a batch being replayed, if any, and the records
+/// read so far.
+type OpenBatch<K, V> = (K, Option<u64>, Vec<AOFRecord<K, V>>);
+
+/// Copy of the entries published for `AsyncCache::get_stale`, replaced as a whole on every refresh.
+type Snapshot<K, V> = Arc<std::sync::RwLock<Arc<HashMap<K, V>>>>;
+
+/// Loader of the values missed by `AsyncCache::get`, see `AsyncCacheBuilder::loader`.
+type Loader<K, V> = Arc<dyn Fn(K) -> Pin<Box<dyn Future<Output = Option<V>> + Send>> + Send + Sync>;
+
+/// Builder to create `AsyncCache` with optional features on top of `AsyncCacheConfig`.
+///
+/// `AsyncCache::new(config)` is same as `AsyncCacheBuilder::new(config).build()`.
+///
+pub struct AsyncCacheBuilder<K, V> {
+    config: AsyncCacheConfig<K>,
+    sweep_interval: Option<Duration>,
+    stale_reads: Option<Duration>,
+    shared_aof: Option<(Arc<AofMultiplexer>, u16)>,
+    replay_progress: Option<(u64, ReplayProgressCallback)>,
+    weigher: Option<Weigher<K, V>>,
+    key_normalizer: Option<SharedKeyNormalizer<K>>,
+    entry_overhead: usize,
+    on_decode_error: Option<DecodeErrorCallback>,
+    flush_jitter: f64,
+    flush_strategy: Option<FlushStrategy>,
+    loader: Option<Loader<K, V>>,
+    loader_timeout: Option<Duration>,
+    loader_ttl: Option<Duration>,
+    refresh_ahead_fraction: Option<f32>,
+    should_persist: Option<PersistFilter<V>>,
+    allow_sync_writes: bool,
+    max_memory: Option<MemoryBudget>,
+    max_record_bytes: u64,
+    value_transform: Option<ValueTransform>,
+    compress_values_over: Option<usize>,
+    memory_aof: Option<MemoryAOF>,
+    aof_failure: AofFailureMode,
+    mmap_aof: Option<usize>,
+    persist_policy_state: bool,
+    policy_state_interval: Option<Duration>,
+    clock: Option<Box<dyn Clock>>,
+    ghost_capacity: usize,
+    name: Option<String>,
+    soft_limit: Option<(usize, Duration)>,
+    file_extension: String,
+    write_manifest: bool,
+}
+
+impl<K, V> AsyncCacheBuilder<K, V> {
+    /// Creates a new builder with the given configurations and all the optional features disabled.
+    pub fn new(config: AsyncCacheConfig<K>) -> Self {
+        Self {
+            config,
+            sweep_interval: None,
+            stale_reads: None,
+            shared_aof: None,
+            replay_progress: None,
+            weigher: None,
+            key_normalizer: None,
+            entry_overhead: 0,
+            on_decode_error: None,
+            flush_jitter: 0.0,
+            flush_strategy: None,
+            loader: None,
+            loader_timeout: None,
+            loader_ttl: None,
+            refresh_ahead_fraction: None,
+            should_persist: None,
+            allow_sync_writes: false,
+            max_memory: None,
+            max_record_bytes: DEFAULT_MAX_RECORD_BYTES,
+            value_transform: None,
+            compress_values_over: None,
+            memory_aof: None,
+            aof_failure: AofFailureMode::Fatal,
+            mmap_aof: None,
+            persist_policy_state: false,
+            policy_state_interval: None,
+            clock: None,
+            ghost_capacity: 0,
+            name: None,
+            soft_limit: None,
+            file_extension: String::from("dat"),
+            write_manifest: false,
+        }
+    }
+
+    /// Interval at which a background task purges expired entries, appending `Remove` records to `AOF`
+    /// for the purged keys. The task stops once the cache is dropped.
+    ///
+    /// In case of `None` (default), expired entries are only removed when accessed or on `purge_expired`.
+    ///
+    /// Panics on `build` if the interval is zero.
+    pub fn sweep_interval(mut self, sweep_interval: Option<Duration>) -> Self {
+        self.sweep_interval = sweep_interval;
+        self
+    }
+
+    /// Enables `AsyncCache::get_stale`, which reads a copy of the entries without locking the cache. A
+    /// background task refreshes the copy every `refresh_interval` if the cache was written since, so reads
+    /// are at most `refresh_interval` (plus the time to copy the entries) behind the writes. The task stops
+    /// once the cache is dropped.
+    ///
+    /// Every refresh clones all the entries, so it suits small or read heavy caches whose readers can
+    /// tolerate stale values, e.g. feature flags or configs.
+    ///
+    /// Panics on `build` if the interval is zero.
+    pub fn stale_reads(mut self, refresh_interval: Duration) -> Self {
+        self.stale_reads = Some(refresh_interval);
+        self
+    }
+
+    /// Persists to the `AOF` shared by several caches instead of a file of its own. Records are tagged with
+    /// `cache_id` which must be unique among the caches using `multiplexer` and stable across restarts, as
+    /// only records with the same id are replayed.
+    ///
+    /// `flush_time` of `multiplexer` is used. `persist_read_ops` is still taken from `AsyncCacheConfig`.
+    ///
+    /// Panics on `build` if `AsyncCacheConfig` also has `AOF` configured or `cache_id` is already registered.
+    pub fn shared_aof(mut self, multiplexer: Arc<AofMultiplexer>, cache_id: u16) -> Self {
+        self.shared_aof = Some((multiplexer, cache_id));
+        self
+    }
+
+    /// Calls `progress` after every `every` records applied while replaying `AOF` and once more when the
+    /// replay is complete, so that slow startups can be reported. Nothing is called if there is no `AOF`.
+    ///
+    /// Panics on `build` if `every` is zero.
+    pub fn replay_progress<F>(mut self, every: u64, progress: F) -> Self
+    where
+        F: Fn(ReplayProgress) + Send + Sync + 'static,
+    {
+        self.replay_progress = Some((every, Box::new(progress)));
+        self
+    }
+
+    /// Computes weight of every inserted entry for the eviction policy, see `Cache::set_weigher`. Entries
+    /// replayed from `AOF` are weighed as well.
+    pub fn weigher<F>(mut self, weigher: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + 'static,
+    {
+        self.weigher = Some(Box::new(weigher));
+        self
+    }
+
+    /// Maps keys to their canonical form, e.g. lowercases them for a case-insensitive cache, see
+    /// `Cache::set_key_normalizer`. This changes which keys are equal. `AOF` records of `put`, `remove` and
+    /// reads hold the normalized key, and replayed keys are normalized again, so it must stay the same across
+    /// restarts.
+    pub fn normalize_key<F>(mut self, normalize_key: F) -> Self
+    where
+        F: Fn(&K) -> K + Send + Sync + 'static,
+    {
+        self.key_normalizer = Some(Arc::new(normalize_key));
+        self
+    }
+
+    /// Estimated heap bytes of an entry used by `AsyncCache::estimated_bytes`, see `Cache::set_entry_overhead`.
+    pub fn entry_overhead(mut self, entry_overhead: usize) -> Self {
+        self.entry_overhead = entry_overhead;
+        self
+    }
+
+    /// Limits the estimated memory of the entries, e.g. `MemoryBudget::PercentOfSystem(10.0)`, evicting on
+    /// inserts once it is exceeded, see `Cache::set_max_memory`. Entries are weighed with `weigher` if set,
+    /// otherwise by their inline size plus `entry_overhead`. Also applies while replaying `AOF`.
+    ///
+    /// Panics on `build` if the budget is invalid or total system memory can not be read, see
+    /// `ConfigError::SystemMemoryUnavailable`.
+    pub fn max_memory(mut self, max_memory: MemoryBudget) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Sets the source of the current time used for expiry, e.g. a `MockClock` in tests, see
+    /// `Cache::set_clock`. The background sweeper, see `sweep_interval`, still runs on real time.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Limits the size of the key and the value of a record read from `AOF`, `DEFAULT_MAX_RECORD_BYTES` by
+    /// default. A bigger record is treated as corruption and ends the replay, like a record cut short by a
+    /// crash, so that a corrupted length can't make the cache allocate any amount of memory. Also applies to
+    /// `AsyncCache::reload`.
+    pub fn max_record_bytes(mut self, max_record_bytes: u64) -> Self {
+        self.max_record_bytes = max_record_bytes;
+        self
+    }
+
+    /// Transforms the bytes of values written to `AOF` with `encode` and back with `decode` while replaying,
+    /// e.g. to encrypt them at rest or to compress them. Keys and the framing of records are not transformed.
+    ///
+    /// Records written without transform, e.g. before it was configured, are still read as they are. A
+    /// transformed record is read as a decode error if `decode` gives bytes which are not a valid value, see
+    /// `on_decode_error`, so it can be skipped.
+    ///
+    /// Panics on `build` if used with `shared_aof`.
+    pub fn value_transform<E, D>(mut self, encode: E, decode: D) -> Self
+    where
+        E: Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
+        D: Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
+    {
+        self.value_transform = Some(ValueTransform { encode: Arc::new(encode), decode: Arc::new(decode), transform_over: None });
+        self
+    }
+
+    /// Only values whose serialized size exceeds `compress_values_over` go through `value_transform`, e.g. a
+    /// compression, so that small values which would not shrink are written as they are. Every record keeps
+    /// whether its value was transformed. `None` (default) transforms all the values.
+    ///
+    /// Only `AOF` is compressed: values stay uncompressed in memory and `CacheEntry` has no compressed flag, as
+    /// `Cache` hands out references to its values and `AsyncCache` shares its entries with it, so `get` never
+    /// decodes. `benches/throughput.rs` compares the size of `AOF` and the put and replay times of a mix of
+    /// small and large values.
+    ///
+    /// Panics on `build` without `value_transform`.
+    pub fn compress_values_over(mut self, compress_values_over: Option<usize>) -> Self {
+        self.compress_values_over = compress_values_over;
+        self
+    }
+
+    /// Persists to `aof` in memory instead of a file, e.g. to test replay without the disk and without waiting
+    /// for flushes. The cache replays the records already in `aof` on `build`, so a clone of the `aof` of a
+    /// dropped cache restores its entries. Reads are not recorded, as there is no `AOF` config to take
+    /// `persist_read_ops` from, and values are written without `value_transform`.
+    ///
+    /// Panics on `build` if `AsyncCacheConfig` also has `AOF` configured or `shared_aof` is used.
+    pub fn memory_aof(mut self, aof: MemoryAOF) -> Self {
+        self.memory_aof = Some(aof);
+        self
+    }
+
+    /// Persists to a new `MemoryAOF`, so that benchmarks measure the cost of encoding records without the disk.
+    #[cfg(feature = "bench-internals")]
+    pub fn in_memory_aof(self) -> Self {
+        self.memory_aof(MemoryAOF::new())
+    }
+
+    /// Reads the manifest at `path`, if any, see `write_manifest`. Panics if it is not valid or was written for
+    /// another eviction policy than `policy`.
+    async fn check_manifest(path: &str, policy: &str) -> Option<AofManifest> {
+        let manifest = match AofManifest::read(path).await {
+            Ok(manifest) => manifest?,
+            // a folder which can not be read fails opening `AOF` as well, according to `aof_failure`.
+            Err(CacheError::Io(_)) => return None,
+            Err(e) => panic!("Error in reading manifest {}: {}", path, e),
+        };
+        if manifest.policy != policy {
+            panic!("{}", CacheError::PolicyMismatch { expected: policy.to_string(), found: manifest.policy });
+        }
+        Some(manifest)
+    }
+
+    /// Skips the records of `AOF` whose key or value can not be decoded while replaying, e.g. after the type
+    /// of values has changed, calling `on_decode_error` with the bytes which failed instead. Also applies to
+    /// `AsyncCache::reload`.
+    ///
+    /// By default, replay stops at the first such record. `Put` records without value are skipped either way
+    /// and reported with `CacheError::Corruption`.
+    pub fn on_decode_error<F>(mut self, on_decode_error: F) -> Self
+    where
+        F: Fn(&[u8], CacheError) + Send + Sync + 'static,
+    {
+        self.on_decode_error = Some(Arc::new(on_decode_error));
+        self
+    }
+
+    /// Moves every periodic flush of `AOF` randomly by up to `flush_jitter` of `flush_time` either way, so that
+    /// many caches with the same `flush_time` don't hit the disk at the same moment. `0.0` (default) flushes
+    /// exactly every `flush_time`. Not applied to a shared `AOF`.
+    ///
+    /// Panics on `build` if `flush_jitter` is not in `[0, 1)`.
+    pub fn flush_jitter(mut self, flush_jitter: f64) -> Self {
+        self.flush_jitter = flush_jitter;
+        self
+    }
+
+    /// Decides when the periodic flush of `AOF` writes the pending records instead of every `flush_time`, e.g.
+    /// `FlushStrategy::Adaptive` to flush sooner during bursts of writes and seldom while idle. `flush_time`
+    /// still has to be set to flush periodically at all. Flush jitter is only applied to fixed intervals. Not
+    /// applied to a shared or memory-mapped `AOF`.
+    ///
+    /// Panics on `build` if the cache has no `flush_time` or the intervals are invalid, see
+    /// `ConfigError::InvalidFlushStrategy`.
+    pub fn flush_strategy(mut self, flush_strategy: FlushStrategy) -> Self {
+        self.flush_strategy = Some(flush_strategy);
+        self
+    }
+
+    /// Acknowledges that every operation of an eviction policy is flushed to disk right away, as `flush_time`
+    /// is `None` in `AOF` config. Reads are recorded too, so the cache gets as slow as disk io, that's why this
+    /// combination must be opted into.
+    ///
+    /// By default, `build` panics for it, see `AsyncCacheConfig::has_sync_writes_with_eviction`. `NoEviction`
+    /// and caches which don't persist read ops do not need this.
+    pub fn allow_sync_writes(mut self, allow_sync_writes: bool) -> Self {
+        self.allow_sync_writes = allow_sync_writes;
+        self
+    }
+
+    /// What to do if the `AOF` file of the cache can not be opened, e.g. because the folder is not writable,
+    /// `AofFailureMode::Fatal` by default. With `AofFailureMode::DegradeToMemory`, the cache works in memory
+    /// only: nothing is replayed nor persisted, including the policy state, and the `last_error` of
+    /// `AsyncCache::flush_health` keeps the error, with the path of the file. Does not apply to `shared_aof`.
+    pub fn aof_failure(mut self, aof_failure: AofFailureMode) -> Self {
+        self.aof_failure = aof_failure;
+        self
+    }
+
+    /// Writes `AOF` through a memory-mapped file `{folder}/{cache_name}.mmap` created with room for
+    /// `initial_bytes` of records, instead of appending to `{folder}/{cache_name}.dat`, see `MmapAOF`. Appending
+    /// is then a memory copy, `flush_time` tells how often the records are synced to disk. The file grows when
+    /// it fills up.
+    ///
+    /// The two files have different formats, records of one are not replayed from the other. Panics on `build`
+    /// if the cache has no `AOF` config of its own.
+    #[cfg(all(feature = "mmap", unix))]
+    pub fn mmap_aof(mut self, initial_bytes: usize) -> Self {
+        self.mmap_aof = Some(initial_bytes);
+        self
+    }
+
+    /// Makes the cache read-through: on a miss, `AsyncCache::get` calls `loader` and inserts the value it
+    /// returns with `put`, or with `put_with_ttl` if `loader_ttl` is set. `None` means the key does not exist,
+    /// nothing is cached and `get` returns `None`. To cache such keys as absent for a while, use `Option` values
+    /// and `AsyncCache::get_or_insert_absent_with` instead.
+    ///
+    /// Concurrent misses of the same key are deduplicated as in `AsyncCache::get_or_try_insert_with`, so the
+    /// loader runs once and the other callers get the loaded value. If the loader panics, the panic reaches
+    /// the caller whose `get` ran it, and if that `get` is cancelled, the loader is dropped as well. In both
+    /// cases nothing is cached and the next caller in line runs the loader again.
+    ///
+    /// Only `get` loads, the other reads such as `get_opts`, `peek` and `get_borrowed` don't.
+    pub fn loader<F, Fut>(mut self, loader: F) -> Self
+    where
+        F: Fn(K) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<V>> + Send + 'static,
+    {
+        self.loader = Some(Arc::new(move |key| Box::pin(loader(key))));
+        self
+    }
+
+    /// Bounds the time `AsyncCache::get` waits for the loader on a miss, see
+    /// `AsyncCache::get_with_loader_timeout`. On timeout `get` returns `None` and nothing is cached. By default
+    /// `get` waits as long as the loader takes.
+    pub fn loader_timeout(mut self, loader_timeout: Duration) -> Self {
+        self.loader_timeout = Some(loader_timeout);
+        self
+    }
+
+    /// Inserts the values loaded by `AsyncCache::get` with `put_with_ttl`, so that they expire after
+    /// `loader_ttl` and are loaded again. By default they are inserted with `put` and never expire.
+    pub fn loader_ttl(mut self, loader_ttl: Duration) -> Self {
+        self.loader_ttl = Some(loader_ttl);
+        self
+    }
+
+    /// Makes `AsyncCache::get_or_refresh_ahead` reload entries in the last `refresh_ahead_fraction` of
+    /// `loader_ttl` in background, e.g. `0.2` for the last fifth, while returning the current value right away.
+    /// So popular entries are replaced before they expire and their readers don't wait for the loader.
+    ///
+    /// Panics on `build` if the fraction is not greater than zero and less than one, or without `loader` and
+    /// `loader_ttl`.
+    pub fn refresh_ahead_fraction(mut self, refresh_ahead_fraction: f32) -> Self {
+        self.refresh_ahead_fraction = Some(refresh_ahead_fraction);
+        self
+    }
+
+    /// Persists only the values for which `should_persist` returns `true`, e.g. the `Ok` values of a cache of
+    /// `Result`s, so that transient values don't survive restarts. The others are still inserted in memory,
+    /// but recorded in `AOF` as a `Remove` of the key, so replay doesn't restore an older value in their place.
+    ///
+    /// Applies to every write persisting a value, such as `put`, `put_with_ttl` and batches. By default all the
+    /// values are persisted.
+    pub fn should_persist<F>(mut self, should_persist: F) -> Self
+    where
+        F: Fn(&V) -> bool + Send + Sync + 'static,
+    {
+        self.should_persist = Some(Arc::new(should_persist));
+        self
+    }
+
+    /// Keeps headroom below `max_size`: every `check_interval`, a background task evicts the entries past
+    /// `soft_limit`, a few at a time, through `Cache::evict_n`, so that bursts of inserts rarely have to evict
+    /// on the write path. This trades a slightly smaller effective cache for smoother tail latency. Evictions
+    /// are recorded in `AOF` as `Remove`s. The task stops once the cache is dropped.
+    ///
+    /// Panics on `build` if `soft_limit` is not below `max_size` of an evicting policy or the interval is zero.
+    pub fn soft_limit(mut self, soft_limit: usize, check_interval: Duration) -> Self {
+        self.soft_limit = Some((soft_limit, check_interval));
+        self
+    }
+
+    /// Extension of the `AOF` file, written as `<folder>/<cache_name>.<extension>`, `dat` by default. It must
+    /// not contain dots or path separators.
+    ///
+    /// Panics on `build` if the extension is invalid.
+    pub fn file_extension(mut self, file_extension: impl Into<String>) -> Self {
+        self.file_extension = file_extension.into();
+        self
+    }
+
+    /// Writes `<cache_name>.manifest.json` next to `AOF` on `build`, describing its files, eviction policy and
+    /// `max_size` for operators, see `AofManifest`. A manifest found on `build` is checked whether or not it is
+    /// written, building panics with `CacheError::PolicyMismatch` if it was written for another eviction policy.
+    ///
+    /// Panics on `build` if the cache has no `AOF` config of its own.
+    pub fn write_manifest(mut self, write_manifest: bool) -> Self {
+        self.write_manifest = write_manifest;
+        self
+    }
+
+    /// Name of the cache reported in `CacheStats` and in the errors returned by the cache, to tell apart the
+    /// signals of several caches. By default it is the `cache_name` of the `AOF` config, if any.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Remembers the last `capacity` evicted keys to count the misses which a bigger cache would have hit,
+    /// reported as `CacheStats::would_have_hit`, see `Cache::set_ghost_capacity`.
+    pub fn ghost_capacity(mut self, capacity: usize) -> Self {
+        self.ghost_capacity = capacity;
+        self
+    }
+
+    /// Restores the order of the eviction policy saved by `AsyncCache::save_policy_state` on `build` and
+    /// `AsyncCache::reload`, instead of the order in which replay inserted the entries, e.g. to restart an
+    /// `LRU` cache with the same recency. The state is stored next to `AOF` in `<cache_name>.policy`, accesses
+    /// made after it was last saved only count in the order of replay.
+    ///
+    /// Panics on `build` if the cache has no `AOF` config of its own.
+    pub fn persist_policy_state(mut self, persist_policy_state: bool) -> Self {
+        self.persist_policy_state = persist_policy_state;
+        self
+    }
+
+    /// Interval at which a background task records the state of the eviction policy in `AOF`, for policies
+    /// which export it as bytes, see `EvictionPolicy::export_state_bytes`. Replay restores the last state
+    /// recorded and applies the operations recorded after it, so custom policies survive restarts as well. The
+    /// task stops once the cache is dropped, `AsyncCache::record_policy_state` records it on demand, e.g. before
+    /// shutdown.
+    ///
+    /// In case of `None` (default), the state is only recorded by `AsyncCache::record_policy_state`.
+    ///
+    /// Panics on `build` if the interval is zero or the cache has no `AOF`, of its own, shared or in memory.
+    pub fn policy_state_interval(mut self, policy_state_interval: Option<Duration>) -> Self {
+        self.policy_state_interval = policy_state_interval;
+        self
+    }
+
+    /// Checks the config and the options, returning all the problems found rather than panicking on the
+    /// first one on `build`, e.g. to report misconfiguration at startup, see `AsyncCacheConfig::validate`.
+    ///
+    /// A folder which is not writable is not reported with `AofFailureMode::DegradeToMemory`, as the cache
+    /// then works in memory only.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = self.config.validate().err().unwrap_or_default();
+        if self.aof_failure == AofFailureMode::DegradeToMemory {
+            errors.retain(|x| !matches!(x, ConfigError::FolderNotWritable { .. }));
+        }
+        errors.extend(self.option_errors());
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Problems of the options which `build` panics on, in the order they are checked.
+    fn option_errors(&self) -> Vec<ConfigError> {
+        let mut errors = vec![];
+        let has_aof = self.config.get_aof_config().is_some();
+        if self.config.has_sync_writes_with_eviction() && !self.allow_sync_writes {
+            errors.push(ConfigError::SyncWritesNotAllowed);
+        }
+        if self.persist_policy_state && !has_aof {
+            errors.push(ConfigError::PolicyStateWithoutAof);
+        }
+        if [has_aof, self.shared_aof.is_some(), self.memory_aof.is_some()].into_iter().filter(|x| *x).count() > 1 {
+            errors.push(ConfigError::ConflictingAof);
+        }
+        match (has_aof, self.shared_aof.is_some()) {
+            (false, _) if self.mmap_aof.is_some() => errors.push(ConfigError::MmapWithoutAof),
+            (false, true) if self.value_transform.is_some() => errors.push(ConfigError::ValueTransformWithSharedAof),
+            _ => {},
+        }
+        if self.compress_values_over.is_some() && self.value_transform.is_none() {
+            errors.push(ConfigError::CompressionWithoutTransform);
+        }
+        if !(0.0..1.0).contains(&self.flush_jitter) {
+            errors.push(ConfigError::InvalidFlushJitter(self.flush_jitter));
+        }
+        if let Some(fraction) = self.refresh_ahead_fraction {
+            if !(0.0..1.0).contains(&fraction) || fraction == 0.0 {
+                errors.push(ConfigError::InvalidRefreshAheadFraction(fraction));
+            }
+            if self.loader.is_none() || self.loader_ttl.is_none() {
+                errors.push(ConfigError::RefreshAheadWithoutLoaderTtl);
+            }
+        }
+        if let Some(flush_strategy) = self.flush_strategy {
+            if !self.config.get_aof_config().is_some_and(|x| x.2.is_some()) {
+                errors.push(ConfigError::FlushStrategyWithoutFlushTime);
+            }
+            if !flush_strategy.is_valid() {
+                errors.push(ConfigError::InvalidFlushStrategy);
+            }
+        }
+        if self.replay_progress.as_ref().is_some_and(|x| x.0 == 0) {
+            errors.push(ConfigError::ZeroReplayProgress);
+        }
+        if self.sweep_interval.is_some_and(|x| x.is_zero()) {
+            errors.push(ConfigError::ZeroSweepInterval);
+        }
+        if self.stale_reads.is_some_and(|x| x.is_zero()) {
+            errors.push(ConfigError::ZeroStaleReadsInterval);
+        }
+        if self.policy_state_interval.is_some() && !has_aof && self.shared_aof.is_none() && self.memory_aof.is_none() {
+            errors.push(ConfigError::PolicyStateIntervalWithoutAof);
+        }
+        if self.policy_state_interval.is_some_and(|x| x.is_zero()) {
+            errors.push(ConfigError::ZeroPolicyStateInterval);
+        }
+        if self.file_extension.is_empty() || self.file_extension.contains(['.', '/', '\\']) {
+            errors.push(ConfigError::InvalidFileExtension(self.file_extension.clone()));
+        }
+        if self.write_manifest && !has_aof {
+            errors.push(ConfigError::ManifestWithoutAof);
+        }
+        if let Some(Err(error)) = self.max_memory.map(|x| x.resolve()) {
+            errors.push(error);
+        }
+        if let Some((soft_limit, check_interval)) = self.soft_limit {
+            if !self.config.max_size().is_some_and(|x| soft_limit < x) {
+                errors.push(ConfigError::InvalidSoftLimit(soft_limit));
+            }
+            if check_interval.is_zero() {
+                errors.push(ConfigError::ZeroSoftLimitInterval);
+            }
+        }
+        errors
+    }
+}
+
+impl<K, V> AsyncCacheBuilder<K, V>
+where
+    for<'de> K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + Deserialize<'de> + Serialize + 'static,
+    for<'de> V: Clone + Deserialize<'de> + Serialize + Send + Sync + 'static
+{
+    /// Creates the `AsyncCache`, replaying the `AOF` if configured. See `AsyncCache::new`.
+    pub async fn build(self) -> AsyncCache<K, V> {
+        if let Some(error) = self.option_errors().first() {
+            panic!("{}", error);
+        }
+        let config = self.config;
+        let value_transform = self.value_transform.map(|x| ValueTransform { transform_over: self.compress_values_over, ..x });
+        let policy_state_path = config.get_aof_config()
+            .filter(|_| self.persist_policy_state)
+            .map(|(folder, cache_name, _)| format!("{}/{}.policy", folder, cache_name));
+        let manifest_path = config.get_aof_config().map(|(folder, cache_name, _)| AofManifest::path(&folder, &cache_name));
+        let manifest = match manifest_path.as_ref() {
+            Some(path) => Self::check_manifest(path, config.policy_name()).await,
+            None => None,
+        };
+        let mut subscriber_manager = match (config.get_aof_config(), self.shared_aof) {
+            (Some(v), _) => CacheEventSubscriber::with_options(Some(v.0), Some(v.1), v.2, self.flush_jitter, self.flush_strategy, value_transform, self.aof_failure, self.mmap_aof, &self.file_extension).await,
+            (None, Some((multiplexer, cache_id))) => CacheEventSubscriber::multiplexed(&multiplexer, cache_id),
+            (None, None) => match self.memory_aof {
+                Some(aof) => CacheEventSubscriber::with_persister(Arc::new(aof)),
+                None => CacheEventSubscriber::without_aof(),
+            },
+        };
+        subscriber_manager.set_persist_filter(self.should_persist);
+        if let Some(path) = manifest_path.filter(|_| self.write_manifest && !subscriber_manager.is_degraded()) {
+            let extension = if cfg!(all(feature = "mmap", unix)) && self.mmap_aof.is_some() { "mmap" } else { &self.file_extension };
+            let manifest = AofManifest {
+                format_version: MANIFEST_FORMAT_VERSION,
+                policy: config.policy_name().to_string(),
+                max_size: config.max_size().unwrap_or(0),
+                segments: vec![format!("{}.{}", config.get_aof_config().unwrap().1, extension)],
+                created_at: manifest.map_or_else(|| instant_to_unix_millis(Instant::now()), |x| x.created_at),
+            };
+            if let Err(e) = manifest.write(&path).await {
+                panic!("Error in writing manifest {}: {}", path, e);
+            }
+        }
+        let instance = AsyncCache {
+            persist_read_ops: config.persist_read_ops(),
+            in_flight: Default::default(),
+            on_decode_error: self.on_decode_error,
+            loader: self.loader,
+            loader_timeout: self.loader_timeout,
+            loader_ttl: self.loader_ttl,
+            refresh_ahead_fraction: self.refresh_ahead_fraction,
+            max_record_bytes: self.max_record_bytes,
+            policy_state_path: policy_state_path.filter(|_| !subscriber_manager.is_degraded()),
+            snapshot: self.stale_reads.map(|_| Default::default()),
+            name: self.name.or_else(|| config.get_aof_config().map(|x| x.1)).map(Arc::from),
+            tasks: Default::default(),
+            subscriber_manager: Arc::new(subscriber_manager),
+            key_normalizer: self.key_normalizer.clone(),
+            cache: Arc::new(Mutex::new(Cache::with_options(config.get_sync_config(), self.weigher, self.entry_overhead, self.max_memory, self.clock, self.ghost_capacity, self.key_normalizer.map(|x| Box::new(move |key: &K| x(key)) as KeyNormalizer<K>))))
+        };
+        let mut gaurd = instance.cache.lock().await;
+        if let Ok(mut iter) = instance.subscriber_manager.into_iter().await {
+            iter.set_max_record_bytes(instance.max_record_bytes);
+            let (records, torn_batch) = AsyncCache::replay(&mut gaurd, iter, self.replay_progress.as_ref(), instance.on_decode_error.as_ref()).await;
+            instance.subscriber_manager.set_approx_records(records);
+            if let Some(key) = torn_batch {
+                instance.close_torn_batch(key).await;
+            }
+        }
+        // entries which expired while the cache was down.
+        gaurd.purge_expired();
+        instance.load_policy_state(&mut gaurd).await;
+        drop(gaurd);
+        instance.start_sweeper(self.sweep_interval);
+        instance.start_soft_limit_evictor(self.soft_limit);
+        instance.start_snapshot_refresher(self.stale_reads).await;
+        instance.start_policy_state_recorder(self.policy_state_interval);
+        instance
+    }
+
+    /// Same as `build`, but inserts `entries` after replaying `AOF`, as `AsyncCache::warm_from_snapshot` does:
+    /// in iteration order, evicting per entry as if they were put one by one, with their `Put` records handed
+    /// over to `AOF` in one write in the same order.
+    pub async fn build_with_entries<I: IntoIterator<Item = (K, V)>>(self, entries: I) -> AsyncCache<K, V> {
+        let cache = self.build().await;
+        cache.warm_from_snapshot(entries.into_iter().collect()).await;
+        cache
+    }
+}
+
+impl<K, V> AsyncCache <K, V>
+where
+    for<'de> K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + Deserialize<'de> + Serialize + 'static,
+    for<'de> V: Clone + Deserialize<'de> + Serialize + Send + Sync + 'static
+{
+    /// Performs the operations of `AOF` sequentially on `cache`. Returns the number of records read and the key
+    /// of the batch left open at the end, cut short by a crash, see `close_torn_batch`.
+    async fn replay(
+        cache: &mut Cache<K, V>,
+        mut iter: AOFIterator,
+        replay_progress: Option<&(u64, ReplayProgressCallback)>,
+        on_decode_error: Option<&DecodeErrorCallback>,
+    ) -> (u64, Option<K>) {
+        let total_bytes = iter.total_bytes().await.ok();
+        let mut records = 0;
+        // records of the batch being read, applied once its commit marker is read.
+        let mut batch: Option<OpenBatch<K, V>> = None;
+        loop {
+            let result = iter.next_decoded().await;
+            // recorded before the record just read.
+            if let Some(state) = iter.take_policy_state() {
+                cache.eviction_policy.import_state_bytes(&state);
+            }
+            let record = match result {
+                Ok(Some(Ok(record))) => record,
+                Ok(Some(Err((bytes, e)))) => match on_decode_error {
+                    Some(on_decode_error) => {
+                        on_decode_error(&bytes, e);
+                        continue;
+                    },
+                    None => break,
+                },
+                _ => break,
+            };
+            if record.operation == Operation::Put && record.value.is_none() {
+                // malformed record, skipped even without callback since the records after it can be read.
+                if let Some(on_decode_error) = on_decode_error {
+                    let detail = "put record without value".to_string();
+                    let key_bytes = serde_json::to_vec(&record.key).unwrap_or_default();
+                    on_decode_error(&key_bytes, CacheError::Corruption { offset: iter.bytes_read(), detail });
+                }
+                continue;
+            }
+            match (record.operation.clone(), batch.as_mut()) {
+                // a batch without commit marker, cut short by a crash, is discarded once another batch starts.
+                (Operation::BatchBegin, _) => batch = Some((record.key, record.version, vec![])),
+                (Operation::BatchCommit, _) => {
+                    for record in batch.take().map(|(_, _, records)| records).unwrap_or_default() {
+                        Self::apply_record(cache, record);
+                    }
+                },
+                (Operation::Put, Some((_, len, records))) if len.map_or(true, |len| (records.len() as u64) < len) => {
+                    records.push(record);
+                },
+                // batches only hold the announced number of `Put`s, so the record was written after an open
+                // batch cut short by a crash, which is discarded.
+                _ => {
+                    batch = None;
+                    Self::apply_record(cache, record);
+                },
+            }
+            records += 1;
+            if let Some((every, progress)) = replay_progress {
+                if records % every == 0 {
+                    progress(ReplayProgress { records, bytes_read: iter.bytes_read(), total_bytes });
+                }
+            }
+        }
+        if let Some((_, progress)) = replay_progress {
+            progress(ReplayProgress { records, bytes_read: iter.bytes_read(), total_bytes });
+        }
+        (records, batch.map(|(key, _, _)| key))
+    }
+
+    /// Appends an empty batch after the batch with `key` left open at the end of `AOF` by a crash, so that its
+    /// records are discarded on replay rather than taken for the `Put`s appended after them.
+    async fn close_torn_batch(&self, key: K) {
+        let marker = |operation, version| AOFRecord {
+            key: key.clone(),
+            value: None,
+            operation,
+            expires_at: None,
+            cache_id: None,
+            version,
+            origin_ts: None
+        };
+        // if it fails, writing the records after it fails as well, reported by `flush_health`.
+        let _ = self.subscriber_manager.on_events_synced(vec![marker(Operation::BatchBegin, Some(0)), marker(Operation::BatchCommit, None)]).await;
+    }
+
+    /// Performs the operation of a single record of `AOF` on `cache`.
+    fn apply_record(cache: &mut Cache<K, V>, record: AOFRecord<K, V>) {
+        match record.operation {
+            Operation::Get => {
+                let _ = cache.get(&record.key);
+            },
+            Operation::Put => {
+                let Some(value) = record.value else {
+                    return;
+                };
+                let entry = CacheEntry {
+                    value,
+                    expires_at: record.expires_at.map(unix_millis_to_instant),
+                    version: record.version.unwrap_or(0),
+                    origin_ts: record.origin_ts
+                };
+                // expired while the cache was down, so the key is absent and must not take the room of live
+                // entries until the purge after replay.
+                if entry.is_expired_at(cache.clock.now()) {
+                    cache.last_version = cache.last_version.max(entry.version);
+                    let _ = cache.remove(&record.key);
+                    return;
+                }
+                match record.version {
+                    Some(_) => cache.restore_entry(record.key, entry),
+                    // written before versions were persisted.
+                    None => {
+                        let _ = cache.put_entry(record.key, entry);
+                    }
+                }
+            },
+            Operation::Remove => {
+                let _ = cache.remove(&record.key);
+            },
+            Operation::Pin => {
+                cache.pin(&record.key);
+            },
+            Operation::Unpin => {
+                cache.unpin(&record.key);
+            },
+            Operation::Clear => {
+                cache.clear();
+            },
+            Operation::BatchBegin | Operation::BatchCommit | Operation::PolicyState => {}
+        }
+    }
+
+    /// Replaces the content of the cache with the `AOF` replayed from the start, e.g. after the file was
+    /// modified or replaced by another process.
+    ///
+    /// The file is re-opened and the records waiting for the periodic flush are written to it first, so
+    /// every operation performed so far is replayed exactly once. Entries which have expired are dropped, as
+    /// on `build`. The cache is locked for the whole reload, so concurrent operations wait for it to complete.
+    ///
+    /// Returns error, leaving the cache untouched, if there is no `AOF` or the file can not be opened.
+    pub async fn reload(&self) -> std::io::Result<()> {
+        let mut gaurd = self.cache.lock().await;
+        self.subscriber_manager.reopen().await?;
+        let mut iter = self.subscriber_manager.into_iter().await?;
+        iter.set_max_record_bytes(self.max_record_bytes);
+        gaurd.clear();
+        let (records, torn_batch) = Self::replay(&mut gaurd, iter, None, self.on_decode_error.as_ref()).await;
+        self.subscriber_manager.set_approx_records(records);
+        if let Some(key) = torn_batch {
+            self.close_torn_batch(key).await;
+        }
+        gaurd.purge_expired();
+        self.load_policy_state(&mut gaurd).await;
+        Ok(())
+    }
+
+    /// Seeds the eviction policy with the state saved by `save_policy_state`, if any. A missing or unreadable
+    /// file keeps the order of replay.
+    async fn load_policy_state(&self, cache: &mut Cache<K, V>) {
+        let Some(path) = self.policy_state_path.as_ref() else { return };
+        let Ok(bytes) = tokio::fs::read(path).await else { return };
+        if let Ok(state) = serde_json::from_slice(&bytes) {
+            cache.import_policy_state(state);
+        }
+    }
+
+    /// Saves the order of the eviction policy, so that it is restored on the next start, see
+    /// `AsyncCacheBuilder::persist_policy_state`. Meant to be called periodically or before shutdown, the file
+    /// is replaced atomically.
+    ///
+    /// Returns `CacheError::Config` if persisting policy state is not enabled or the eviction policy can not
+    /// export its state, see `EvictionPolicy::export_state`.
+    pub async fn save_policy_state(&self) -> Result<(), CacheError> {
+        self.named(self.save_policy_state_unnamed().await)
+    }
+
+    /// `save_policy_state` without the name of the cache in errors.
+    async fn save_policy_state_unnamed(&self) -> Result<(), CacheError> {
+        let Some(path) = self.policy_state_path.as_ref() else {
+            return Err(CacheError::Config(String::from("Policy state is not persisted, see `persist_policy_state`.")));
+        };
+        let state = self.cache.lock().await.export_policy_state()
+            .ok_or_else(|| CacheError::Config(String::from("Eviction policy can not export its state.")))?;
+        let tmp_path = format!("{}.tmp", path);
+        tokio::fs::write(&tmp_path, serde_json::to_vec(&state)?).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+
+    /// Records the state of the eviction policy in `AOF` right away, see
+    /// `AsyncCacheBuilder::policy_state_interval`, and waits until it is on the disk. Returns whether the policy
+    /// exported a state, see `EvictionPolicy::export_state_bytes`.
+    ///
+    /// Returns `CacheError::Io` if the policy exported a state but there is no `AOF` or writing fails.
+    pub async fn record_policy_state(&self) -> Result<bool, CacheError> {
+        self.named(Self::record_policy_state_to(&self.cache, &self.subscriber_manager).await)
+    }
+
+    /// Writes the live entries to `w` as NDJSON, one `{"key":...,"value":...}` object per line, e.g. to back up
+    /// the cache or move it to another system. Expiry and versions are not written. Returns the number of
+    /// entries written.
+    ///
+    /// Entries are copied and written in chunks, locking the cache only while copying a chunk, so the whole
+    /// cache is never buffered and other tasks are not blocked by a slow writer. Entries written or removed
+    /// meanwhile may or may not be written. This does not account for access.
+    pub async fn export_ndjson<W: AsyncWrite + Unpin>(&self, w: W) -> Result<u64, CacheError> {
+        self.named(self.export_ndjson_unnamed(w).await)
+    }
+
+    /// `export_ndjson` without the name of the cache in errors.
+    async fn export_ndjson_unnamed<W: AsyncWrite + Unpin>(&self, mut w: W) -> Result<u64, CacheError> {
+        let keys: Vec<K> = self.cache.lock().await.cache.keys().cloned().collect();
+        let mut exported = 0;
+        let mut bytes = vec![];
+        for keys in keys.chunks(NDJSON_CHUNK) {
+            let entries: Vec<(K, V)> = {
+                let gaurd = self.cache.lock().await;
+                let now = gaurd.clock.now();
+                keys.iter()
+                    .filter_map(|key| gaurd.cache.get_key_value(key))
+                    .filter(|(_, entry)| !entry.is_expired_at(now))
+                    .map(|(key, entry)| (key.clone(), entry.value.clone()))
+                    .collect()
+            };
+            bytes.clear();
+            for (key, value) in entries.iter() {
+                serde_json::to_writer(&mut bytes, &NdjsonEntry { key, value })?;
+                bytes.push(b'\n');
+            }
+            w.write_all(&bytes).await?;
+            exported += entries.len() as u64;
+        }
+        w.flush().await?;
+        Ok(exported)
+    }
+
+    /// Reads entries written by `export_ndjson` from `r` line by line and inserts them with `put`, so they are
+    /// recorded in `AOF` and evicted like any other write. Empty lines are skipped. Returns the number of
+    /// entries imported.
+    ///
+    /// Returns `CacheError::Serialize` with the number of the line at the first line which is not a valid
+    /// entry, the entries before it stay imported.
+    pub async fn import_ndjson<R: AsyncRead + Unpin>(&self, r: R) -> Result<u64, CacheError> {
+        self.named(self.import_ndjson_unnamed(r).await)
+    }
+
+    /// `import_ndjson` without the name of the cache in errors.
+    async fn import_ndjson_unnamed<R: AsyncRead + Unpin>(&self, r: R) -> Result<u64, CacheError> {
+        let mut lines = tokio::io::BufReader::new(r).lines();
+        let mut imported = 0;
+        let mut line_number = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: NdjsonEntry<K, V> = serde_json::from_str(&line)
+                .map_err(|e| CacheError::Serialize(serde::de::Error::custom(format!("line {}: {}", line_number, e))))?;
+            self.put(entry.key, entry.value).await;
+            imported += 1;
+        }
+        Ok(imported)
+    }
+}
+
+/// Number of entries evicted under one lock down to `AsyncCacheBuilder::soft_limit`.
+const SOFT_LIMIT_BATCH: usize = 64;
+
+/// Number of entries copied under one lock by `AsyncCache::export_ndjson`.
+const NDJSON_CHUNK: usize = 1024;
+
+/// Line of `AsyncCache::export_ndjson`.
+#[derive(Serialize, Deserialize)]
+struct NdjsonEntry<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<K, V> AsyncCacheBuilder<K, V>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static
+{
+    /// Creates an in-memory `AsyncCache`. See `AsyncCache::new_without_aof`.
+    pub async fn build_without_aof(self) -> AsyncCache<K, V> {
+        if self.config.get_aof_config().is_some() || self.shared_aof.is_some() || self.memory_aof.is_some() {
+            panic!("AOF can not be used without serializable keys and values, use `AsyncCache::new` instead.");
+        }
+        if let Some(error) = self.option_errors().first() {
+            panic!("{}", error);
+        }
+        let config = self.config;
+        let instance = AsyncCache {
+            persist_read_ops: config.persist_read_ops(),
+            subscriber_manager: Arc::new(CacheEventSubscriber::without_aof()),
+            in_flight: Default::default(),
+            on_decode_error: self.on_decode_error,
+            loader: self.loader,
+            loader_timeout: self.loader_timeout,
+            loader_ttl: self.loader_ttl,
+            refresh_ahead_fraction: self.refresh_ahead_fraction,
+            max_record_bytes: self.max_record_bytes,
+            policy_state_path: None,
+            snapshot: self.stale_reads.map(|_| Default::default()),
+            name: self.name.map(Arc::from),
+            tasks: Default::default(),
+            key_normalizer: self.key_normalizer.clone(),
+            cache: Arc::new(Mutex::new(Cache::with_options(config.get_sync_config(), self.weigher, self.entry_overhead, self.max_memory, self.clock, self.ghost_capacity, self.key_normalizer.map(|x| Box::new(move |key: &K| x(key)) as KeyNormalizer<K>))))
+        };
+        instance.start_sweeper(self.sweep_interval);
+        instance.start_soft_limit_evictor(self.soft_limit);
+        instance.start_snapshot_refresher(self.stale_reads).await;
+        instance
+    }
+}
+
+impl<K, V> AsyncCache <K, V>
+where
+    for<'de> K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + Deserialize<'de> + Serialize + 'static,
+    for<'de> V: Clone + Deserialize<'de> + Serialize + Send + Sync + 'static
+{
+    /// Creates a new `AsyncCache` instance based on configurations.
+    /// 
+    /// In case of `AOF`, if given `cache_name` already exists in persistent files, it goes through all the
+    /// operations sequentially and performs those on the newly created instance to get the latest cache.
+    /// 
+    /// Data may be lost in case of `flush_time` being not `None` for the last `flush_time` milliseconds before
+    /// crash or stop.
+    /// 
+    /// Changing `EvictionPolicy` may load different keys as no meta data regarding policy, flushtime etc
+    /// is persisted.
+    ///
+    /// In case of `NoEviction` and `read heavy` cache, using `flush_time = None` with `persist_read_ops = false`
+    /// i.e. flush on every write but reads will not be persisted remove may be useful as `writes` 
+    /// speed will be slow but `reads` will become faster.
+    /// 
+    /// In case of eviction policies, setting `flush_time` as `None` is *NOT RECOMMENDED* as it will make it as slow
+    /// as disk io, so it panics unless allowed through `AsyncCacheBuilder::allow_sync_writes`.
+    /// 
+    /// Keys and values must be serializable even when `AOF` is not configured, for types which do not implement
+    /// serde traits, use `new_without_aof`.
+    /// 
+    /// For optional features like background purging of expired entries, use `AsyncCacheBuilder`.
+    /// 
+    pub async fn new(config: AsyncCacheConfig<K>) -> Self {
+        AsyncCacheBuilder::new(config).build().await
+    }
+
+    /// Same as `new`, but calls `progress` after every `every` records replayed from `AOF` and once when the
+    /// replay completes. See `AsyncCacheBuilder::replay_progress`.
+    pub async fn new_with_progress<F>(config: AsyncCacheConfig<K>, every: u64, progress: F) -> Self
+    where
+        F: Fn(ReplayProgress) + Send + Sync + 'static,
+    {
+        AsyncCacheBuilder::new(config).replay_progress(every, progress).build().await
+    }
+
+    /// Creates a new `AsyncCache` pre-populated with `entries`, e.g. read from a configuration source or a
+    /// database at startup. See `AsyncCacheBuilder::build_with_entries`.
+    pub async fn from_entries<I: IntoIterator<Item = (K, V)>>(config: AsyncCacheConfig<K>, entries: I) -> Self {
+        AsyncCacheBuilder::new(config).build_with_entries(entries).await
+    }
+}
+
+impl<K, V> AsyncCache <K, V>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static
+{
+    /// Creates a new in-memory `AsyncCache` instance based on configurations.
+    ///
+    /// Unlike `new`, keys and values are not required to implement `Serialize` and `Deserialize` as nothing
+    /// is persisted.
+    ///
+    /// Panics if `AOF` is configured in `config`, use `new` in that case.
+    ///
+    pub async fn new_without_aof(config: AsyncCacheConfig<K>) -> Self {
+        AsyncCacheBuilder::new(config).build_without_aof().await
+    }
+
+    /// Spawns the background task purging expired entries. It holds weak references only, so it stops
+    /// once the cache is dropped, or on `shutdown`.
+    fn start_sweeper(&self, sweep_interval: Option<Duration>) {
+        let Some(sweep_interval) = sweep_interval else {
+            return;
+        };
+        let cache = Arc::downgrade(&self.cache);
+        let subscriber_manager = Arc::downgrade(&self.subscriber_manager);
+        self.tasks.spawn(|mut stop| async move {
+            while sleep_unless_stopped(sweep_interval, &mut stop).await {
+                let (Some(cache), Some(subscriber_manager)) = (cache.upgrade(), subscriber_manager.upgrade()) else {
+                    return;
+                };
+                Self::purge_expired_and_persist(&cache, &subscriber_manager).await;
+            }
+        });
+    }
+
+    /// Spawns the background task evicting down to the soft limit, see `AsyncCacheBuilder::soft_limit`. It
+    /// holds weak references only, so it stops once the cache is dropped, or on `shutdown`.
+    fn start_soft_limit_evictor(&self, soft_limit: Option<(usize, Duration)>) {
+        let Some((soft_limit, check_interval)) = soft_limit else {
+            return;
+        };
+        let cache = Arc::downgrade(&self.cache);
+        let subscriber_manager = Arc::downgrade(&self.subscriber_manager);
+        self.tasks.spawn(|mut stop| async move {
+            while sleep_unless_stopped(check_interval, &mut stop).await {
+                let (Some(cache), Some(subscriber_manager)) = (cache.upgrade(), subscriber_manager.upgrade()) else {
+                    return;
+                };
+                while Self::evict_past_soft_limit(&cache, &subscriber_manager, soft_limit).await > 0 {}
+            }
+        });
+    }
+
+    /// Evicts at most `SOFT_LIMIT_BATCH` of the entries past `soft_limit` under one lock, so that writers are
+    /// not blocked for long, and records a `Remove` for each of them. Returns the number evicted.
+    async fn evict_past_soft_limit(cache: &Mutex<Cache<K, V>>, subscriber_manager: &CacheEventSubscriber<K, V>, soft_limit: usize) -> usize {
+        let mut gaurd = cache.lock().await;
+        let excess = gaurd.size().saturating_sub(soft_limit);
+        let evicted = gaurd.evict_n(excess.min(SOFT_LIMIT_BATCH));
+        let count = evicted.len();
+        if count == 0 {
+            return 0;
+        }
+        let records = evicted.into_iter().map(|key| AOFRecord {
+            key,
+            value: None,
+            operation: crate::common::Operation::Remove,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None
+        }).collect();
+        subscriber_manager.on_event_multi(records).await;
+        drop(gaurd);
+        count
+    }
+
+    /// Publishes the entries for `get_stale` and spawns the background task refreshing them. It holds weak
+    /// references only, so it stops once the cache is dropped, or on `shutdown`.
+    async fn start_snapshot_refresher(&self, refresh_interval: Option<Duration>) {
+        let (Some(refresh_interval), Some(snapshot)) = (refresh_interval, self.snapshot.as_ref()) else {
+            return;
+        };
+        let mut published = Self::publish_snapshot(&self.cache, snapshot, None).await;
+        let cache = Arc::downgrade(&self.cache);
+        let snapshot = Arc::downgrade(snapshot);
+        self.tasks.spawn(|mut stop| async move {
+            while sleep_unless_stopped(refresh_interval, &mut stop).await {
+                let (Some(cache), Some(snapshot)) = (cache.upgrade(), snapshot.upgrade()) else {
+                    return;
+                };
+                published = Self::publish_snapshot(&cache, &snapshot, Some(published)).await;
+            }
+        });
+    }
+
+    /// Replaces the snapshot with a copy of the live entries unless the cache is unchanged since `published`,
+    /// the last version and length at the previous refresh. Returns the ones of the cache now.
+    async fn publish_snapshot(cache: &Mutex<Cache<K, V>>, snapshot: &std::sync::RwLock<Arc<HashMap<K, V>>>, published: Option<(u64, usize)>) -> (u64, usize) {
+        let gaurd = cache.lock().await;
+        let current = (gaurd.last_version, gaurd.cache.len());
+        if published == Some(current) {
+            return current;
+        }
+        let entries = gaurd.snapshot(None);
+        drop(gaurd);
+        *snapshot.write().unwrap() = Arc::new(entries);
+        current
+    }
+
+    /// Spawns the background task recording the state of the eviction policy, see
+    /// `AsyncCacheBuilder::policy_state_interval`. It holds weak references only, so it stops once the cache
+    /// is dropped, or on `shutdown`.
+    fn start_policy_state_recorder(&self, policy_state_interval: Option<Duration>) {
+        let Some(policy_state_interval) = policy_state_interval else {
+            return;
+        };
+        let cache = Arc::downgrade(&self.cache);
+        let subscriber_manager = Arc::downgrade(&self.subscriber_manager);
+        self.tasks.spawn(|mut stop| async move {
+            while sleep_unless_stopped(policy_state_interval, &mut stop).await {
+                let (Some(cache), Some(subscriber_manager)) = (cache.upgrade(), subscriber_manager.upgrade()) else {
+                    return;
+                };
+                // a failed write is reported by `flush_health` and retried on the next tick.
+                let _ = Self::record_policy_state_to(&cache, &subscriber_manager).await;
+            }
+        });
+    }
+
+    /// Exports the state of the eviction policy and records it, holding the lock so that it lands between the
+    /// records of the operations before and after it.
+    async fn record_policy_state_to(cache: &Mutex<Cache<K, V>>, subscriber_manager: &CacheEventSubscriber<K, V>) -> Result<bool, CacheError> {
+        let gaurd = cache.lock().await;
+        let Some(state) = gaurd.eviction_policy.export_state_bytes() else {
+            return Ok(false);
+        };
+        subscriber_manager.on_policy_state(state).await?;
+        drop(gaurd);
+        Ok(true)
+    }
+
+    /// Purges the expired entries and records a `Remove` for each of them.
+    async fn purge_expired_and_persist(cache: &Mutex<Cache<K, V>>, subscriber_manager: &CacheEventSubscriber<K, V>) -> usize {
+        let mut gaurd = cache.lock().await;
+        let expired = gaurd.purge_expired();
+        let count = expired.len();
+        for key in expired {
+            subscriber_manager.on_event(AOFRecord {
+                key,
+                value: None,
+                operation: crate::common::Operation::Remove,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        }
+        drop(gaurd);
+        count
+    }
+
+    /// Retrieves the value associated with the given key from the cache.
+    ///
+    /// Asynchronously retrieves the value associated with the provided `key` from the cache.
+    /// Returns `None` if the key is not found.
+    ///
+    /// If a loader is configured, see `AsyncCacheBuilder::loader`, a miss is loaded and inserted first.
+
+    pub async fn get(&self, key: &K) -> Option<V>
+    {
+        self.get_loading(key, self.loader_timeout).await.unwrap_or(None)
+    }
+
+    /// Same as `get`, but gives up on the loader after `timeout` and returns `LoaderTimeout`, e.g. so that a slow
+    /// backend does not hang the caller. The loader is dropped, i.e. cancelled, and nothing is cached.
+    ///
+    /// Every caller waiting for the same key counts `timeout` from its own call, including the time spent
+    /// waiting for the loader of another caller, so all of them observe the timeout of a hanging backend.
+    pub async fn get_with_loader_timeout(&self, key: &K, timeout: Duration) -> Result<Option<V>, LoaderTimeout> {
+        self.get_loading(key, Some(timeout)).await
+    }
+
+    /// Same as `get`, but if the entry has at most `refresh_ahead_fraction` of `loader_ttl` left, the current
+    /// value is returned and the loader reloads it in background, see `AsyncCacheBuilder::refresh_ahead_fraction`.
+    /// Without refresh ahead configured it is the same as `get`.
+    ///
+    /// A refresh is single-flight with the other loads of the key: it is skipped while the key is being loaded
+    /// or refreshed already, and a miss of the key waits for it. The reloaded value is inserted like
+    /// `put_with_ttl`, but only if the entry was not written nor removed while loading, so that a refresh never
+    /// overwrites a newer value or brings back a removed key. If the loader returns `None` the entry is kept
+    /// until it expires. If the loader panics, only the background task fails.
+    pub async fn get_or_refresh_ahead(&self, key: &K) -> Option<V> {
+        let value = self.get(key).await?;
+        if let Some((fraction, loader_ttl)) = self.refresh_ahead_fraction.zip(self.loader_ttl) {
+            let gaurd = self.cache.lock().await;
+            let key = gaurd.normalized(key);
+            let ttl_and_version = gaurd.get_ttl(&key).zip(gaurd.cache.get(key.as_ref()).map(|x| x.version));
+            drop(gaurd);
+            if let Some((_, version)) = ttl_and_version.filter(|(ttl, _)| *ttl <= loader_ttl.mul_f32(fraction)) {
+                self.refresh_in_background(key.into_owned(), loader_ttl, version);
+            }
+        }
+        Some(value)
+    }
+
+    /// Reloads the entry of `key` in background and inserts it with `ttl` if it still has `version`, unless the
+    /// key is being loaded already, see `get_or_refresh_ahead`.
+    fn refresh_in_background(&self, key: K, ttl: Duration, version: u64) {
+        let Some(loader) = self.loader.clone() else { return };
+        let key_lock = self.in_flight.lock().unwrap().entry(key.clone()).or_default().clone();
+        let Ok(key_guard) = key_lock.clone().try_lock_owned() else {
+            drop(key_lock);
+            self.forget_in_flight(&key);
+            return;
+        };
+        let cache = self.clone();
+        tokio::spawn(async move {
+            if let Some(value) = loader(key.clone()).await {
+                cache.put_with_ttl_if_version(key.clone(), value, ttl, Some(version)).await;
+            }
+            drop(key_guard);
+            drop(key_lock);
+            cache.forget_in_flight(&key);
+        });
+    }
+
+    /// Retrieves the value, loading a miss with the loader, if any, within `timeout`.
+    async fn get_loading(&self, key: &K, timeout: Option<Duration>) -> Result<Option<V>, LoaderTimeout> {
+        let value = self.get_opts(key, true).await;
+        match (value, self.loader.as_ref()) {
+            (None, Some(loader)) => {
+                let (loader, owned_key) = (loader.clone(), key.clone());
+                let load = || async move { loader(owned_key).await.ok_or(()) };
+                let loading = self.insert_missing_with_ttl(key.clone(), load, |_| self.loader_ttl);
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, loading).await
+                        .map(|x| x.ok())
+                        .map_err(|_| LoaderTimeout { timeout }),
+                    None => Ok(loading.await.ok()),
+                }
+            },
+            (value, _) => Ok(value),
+        }
+    }
+
+    /// Same as `get`, but if `record_access` is `false` the read neither counts as an access for the eviction
+    /// policy nor is recorded in `AOF`, e.g. for tools walking the cache.
+    pub async fn get_opts(&self, key: &K, record_access: bool) -> Option<V>
+    {
+        let mut guard = self.cache.lock().await;
+        let value = guard.get_opts(key, record_access).cloned();
+        // a miss is not an access, so it is not recorded.
+        if record_access && value.is_some() && self.persist_read_ops.as_ref().is_some_and(|x| x.clone()) {
+            self.subscriber_manager.on_event(AOFRecord {
+                key: guard.normalized(key).into_owned(),
+                value: None,
+                operation: crate::common::Operation::Get,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        };
+        drop(guard);
+        value
+    }
+
+    /// Retrieves the value without counting as an access or recording it, same as `get_opts(key, false)`.
+    pub async fn peek(&self, key: &K) -> Option<V>
+    {
+        self.get_opts(key, false).await
+    }
+
+    /// Reads the value from the copy of the entries refreshed in background, without locking the cache, see
+    /// `AsyncCacheBuilder::stale_reads`. The value may be up to one refresh interval behind the writes, so a
+    /// key just written or removed may still be missing or present, and an entry which expired since the
+    /// last refresh may still be returned. The read neither counts as an access nor is recorded in `AOF`.
+    ///
+    /// Panics if stale reads are not enabled.
+    pub fn get_stale(&self, key: &K) -> Option<V>
+    {
+        let snapshot = self.snapshot.as_ref().expect("Stale reads are not enabled, see `AsyncCacheBuilder::stale_reads`.");
+        // the lock is only held to clone the `Arc`, readers never wait for a refresh copying the entries.
+        let entries = snapshot.read().unwrap().clone();
+        match self.key_normalizer.as_ref() {
+            Some(key_normalizer) => entries.get(&key_normalizer(key)).cloned(),
+            None => entries.get(key).cloned(),
+        }
+    }
+
+    /// Same as `get`, but takes any borrowed form of the key, e.g. `&str` for `String` keys, so that no owned
+    /// key has to be allocated for the lookup. The stored key is cloned only to record the read in `AOF`.
+    pub async fn get_borrowed<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let mut guard = self.cache.lock().await;
+        let persist_read_ops = self.persist_read_ops.is_some_and(|x| x);
+        let (key, value) = guard.get_entry_borrowed(key)
+            .map(|(key, value)| (persist_read_ops.then(|| key.clone()), value.clone()))?;
+        if let Some(key) = key {
+            self.subscriber_manager.on_event(AOFRecord {
+                key,
+                value: None,
+                operation: crate::common::Operation::Get,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        }
+        drop(guard);
+        Some(value)
+    }
+
+    /// Retrieves a reference to the value associated with the given key from the cache.
+    ///
+    /// Asynchronously retrieves a reference to the value associated with the provided `key` from the cache,
+    /// without cloning it. Returns `None` if the key is not found.
+    ///
+    /// The returned `ValueRef` keeps the cache locked, so that the value can not be evicted or removed while
+    /// it is borrowed. All the other operations wait until it is dropped, so drop it as soon as possible and
+    /// never hold it across an operation on the same cache, which would deadlock.
+    pub async fn get_ref(&self, key: &K) -> Option<ValueRef<'_, V>>
+    {
+        let gaurd = self.cache.lock().await;
+        let key = gaurd.normalized(key);
+        let val = tokio::sync::MutexGuard::try_map(gaurd, |x| x.get_mut(&key)).ok();
+        if val.is_some() && self.persist_read_ops.as_ref().is_some_and(|x| x.clone()) {
+            self.subscriber_manager.on_event(AOFRecord {
+                key: key.into_owned(),
+                value: None,
+                operation: crate::common::Operation::Get,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        };
+        val.map(ValueRef)
+    }
+
+    /// Calls `f` with references to the values of `keys`, in the same order, all read under one lock so that
+    /// they are a consistent point-in-time view, e.g. of all the shards of an object. Every hit counts as an
+    /// access, same as `get`.
+    ///
+    /// The cache stays locked while `f` runs, so keep it short and never use the same cache inside it.
+    pub async fn read_many<R>(&self, keys: &[K], f: impl FnOnce(&[Option<&V>]) -> R) -> R
+    {
+        let mut gaurd = self.cache.lock().await;
+        let keys: Vec<Cow<K>> = keys.iter().map(|key| gaurd.normalized(key)).collect();
+        let hits: Vec<&K> = keys.iter().map(Cow::as_ref).filter(|key| gaurd.get(key).is_some()).collect();
+        if !hits.is_empty() && self.persist_read_ops.is_some_and(|x| x) {
+            self.subscriber_manager.on_event_multi(hits.into_iter().map(|key| AOFRecord {
+                key: key.clone(),
+                value: None,
+                operation: crate::common::Operation::Get,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).collect()).await;
+        }
+        let values: Vec<Option<&V>> = keys.iter().map(|key| gaurd.cache.get(key.as_ref()).map(|x| &x.value)).collect();
+        f(&values)
+    }
+
+    /// Counts an access to each of `keys` present in the cache, same as `get` but without cloning the values,
+    /// e.g. to protect a working set from eviction ahead of a known burst. All the keys are touched under one
+    /// lock and, with `persist_read_ops`, recorded in `AOF` in one write. Absent and expired keys are skipped.
+    ///
+    /// Returns the number of keys touched.
+    pub async fn touch_many(&self, keys: &[K]) -> usize
+    {
+        let mut gaurd = self.cache.lock().await;
+        let keys: Vec<Cow<K>> = keys.iter().map(|key| gaurd.normalized(key)).collect();
+        let hits: Vec<&K> = keys.iter().map(Cow::as_ref).filter(|key| gaurd.get(key).is_some()).collect();
+        let touched = hits.len();
+        if !hits.is_empty() && self.persist_read_ops.is_some_and(|x| x) {
+            self.subscriber_manager.on_event_multi(hits.into_iter().map(|key| AOFRecord {
+                key: key.clone(),
+                value: None,
+                operation: crate::common::Operation::Get,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).collect()).await;
+        }
+        drop(gaurd);
+        touched
+    }
+
+    /// Returns the value of the key, inserting the one computed by `init` if the key is absent.
+    ///
+    /// See `get_or_try_insert_with`, `init` runs at most once at a time per key.
+    pub async fn get_or_insert_with<F, Fut>(&self, key: K, init: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let result: Result<V, Infallible> = self.get_or_try_insert_with(key, || async { Ok(init().await) }).await;
+        match result {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Returns the value of the key, inserting the one computed by `init` if the key is absent and `init`
+    /// succeeds. Errors are returned to the caller and nothing is cached.
+    ///
+    /// Initialization is single-flight per key: while one caller runs `init`, other callers for the same key
+    /// wait, without holding the cache lock, and then get the inserted value. If `init` fails, the waiting
+    /// callers don't receive that error, the next one in line runs its own `init` instead, so every caller
+    /// ends up either with a value or with the error of its own `init`.
+    ///
+    /// The value is inserted with `put`, so a `Put` is recorded in `AOF` only on success.
+    pub async fn get_or_try_insert_with<E, F, Fut>(&self, key: K, init: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        self.get_or_try_insert_with_ttl(key, init, |_| None).await
+    }
+
+    /// Same as `get_or_try_insert_with`, but the value is inserted with the ttl returned by `ttl`, if any.
+    async fn get_or_try_insert_with_ttl<E, F, Fut, T>(&self, key: K, init: F, ttl: T) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        T: FnOnce(&V) -> Option<Duration>,
+    {
+        if let Some(value) = self.get_opts(&key, true).await {
+            return Ok(value);
+        }
+        self.insert_missing_with_ttl(key, init, ttl).await
+    }
+
+    /// Initializes the value of a key which was just missed with `init`, once for all the concurrent callers,
+    /// and inserts it with the ttl returned by `ttl`, if any. If a previous initialization inserted the key
+    /// while waiting, its value is returned instead, without counting as another lookup.
+    async fn insert_missing_with_ttl<E, F, Fut, T>(&self, key: K, init: F, ttl: T) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        T: FnOnce(&V) -> Option<Duration>,
+    {
+        // callers of keys normalized to the same key share the lock.
+        let key = self.cache.lock().await.normalize_key(key);
+        let key_lock = self.in_flight.lock().unwrap().entry(key.clone()).or_default().clone();
+        let key_guard = key_lock.lock().await;
+        // it may have been inserted while waiting for the previous initialization.
+        let result = match self.get_opts(&key, false).await {
+            Some(value) => Ok(value),
+            None => match init().await {
+                Ok(value) => {
+                    match ttl(&value) {
+                        Some(ttl) => self.put_with_ttl(key.clone(), value.clone(), ttl).await,
+                        None => self.put(key.clone(), value.clone()).await,
+                    }
+                    Ok(value)
+                },
+                Err(e) => Err(e)
+            }
+        };
+        drop(key_guard);
+        drop(key_lock);
+        self.forget_in_flight(&key);
+        result
+    }
+
+    /// Removes the lock of `key` from `in_flight` once its holder released it, unless others are waiting.
+    fn forget_in_flight(&self, key: &K) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // only the map holds the lock, no one else is waiting.
+        if in_flight.get(key).is_some_and(|x| Arc::strong_count(x) == 1) {
+            in_flight.remove(key);
+        }
+    }
+
+    /// Inserts a new key-value pair into the cache.
     ///
     /// Asynchronously inserts a new key-value pair into the cache.
      
     pub async fn put(&self, key: K, value: V) {
         let mut gaurd = self.cache.lock().await;
-        gaurd.put(key.clone(), value.clone());
+        let key = gaurd.normalize_key(key);
+        gaurd.put(key.clone(), value.clone());
+        self.subscriber_manager.on_event(AOFRecord {
+            key: key,
+            value: Some(value),
+            operation: crate::common::Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(gaurd.last_version),
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+    }
+
+    /// Same as `put`, but fails if no entry can be evicted to make room, see `Cache::try_put`. Nothing is
+    /// recorded in `AOF` if it fails.
+    pub async fn try_put(&self, key: K, value: V) -> Result<Option<V>, CacheFull<K, V>> {
+        let mut gaurd = self.cache.lock().await;
+        let key = gaurd.normalize_key(key);
+        let previous = gaurd.try_put(key.clone(), value.clone())?;
+        self.subscriber_manager.on_event(AOFRecord {
+            key,
+            value: Some(value),
+            operation: crate::common::Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(gaurd.last_version),
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+        Ok(previous)
+    }
+
+    /// Inserts a new key-value pair into the cache which expires after `ttl`.
+    ///
+    /// The expiry is persisted in `AOF` as an absolute time, so an entry which expired while the cache was down
+    /// is not restored.
+
+    pub async fn put_with_ttl(&self, key: K, value: V, ttl: Duration) {
+        self.put_with_ttl_if_version(key, value, ttl, None).await;
+    }
+
+    /// Same as `put_with_ttl`, but if `expected_version` is given the value is written only if the entry of the
+    /// key still has this version, so never to an absent key.
+    async fn put_with_ttl_if_version(&self, key: K, value: V, ttl: Duration, expected_version: Option<u64>) {
+        let mut gaurd = self.cache.lock().await;
+        let key = gaurd.normalize_key(key);
+        if expected_version.is_some_and(|version| gaurd.cache.get(&key).map(|x| x.version) != Some(version)) {
+            return;
+        }
+        let entry = gaurd.entry_with_ttl(value.clone(), ttl);
+        let expires_at = entry.expires_at.map(instant_to_unix_millis);
+        let _ = gaurd.put_entry(key.clone(), entry);
+        self.subscriber_manager.on_event(AOFRecord {
+            key,
+            value: Some(value),
+            operation: crate::common::Operation::Put,
+            expires_at,
+            cache_id: None,
+            version: Some(gaurd.last_version),
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+    }
+
+    /// Returns the time left until the entry expires, see `Cache::get_ttl`.
+    pub async fn get_ttl(&self, key: &K) -> Option<Duration> {
+        self.cache.lock().await.get_ttl(key)
+    }
+
+    /// Makes the entry expire after `ttl`, or never in case of `None`, keeping its value and version, see
+    /// `Cache::set_ttl`. Returns whether the key was present.
+    ///
+    /// The change is persisted to `AOF` as a `Put` of the current value with the new expiry.
+    pub async fn set_ttl(&self, key: &K, ttl: Option<Duration>) -> bool {
+        let mut gaurd = self.cache.lock().await;
+        if !gaurd.set_ttl(key, ttl) {
+            return false;
+        }
+        let key = gaurd.normalized(key);
+        let entry = gaurd.cache.get(key.as_ref()).unwrap();
+        self.subscriber_manager.on_event(AOFRecord {
+            key: key.into_owned(),
+            value: Some(entry.value.clone()),
+            operation: crate::common::Operation::Put,
+            expires_at: entry.expires_at.map(instant_to_unix_millis),
+            cache_id: None,
+            version: Some(entry.version),
+            origin_ts: entry.origin_ts
+        }).await;
+        drop(gaurd);
+        true
+    }
+
+    /// Inserts all the entries together: other operations see either none or all of them, and they are persisted
+    /// in `AOF` all or nothing.
+    ///
+    /// The `Put` records are written between `BatchBegin` and `BatchCommit` markers in one go, right away
+    /// regardless of `flush_time`, and synced to disk before the entries are inserted in memory. On replay, a
+    /// batch without commit marker, e.g. cut short by a crash, is discarded entirely.
+    ///
+    /// Returns error, inserting nothing, if writing to `AOF` fails. Entries with the same key are inserted in
+    /// order, so the last one wins.
+    pub async fn put_batch_atomic(&self, entries: Vec<(K, V)>) -> Result<(), CacheError> {
+        self.named(self.put_batch_atomic_unnamed(entries).await)
+    }
+
+    /// `put_batch_atomic` without the name of the cache in errors.
+    async fn put_batch_atomic_unnamed(&self, entries: Vec<(K, V)>) -> Result<(), CacheError> {
+        let Some((first_key, _)) = entries.first() else {
+            return Ok(());
+        };
+        let marker = |operation, version| AOFRecord {
+            key: first_key.clone(),
+            value: None,
+            operation,
+            expires_at: None,
+            cache_id: None,
+            version,
+            origin_ts: None
+        };
+        let mut gaurd = self.cache.lock().await;
+        // the number of records tells replay where the batch ends, should its commit marker be missing.
+        let mut records = vec![marker(Operation::BatchBegin, Some(entries.len() as u64))];
+        // every put takes the next version.
+        for (i, (key, value)) in entries.iter().enumerate() {
+            records.push(AOFRecord {
+                key: gaurd.normalize_key(key.clone()),
+                value: Some(value.clone()),
+                operation: Operation::Put,
+                expires_at: None,
+                cache_id: None,
+                version: Some(gaurd.last_version + i as u64 + 1),
+                origin_ts: None
+            });
+        }
+        records.push(marker(Operation::BatchCommit, None));
+        self.subscriber_manager.on_events_synced(records).await?;
+        for (key, value) in entries {
+            gaurd.put(key, value);
+        }
+        drop(gaurd);
+        Ok(())
+    }
+
+    /// Bulk loads `entries`, e.g. restored from a snapshot, locking the cache once for all of them, so that
+    /// other tasks see either none or all of the entries.
+    ///
+    /// Entries are inserted in order by `Cache::put_many`, i.e. one by one through the eviction policy, so pass
+    /// them sorted from the first to evict to the last. A `Put` is recorded in `AOF` for every entry, handed
+    /// over in one go. It saves the locking and the `AOF` call of every `put`, not the insertions, see
+    /// `benches/throughput.rs`.
+    pub async fn warm_from_snapshot(&self, entries: Vec<(K, V)>) {
+        let mut gaurd = self.cache.lock().await;
+        let first_version = gaurd.last_version + 1;
+        let records = entries.iter().enumerate().map(|(i, (key, value))| AOFRecord {
+            key: gaurd.normalize_key(key.clone()),
+            value: Some(value.clone()),
+            operation: Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(first_version + i as u64),
+            origin_ts: None
+        }).collect();
+        gaurd.put_many(entries);
+        self.subscriber_manager.on_event_multi(records).await;
+        drop(gaurd);
+    }
+
+    /// Inserts a new key-value pair into the cache and returns the previous value of the key, atomically.
+    ///
+    /// Same as `put` apart from the returned value, a single `Put` is recorded in `AOF`.
+    pub async fn replace(&self, key: K, value: V) -> Option<V> {
+        let mut gaurd = self.cache.lock().await;
+        let key = gaurd.normalize_key(key);
+        let previous = gaurd.replace(key.clone(), value.clone());
         self.subscriber_manager.on_event(AOFRecord {
-            key: key,
+            key,
+            value: Some(value),
+            operation: crate::common::Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(gaurd.last_version),
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+        previous
+    }
+
+    /// Writes `value` only if the key is present, see `Cache::replace_if_present`. A `Put` is recorded in `AOF`
+    /// only if the value was written.
+    pub async fn replace_if_present(&self, key: &K, value: V) -> Option<V> {
+        let mut gaurd = self.cache.lock().await;
+        let previous = gaurd.replace_if_present(key, value.clone())?;
+        self.subscriber_manager.on_event(AOFRecord {
+            key: gaurd.normalized(key).into_owned(),
+            value: Some(value),
+            operation: crate::common::Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(gaurd.last_version),
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+        Some(previous)
+    }
+
+    /// Calls `f` with a mutable reference to the value of the key in place, returning its result, or `None`
+    /// if the key is absent. The access counts for the eviction policy only if `record_access` is `true`, see
+    /// `Cache::get_mut_no_touch`.
+    ///
+    /// The value gets a new version and is recorded in `AOF` as a `Put` keeping its expiry, which counts as a
+    /// write when replayed.
+    pub async fn update<R>(&self, key: &K, record_access: bool, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        let mut gaurd = self.cache.lock().await;
+        let key = gaurd.normalized(key);
+        let value = match record_access {
+            true => gaurd.get_mut(&key),
+            false => gaurd.get_mut_no_touch(&key),
+        }?;
+        let result = f(value);
+        let entry = gaurd.bump_version(&key).unwrap();
+        self.subscriber_manager.on_event(AOFRecord {
+            key: key.into_owned(),
+            value: Some(entry.value.clone()),
+            operation: crate::common::Operation::Put,
+            expires_at: entry.expires_at.map(instant_to_unix_millis),
+            cache_id: None,
+            version: Some(entry.version),
+            origin_ts: entry.origin_ts
+        }).await;
+        drop(gaurd);
+        Some(result)
+    }
+
+    /// Retrieves the value along with its version, see `Cache::get_with_version`.
+    pub async fn get_with_version(&self, key: &K) -> Option<(V, u64)> {
+        let mut guard = self.cache.lock().await;
+        let value = guard.get_with_version(key).map(|(value, version)| (value.clone(), version));
+        if value.is_some() && self.persist_read_ops.is_some_and(|x| x) {
+            self.subscriber_manager.on_event(AOFRecord {
+                key: guard.normalized(key).into_owned(),
+                value: None,
+                operation: crate::common::Operation::Get,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        };
+        drop(guard);
+        value
+    }
+
+    /// Writes `value` only if the current version of the key is `expected_version` and returns the new version,
+    /// see `Cache::compare_and_swap`. The check and the write happen under one lock, a `Put` with the new
+    /// version is recorded in `AOF` only if the value was written, so versions survive restarts.
+    pub async fn compare_and_swap(&self, key: K, expected_version: u64, value: V) -> Result<u64, CasError> {
+        let mut gaurd = self.cache.lock().await;
+        let key = gaurd.normalize_key(key);
+        let version = gaurd.compare_and_swap(key.clone(), expected_version, value.clone())?;
+        self.subscriber_manager.on_event(AOFRecord {
+            key,
+            value: Some(value),
+            operation: crate::common::Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(version),
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+        Ok(version)
+    }
+
+    /// Writes `value` only if `timestamp` is greater than the timestamp of the current value, see
+    /// `Cache::put_if_newer`. A `Put` with the timestamp is recorded in `AOF` only if the value was written, so
+    /// the latest value wins after a restart as well.
+    pub async fn put_if_newer(&self, key: K, value: V, timestamp: u64) -> bool {
+        let mut gaurd = self.cache.lock().await;
+        let key = gaurd.normalize_key(key);
+        if !gaurd.put_if_newer(key.clone(), value.clone(), timestamp) {
+            return false;
+        }
+        self.subscriber_manager.on_event(AOFRecord {
+            key,
             value: Some(value),
-            operation: crate::common::Operation::Put
+            operation: crate::common::Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: Some(gaurd.last_version),
+            origin_ts: Some(timestamp)
         }).await;
         drop(gaurd);
+        true
     }
 
     /// Removes the entry with the given key from the cache.
     ///
     /// Asynchronously removes the entry associated with the provided `key` from the cache.
-    pub async fn remove(&self, key: &K) {
+    ///
+    /// Returns the removed value, `None` if the key was not present. `Remove` is recorded in `AOF` only
+    /// when something was actually removed.
+    pub async fn remove(&self, key: &K) -> Option<V> {
+        let mut gaurd = self.cache.lock().await;
+        let removed = gaurd.remove(key);
+        if removed.is_some() {
+            self.subscriber_manager.on_event(AOFRecord {
+                key: gaurd.normalized(key).into_owned(),
+                value: None,
+                operation: crate::common::Operation::Remove,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        }
+        drop(gaurd);
+        removed
+    }
+
+    /// Removes the key only if its current value equals `expected`, see `Cache::compare_and_remove`. The check
+    /// and the removal happen under one lock, `Remove` is recorded in `AOF` only if the key was removed.
+    pub async fn compare_and_remove(&self, key: &K, expected: &V) -> bool
+    where
+        V: PartialEq,
+    {
         let mut gaurd = self.cache.lock().await;
-        gaurd.remove(key);
+        if !gaurd.compare_and_remove(key, expected) {
+            return false;
+        }
         self.subscriber_manager.on_event(AOFRecord {
-            key: key.clone(),
+            key: gaurd.normalized(key).into_owned(),
+            value: None,
+            operation: crate::common::Operation::Remove,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+        true
+    }
+
+    /// Removes all the entries, see `Cache::clear`. A single `Clear` is recorded in `AOF`, nothing if the cache
+    /// is already empty.
+    pub async fn clear(&self) {
+        let mut gaurd = self.cache.lock().await;
+        let Some(key) = gaurd.cache.keys().next().cloned() else {
+            return;
+        };
+        gaurd.clear();
+        self.subscriber_manager.on_event(AOFRecord {
+            key,
+            value: None,
+            operation: crate::common::Operation::Clear,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None
+        }).await;
+        drop(gaurd);
+    }
+
+    /// Pins the key, so that it is never evicted, see `Cache::pin`. The pin is recorded in `AOF`, so that it
+    /// survives restarts.
+    pub async fn pin(&self, key: &K) -> bool {
+        let mut gaurd = self.cache.lock().await;
+        let pinned = gaurd.pin(key);
+        if pinned {
+            self.subscriber_manager.on_event(AOFRecord {
+                key: gaurd.normalized(key).into_owned(),
+                value: None,
+                operation: crate::common::Operation::Pin,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        }
+        drop(gaurd);
+        pinned
+    }
+
+    /// Unpins the key, see `Cache::unpin`. The unpin is recorded in `AOF` as well.
+    pub async fn unpin(&self, key: &K) -> bool {
+        let mut gaurd = self.cache.lock().await;
+        let unpinned = gaurd.unpin(key);
+        if unpinned {
+            self.subscriber_manager.on_event(AOFRecord {
+                key: gaurd.normalized(key).into_owned(),
+                value: None,
+                operation: crate::common::Operation::Unpin,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).await;
+        }
+        drop(gaurd);
+        unpinned
+    }
+
+    /// Returns whether the key is pinned, see `Cache::pin`.
+    pub async fn is_pinned(&self, key: &K) -> bool {
+        self.cache.lock().await.is_pinned(key)
+    }
+
+    /// Removes all the `keys`, locking the cache once, and returns the number of entries actually removed.
+    ///
+    /// A `Remove` is recorded in `AOF` for every removed key, handed over in one go. Keys which are not present
+    /// are not recorded.
+    pub async fn remove_many(&self, keys: &[K]) -> usize {
+        let mut gaurd = self.cache.lock().await;
+        let records: Vec<_> = keys.iter()
+            .filter_map(|key| gaurd.remove(key).map(|_| gaurd.normalized(key).into_owned()))
+            .map(|key| AOFRecord {
+                key,
+                value: None,
+                operation: Operation::Remove,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            })
+            .collect();
+        let removed = records.len();
+        if removed > 0 {
+            self.subscriber_manager.on_event_multi(records).await;
+        }
+        drop(gaurd);
+        removed
+    }
+
+    /// Applies `ops` in order, locking the cache once, and returns their results at the same positions, e.g. to
+    /// read and update related keys without other operations in between.
+    ///
+    /// The records of all the operations are handed to `AOF` in one go, as for `remove_many`. Gets are recorded
+    /// as for `get`, but misses are not loaded even if a loader is configured.
+    pub async fn pipeline(&self, ops: Vec<CacheOp<K, V>>) -> Vec<OpResult<V>> {
+        let mut gaurd = self.cache.lock().await;
+        let persist_read_ops = self.persist_read_ops.is_some_and(|x| x);
+        let record = |key: K, value: Option<V>, operation: Operation, version: Option<u64>| AOFRecord {
+            key,
+            value,
+            operation,
+            expires_at: None,
+            cache_id: None,
+            version,
+            origin_ts: None
+        };
+        let mut records = vec![];
+        let mut results = Vec::with_capacity(ops.len());
+        for op in ops {
+            let result = match op {
+                CacheOp::Get(key) => {
+                    let value = gaurd.get(&key).cloned();
+                    if value.is_some() && persist_read_ops {
+                        records.push(record(key, None, Operation::Get, None));
+                    }
+                    OpResult::Get(value)
+                },
+                CacheOp::Put(key, value) => {
+                    gaurd.put(key.clone(), value.clone());
+                    records.push(record(key, Some(value), Operation::Put, Some(gaurd.last_version)));
+                    OpResult::Put
+                },
+                CacheOp::Remove(key) => {
+                    let removed = gaurd.remove(&key);
+                    if removed.is_some() {
+                        records.push(record(key, None, Operation::Remove, None));
+                    }
+                    OpResult::Remove(removed)
+                },
+                CacheOp::Contains(key) => OpResult::Contains(gaurd.contains_key(&key)),
+            };
+            results.push(result);
+        }
+        if !records.is_empty() {
+            self.subscriber_manager.on_event_multi(records).await;
+        }
+        drop(gaurd);
+        results
+    }
+
+    /// Same as `remove`, but takes any borrowed form of the key, see `Cache::get_borrowed`.
+    pub async fn remove_borrowed<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        let mut gaurd = self.cache.lock().await;
+        let (key, removed) = gaurd.remove_entry_borrowed(key)?;
+        self.subscriber_manager.on_event(AOFRecord {
+            key,
             value: None,
-            operation: crate::common::Operation::Remove
+            operation: crate::common::Operation::Remove,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None
         }).await;
         drop(gaurd);
+        Some(removed)
+    }
+
+    /// Removes all the expired entries from the cache.
+    ///
+    /// A `Remove` is recorded in `AOF` for every purged key. Returns the number of purged entries.
+    pub async fn purge_expired(&self) -> usize {
+        Self::purge_expired_and_persist(&self.cache, &self.subscriber_manager).await
+    }
+
+    /// Removes all the expired entries from the cache and returns them, see `Cache::drain_expired`.
+    ///
+    /// A `Remove` is recorded in `AOF` for every drained key, handed over in one go. Entries evicted for
+    /// capacity are never returned, so expired ones can be told apart, e.g. refreshed from the source.
+    pub async fn drain_expired(&self) -> Vec<(K, V)> {
+        let mut gaurd = self.cache.lock().await;
+        let drained = gaurd.drain_expired();
+        if !drained.is_empty() {
+            self.subscriber_manager.on_event_multi(drained.iter().map(|(key, _)| AOFRecord {
+                key: key.clone(),
+                value: None,
+                operation: Operation::Remove,
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None
+            }).collect()).await;
+        }
+        drop(gaurd);
+        drained
+    }
+
+    /// Removes all the entries and returns the ones which have not expired, see `Cache::drain`.
+    ///
+    /// A `Remove` is recorded in `AOF` for every key, expired ones included, handed over in one go.
+    pub async fn drain(&self) -> Vec<(K, V)> {
+        let mut gaurd = self.cache.lock().await;
+        let records: Vec<_> = gaurd.cache.keys().map(|key| AOFRecord {
+            key: key.clone(),
+            value: None,
+            operation: Operation::Remove,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None
+        }).collect();
+        let drained = gaurd.drain().collect();
+        if !records.is_empty() {
+            self.subscriber_manager.on_event_multi(records).await;
+        }
+        drop(gaurd);
+        drained
     }
 
     /// Checks if the cache contains the given key.
@@ -278,11 +3451,348 @@ where
         return self.cache.lock().await.contains_key(&key);
     }
 
+    /// Same as `contains_key`, but takes any borrowed form of the key, see `Cache::get_borrowed`.
+    pub async fn contains_key_borrowed<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.cache.lock().await.contains_key_borrowed(key)
+    }
+
     /// Returns the current size of the cache.
     ///
     /// Asynchronously returns the current number of entries in the cache.
     pub async fn size(&self) -> usize {
         return self.cache.lock().await.size();
     }
+
+    /// Returns a read-only handle to the cache, see `CacheReader`.
+    pub fn reader(&self) -> CacheReader<K, V> {
+        CacheReader { cache: self.clone() }
+    }
+
+    /// Returns the number of entries in the cache, same as `size`.
+    pub async fn len(&self) -> usize {
+        self.cache.lock().await.len()
+    }
+
+    /// Returns the number of entries which have not expired, see `Cache::live_size`.
+    pub async fn live_size(&self) -> usize {
+        self.cache.lock().await.live_size()
+    }
+
+    /// Whether the cache has no entries.
+    pub async fn is_empty(&self) -> bool {
+        self.cache.lock().await.is_empty()
+    }
+
+    /// Name of the cache reported in stats and errors, see `AsyncCacheBuilder::name`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Tags the error with the name of the cache, if any, see `CacheError::Named`.
+    fn named<T>(&self, result: Result<T, CacheError>) -> Result<T, CacheError> {
+        match self.name() {
+            Some(cache_name) => result.map_err(|e| CacheError::Named { cache_name: cache_name.to_string(), error: Box::new(e) }),
+            None => result,
+        }
+    }
+
+    /// Copies the values of `keys`, or of all the entries in case of `None`, under one lock, so that the map
+    /// holds the values of a single moment which concurrent writes can not tear, unlike separate `get`s. See
+    /// `Cache::snapshot`, a full snapshot needs as much memory again as the entries.
+    pub async fn snapshot(&self, keys: Option<&[K]>) -> HashMap<K, V>
+    where
+        V: Clone,
+    {
+        self.cache.lock().await.snapshot(keys)
+    }
+
+    /// Returns the configured maximum number of entries, see `Cache::max_size`.
+    pub async fn max_size(&self) -> Option<usize> {
+        self.cache.lock().await.max_size()
+    }
+
+    /// Releases unused memory, see `Cache::shrink_to_fit`.
+    ///
+    /// Holds the lock while all the entries may be rehashed, so don't call it on the hot path.
+    pub async fn shrink_to_fit(&self) {
+        self.cache.lock().await.shrink_to_fit();
+    }
+
+    /// Reserves capacity for at least `additional` more entries, see `Cache::reserve`.
+    pub async fn reserve(&self, additional: usize) {
+        self.cache.lock().await.reserve(additional);
+    }
+
+    /// Returns the key which would be evicted next, see `Cache::next_victim`.
+    ///
+    /// This does not account for access.
+    pub async fn next_victim(&self) -> Option<K> {
+        self.cache.lock().await.next_victim().cloned()
+    }
+
+    /// Returns the key which would be evicted next and why, see `Cache::explain_next_eviction`.
+    pub async fn explain_next_eviction(&self) -> EvictionExplanation<K> {
+        self.cache.lock().await.explain_next_eviction()
+    }
+
+    /// Returns the number of keys at every access frequency, see `Cache::frequency_histogram`.
+    pub async fn frequency_histogram(&self) -> Option<BTreeMap<u64, usize>> {
+        self.cache.lock().await.frequency_histogram()
+    }
+
+    /// Estimates the memory used by the entries, see `Cache::estimated_bytes`. It is an approximation.
+    pub async fn estimated_bytes(&self) -> usize {
+        self.cache.lock().await.estimated_bytes()
+    }
+
+    /// Health of writing `AOF` to disk, for liveness checks: when records were last written successfully, the
+    /// error of the last write if it failed and the number of records waiting for the next periodic flush.
+    ///
+    /// A failed periodic flush keeps the records in memory and retries on the next flush. Without `AOF`,
+    /// nothing is ever written or pending, and if `AOF` could not be opened with
+    /// `AofFailureMode::DegradeToMemory`, `last_error` is the error.
+    pub async fn flush_health(&self) -> FlushHealth {
+        self.subscriber_manager.flush_health().await
+    }
+
+    /// Shuts the cache down deterministically instead of relying on the process exit: signals the background
+    /// tasks, i.e. the periodic flush of `AOF`, the sweeper, the soft limit evictor, the `get_stale` refresher and
+    /// the policy state recorder, to stop and waits for the ones running, then writes the pending records to
+    /// `AOF` and waits until they are on the disk, so that the writes before are not lost.
+    ///
+    /// Clones share the tasks, so they should not be written to afterwards, their records would wait for a
+    /// flush which never comes. Streams of `stats_stream` end, the task of `utilization_events` stops once its
+    /// receiver is dropped, and a shared `AofMultiplexer` keeps flushing for the other caches.
+    ///
+    /// Returns `CacheError::Io` if writing fails.
+    pub async fn shutdown(self) -> Result<(), CacheError> {
+        self.tasks.stop().await;
+        let result = self.subscriber_manager.shutdown().await.map_err(CacheError::from);
+        self.named(result)
+    }
+
+    /// Whether the cache holds the same keys with equal values as `other`, see `Cache::content_eq`.
+    pub async fn content_eq_with<S>(&self, other: &Cache<K, V, S>) -> bool
+    where
+        V: PartialEq,
+        S: BuildHasher,
+    {
+        self.cache.lock().await.content_eq(other)
+    }
+
+    /// Size of the `AOF` file and approximate number of records in it, e.g. to compact it once it grows past a
+    /// threshold. `None` if there is no `AOF` or its size can not be read.
+    pub async fn aof_stats(&self) -> Option<AofStats> {
+        self.subscriber_manager.aof_stats().await
+    }
+
+    /// Snapshot of the size of the cache, its estimated memory and the state of `AOF`, see `CacheStats`.
+    ///
+    /// Estimating memory walks all the entries while the cache is locked.
+    pub async fn stats(&self) -> CacheStats {
+        Self::collect_stats(&self.cache, &self.subscriber_manager, self.name()).await
+    }
+
+    /// Streams `stats` every `interval` from a background task, e.g. to feed a time series database without a
+    /// polling loop, see `StatsStream`.
+    ///
+    /// The task holds weak references only, so it stops once the cache is dropped or on `shutdown`, ending the
+    /// stream, as well as once the stream is dropped. A stream which lags behind delays the next snapshots
+    /// rather than queueing them.
+    ///
+    /// Panics if `interval` is zero.
+    pub fn stats_stream(&self, interval: Duration) -> StatsStream {
+        if interval.is_zero() {
+            panic!("stats interval must be greater than zero.");
+        }
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        let cache = Arc::downgrade(&self.cache);
+        let subscriber_manager = Arc::downgrade(&self.subscriber_manager);
+        let name = self.name.clone();
+        self.tasks.spawn(|mut stop| async move {
+            while sleep_unless_stopped(interval, &mut stop).await {
+                let (Some(cache), Some(subscriber_manager)) = (cache.upgrade(), subscriber_manager.upgrade()) else {
+                    return;
+                };
+                let stats = Self::collect_stats(&cache, &subscriber_manager, name.as_deref()).await;
+                // the cache must not be kept alive while waiting for the receiver.
+                drop((cache, subscriber_manager));
+                // nor must `shutdown` wait for it.
+                tokio::select! {
+                    sent = sender.send(stats) => if sent.is_err() {
+                        return;
+                    },
+                    _ = stop.wait_for(|x| *x) => return,
+                }
+            }
+        });
+        StatsStream(receiver)
+    }
+
+    /// Returns the utilization of the cache, see `Cache::utilization`.
+    pub async fn utilization(&self) -> f32 {
+        self.cache.lock().await.utilization()
+    }
+
+    /// Sends a `UtilizationEvent` whenever the utilization of the cache crosses one of `watermarks`, checking
+    /// it every `interval` from a background task, e.g. to scale out before the cache starts evicting.
+    ///
+    /// Events are edge-triggered: crossing `high` is reported once, and reported again only after the cache
+    /// dropped below `high - hysteresis` in between, likewise for `low`. A cache outside of the watermarks at
+    /// the first check is reported then. Crossings which revert within `interval` are not seen.
+    ///
+    /// The task stops once the cache or the receiver is dropped.
+    ///
+    /// Panics if `interval` is zero or `watermarks` are not `0 <= low < high` with non-overlapping hysteresis.
+    pub fn utilization_events(&self, watermarks: Watermarks, interval: Duration) -> tokio::sync::mpsc::Receiver<UtilizationEvent> {
+        if interval.is_zero() {
+            panic!("utilization interval must be greater than zero.");
+        }
+        if !watermarks.is_valid() {
+            panic!("invalid watermarks {:?}.", watermarks);
+        }
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        let cache = Arc::downgrade(&self.cache);
+        tokio::spawn(async move {
+            let mut zone = None;
+            loop {
+                let Some(cache) = cache.upgrade() else {
+                    return;
+                };
+                let utilization = cache.lock().await.utilization();
+                drop(cache);
+                if let Some(event) = watermarks.crossing(&mut zone, utilization) {
+                    if sender.send(event).await.is_err() {
+                        return;
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+        receiver
+    }
+
+    /// Snapshots the stats of the cache, see `stats`.
+    async fn collect_stats(cache: &Mutex<Cache<K, V>>, subscriber_manager: &CacheEventSubscriber<K, V>, name: Option<&str>) -> CacheStats {
+        let (size, live_size, estimated_bytes, would_have_hit, lookups) = {
+            let gaurd = cache.lock().await;
+            (gaurd.size(), gaurd.live_size(), gaurd.estimated_bytes(), gaurd.would_have_hit(), gaurd.lookup_counts())
+        };
+        CacheStats {
+            cache_name: name.map(String::from),
+            size,
+            live_size,
+            estimated_bytes,
+            would_have_hit,
+            lookups,
+            flush_health: subscriber_manager.flush_health().await,
+            aof_stats: subscriber_manager.aof_stats().await,
+        }
+    }
+}
+
+/// Caching values owned elsewhere, see `Cache::get_upgrade`. Keep it in-memory with `new_without_aof` as
+/// `Weak` can not be persisted.
+impl<K, T> AsyncCache<K, Weak<T>>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    T: Send + Sync + 'static
+{
+    /// Retrieves the value and upgrades it. If the value has been dropped by its owners, the dead entry is
+    /// removed and `None` is returned.
+    pub async fn get_upgrade(&self, key: &K) -> Option<Arc<T>> {
+        self.cache.lock().await.get_upgrade(key)
+    }
+
+    /// Removes all the entries whose values have been dropped. Returns the number of removed entries.
+    ///
+    /// This scans the whole cache under the lock, so call it periodically rather than on every operation.
+    pub async fn prune_dead(&self) -> usize {
+        self.cache.lock().await.prune_dead().len()
+    }
+}
+
+/// Shared values, reading one only clones the pointer.
+impl<K, T> AsyncCache<K, Arc<T>>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    T: Send + Sync + 'static
+{
+    /// Returns the value of the key, inserting the one computed by `init` if the key is absent, see
+    /// `get_or_insert_with`. Concurrent misses of the same key wait for a single `init` and every caller gets
+    /// the same `Arc`, so large values are computed once and never cloned.
+    pub async fn get_or_insert_arc<F, Fut>(&self, key: K, init: F) -> Arc<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.get_or_insert_with(key, || async { Arc::new(init().await) }).await
+    }
+}
+
+/// Values kept serialized, so that large values are neither cloned nor serialized again when they are written to
+/// `AOF`: the cache and the `Put` record share the buffer of the `Bytes`, which is written as it is.
+impl<K> AsyncCache<K, Bytes>
+where
+    for<'de> K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + Deserialize<'de> + Serialize + 'static,
+{
+    /// Serializes `value` as JSON once and inserts it with `put`. Returns error, inserting nothing, if
+    /// serialization fails.
+    pub async fn put_serialized<T: Serialize + ?Sized>(&self, key: K, value: &T) -> Result<(), CacheError> {
+        let bytes = self.named(serde_json::to_vec(value).map_err(CacheError::from))?;
+        self.put(key, Bytes::from(bytes)).await;
+        Ok(())
+    }
+
+    /// Retrieves the value with `get` and deserializes it from JSON, see `put_serialized`. Returns error if it
+    /// can not be deserialized as `T`.
+    pub async fn get_deserialized<T: DeserializeOwned>(&self, key: &K) -> Result<Option<T>, CacheError> {
+        let Some(bytes) = self.get(key).await else {
+            return Ok(None);
+        };
+        self.named(serde_json::from_slice(&bytes).map(Some).map_err(CacheError::from))
+    }
 }
 
+/// Negative caching, see `Cache::lookup`. `None` values are persisted like any other value.
+impl<K, V> AsyncCache<K, Option<V>>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static
+{
+    /// Remembers that `key` has no value for `ttl`. It is recorded as a `Put` of `None` with the expiry.
+    pub async fn put_absent(&self, key: K, ttl: Duration) {
+        self.put_with_ttl(key, None, ttl).await;
+    }
+
+    /// Looks up the key, telling apart a key known to be absent from a key not cached at all.
+    pub async fn lookup(&self, key: &K) -> Lookup<V> {
+        match self.get(key).await {
+            Some(Some(value)) => Lookup::Present(value),
+            Some(None) => Lookup::KnownAbsent,
+            None => Lookup::Unknown,
+        }
+    }
+
+    /// Same as `get_or_insert_with`, but when `init` returns `None` the absence is cached for `absent_ttl`
+    /// only, so the key is looked up again afterwards. Values are cached without ttl.
+    pub async fn get_or_insert_absent_with<F, Fut>(&self, key: K, absent_ttl: Duration, init: F) -> Option<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Option<V>>,
+    {
+        let result: Result<Option<V>, Infallible> = self.get_or_try_insert_with_ttl(
+            key,
+            || async { Ok(init().await) },
+            |value| value.is_none().then_some(absent_ttl)
+        ).await;
+        match result {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+}
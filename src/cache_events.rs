@@ -1,77 +1,495 @@
 //! Contains logic what to do when some event take place in `ThreadSafeCache.`
-//! 
+//!
 
 use std::{io, sync::Arc};
 
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{aof::{periodic_flush, AOFIterator, AOFSubscriber}, common::AOFRecord};
+use crate::{aof::{periodic_flush_until_stopped, AOFIterator, AOFSubscriber, AofMultiplexer, MultiplexedAOFSubscriber, ValueTransform}, common::{AOFRecord, AofStats, BackgroundTasks, FlushHealth, Operation}, config::{AofFailureMode, FlushStrategy}};
 
-/// Struct to perform operations after some event takes place in `ThreadSafeCache`
-/// For now it handles the `AOF` and when to write to disk.
-pub struct CacheEventSubscriber<K, V>
+/// Object safe facade over the persistence layer.
+///
+/// `CacheEventSubscriber` stores the `AOF` behind this trait so that the serde bounds needed to
+/// write records are only required where the `AOF` is created, not on every cache operation.
+#[async_trait]
+pub trait EventPersister<K, V>: Send + Sync {
+    /// Records the event.
+    async fn on_event(&self, r: AOFRecord<K, V>);
+
+    /// Records several events in order.
+    async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>);
+
+    /// Returns Iterator with all the persisted operations sequentially.
+    #[allow(clippy::wrong_self_convention)]
+    async fn into_iter(&self) -> io::Result<AOFIterator>;
+
+    /// Result of the last write to disk and the number of records waiting to be flushed.
+    async fn flush_health(&self) -> FlushHealth;
+
+    /// Re-opens the file and writes the pending records to it.
+    async fn reopen(&self) -> io::Result<()>;
+
+    /// Writes the records in one go, after the pending ones, and waits until they are on the disk.
+    async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()>;
+
+    /// Writes the state of the eviction policy right away, after the pending records, and waits until it is on
+    /// the disk.
+    async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()>;
+
+    /// Size of the file and approximate number of records in it.
+    async fn aof_stats(&self) -> io::Result<AofStats>;
+
+    /// Sets the number of records in the file, counted while replaying.
+    fn set_approx_records(&self, records: u64);
+}
+
+#[async_trait]
+impl<K, V> EventPersister<K, V> for AOFSubscriber<K, V>
 where
-    for<'de> K: Deserialize<'de> + Serialize + Send + Sync,
-    for<'de> V: Deserialize<'de> + Serialize + Send + Sync,
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync + 'static,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
 {
-    aof_subscriber: Option<Arc<AOFSubscriber<K, V>>>
+    async fn on_event(&self, r: AOFRecord<K, V>) {
+        AOFSubscriber::on_event(self, r).await;
+    }
+
+    async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        AOFSubscriber::on_event_multi(self, records).await;
+    }
+
+    async fn into_iter(&self) -> io::Result<AOFIterator> {
+        AOFSubscriber::into_iter(self).await
+    }
+
+    async fn flush_health(&self) -> FlushHealth {
+        AOFSubscriber::flush_health(self).await
+    }
+
+    async fn reopen(&self) -> io::Result<()> {
+        AOFSubscriber::reopen(self).await
+    }
+
+    async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()> {
+        AOFSubscriber::on_events_synced(self, records).await
+    }
+
+    async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        AOFSubscriber::on_policy_state(self, state).await
+    }
+
+    async fn aof_stats(&self) -> io::Result<AofStats> {
+        AOFSubscriber::aof_stats(self).await
+    }
+
+    fn set_approx_records(&self, records: u64) {
+        AOFSubscriber::set_approx_records(self, records);
+    }
 }
 
-impl<K, V> CacheEventSubscriber<K, V> 
+#[async_trait]
+impl<K, V> EventPersister<K, V> for MultiplexedAOFSubscriber<K, V>
+where
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync + 'static,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
+{
+    async fn on_event(&self, r: AOFRecord<K, V>) {
+        MultiplexedAOFSubscriber::on_event(self, r).await;
+    }
+
+    async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        MultiplexedAOFSubscriber::on_event_multi(self, records).await;
+    }
+
+    async fn into_iter(&self) -> io::Result<AOFIterator> {
+        MultiplexedAOFSubscriber::into_iter(self).await
+    }
+
+    async fn flush_health(&self) -> FlushHealth {
+        MultiplexedAOFSubscriber::flush_health(self).await
+    }
+
+    async fn reopen(&self) -> io::Result<()> {
+        MultiplexedAOFSubscriber::reopen(self).await
+    }
+
+    async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()> {
+        MultiplexedAOFSubscriber::on_events_synced(self, records).await
+    }
+
+    async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        MultiplexedAOFSubscriber::on_policy_state(self, state).await
+    }
+
+    async fn aof_stats(&self) -> io::Result<AofStats> {
+        MultiplexedAOFSubscriber::aof_stats(self).await
+    }
+
+    fn set_approx_records(&self, records: u64) {
+        MultiplexedAOFSubscriber::set_approx_records(self, records);
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[async_trait]
+impl<K, V> EventPersister<K, V> for crate::mmap_aof::MmapAOFSubscriber<K, V>
+where
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync + 'static,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
+{
+    async fn on_event(&self, r: AOFRecord<K, V>) {
+        let _ = self.append(vec![r], false).await;
+    }
+
+    async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        let _ = self.append(records, false).await;
+    }
+
+    async fn into_iter(&self) -> io::Result<AOFIterator> {
+        Ok(crate::mmap_aof::MmapAOFSubscriber::into_iter(self).await)
+    }
+
+    async fn flush_health(&self) -> FlushHealth {
+        crate::mmap_aof::MmapAOFSubscriber::flush_health(self).await
+    }
+
+    async fn reopen(&self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "memory-mapped AOF can not be re-opened."))
+    }
+
+    async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()> {
+        self.append(records, true).await
+    }
+
+    async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        crate::mmap_aof::MmapAOFSubscriber::on_policy_state(self, state).await
+    }
+
+    async fn aof_stats(&self) -> io::Result<AofStats> {
+        Ok(crate::mmap_aof::MmapAOFSubscriber::aof_stats(self).await)
+    }
+
+    fn set_approx_records(&self, records: u64) {
+        crate::mmap_aof::MmapAOFSubscriber::set_approx_records(self, records);
+    }
+}
+
+#[async_trait]
+impl<K, V> EventPersister<K, V> for crate::aof::MemoryAOF
+where
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync + 'static,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
+{
+    async fn on_event(&self, r: AOFRecord<K, V>) {
+        self.on_event_multi(vec![r]).await;
+    }
+
+    async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        crate::aof::MemoryAOF::on_event_multi(self, records).await;
+    }
+
+    async fn into_iter(&self) -> io::Result<AOFIterator> {
+        Ok(crate::aof::MemoryAOF::into_iter(self).await)
+    }
+
+    async fn flush_health(&self) -> FlushHealth {
+        FlushHealth::default()
+    }
+
+    async fn reopen(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> io::Result<()> {
+        self.on_event_multi(records).await;
+        Ok(())
+    }
+
+    async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        crate::aof::MemoryAOF::on_policy_state(self, state).await;
+        Ok(())
+    }
+
+    async fn aof_stats(&self) -> io::Result<AofStats> {
+        Ok(crate::aof::MemoryAOF::aof_stats(self).await)
+    }
+
+    fn set_approx_records(&self, records: u64) {
+        crate::aof::MemoryAOF::set_approx_records(self, records);
+    }
+}
+
+/// Predicate deciding which values are persisted, see `AsyncCacheBuilder::should_persist`.
+pub(crate) type PersistFilter<V> = Arc<dyn Fn(&V) -> bool + Send + Sync>;
+
+/// Struct to perform operations after some event takes place in `ThreadSafeCache`
+/// For now it handles the `AOF` and when to write to disk.
+pub struct CacheEventSubscriber<K, V> {
+    aof_subscriber: Option<Arc<dyn EventPersister<K, V>>>,
+    /// Error which disabled persistence, when `AOF` could not be opened with `AofFailureMode::DegradeToMemory`.
+    aof_error: Option<String>,
+    /// Values for which it returns `false` are not persisted, see `AsyncCacheBuilder::should_persist`.
+    persist_filter: Option<PersistFilter<V>>,
+    /// Periodic flush of `AOF`, stopped by `shutdown`.
+    tasks: BackgroundTasks,
+}
+
+impl<K, V> CacheEventSubscriber<K, V>
 where
     for<'de> K: Deserialize<'de> + Serialize + Send + Sync + 'static,
     for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
 {
     /// Creates new instance of `CacheEventSubscriber`
-    /// 
+    ///
     /// `filedir`: Folder where the persistent file should be created.
-    /// 
+    ///
     /// `cache_name`: Unique cache_name as with same name file will be created.
-    /// 
+    ///
     /// `flush_time`: Periodic time to flush data. If `None`, it will flush every operation which will make it
     /// really slow. don't do that untill you know what you are doing.
-    /// 
+    ///
     /// If both `filedir` and `cache_name` are `None`, no `AOF` will be created.
-    /// 
+    ///
     /// In case of invalid inputs, it will panic.
-    /// 
+    ///
     pub async fn new(
         filedir: Option<String>,
         cache_name: Option<String>,
         flush_time: Option<u32>,
     ) -> Self {
+        Self::with_flush_jitter(filedir, cache_name, flush_time, 0.0).await
+    }
+
+    /// Same as `new`, but every periodic flush happens after `flush_time` moved randomly by up to
+    /// `flush_jitter` of it either way, so that caches with the same `flush_time` don't hit the disk together.
+    ///
+    /// Panics if `flush_jitter` is not in `[0, 1)`.
+    pub async fn with_flush_jitter(
+        filedir: Option<String>,
+        cache_name: Option<String>,
+        flush_time: Option<u32>,
+        flush_jitter: f64,
+    ) -> Self {
+        Self::with_options(filedir, cache_name, flush_time, flush_jitter, None, None, AofFailureMode::Fatal, None, "dat").await
+    }
+
+    /// Same as `with_flush_jitter`, transforming the values written to and read from the file as well, see
+    /// `ValueTransform`. If the file can not be opened, `aof_failure` tells whether to panic or to persist
+    /// nothing.
+    ///
+    /// `flush_strategy` replaces the fixed `flush_time` of the periodic flush, see `FlushStrategy`.
+    ///
+    /// With `mmap_bytes`, records are written to a memory-mapped file with room for that many bytes instead,
+    /// see `MmapAOF`. Otherwise they are written to the file with `file_extension`, `dat` for `new`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn with_options(
+        filedir: Option<String>,
+        cache_name: Option<String>,
+        flush_time: Option<u32>,
+        flush_jitter: f64,
+        flush_strategy: Option<FlushStrategy>,
+        value_transform: Option<ValueTransform>,
+        aof_failure: AofFailureMode,
+        mmap_bytes: Option<usize>,
+        file_extension: &str,
+    ) -> Self {
+        if !(0.0..1.0).contains(&flush_jitter) {
+            panic!("flush jitter must be at least zero and less than one.");
+        }
         if (cache_name.as_ref().is_none() && filedir.as_ref().is_some())
             || (filedir.as_ref().is_none() && cache_name.as_ref().is_some())
             || (flush_time.is_some_and(|x| x == 0))
         {
             panic!("Either both File dir and cache name are None or neither one. flush time must be greater than zero.");
         } else if filedir.as_ref().is_some() && cache_name.as_ref().is_some() {
-            let aof_subscriber = Arc::new(AOFSubscriber::new(filedir, cache_name, flush_time).await);
+            #[cfg(all(feature = "mmap", unix))]
+            if let Some(mmap_bytes) = mmap_bytes {
+                let folder = filedir.unwrap();
+                let path = format!("{}/{}.mmap", folder, cache_name.unwrap());
+                let _ = std::fs::create_dir_all(&folder);
+                return match crate::mmap_aof::MmapAOFSubscriber::open(&path, mmap_bytes, flush_time, value_transform) {
+                    Ok(aof_subscriber) => Self { aof_subscriber: Some(aof_subscriber), aof_error: None, persist_filter: None, tasks: Default::default() },
+                    Err(e) => Self::aof_failed(&path, e, aof_failure),
+                };
+            }
+            #[cfg(not(all(feature = "mmap", unix)))]
+            let _ = mmap_bytes;
+            let path = format!("{}/{}.{}", filedir.as_ref().unwrap(), cache_name.as_ref().unwrap(), file_extension);
+            let mut aof_subscriber = match AOFSubscriber::try_with_extension(filedir, cache_name, file_extension, flush_time).await {
+                Ok(aof_subscriber) => aof_subscriber,
+                Err(e) => return Self::aof_failed(&path, e, aof_failure),
+            };
+            aof_subscriber.flush_jitter = flush_jitter;
+            aof_subscriber.flush_strategy = flush_strategy;
+            aof_subscriber.set_value_transform(value_transform);
+            let aof_subscriber = Arc::new(aof_subscriber);
             let instance = Self {
-                aof_subscriber: Some(aof_subscriber.clone())
+                aof_subscriber: Some(aof_subscriber.clone()),
+                aof_error: None,
+                persist_filter: None,
+                tasks: Default::default(),
             };
-            tokio::spawn(async move {periodic_flush(aof_subscriber.clone()).await});
+            instance.tasks.spawn(|stop| periodic_flush_until_stopped(aof_subscriber, stop));
             instance
         } else {
-            Self {
-                aof_subscriber: None
-            }
+            Self::without_aof()
+        }
+    }
+
+    /// Subscriber of a cache whose `AOF` at `path` could not be opened: panics, or persists nothing and keeps the
+    /// error, reported by `flush_health`, with `AofFailureMode::DegradeToMemory`.
+    fn aof_failed(path: &str, e: io::Error, aof_failure: AofFailureMode) -> Self {
+        let error = format!("Error in opening aof {} file: {}", path, e);
+        if aof_failure == AofFailureMode::Fatal {
+            panic!("{}", error);
+        }
+        Self {
+            aof_subscriber: None,
+            aof_error: Some(error),
+            persist_filter: None,
+            tasks: Default::default(),
+        }
+    }
+
+    /// Creates new instance of `CacheEventSubscriber` which persists to the shared `AOF` of `multiplexer`,
+    /// tagging every record with `cache_id`.
+    ///
+    /// Panics if `cache_id` is already registered with the `multiplexer`.
+    pub fn multiplexed(multiplexer: &Arc<AofMultiplexer>, cache_id: u16) -> Self {
+        Self {
+            aof_subscriber: Some(Arc::new(multiplexer.register::<K, V>(cache_id))),
+            aof_error: None,
+            persist_filter: None,
+            tasks: Default::default(),
+        }
+    }
+}
+
+impl<K, V> CacheEventSubscriber<K, V> {
+    /// Creates new instance of `CacheEventSubscriber` which persists through `persister`.
+    pub(crate) fn with_persister(persister: Arc<dyn EventPersister<K, V>>) -> Self {
+        Self {
+            aof_subscriber: Some(persister),
+            aof_error: None,
+            persist_filter: None,
+            tasks: Default::default(),
         }
     }
 
+    /// Creates new instance of `CacheEventSubscriber` which does not persist anything.
+    ///
+    /// Unlike `new`, it does not require keys and values to be serializable.
+    pub fn without_aof() -> Self {
+        Self {
+            aof_subscriber: None,
+            aof_error: None,
+            persist_filter: None,
+            tasks: Default::default(),
+        }
+    }
+
+    /// Skips persisting the values rejected by `persist_filter`, see `AsyncCacheBuilder::should_persist`.
+    pub(crate) fn set_persist_filter(&mut self, persist_filter: Option<PersistFilter<V>>) {
+        self.persist_filter = persist_filter;
+    }
+
+    /// Turns a `Put` of a value rejected by `persist_filter` into a `Remove` of the key, so that replay drops
+    /// the key rather than restoring an older value of it.
+    fn filter(&self, mut r: AOFRecord<K, V>) -> AOFRecord<K, V> {
+        let rejected = match (&self.persist_filter, &r.value) {
+            (Some(persist_filter), Some(value)) => r.operation == Operation::Put && !persist_filter(value),
+            _ => false,
+        };
+        if rejected {
+            r.operation = Operation::Remove;
+            r.value = None;
+            r.expires_at = None;
+            r.origin_ts = None;
+        }
+        r
+    }
+
     /// Method will be called when something happens in the cache.
     pub async fn on_event(&self, r: AOFRecord<K, V>) {
-        if self.aof_subscriber.as_ref().is_some(){
-            self.aof_subscriber.as_ref().unwrap().on_event(r).await;
+        if let Some(aof_subscriber) = self.aof_subscriber.as_ref() {
+            aof_subscriber.on_event(self.filter(r)).await;
+        }
+    }
+
+    /// Method will be called when several things happen in the cache at once, e.g. a bulk load.
+    pub async fn on_event_multi(&self, records: Vec<AOFRecord<K, V>>) {
+        if let Some(aof_subscriber) = self.aof_subscriber.as_ref() {
+            aof_subscriber.on_event_multi(records.into_iter().map(|x| self.filter(x)).collect()).await;
         }
     }
 
     /// Returns Iterator with all the operations sequentially. Throws error if AOF has not been initialized.
     pub async fn into_iter(&self) -> std::io::Result<AOFIterator> {
-        if self.aof_subscriber.as_ref().is_some(){
-            self.aof_subscriber.as_ref().unwrap().into_iter().await
+        if let Some(aof_subscriber) = self.aof_subscriber.as_ref() {
+            aof_subscriber.into_iter().await
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."))
+        }
+    }
+
+    /// Writes the records in one go and waits until they are on the disk. Does nothing if there is no `AOF`.
+    pub async fn on_events_synced(&self, records: Vec<AOFRecord<K, V>>) -> std::io::Result<()> {
+        match self.aof_subscriber.as_ref() {
+            Some(aof_subscriber) => aof_subscriber.on_events_synced(records.into_iter().map(|x| self.filter(x)).collect()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the state of the eviction policy and waits until it is on the disk. Throws error if AOF has not
+    /// been initialized.
+    pub async fn on_policy_state(&self, state: Vec<u8>) -> std::io::Result<()> {
+        if let Some(aof_subscriber) = self.aof_subscriber.as_ref() {
+            aof_subscriber.on_policy_state(state).await
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."))
         }
     }
-}
\ No newline at end of file
+
+    /// Re-opens the `AOF` file and writes the pending records to it. Throws error if AOF has not been initialized.
+    pub async fn reopen(&self) -> std::io::Result<()> {
+        if let Some(aof_subscriber) = self.aof_subscriber.as_ref() {
+            aof_subscriber.reopen().await
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, "AOF isn inited."))
+        }
+    }
+
+    /// Health of writing `AOF` to disk, the default (nothing written, nothing pending) if there is no `AOF`.
+    /// If persistence was disabled because `AOF` could not be opened, `last_error` is the error.
+    pub async fn flush_health(&self) -> FlushHealth {
+        match self.aof_subscriber.as_ref() {
+            Some(aof_subscriber) => aof_subscriber.flush_health().await,
+            None => FlushHealth { last_error: self.aof_error.clone(), ..Default::default() },
+        }
+    }
+
+    /// Whether persistence was disabled because `AOF` could not be opened, see `AofFailureMode::DegradeToMemory`.
+    pub fn is_degraded(&self) -> bool {
+        self.aof_error.is_some()
+    }
+
+    /// Size of the `AOF` file and approximate number of records in it, `None` if there is no `AOF` or its size
+    /// can not be read.
+    pub async fn aof_stats(&self) -> Option<AofStats> {
+        self.aof_subscriber.as_ref()?.aof_stats().await.ok()
+    }
+
+    /// Stops the periodic flush, waiting for a flush in progress, and writes the pending records, waiting until
+    /// they are on the disk. Does nothing if there is no `AOF`.
+    pub async fn shutdown(&self) -> std::io::Result<()> {
+        self.tasks.stop().await;
+        self.on_events_synced(vec![]).await
+    }
+
+    /// Sets the number of records in the `AOF` file, counted while replaying. Does nothing if there is no `AOF`.
+    pub fn set_approx_records(&self, records: u64) {
+        if let Some(aof_subscriber) = self.aof_subscriber.as_ref() {
+            aof_subscriber.set_approx_records(records);
+        }
+    }
+}
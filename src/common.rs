@@ -1,6 +1,12 @@
 //! Contains common structs and traits used throughout the library.
 
-use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::{ConfigError, InvalidOperation};
+
 /// A cached entry representing a key-value pair.
 ///
 /// This struct, `CacheEntry<T>`, stores a cached value of type `T` along
@@ -9,6 +15,16 @@ use serde::{Deserialize, Serialize};
 pub struct CacheEntry<T> {
     /// The actual value stored in the cache entry.
     pub value: T,
+
+    /// Instant after which the entry is considered expired. `None` means the entry never expires.
+    pub expires_at: Option<Instant>,
+
+    /// Version of the value, assigned by the cache on every write, see `Cache::compare_and_swap`.
+    /// `0` until the entry is inserted.
+    pub version: u64,
+
+    /// Timestamp given by the origin of the value, see `Cache::put_if_newer`. `None` for other writes.
+    pub origin_ts: Option<u64>,
 }
 
 impl<T> CacheEntry<T> {
@@ -17,7 +33,70 @@ impl<T> CacheEntry<T> {
     /// This function constructs a new `CacheEntry` with the provided `value`
     /// of type `T`.
     pub fn new(value: T) -> Self {
-        CacheEntry { value }
+        CacheEntry { value, expires_at: None, version: 0, origin_ts: None }
+    }
+
+    /// Creates a new `CacheEntry` which expires after `ttl`, never if `ttl` is too long to be represented as an
+    /// `Instant`.
+    pub fn with_ttl(value: T, ttl: Duration) -> Self {
+        CacheEntry { value, expires_at: Instant::now().checked_add(ttl), version: 0, origin_ts: None }
+    }
+
+    /// Whether the entry has expired.
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Instant::now())
+    }
+
+    /// Whether the entry has expired at `now`, e.g. the time of a `Clock`.
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|x| x <= now)
+    }
+}
+
+/// Source of the current time for expiry, see `Cache::set_clock`.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// Clock of the system, `Instant::now()`. Used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock which only moves when advanced, so that tests can check expiry without sleeping. Clones share the
+/// same time.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> Self {
+        Self { now: Arc::new(Mutex::new(Instant::now())) }
+    }
+
+    /// Moves the time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
     }
 }
 
@@ -27,6 +106,20 @@ pub enum Operation {
     Put,
     Get,
     Remove,
+    /// Starts the records of a batch written by `AsyncCache::put_batch_atomic`. Its key is the first key of the
+    /// batch and its version the number of `Put` records in the batch.
+    BatchBegin,
+    /// Ends a batch, whose records are replayed only if this marker has been written as well.
+    BatchCommit,
+    /// Pins the key, see `AsyncCache::pin`.
+    Pin,
+    /// Unpins the key, see `AsyncCache::unpin`.
+    Unpin,
+    /// State of the eviction policy, see `EvictionPolicy::export_state_bytes`. It is restored while replaying and
+    /// never returned by `AOFIterator`.
+    PolicyState,
+    /// Removes all the entries, see `AsyncCache::clear`. Its key is one of the removed keys.
+    Clear,
 }
 
 impl Operation {
@@ -34,11 +127,23 @@ impl Operation {
     /// `Get` = `0`
     /// `Put` = `1`
     /// `Remove` = `2`
+    /// `BatchBegin` = `3`
+    /// `BatchCommit` = `4`
+    /// `Pin` = `5`
+    /// `Unpin` = `6`
+    /// `PolicyState` = `7`
+    /// `Clear` = `8`
     pub fn to_int(&self) -> i8 {
         match self {
             Self::Get => 0,
             Self::Put => 1,
             Self::Remove => 2,
+            Self::BatchBegin => 3,
+            Self::BatchCommit => 4,
+            Self::Pin => 5,
+            Self::Unpin => 6,
+            Self::PolicyState => 7,
+            Self::Clear => 8,
         }
     }
 
@@ -46,27 +151,317 @@ impl Operation {
     /// `Get` = `0`
     /// `Put` = `1`
     /// `Remove` = `2`
-    pub fn from_int(i: u8) -> Self {
-        if i == 0 {
-            Self::Get
-        } else if i == 1 {
-            Self::Put
-        } else if i == 2 {
-            Self::Remove
-        } else {
-            panic!("Invalid integer {:?}", i);
+    /// `BatchBegin` = `3`
+    /// `BatchCommit` = `4`
+    /// `Pin` = `5`
+    /// `Unpin` = `6`
+    /// `PolicyState` = `7`
+    /// `Clear` = `8`
+    ///
+    /// Returns error for any other integer, e.g. read from a corrupted `AOF`.
+    pub fn from_int(i: u8) -> Result<Self, InvalidOperation> {
+        match i {
+            0 => Ok(Self::Get),
+            1 => Ok(Self::Put),
+            2 => Ok(Self::Remove),
+            3 => Ok(Self::BatchBegin),
+            4 => Ok(Self::BatchCommit),
+            5 => Ok(Self::Pin),
+            6 => Ok(Self::Unpin),
+            7 => Ok(Self::PolicyState),
+            8 => Ok(Self::Clear),
+            _ => Err(InvalidOperation { operation: i }),
         }
     }
 }
 
 /// struct to represent the single record in AOF.
+///
+/// Serde bounds are only required by the code which writes or reads records from disk, so that
+/// caches without `AOF` can use keys and values which do not implement serde traits.
 #[derive(Clone)]
-pub struct AOFRecord<K, V>
-where
-    for<'de> K: Deserialize<'de> + Serialize,
-    for<'de> V: Deserialize<'de> + Serialize,
-{
+pub struct AOFRecord<K, V> {
     pub key: K,
     pub value: Option<V>,
     pub operation: Operation,
+    /// Expiry of the value in milliseconds since unix epoch, only meaningful for `Put`.
+    pub expires_at: Option<u64>,
+    /// Id of the cache which produced the record when several caches share one `AOF`, see `AofMultiplexer`.
+    /// `None` for records of a cache with its own file.
+    pub cache_id: Option<u16>,
+    /// Version of the value, only meaningful for `Put`.
+    pub version: Option<u64>,
+    /// Timestamp of the origin of the value, only for `Put` written by `AsyncCache::put_if_newer`.
+    pub origin_ts: Option<u64>,
+}
+
+/// Progress of replaying `AOF` while creating `AsyncCache`, see `AsyncCacheBuilder::replay_progress`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// Number of records applied so far.
+    pub records: u64,
+    /// Number of bytes read from `AOF` so far.
+    pub bytes_read: u64,
+    /// Size of `AOF` in bytes, `None` if it could not be read.
+    pub total_bytes: Option<u64>,
+}
+
+/// Health of writing `AOF` to disk, see `AsyncCache::flush_health`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlushHealth {
+    /// When records were last written to disk successfully.
+    pub last_ok: Option<Instant>,
+    /// Error of the last write, `None` if it succeeded.
+    pub last_error: Option<String>,
+    /// Number of records waiting for the next periodic flush.
+    pub pending: usize,
+}
+
+impl FlushHealth {
+    /// Updates the state with the result of a write.
+    pub(crate) fn record<T>(&mut self, result: &std::io::Result<T>) {
+        match result {
+            Ok(_) => {
+                self.last_ok = Some(Instant::now());
+                self.last_error = None;
+            },
+            Err(e) => self.last_error = Some(e.to_string()),
+        }
+    }
+}
+
+/// Size of `AOF`, e.g. to decide when it should be compacted, see `AsyncCache::aof_stats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AofStats {
+    /// Size of the file in bytes.
+    pub file_bytes: u64,
+    /// Number of records of the cache in the file, counting the ones replayed at start and the ones recorded
+    /// since. Records waiting for the periodic flush are included and records of other caches sharing the
+    /// file are not.
+    pub approx_records: u64,
+}
+
+/// Outcome of `AOF::repair`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Number of valid records kept, including the ones of all caches sharing the file.
+    pub records_kept: u64,
+    /// Number of bytes cut off after the last valid record, `0` if the file was valid.
+    pub bytes_discarded: u64,
+}
+
+/// Lookups of `get` by outcome, see `Cache::lookup_counts`.
+///
+/// Many `misses_expired` compared to `misses_absent` tell that entries expire shortly before they are read
+/// again, i.e. that the TTL is too short for how often the keys are reused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LookupCounts {
+    /// Lookups which found a live entry.
+    pub hits: u64,
+    /// Lookups of keys without an entry.
+    pub misses_absent: u64,
+    /// Lookups of keys whose entry had expired, which removed the entry.
+    pub misses_expired: u64,
+}
+
+/// Snapshot of the state of a cache, see `AsyncCache::stats`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Name of the cache, see `AsyncCache::name`.
+    pub cache_name: Option<String>,
+    /// Number of entries, expired ones which have not been removed yet included.
+    pub size: usize,
+    /// Number of entries which have not expired, see `Cache::live_size`.
+    pub live_size: usize,
+    /// Estimated memory used by the entries, see `Cache::estimated_bytes`.
+    pub estimated_bytes: usize,
+    /// Misses of `get` on keys evicted recently, which a bigger cache would have hit, see
+    /// `AsyncCacheBuilder::ghost_capacity`.
+    pub would_have_hit: u64,
+    /// Lookups of `get` by outcome, see `Cache::lookup_counts`.
+    pub lookups: LookupCounts,
+    /// Health of writing `AOF`, see `AsyncCache::flush_health`.
+    pub flush_health: FlushHealth,
+    /// Size of `AOF`, `None` without it, see `AsyncCache::aof_stats`.
+    pub aof_stats: Option<AofStats>,
+}
+
+/// Stream of `CacheStats` returned by `AsyncCache::stats_stream`. It ends once the cache is dropped or shut down.
+///
+/// The crate does not depend on `futures`, so it does not implement `Stream`, but `poll_next` has the same
+/// signature as `Stream::poll_next`, so that implementing it for a wrapper only forwards the call.
+pub struct StatsStream(pub(crate) tokio::sync::mpsc::Receiver<CacheStats>);
+
+impl StatsStream {
+    /// Waits for the next snapshot, `None` once the stream has ended.
+    pub async fn next(&mut self) -> Option<CacheStats> {
+        self.0.recv().await
+    }
+
+    /// Polls for the next snapshot, same as `Stream::poll_next`.
+    pub fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<CacheStats>> {
+        self.get_mut().0.poll_recv(cx)
+    }
+}
+
+/// Crossing of a watermark by the utilization of a cache, see `AsyncCache::utilization_events`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UtilizationEvent {
+    /// Utilization reached `Watermarks::high`, with the utilization observed.
+    High(f32),
+    /// Utilization dropped to `Watermarks::low`, with the utilization observed.
+    Low(f32),
+}
+
+/// Key which the eviction policy of a cache would evict next and why, see `Cache::explain_next_eviction`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvictionExplanation<K> {
+    /// Key which would be evicted next, `None` if the cache is empty or the policy can not tell.
+    pub victim: Option<K>,
+    /// Why `victim` is picked, e.g. `"LRU tail"` or `"LFU freq=2 oldest"`, or why there is none.
+    pub reason: String,
+}
+
+/// Memory budget of a cache, see `Cache::set_max_memory`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemoryBudget {
+    /// Fixed number of bytes.
+    Bytes(u64),
+    /// Percentage of the total memory of the system, e.g. `25.0` for a quarter of it.
+    PercentOfSystem(f32),
+}
+
+impl MemoryBudget {
+    /// Resolves the budget to bytes, reading total system memory for `PercentOfSystem`.
+    ///
+    /// Fails if the percentage is not in `(0, 100]` or total system memory can not be read, e.g. on platforms
+    /// other than Linux, see `total_system_memory`.
+    pub fn resolve(&self) -> Result<u64, ConfigError> {
+        match *self {
+            Self::Bytes(bytes) => Ok(bytes),
+            Self::PercentOfSystem(percent) => {
+                if !(percent > 0.0 && percent <= 100.0) {
+                    return Err(ConfigError::InvalidMemoryPercent(percent));
+                }
+                let total = total_system_memory().ok_or(ConfigError::SystemMemoryUnavailable)?;
+                Ok((total as f64 * percent as f64 / 100.0) as u64)
+            },
+        }
+    }
+}
+
+/// Total memory of the system in bytes, read from `/proc/meminfo`. `None` on other platforms or if it can not
+/// be read.
+pub fn total_system_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|x| x.starts_with("MemTotal:"))?;
+    let kb: u64 = line.trim_start_matches("MemTotal:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Result of looking up a key in a cache of `Option` values, where `None` marks a key known to be absent,
+/// see `Cache::lookup`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookup<V> {
+    /// The key has a value.
+    Present(V),
+    /// The key was cached as absent, e.g. the backend answered "not found".
+    KnownAbsent,
+    /// Nothing is cached for the key.
+    Unknown,
+}
+
+/// Operation of `AsyncCache::pipeline`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CacheOp<K, V> {
+    /// Same as `AsyncCache::get`, without loading misses.
+    Get(K),
+    /// Same as `AsyncCache::put`.
+    Put(K, V),
+    /// Same as `AsyncCache::remove`.
+    Remove(K),
+    /// Same as `AsyncCache::contains_key`.
+    Contains(K),
+}
+
+/// Result of a `CacheOp`, at the same position as the operation in `AsyncCache::pipeline`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpResult<V> {
+    /// The value read.
+    Get(Option<V>),
+    /// The value was inserted.
+    Put,
+    /// The value removed.
+    Remove(Option<V>),
+    /// Whether the key is present.
+    Contains(bool),
+}
+
+/// Background tasks of a cache, which `AsyncCache::shutdown` signals to stop and waits for. The tasks check the
+/// signal only between their rounds of work, with `sleep_unless_stopped`, so that no work is cut off midway.
+pub(crate) struct BackgroundTasks {
+    stop: tokio::sync::watch::Sender<bool>,
+    handles: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+}
+
+impl Default for BackgroundTasks {
+    fn default() -> Self {
+        Self { stop: tokio::sync::watch::channel(false).0, handles: Default::default() }
+    }
+}
+
+impl BackgroundTasks {
+    /// Spawns the task returned by `task` for the stop signal. Tasks spawned once stopped return right away.
+    pub(crate) fn spawn<F>(&self, task: impl FnOnce(tokio::sync::watch::Receiver<bool>) -> F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task(self.stop.subscribe()));
+        let mut handles = self.handles.lock().unwrap();
+        handles.retain(|x| !x.is_finished());
+        handles.push(handle);
+    }
+
+    /// Signals the tasks to stop and waits until they have returned.
+    pub(crate) async fn stop(&self) {
+        self.stop.send_replace(true);
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap());
+        for handle in handles {
+            // a task which panicked is stopped as well.
+            let _ = handle.await;
+        }
+    }
+}
+
+/// Sleeps for `duration`, returning `false` right away instead if `stop` is signalled, see `BackgroundTasks`.
+pub(crate) async fn sleep_unless_stopped(duration: Duration, stop: &mut tokio::sync::watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        Ok(_) = stop.wait_for(|x| *x) => false,
+    }
+}
+
+/// Converts an `Instant` to milliseconds since unix epoch so that it can be persisted.
+pub fn instant_to_unix_millis(instant: Instant) -> u64 {
+    let now = Instant::now();
+    let unix_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let unix = if instant >= now {
+        unix_now + (instant - now)
+    } else {
+        unix_now.saturating_sub(now - instant)
+    };
+    unix.as_millis() as u64
+}
+
+/// Converts milliseconds since unix epoch back to an `Instant`. Times which can not be represented
+/// as an `Instant`, e.g. of a corrupted record, are clamped to now.
+pub fn unix_millis_to_instant(millis: u64) -> Instant {
+    let now = Instant::now();
+    let unix_now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let unix = Duration::from_millis(millis);
+    if unix >= unix_now {
+        now.checked_add(unix - unix_now).unwrap_or(now)
+    } else {
+        now.checked_sub(unix_now - unix).unwrap_or(now)
+    }
 }
@@ -1,7 +1,13 @@
 //! Contains code to define different configurations to use `Cache` and `AsyncCache
 //! `
 
-use crate::eviction_policies::{common::EvictionPolicy, fifo::FIFO, lfu::LFU, lru::LRU, noevicton::NoEviction};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{common::UtilizationEvent, error::ConfigError, eviction_policies::{approx_lfu::ApproxLFU, common::EvictionPolicy, fifo::FIFO, lfu::LFU, lru::LRU, noevicton::NoEviction, sampled_lru::SampledLRU}};
+
+/// Maximum number of keys built-in policies pre-allocate room for, see `EvictionPolicyEnum::create_policy`.
+pub const MAX_PREALLOCATED_KEYS: usize = 1 << 16;
 
 /// Lists all supported policies
 pub enum EvictionPolicyEnum <K> {
@@ -9,18 +15,29 @@ pub enum EvictionPolicyEnum <K> {
     LRU,
     LFU,
     FIFO,
+    /// Approximate LFU, see `ApproxLFU`.
+    ApproxLFU,
+    /// Sampled LRU, see `SampledLRU`.
+    SampledLRU,
     Custom(Box<dyn EvictionPolicy<K> + Send>)
 }
 
 impl<K: std::hash::Hash + Eq + PartialEq + Eq + Send + Sync + Clone + core::fmt::Debug + 'static,> EvictionPolicyEnum<K> {
     /// get empty policy instance based on the value of enum.
+    ///
+    /// `max_size` is the capacity of the cache. Built-in policies pre-allocate room for that many keys, up to
+    /// `MAX_PREALLOCATED_KEYS` so that huge sizes used as "unbounded" don't allocate up front.
     pub fn create_policy(
         self,
+        max_size: usize,
     ) -> Box<dyn EvictionPolicy<K> + Send> {
+        let capacity = max_size.min(MAX_PREALLOCATED_KEYS);
         match self {
-            Self::FIFO => Box::new(FIFO::new()),
-            Self::LFU => Box::new(LFU::new()),
-            Self::LRU => Box::new(LRU::new()),
+            Self::FIFO => Box::new(FIFO::with_capacity(capacity)),
+            Self::LFU => Box::new(LFU::with_capacity(capacity)),
+            Self::LRU => Box::new(LRU::with_capacity(capacity)),
+            Self::ApproxLFU => Box::new(ApproxLFU::with_capacity(capacity)),
+            Self::SampledLRU => Box::new(SampledLRU::with_capacity(capacity)),
             Self::NoEviction => Box::new(NoEviction::new()),
             Self::Custom(e) => e
         }
@@ -44,6 +61,8 @@ pub enum CacheSyncConfig<K> {
     LRU(CacheConfig),
     LFU(CacheConfig),
     FIFO(CacheConfig),
+    ApproxLFU(CacheConfig),
+    SampledLRU(CacheConfig),
     Custom(CustomCacheConfig<K>)
 }
 
@@ -61,12 +80,27 @@ impl<K> CacheSyncConfig<K> {
             Self::LFU(v) => CacheConfig {
                 max_size: v.max_size
             },
+            Self::ApproxLFU(v) => CacheConfig {
+                max_size: v.max_size
+            },
+            Self::SampledLRU(v) => CacheConfig {
+                max_size: v.max_size
+            },
             Self::Custom(v) => CacheConfig {
                 max_size: v.max_size
             }
         }
     }
 
+    /// Checks that evicting policies have a `max_size` greater than zero.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        match self {
+            Self::NoEviction => Ok(()),
+            _ if self.get_config().max_size == 0 => Err(vec![ConfigError::ZeroMaxSize]),
+            _ => Ok(()),
+        }
+    }
+
     /// Returns the eviction policy type.
     pub fn get_policy_type(self) -> EvictionPolicyEnum<K> {
         match self {
@@ -74,6 +108,8 @@ impl<K> CacheSyncConfig<K> {
             Self::FIFO(_) => EvictionPolicyEnum::FIFO,
             Self::LRU(_) => EvictionPolicyEnum::LRU,
             Self::LFU(_) => EvictionPolicyEnum::LFU,
+            Self::ApproxLFU(_) => EvictionPolicyEnum::ApproxLFU,
+            Self::SampledLRU(_) => EvictionPolicyEnum::SampledLRU,
             Self::Custom(v) => EvictionPolicyEnum::Custom(v.policy)
         }
     }
@@ -98,6 +134,7 @@ pub struct EvictionAOFConfig {
     pub folder: String, // folder in which persistent data will be written. e.g. "./folder"
     pub cache_name: String, //unique cache name as the file with same name will be created and utilized upon restart.
     pub flush_time: Option<u32>, // time in milliseconds in which data will be periodically flushed to disk. In case of `None`, data will be flushed on every event.
+    pub persist_read_ops: bool, // If `false`, get operations will not be recorded in AOF file, making reads faster. Eviction order is then rebuilt from writes only on restart, so it may differ from the one before.
 }
 
 /// Evictions related `Async` configurations.
@@ -123,6 +160,93 @@ pub struct CustomEvictionAsyncConfig<K> {
     pub policy: Box<dyn EvictionPolicy<K> + Send>
 }
 
+/// How often the periodic flush of `AOF` writes the pending records to disk, see
+/// `AsyncCacheBuilder::flush_strategy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushStrategy {
+    /// Flushes every interval, same as `flush_time`.
+    Fixed(Duration),
+    /// Flushes after an interval which shrinks from `max`, while nothing is pending, down to `min` as the number
+    /// of pending records approaches `target_batch`, so that bursts of writes are flushed in batches of about
+    /// `target_batch` records and idle caches rarely touch the disk.
+    Adaptive {
+        min: Duration,
+        max: Duration,
+        target_batch: usize,
+    },
+}
+
+impl FlushStrategy {
+    /// Interval after which `pending` records should be flushed.
+    pub fn interval(&self, pending: usize) -> Duration {
+        match *self {
+            Self::Fixed(interval) => interval,
+            Self::Adaptive { min, max, target_batch } => {
+                let fill = pending.min(target_batch) as f64 / target_batch.max(1) as f64;
+                max.saturating_sub(max.saturating_sub(min).mul_f64(fill))
+            },
+        }
+    }
+
+    /// Whether the intervals are usable, i.e. greater than zero with `min <= max` and a positive `target_batch`.
+    pub(crate) fn is_valid(&self) -> bool {
+        match *self {
+            Self::Fixed(interval) => !interval.is_zero(),
+            Self::Adaptive { min, max, target_batch } => !min.is_zero() && min <= max && target_batch > 0,
+        }
+    }
+}
+
+/// Utilization levels at which `AsyncCache::utilization_events` reports the cache as filling up or emptying,
+/// see `Cache::utilization`.
+///
+/// Crossings are reported once: after reaching `high`, the cache is reported again only after dropping below
+/// `high - hysteresis`, and likewise after reaching `low`, so a cache hovering around a watermark does not
+/// flap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watermarks {
+    pub low: f32,
+    pub high: f32,
+    pub hysteresis: f32,
+}
+
+impl Watermarks {
+    /// Event to report for `utilization`, if any, given `zone`, the last event reported and not left since,
+    /// which is updated.
+    pub(crate) fn crossing(&self, zone: &mut Option<UtilizationEvent>, utilization: f32) -> Option<UtilizationEvent> {
+        match *zone {
+            Some(UtilizationEvent::High(_)) if utilization >= self.high - self.hysteresis => return None,
+            Some(UtilizationEvent::Low(_)) if utilization <= self.low + self.hysteresis => return None,
+            _ => {},
+        }
+        *zone = if utilization >= self.high {
+            Some(UtilizationEvent::High(utilization))
+        } else if utilization <= self.low {
+            Some(UtilizationEvent::Low(utilization))
+        } else {
+            None
+        };
+        *zone
+    }
+
+    /// Whether the watermarks are usable, i.e. `0 <= low < high` with their hysteresis bands not overlapping.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.low >= 0.0 && self.hysteresis >= 0.0 && self.low + self.hysteresis < self.high - self.hysteresis
+    }
+}
+
+/// What `AsyncCache` does when its `AOF` file can not be opened, e.g. because the folder is not writable, see
+/// `AsyncCacheBuilder::aof_failure`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AofFailureMode {
+    /// Panics while creating the cache.
+    #[default]
+    Fatal,
+    /// Keeps working in memory only, nothing is replayed nor persisted, and reports the error through
+    /// `AsyncCache::flush_health`.
+    DegradeToMemory,
+}
+
 /// Config for `AsyncCache`
 ///
 pub enum AsyncCacheConfig<K> {
@@ -130,6 +254,8 @@ pub enum AsyncCacheConfig<K> {
     LFU(EvictionAsyncConfig),
     LRU(EvictionAsyncConfig),
     FIFO(EvictionAsyncConfig),
+    ApproxLFU(EvictionAsyncConfig),
+    SampledLRU(EvictionAsyncConfig),
     Custom(CustomEvictionAsyncConfig<K>)
 }
 
@@ -148,6 +274,12 @@ impl<K> AsyncCacheConfig<K> {
             Self::LRU(v) => CacheSyncConfig::LRU(CacheConfig {
                 max_size: v.max_size,
             }),
+            Self::ApproxLFU(v) => CacheSyncConfig::ApproxLFU(CacheConfig {
+                max_size: v.max_size,
+            }),
+            Self::SampledLRU(v) => CacheSyncConfig::SampledLRU(CacheConfig {
+                max_size: v.max_size,
+            }),
             Self::Custom(v) => CacheSyncConfig::Custom(CustomCacheConfig {
                 max_size: v.max_size,
                 policy: v.policy
@@ -155,13 +287,27 @@ impl<K> AsyncCacheConfig<K> {
         }
     }
 
-    /// get whether to include read ops or not in `AOF`. In case of no-evictions and aof not configured, returns `None`.
+    /// get whether to include read ops or not in `AOF`. In case aof is not configured, returns `None`.
     ///
     pub fn persist_read_ops(&self) -> Option<bool> {
         match self {
             Self::NoEviction(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
+            Self::FIFO(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
+            Self::LFU(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
+            Self::LRU(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
+            Self::ApproxLFU(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
+            Self::SampledLRU(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
             Self::Custom(v) => v.aof_config.as_ref().map(|x| x.persist_read_ops),
-            _ => Some(true),
+        }
+    }
+
+    /// Whether every operation is flushed to disk right away while keys are evicted, i.e. `AOF` with `flush_time`
+    /// as `None` for any policy but `NoEviction` which persists read ops, so that every operation, reads included,
+    /// waits for disk io. See `AsyncCacheBuilder::allow_sync_writes`.
+    pub fn has_sync_writes_with_eviction(&self) -> bool {
+        match self {
+            Self::NoEviction(_) => false,
+            _ => self.persist_read_ops() == Some(true) && self.get_aof_config().is_some_and(|x| x.2.is_none()),
         }
     }
 
@@ -177,10 +323,56 @@ impl<K> AsyncCacheConfig<K> {
             Self::FIFO(v) => v.aof_config.as_ref().map(|x| (x.folder.clone(), x.cache_name.clone(), x.flush_time)),
             Self::LFU(v) => v.aof_config.as_ref().map(|x| (x.folder.clone(), x.cache_name.clone(), x.flush_time)),
             Self::LRU(v) => v.aof_config.as_ref().map(|x| (x.folder.clone(), x.cache_name.clone(), x.flush_time)),
+            Self::ApproxLFU(v) => v.aof_config.as_ref().map(|x| (x.folder.clone(), x.cache_name.clone(), x.flush_time)),
+            Self::SampledLRU(v) => v.aof_config.as_ref().map(|x| (x.folder.clone(), x.cache_name.clone(), x.flush_time)),
             Self::Custom(v) => v.aof_config.as_ref().map(|x| (x.folder.clone(), x.cache_name.clone(), x.flush_time)),
         }
     }
 
+    /// Name of the eviction policy, e.g. `LRU`, recorded in `AofManifest`. `Custom` for custom policies.
+    pub fn policy_name(&self) -> &'static str {
+        match self {
+            Self::NoEviction(_) => "NoEviction",
+            Self::FIFO(_) => "FIFO",
+            Self::LFU(_) => "LFU",
+            Self::LRU(_) => "LRU",
+            Self::ApproxLFU(_) => "ApproxLFU",
+            Self::SampledLRU(_) => "SampledLRU",
+            Self::Custom(_) => "Custom",
+        }
+    }
+
+    /// Maximum number of entries, `None` for `NoEviction` which has no limit.
+    pub fn max_size(&self) -> Option<usize> {
+        match self {
+            Self::NoEviction(_) => None,
+            Self::FIFO(v) | Self::LFU(v) | Self::LRU(v) | Self::ApproxLFU(v) | Self::SampledLRU(v) => Some(v.max_size),
+            Self::Custom(v) => Some(v.max_size),
+        }
+    }
+
+    /// Checks the config before creating a cache, returning all the problems found rather than panicking on
+    /// the first one: `max_size` of evicting policies and `flush_time` must be greater than zero, `cache_name`
+    /// must be a single path component and `folder` must be writable, or be created in a writable folder.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = vec![];
+        if self.max_size() == Some(0) {
+            errors.push(ConfigError::ZeroMaxSize);
+        }
+        if let Some((folder, cache_name, flush_time)) = self.get_aof_config() {
+            if flush_time == Some(0) {
+                errors.push(ConfigError::ZeroFlushTime);
+            }
+            if cache_name.is_empty() || cache_name == "." || cache_name == ".." || cache_name.contains(['/', '\\']) {
+                errors.push(ConfigError::InvalidCacheName(cache_name));
+            }
+            if let Err(detail) = check_writable_folder(&folder) {
+                errors.push(ConfigError::FolderNotWritable { folder, detail });
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
     /// Returns eviction policy type
     /// 
     pub fn get_policy_type(self) -> EvictionPolicyEnum<K> {
@@ -189,7 +381,28 @@ impl<K> AsyncCacheConfig<K> {
             Self::FIFO(_) => EvictionPolicyEnum::FIFO,
             Self::LRU(_) => EvictionPolicyEnum::LRU,
             Self::LFU(_) => EvictionPolicyEnum::LFU,
+            Self::ApproxLFU(_) => EvictionPolicyEnum::ApproxLFU,
+            Self::SampledLRU(_) => EvictionPolicyEnum::SampledLRU,
             Self::Custom(v) => EvictionPolicyEnum::Custom(v.policy)
         }
     }
 }
+
+/// Checks that `folder` is a writable folder or, as missing folders of `AOF` are created, that its closest
+/// existing ancestor is. Returns why it is not.
+fn check_writable_folder(folder: &str) -> Result<(), String> {
+    let mut path = Path::new(folder);
+    loop {
+        match std::fs::metadata(path) {
+            Ok(metadata) if !metadata.is_dir() => return Err(format!("{} is not a folder", path.display())),
+            Ok(metadata) if metadata.permissions().readonly() => return Err(format!("{} is read-only", path.display())),
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => path = parent,
+                // relative to the working directory.
+                _ => return Ok(()),
+            },
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Errors returned by the cache operations.
+
+use std::{fmt, io};
+
+/// Error returned by `compare_and_swap` when the value was not written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CasError {
+    /// The current version of the key did not match the expected one. Version `0` means the key is absent.
+    VersionMismatch {
+        expected: u64,
+        current: u64,
+    },
+}
+
+impl fmt::Display for CasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VersionMismatch { expected, current } => {
+                write!(f, "expected version {} but current version is {}", expected, current)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CasError {}
+
+/// Error returned by `AsyncCache::get_with_loader_timeout` when the loader did not complete in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoaderTimeout {
+    /// Time the loader was given.
+    pub timeout: std::time::Duration,
+}
+
+impl fmt::Display for LoaderTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "loader did not complete within {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for LoaderTimeout {}
+
+/// Error returned by `Operation::from_int` for an integer which is not an operation, e.g. read from a corrupted
+/// `AOF` or written by a newer version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidOperation {
+    /// Integer which was read.
+    pub operation: u8,
+}
+
+impl fmt::Display for InvalidOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid operation {}", self.operation)
+    }
+}
+
+impl std::error::Error for InvalidOperation {}
+
+/// Error returned by `try_put` when no entry could be evicted to make room for the new one, with the key and
+/// the value which were not inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheFull<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+impl<K, V> fmt::Display for CacheFull<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cache is full and no entry can be evicted")
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> std::error::Error for CacheFull<K, V> {}
+
+/// Problem of a configuration found before creating a cache, see `AsyncCacheBuilder::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// `max_size` of an evicting policy is zero.
+    ZeroMaxSize,
+    /// `flush_time` of `AOF` is zero.
+    ZeroFlushTime,
+    /// `cache_name` is empty or not a single path component, e.g. `"a/b"` or `".."`.
+    InvalidCacheName(String),
+    /// The folder of `AOF` can not be written, e.g. it is a file or read-only.
+    FolderNotWritable {
+        folder: String,
+        detail: String,
+    },
+    /// Flush jitter is not in `[0, 1)`, see `AsyncCacheBuilder::flush_jitter`.
+    InvalidFlushJitter(f64),
+    /// Every operation would be flushed to disk while evicting, see `AsyncCacheBuilder::allow_sync_writes`.
+    SyncWritesNotAllowed,
+    /// Policy state is persisted without the `AOF` config of the cache.
+    PolicyStateWithoutAof,
+    /// More than one of the `AOF` config of the cache, shared `AOF` and memory `AOF` are used.
+    ConflictingAof,
+    /// Memory-mapped `AOF` is used without the `AOF` config of the cache.
+    MmapWithoutAof,
+    /// Value transform is used with shared `AOF`.
+    ValueTransformWithSharedAof,
+    /// Values are compressed over a size without value transform.
+    CompressionWithoutTransform,
+    /// Replay progress is reported every zero records.
+    ZeroReplayProgress,
+    /// Sweep interval is zero.
+    ZeroSweepInterval,
+    /// Refresh interval of stale reads is zero.
+    ZeroStaleReadsInterval,
+    /// Policy state is recorded in `AOF` without `AOF`, see `AsyncCacheBuilder::policy_state_interval`.
+    PolicyStateIntervalWithoutAof,
+    /// Interval of recording policy state is zero.
+    ZeroPolicyStateInterval,
+    /// Flush strategy is used without a periodic flush of the `AOF` config of the cache.
+    FlushStrategyWithoutFlushTime,
+    /// Intervals of the flush strategy are zero or `min` is greater than `max`, or its target batch is zero.
+    InvalidFlushStrategy,
+    /// Soft limit is not below `max_size` of an evicting policy, see `AsyncCacheBuilder::soft_limit`.
+    InvalidSoftLimit(usize),
+    /// Interval of evicting down to the soft limit is zero.
+    ZeroSoftLimitInterval,
+    /// File extension of `AOF` is empty or not a single path component, see `AsyncCacheBuilder::file_extension`.
+    InvalidFileExtension(String),
+    /// Manifest is written without the `AOF` config of the cache, see `AsyncCacheBuilder::write_manifest`.
+    ManifestWithoutAof,
+    /// Refresh ahead fraction is not in `(0, 1)`, see `AsyncCacheBuilder::refresh_ahead_fraction`.
+    InvalidRefreshAheadFraction(f32),
+    /// Entries are refreshed ahead without a loader or without ttl of the loaded values.
+    RefreshAheadWithoutLoaderTtl,
+    /// Percentage of `MemoryBudget::PercentOfSystem` is not in `(0, 100]`.
+    InvalidMemoryPercent(f32),
+    /// Total system memory can not be read for `MemoryBudget::PercentOfSystem`, e.g. without `/proc/meminfo`.
+    SystemMemoryUnavailable,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroMaxSize => write!(f, "max size must be greater than zero."),
+            Self::ZeroFlushTime => write!(f, "flush time must be greater than zero."),
+            Self::InvalidCacheName(name) => write!(f, "cache name {:?} must be a single non-empty path component.", name),
+            Self::FolderNotWritable { folder, detail } => write!(f, "folder {} is not writable: {}", folder, detail),
+            Self::InvalidFlushJitter(_) => write!(f, "flush jitter must be at least zero and less than one."),
+            Self::SyncWritesNotAllowed => {
+                write!(f, "Flushing every operation with eviction is as slow as disk io, use a flush time or `allow_sync_writes`.")
+            },
+            Self::PolicyStateWithoutAof => write!(f, "Policy state can only be persisted with the AOF config of the cache."),
+            Self::ConflictingAof => write!(f, "Use only one of the AOF config of the cache, shared AOF and memory AOF."),
+            Self::MmapWithoutAof => write!(f, "Memory-mapped AOF can only be used with the AOF config of the cache."),
+            Self::ValueTransformWithSharedAof => write!(f, "Value transform can not be used with shared AOF."),
+            Self::CompressionWithoutTransform => write!(f, "Values can only be compressed over a size with a value transform."),
+            Self::ZeroReplayProgress => write!(f, "Replay progress must be reported after at least one record."),
+            Self::ZeroSweepInterval => write!(f, "sweep interval must be greater than zero."),
+            Self::ZeroStaleReadsInterval => write!(f, "stale reads refresh interval must be greater than zero."),
+            Self::PolicyStateIntervalWithoutAof => write!(f, "Policy state can only be recorded in AOF with AOF, shared AOF or memory AOF."),
+            Self::ZeroPolicyStateInterval => write!(f, "policy state interval must be greater than zero."),
+            Self::FlushStrategyWithoutFlushTime => write!(f, "Flush strategy can only be used with the flush time of the AOF config of the cache."),
+            Self::InvalidFlushStrategy => write!(f, "flush intervals must be greater than zero with min at most max, and target batch greater than zero."),
+            Self::InvalidSoftLimit(_) => write!(f, "soft limit must be below the max size of an evicting policy."),
+            Self::ZeroSoftLimitInterval => write!(f, "soft limit interval must be greater than zero."),
+            Self::InvalidFileExtension(e) => write!(f, "invalid file extension {:?}, it must be a non-empty file name suffix without dots or separators.", e),
+            Self::ManifestWithoutAof => write!(f, "Manifest can only be written with the AOF config of the cache."),
+            Self::InvalidRefreshAheadFraction(_) => write!(f, "refresh ahead fraction must be greater than zero and less than one."),
+            Self::RefreshAheadWithoutLoaderTtl => write!(f, "Entries can only be refreshed ahead with a loader and loader ttl."),
+            Self::InvalidMemoryPercent(_) => write!(f, "percent of system memory must be greater than zero and at most 100."),
+            Self::SystemMemoryUnavailable => write!(f, "Total system memory can not be read, use a memory budget in bytes."),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Error of a cache operation.
+///
+/// Variants tell the failure modes apart, e.g. `Corruption` of `AOF` can be recovered from by truncating the
+/// file at `offset`, while `Config` and `PolicyMismatch` need the configuration to be fixed.
+#[derive(Debug)]
+pub enum CacheError {
+    /// Invalid configuration.
+    Config(String),
+    /// Reading or writing `AOF` failed.
+    Io(io::Error),
+    /// A key or a value could not be serialized, or read from `AOF` could not be deserialized, e.g. after the
+    /// type of values changed.
+    Serialize(serde_json::Error),
+    /// `AOF` has bytes which are not a valid record, e.g. after a partial write.
+    Corruption {
+        /// Position of the invalid record in the file.
+        offset: u64,
+        detail: String,
+    },
+    /// The data was written by a cache with another eviction policy than the configured one.
+    PolicyMismatch {
+        expected: String,
+        found: String,
+    },
+    /// `error` of the cache named `cache_name`, see `AsyncCache::name`. Use `kind` to match the error itself.
+    Named {
+        cache_name: String,
+        error: Box<CacheError>,
+    },
+}
+
+impl CacheError {
+    /// The error without the name of the cache it happened in, if `Named`, e.g. to match its variant.
+    pub fn kind(&self) -> &CacheError {
+        match self {
+            Self::Named { error, .. } => error.kind(),
+            e => e,
+        }
+    }
+
+    /// Name of the cache the error happened in, if known.
+    pub fn cache_name(&self) -> Option<&str> {
+        match self {
+            Self::Named { cache_name, .. } => Some(cache_name),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "invalid config: {}", e),
+            Self::Io(e) => write!(f, "aof io failed: {}", e),
+            Self::Serialize(e) => write!(f, "failed to (de)serialize record: {}", e),
+            Self::Corruption { offset, detail } => write!(f, "corrupted aof at offset {}: {}", offset, detail),
+            Self::PolicyMismatch { expected, found } => {
+                write!(f, "expected eviction policy {} but found {}", expected, found)
+            }
+            Self::Named { cache_name, error } => write!(f, "cache {}: {}", cache_name, error),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Serialize(e) => Some(e),
+            Self::Named { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CacheError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CacheError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serialize(e)
+    }
+}
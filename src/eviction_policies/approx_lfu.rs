@@ -0,0 +1,181 @@
+//! Implements an approximate LFU (Least Frequently Used) eviction policy for a cache.
+//!
+//! Exact `LFU` keeps a frequency per key and an LRU list per frequency, which gets memory heavy for
+//! millions of keys. `ApproxLFU<K>` estimates frequencies with a count-min sketch instead and evicts by
+//! sampling: on `evict`, a handful of random keys are compared and the one with the lowest estimated
+//! frequency is dropped.
+//!
+//! ## Trade-off
+//! - Memory: keys are stored once in a list to sample from and once in a map of their positions, the
+//!   frequencies take a fixed sketch of `4 * width` bytes instead of per key nodes and lists.
+//! - Accuracy: estimates never undercount, but colliding keys may be overcounted, and the victim is only the
+//!   least frequent of the sampled keys, not of all keys. Counters are halved periodically so that old
+//!   popularity fades. On skewed workloads the hit ratio stays close to exact `LFU`.
+//! - Evictions are random, so `peek_victim` can not tell the next victim and returns `None`.
+
+use std::collections::{hash_map::RandomState, HashMap};
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use rand::Rng;
+
+use super::common::EvictionPolicy;
+
+/// Number of keys compared on every eviction.
+const SAMPLES: usize = 5;
+
+/// Number of rows of the sketch, every key is counted once per row.
+const DEPTH: usize = 4;
+
+/// Minimum number of counters per row.
+const MIN_WIDTH: usize = 64;
+
+/// Count-min sketch with small saturating counters which are halved after `width * 10` increments.
+struct CountMinSketch {
+    rows: Vec<Vec<u8>>,
+    /// `width - 1`, width being a power of two.
+    mask: usize,
+    increments: usize,
+    hash_builder: RandomState,
+}
+
+impl CountMinSketch {
+    fn new(width: usize) -> Self {
+        let width = width.max(MIN_WIDTH).next_power_of_two();
+        Self {
+            rows: vec![vec![0; width]; DEPTH],
+            mask: width - 1,
+            increments: 0,
+            hash_builder: RandomState::new(),
+        }
+    }
+
+    fn index<K: Hash>(&self, row: usize, key: &K) -> usize {
+        let mut hasher = self.hash_builder.build_hasher();
+        (row, key).hash(&mut hasher);
+        hasher.finish() as usize & self.mask
+    }
+
+    fn increment<K: Hash>(&mut self, key: &K) {
+        for row in 0..DEPTH {
+            let index = self.index(row, key);
+            self.rows[row][index] = self.rows[row][index].saturating_add(1);
+        }
+        self.increments += 1;
+        if self.increments >= (self.mask + 1) * 10 {
+            for row in self.rows.iter_mut() {
+                row.iter_mut().for_each(|x| *x /= 2);
+            }
+            self.increments = 0;
+        }
+    }
+
+    fn estimate<K: Hash>(&self, key: &K) -> u8 {
+        (0..DEPTH).map(|row| self.rows[row][self.index(row, key)]).min().unwrap_or(0)
+    }
+}
+
+/// Approximate LFU eviction policy, see the module documentation for the trade-off against `LFU`.
+pub struct ApproxLFU<K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Keys in no particular order, to sample victims from.
+    keys: Vec<K>,
+
+    /// Position of every key in `keys`.
+    positions: HashMap<K, usize>,
+
+    /// Estimated access frequencies.
+    sketch: CountMinSketch,
+}
+
+impl<K: Eq + Hash + Clone> ApproxLFU<K> {
+    /// Creates a new instance of `ApproxLFU`.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates a new instance of `ApproxLFU` with room for `capacity` keys. The sketch gets a counter per key
+    /// and row, so estimates get less accurate once there are many more keys than `capacity`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+            sketch: CountMinSketch::new(capacity),
+        }
+    }
+
+    /// Returns the number of keys tracked.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Estimated number of accesses of the key, sets included. Saturates at `255` and is halved periodically.
+    pub fn estimate(&self, key: &K) -> u8 {
+        self.sketch.estimate(key)
+    }
+
+    /// Removes the key at `position`, moving the last key in its place.
+    fn remove_at(&mut self, position: usize) -> K {
+        let key = self.keys.swap_remove(position);
+        self.positions.remove(&key);
+        if let Some(moved) = self.keys.get(position) {
+            self.positions.insert(moved.clone(), position);
+        }
+        key
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for ApproxLFU<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for ApproxLFU<K> {
+    fn on_get(&mut self, key: &K) {
+        if self.positions.contains_key(key) {
+            self.sketch.increment(key);
+        }
+    }
+
+    fn on_set(&mut self, key: K) {
+        self.sketch.increment(&key);
+        if !self.positions.contains_key(&key) {
+            self.positions.insert(key.clone(), self.keys.len());
+            self.keys.push(key);
+        }
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        let victim = (0..SAMPLES.min(self.keys.len()))
+            .map(|_| rng.gen_range(0..self.keys.len()))
+            .min_by_key(|x| self.sketch.estimate(&self.keys[*x]))?;
+        Some(self.remove_at(victim))
+    }
+
+    fn remove(&mut self, key: K) {
+        if let Some(position) = self.positions.get(&key).copied() {
+            self.remove_at(position);
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.keys.shrink_to_fit();
+        self.positions.shrink_to_fit();
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.keys.reserve(additional);
+        self.positions.reserve(additional);
+    }
+}
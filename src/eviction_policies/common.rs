@@ -1,16 +1,61 @@
 //! Traits and structs used in eviction_policies modules.
 
+use std::collections::BTreeMap;
+
 /// A trait defining the eviction policy for a cache.
 ///
 /// This trait, `EvictionPolicy<K>`, establishes a contract for different eviction
 /// strategies a cache can employ. It outlines the functions an eviction policy
 /// must implement.
+///
+/// All the methods take the cache key type `K` itself, built-in and custom policies alike.
+/// A minimal custom policy only needs the four required methods:
+///
+/// ```rust
+/// use std::collections::VecDeque;
+/// use sine_cache::{cache::Cache, config::{CacheSyncConfig, CustomCacheConfig}};
+/// use sine_cache::eviction_policies::common::EvictionPolicy;
+///
+/// /// Evicts the most recently inserted key.
+/// struct Stack<K> {
+///     keys: VecDeque<K>,
+/// }
+///
+/// impl<K: PartialEq> EvictionPolicy<K> for Stack<K> {
+///     fn on_get(&mut self, _key: &K) {}
+///
+///     fn on_set(&mut self, key: K) {
+///         self.keys.retain(|x| x != &key);
+///         self.keys.push_back(key);
+///     }
+///
+///     fn evict(&mut self) -> Option<K> {
+///         self.keys.pop_back()
+///     }
+///
+///     fn remove(&mut self, key: K) {
+///         self.keys.retain(|x| x != &key);
+///     }
+/// }
+///
+/// let mut cache = Cache::new(CacheSyncConfig::Custom(CustomCacheConfig {
+///     max_size: 2,
+///     policy: Box::new(Stack { keys: VecDeque::new() }),
+/// }));
+/// cache.put(1, "one");
+/// cache.put(2, "two");
+/// cache.put(3, "three");
+/// assert!(!cache.contains_key(&2));
+/// ```
 pub trait EvictionPolicy<K> {
     /// Called when a value is retrieved from the cache using the given key.
     ///
     /// This function is triggered on a cache get operation with the provided `key`.
     /// The specific behavior of this function might vary depending on the
     /// eviction policy implementation.
+    ///
+    /// It is called for misses as well, so keys which have not been set must be ignored
+    /// without creating any internal state for them.
     fn on_get(&mut self, key: &K);
 
     /// Called when a new value is inserted into the cache using the given key.
@@ -21,6 +66,15 @@ pub trait EvictionPolicy<K> {
     /// behavior might vary depending on the eviction policy.
     fn on_set(&mut self, key: K);
 
+    /// Called instead of `on_set` when the cache has a weigher configured, with the weight of the value.
+    ///
+    /// Size or cost aware policies can override this to account for the weight of entries. By default the
+    /// weight is ignored and `on_set` is called, so weight agnostic policies need not implement it.
+    fn on_set_weighted(&mut self, key: K, weight: usize) {
+        let _ = weight;
+        self.on_set(key);
+    }
+
     /// Attempts to evict a key-value pair from the cache according to the eviction policy.
     ///
     /// This function is responsible for selecting a key-value pair to evict from
@@ -29,6 +83,65 @@ pub trait EvictionPolicy<K> {
     /// eviction is necessary, it returns `None`.
     fn evict(&mut self) -> Option<K>;
 
+    /// Returns the key which `evict` would pick next, without evicting it.
+    ///
+    /// Meant for introspection, the default implementation returns `None` for policies which can not tell.
+    fn peek_victim(&self) -> Option<&K> {
+        None
+    }
+
+    /// Returns the key which `evict` would pick next with a short reason, e.g. `"LRU tail"`, without evicting
+    /// it, see `Cache::explain_next_eviction`.
+    ///
+    /// Meant for debugging, the default implementation returns `None` for policies which can not tell.
+    fn explain(&self) -> Option<(K, String)> {
+        None
+    }
+
+    /// Returns the number of keys at every access frequency, for frequency based policies.
+    ///
+    /// Meant for diagnostics, the default implementation returns `None` for policies which don't count accesses.
+    fn frequency_histogram(&self) -> Option<BTreeMap<u64, usize>> {
+        None
+    }
+
+    /// Returns the tracked keys in eviction order, the next victim first, each with its access count for
+    /// frequency based policies and `0` otherwise, so that the state can be persisted and restored later with
+    /// `import_state`.
+    ///
+    /// The default implementation returns `None` for policies which can not export their state.
+    fn export_state(&self) -> Option<Vec<(K, u64)>> {
+        None
+    }
+
+    /// Tracks the keys of a state returned by `export_state`, in the same order, after the keys tracked already.
+    ///
+    /// The default implementation calls `on_set` for every key and ignores the access counts, which restores
+    /// the order of recency and insertion based policies.
+    fn import_state(&mut self, state: Vec<(K, u64)>) {
+        for (key, _) in state {
+            self.on_set(key);
+        }
+    }
+
+    /// Returns the internal state of the policy as opaque bytes, so that it is recorded in `AOF` and restored
+    /// with `import_state_bytes` on the next start, see `AsyncCacheBuilder::policy_state_interval`. Meant for
+    /// custom policies whose state does not fit `export_state`, e.g. priorities or statistics of their own.
+    ///
+    /// The default implementation returns `None`, nothing is recorded then.
+    fn export_state_bytes(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restores a state returned by `export_state_bytes` while `AOF` is replayed, at the point it was recorded.
+    /// The keys of the cache at that point have been passed to `on_set` already, so the state replaces the
+    /// tracked one rather than adding to it, the operations recorded after it are replayed afterwards.
+    ///
+    /// Does nothing by default.
+    fn import_state_bytes(&mut self, bytes: &[u8]) {
+        let _ = bytes;
+    }
+
     /// Removes the entry with the given key from the cache.
     ///
     /// This function explicitly removes the key-value entry associated with the
@@ -36,4 +149,15 @@ pub trait EvictionPolicy<K> {
     /// key might vary based on the cache implementation (e.g., return an error
     /// or a boolean indicating success).
     fn remove(&mut self, key: K);
+
+    /// Releases the unused capacity of internal collections, called from `Cache::shrink_to_fit`.
+    ///
+    /// Does nothing by default.
+    fn shrink_to_fit(&mut self) {}
+
+    /// Reserves capacity of internal collections for at least `additional` more keys, called from
+    /// `Cache::reserve`.
+    ///
+    /// Does nothing by default.
+    fn reserve(&mut self, _additional: usize) {}
 }
@@ -4,7 +4,8 @@
 //! It maintains a queue using `VecDeque<K>` to store keys in the order they were inserted. The eviction policy
 //! evicts the least recently accessed key (the one at the front of the queue).
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::{hash_map::RandomState, HashSet, VecDeque};
+use std::hash::BuildHasher;
 
 use super::common::EvictionPolicy;
 
@@ -13,12 +14,14 @@ use super::common::EvictionPolicy;
 /// This struct, `FIFO<K>`, implements a FIFO eviction policy for a cache. It maintains a queue using `VecDeque<K>`
 /// to store keys in the order of insertion. The eviction policy evicts the least recently accessed key (the one at
 /// the front of the queue).
-pub struct FIFO<K> {
+///
+/// `S` is the hasher of the tombstone set, `RandomState` by default.
+pub struct FIFO<K, S = RandomState> {
     /// The queue that stores keys in the order of insertion (FIFO).
     queue: VecDeque<K>,
 
     /// A set containing keys that have been logically removed from the queue but not yet evicted.
-    tombstones: HashSet<K>,
+    tombstones: HashSet<K, S>,
 }
 
 impl<K: Eq + std::hash::Hash + Clone > FIFO<K> {
@@ -26,17 +29,49 @@ impl<K: Eq + std::hash::Hash + Clone > FIFO<K> {
     ///
     /// Constructs a new `FIFO` eviction policy with an empty queue and an empty tombstone set.
     pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Creates a new `FIFO` eviction policy instance with room for `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Eq + std::hash::Hash + Clone, S: BuildHasher> FIFO<K, S> {
+    /// Creates a new `FIFO` eviction policy instance which uses `hash_builder` to hash tombstones.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Creates a new `FIFO` eviction policy instance with room for `capacity` keys which uses `hash_builder` to
+    /// hash tombstones.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
         Self {
-            queue: VecDeque::new(),
-            tombstones: HashSet::new(),
+            queue: VecDeque::with_capacity(capacity),
+            tombstones: HashSet::with_hasher(hash_builder),
         }
     }
+
+    /// Keys of the `queue` in the order `evict` would return them. A tombstone is consumed by the first key of
+    /// the `queue` equal to it, as `evict` discards that one.
+    fn live_keys(&self) -> impl Iterator<Item = &K> {
+        let mut skipped: Vec<&K> = vec![];
+        self.queue.iter().filter(move |key| {
+            if self.tombstones.contains(*key) && !skipped.contains(key) {
+                skipped.push(key);
+                return false;
+            }
+            true
+        })
+    }
 }
 
-impl<K: Eq + std::hash::Hash + Clone > EvictionPolicy<K> for FIFO<K> {
+impl<K: Eq + std::hash::Hash + Clone, S: BuildHasher> EvictionPolicy<K> for FIFO<K, S> {
     /// Called when a value is retrieved from the cache using the given key.
     ///
-    /// In a FIFO policy, there's no specific action required upon a get operation. This function is a placeholder.
+    /// In a FIFO policy, there's no specific action required upon a get operation. This function is a placeholder,
+    /// so unknown keys are ignored as well.
     fn on_get(&mut self, _key: &K) {}
 
     /// Called when a new value is inserted into the cache using the given key.
@@ -64,10 +99,38 @@ impl<K: Eq + std::hash::Hash + Clone > EvictionPolicy<K> for FIFO<K> {
         evicted_key
     }
 
+    /// Returns the key which would be evicted next without evicting it.
+    ///
+    /// Skips the keys at the front of the `queue` which `evict` would discard as tombstones.
+    fn peek_victim(&self) -> Option<&K> {
+        self.live_keys().next()
+    }
+
+    /// Returns the key which `evict` would pick next, explained as `"FIFO front"`, see `peek_victim`.
+    fn explain(&self) -> Option<(K, String)> {
+        self.peek_victim().map(|key| (key.clone(), String::from("FIFO front")))
+    }
+
+    /// Returns the keys of the `queue` in the order `evict` would return them, without the tombstones.
+    fn export_state(&self) -> Option<Vec<(K, u64)>> {
+        Some(self.live_keys().map(|key| (key.clone(), 0)).collect())
+    }
+
     /// Removes the entry with the given key from the cache (logically).
     ///
     /// Marks the key for eviction by adding it to the `tombstones` set. The actual eviction happens during the `evict` function.
     fn remove(&mut self, key: K) {
         self.tombstones.insert(key);
     }
+
+    /// Shrinks the capacity of the queue and the tombstone set.
+    fn shrink_to_fit(&mut self) {
+        self.queue.shrink_to_fit();
+        self.tombstones.shrink_to_fit();
+    }
+
+    /// Reserves capacity of the queue.
+    fn reserve(&mut self, additional: usize) {
+        self.queue.reserve(additional);
+    }
 }
@@ -31,7 +31,8 @@
 //! This LFU eviction policy is suitable for applications requiring efficient management of
 //! frequently accessed data in memory, ensuring optimal performance under high load conditions.
 
-use std::collections::HashMap;
+use std::collections::{hash_map::RandomState, BTreeMap, HashMap};
+use std::hash::BuildHasher;
 
 use super::{
     common::EvictionPolicy,
@@ -42,19 +43,27 @@ use super::{
 ///
 /// This struct, `LFU<K>`, implements an LFU eviction policy for a cache. It tracks the frequency
 /// of accesses to keys and evicts keys that are least frequently accessed when space is needed.
-pub struct LFU<K>
+///
+/// `S` is the hasher used for the key maps, `RandomState` by default.
+///
+/// Frequencies are counted as `u64` whatever the pointer width, so that long running caches on 32-bit
+/// platforms don't overflow them and persisted states, see `export_state`, read the same on every platform.
+pub struct LFU<K, S = RandomState>
 where
     K: Eq + std::hash::Hash + Clone + std::fmt::Debug, // Key requirements: Eq, Hash, Clone, Debug
 {
     /// Maps each key to its access frequency count.
-    map: HashMap<K, usize>,
+    map: HashMap<K, u64, S>,
 
     /// Tracks the smallest frequency of any key in the cache.
-    least_freq: usize,
+    least_freq: u64,
 
     /// Stores keys grouped by their access frequencies using LRU structures.
     /// Each frequency is associated with an LRU list containing keys accessed at that frequency.
-    freq_nodes: HashMap<usize, LRU<K>>,
+    freq_nodes: HashMap<u64, LRU<K, S>>,
+
+    /// Hasher cloned into every new frequency list.
+    hash_builder: S,
 }
 
 impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug> LFU<K> {
@@ -62,13 +71,54 @@ impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug> LFU<K> {
     ///
     /// Initializes an empty LFU cache with default values.
     pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Creates a new instance of `LFU` with room for `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug, S: BuildHasher + Clone> LFU<K, S> {
+    /// Creates a new instance of `LFU` which uses `hash_builder` to hash keys.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Creates a new instance of `LFU` with room for `capacity` keys which uses `hash_builder` to hash keys.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
         Self {
-            map: HashMap::new(),
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder.clone()),
             least_freq: 0,
             freq_nodes: HashMap::new(),
+            hash_builder,
         }
     }
 
+    /// Returns the number of keys tracked.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of frequency lists, including the empty ones which have not been dropped yet.
+    pub fn freq_lists(&self) -> usize {
+        self.freq_nodes.len()
+    }
+
+    /// Returns the number of keys at every access frequency, frequencies without keys are left out.
+    pub fn frequency_histogram(&self) -> BTreeMap<u64, usize> {
+        self.freq_nodes.iter()
+            .filter(|(_, lru)| lru.len() > 0)
+            .map(|(freq, lru)| (*freq, lru.len()))
+            .collect()
+    }
+
     /// Records an access to a key, incrementing its access frequency and updating internal structures.
     ///
     /// If the key exists in the LFU cache, its access frequency is incremented. The key is then moved
@@ -96,7 +146,7 @@ impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug> LFU<K> {
             // Add the key to the new frequency list (create one if it doesn't exist)
             self.freq_nodes
                 .entry(*freq)
-                .or_insert_with(LRU::new) // Create a new LRU list if necessary
+                .or_insert_with(|| LRU::with_hasher(self.hash_builder.clone())) // Create a new LRU list if necessary
                 .on_set(key.clone()); // Add key to the LRU list at the new frequency
         }
     }
@@ -154,11 +204,16 @@ impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug> LFU<K> {
     }
 }
 
-impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug> EvictionPolicy<K> for LFU<K> {
+impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug, S: BuildHasher + Clone> EvictionPolicy<K> for LFU<K, S> {
     /// Called when a value associated with a key is retrieved from the cache.
     ///
     /// Records the access of the key to adjust its frequency in the LFU cache.
+    ///
+    /// Unknown keys are ignored, neither `least_freq` nor the frequency lists are touched for them.
     fn on_get(&mut self, key: &K) {
+        if !self.map.contains_key(key) {
+            return;
+        }
         self.record_access(key);
     }
 
@@ -180,10 +235,74 @@ impl<K: Eq + std::hash::Hash + Clone + std::fmt::Debug> EvictionPolicy<K> for LF
         return self.remove_lfu_key();
     }
 
+    /// Returns the least recently used key among the least frequently used ones without evicting it.
+    fn peek_victim(&self) -> Option<&K> {
+        if self.least_freq == 0 {
+            return None;
+        }
+        self.freq_nodes.get(&self.least_freq)?.peek_lru()
+    }
+
+    /// Returns the least recently used key of the least frequency, explained with the frequency, e.g.
+    /// `"LFU freq=2 oldest"`.
+    fn explain(&self) -> Option<(K, String)> {
+        let key = self.peek_victim()?;
+        Some((key.clone(), format!("LFU freq={} oldest", self.least_freq)))
+    }
+
+    fn frequency_histogram(&self) -> Option<BTreeMap<u64, usize>> {
+        Some(LFU::frequency_histogram(self))
+    }
+
+    /// Returns the keys from the least to the most frequently used one with their frequencies, keys of the same
+    /// frequency from the least to the most recently used one.
+    fn export_state(&self) -> Option<Vec<(K, u64)>> {
+        let mut freqs: Vec<&u64> = self.freq_nodes.keys().collect();
+        freqs.sort();
+        let state = freqs.into_iter()
+            .flat_map(|freq| {
+                let keys = self.freq_nodes[freq].export_state().unwrap_or_default();
+                keys.into_iter().map(move |(key, _)| (key, *freq))
+            })
+            .collect();
+        Some(state)
+    }
+
+    /// Sets the frequencies of the keys directly, instead of replaying their accesses.
+    fn import_state(&mut self, state: Vec<(K, u64)>) {
+        for (key, freq) in state {
+            self.remove_key(key.clone());
+            let freq = freq.max(1);
+            self.map.insert(key.clone(), freq);
+            self.freq_nodes
+                .entry(freq)
+                .or_insert_with(|| LRU::with_hasher(self.hash_builder.clone()))
+                .on_set(key);
+            if self.least_freq == 0 || freq < self.least_freq {
+                self.least_freq = freq;
+            }
+        }
+    }
+
     /// Removes a key-value pair from the LFU cache based on the key.
     ///
     /// Removes the specified key and its associated value from the LFU cache.
     fn remove(&mut self, key: K) {
         self.remove_key(key);
     }
+
+    /// Drops the empty frequency lists and shrinks the capacity of the remaining maps.
+    fn shrink_to_fit(&mut self) {
+        self.freq_nodes.retain(|_, lru| lru.len() > 0);
+        for lru in self.freq_nodes.values_mut() {
+            lru.shrink_to_fit();
+        }
+        self.freq_nodes.shrink_to_fit();
+        self.map.shrink_to_fit();
+    }
+
+    /// Reserves capacity of the frequency map. The frequency lists grow as keys move between them.
+    fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
 }
@@ -22,7 +22,12 @@
 //! `Send` and `Sync`.
 //!
 
-use std::{collections::HashMap, fmt::Debug, ptr::NonNull};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt::Debug,
+    hash::BuildHasher,
+    ptr::NonNull,
+};
 
 use super::common::EvictionPolicy;
 
@@ -51,11 +56,13 @@ where
 }
 
 /// Represents an LRU (Least Recently Used) cache implementation.
-pub struct LRU<K>
+///
+/// `S` is the hasher of the internal `HashMap`, `RandomState` by default.
+pub struct LRU<K, S = RandomState>
 where
     K: Eq + std::hash::Hash + Clone,
 {
-    map: HashMap<K, NonNull<LinkedListNode<K>>>,
+    map: HashMap<K, NonNull<LinkedListNode<K>>, S>,
     head: Option<*mut LinkedListNode<K>>,
     tail: Option<*mut LinkedListNode<K>>,
 }
@@ -66,8 +73,29 @@ where
 {
     /// Creates a new instance of `LRU`.
     pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Creates a new instance of `LRU` with room for `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, S> LRU<K, S>
+where
+    K: Eq + std::hash::Hash + Clone,
+    S: BuildHasher,
+{
+    /// Creates a new instance of `LRU` which uses `hash_builder` to hash keys.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Creates a new instance of `LRU` with room for `capacity` keys which uses `hash_builder` to hash keys.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
         Self {
-            map: HashMap::new(),
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder),
             head: None,
             tail: None,
         }
@@ -136,6 +164,16 @@ where
         }
     }
 
+    /// Returns the least recently used key, the one which would be evicted next, without evicting it.
+    pub fn peek_lru(&self) -> Option<&K> {
+        self.tail.map(|tail| unsafe { &(*tail).key })
+    }
+
+    /// Returns the most recently used key.
+    pub fn peek_mru(&self) -> Option<&K> {
+        self.head.map(|head| unsafe { &(*head).key })
+    }
+
     /// Removes the least recently used node from the linked list and returns its key.
     fn remove_from_last(&mut self) -> Option<K> {
         if let Some(tail) = self.tail {
@@ -157,11 +195,12 @@ where
 
 /// Implements the `EvictionPolicy` trait for `LRU`, providing methods for managing cache
 /// evictions based on key access patterns.
-impl<K> EvictionPolicy<K> for LRU<K>
+impl<K, S> EvictionPolicy<K> for LRU<K, S>
 where
     K: Eq + std::hash::Hash + Clone + Debug,
+    S: BuildHasher,
 {
-    /// Adjusts the cache structure when a key is accessed.
+    /// Adjusts the cache structure when a key is accessed. Unknown keys are ignored.
     fn on_get(&mut self, key: &K) {
         if self.map.contains_key(key) {
             self.move_to_front(key);
@@ -183,16 +222,49 @@ where
         self.remove_from_last()
     }
 
+    /// Returns the least recently used key without evicting it.
+    fn peek_victim(&self) -> Option<&K> {
+        self.peek_lru()
+    }
+
+    /// Returns the least recently used key, explained as `"LRU tail"`.
+    fn explain(&self) -> Option<(K, String)> {
+        self.peek_lru().map(|key| (key.clone(), String::from("LRU tail")))
+    }
+
+    /// Returns the keys from the least to the most recently used one.
+    fn export_state(&self) -> Option<Vec<(K, u64)>> {
+        let mut state = Vec::with_capacity(self.map.len());
+        let mut node = self.tail;
+        while let Some(current) = node {
+            unsafe {
+                state.push(((*current).key.clone(), 0));
+                node = (*current).pre;
+            }
+        }
+        Some(state)
+    }
+
     /// Removes a specific key from the cache.
     fn remove(&mut self, key: K) {
         if let Some(removed) = self.map.remove(&key) {
             self.remove_node(&removed);
         }
     }
+
+    /// Shrinks the capacity of the key map.
+    fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// Reserves capacity of the key map.
+    fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
 }
 
 /// Enables safe concurrent access to `LRU` instances across threads when `K` is `Send`.
-unsafe impl<K: Eq + std::hash::Hash + Clone + Send> Send for LRU<K> {}
+unsafe impl<K: Eq + std::hash::Hash + Clone + Send, S: Send> Send for LRU<K, S> {}
 
 /// Enables safe concurrent access to `LRU` instances across threads when `K` is `Sync`.
-unsafe impl<K: Eq + std::hash::Hash + Clone + Sync> Sync for LRU<K> {}
+unsafe impl<K: Eq + std::hash::Hash + Clone + Sync, S: Sync> Sync for LRU<K, S> {}
@@ -7,5 +7,7 @@ pub mod fifo;  // FIFO eviction policy
 pub mod common; // Common traits and structs used by eviction policies
 pub mod lru;   // LRU eviction policy
 pub mod lfu; //LFU Eviction policy
+pub mod approx_lfu; //Approximate LFU eviction policy
+pub mod sampled_lru; //Sampled LRU eviction policy
 pub mod noevicton; //No eviction
 mod tests;
\ No newline at end of file
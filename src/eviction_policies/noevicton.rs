@@ -16,6 +16,7 @@ impl<K: Eq + std::hash::Hash + Clone> NoEviction<K> {
 }
 
 impl<K: Eq + std::hash::Hash + Clone> EvictionPolicy<K> for NoEviction<K> {
+    // nothing is tracked, so unknown keys are ignored as well.
     fn on_get(&mut self, key: &K) {
         // nothing to do.
     }
@@ -0,0 +1,161 @@
+//! Implements a sampled, approximate LRU (Least Recently Used) eviction policy for a cache.
+//!
+//! Exact `LRU` moves the node of a key to the head of a linked list on every access. `SampledLRU<K>` only
+//! stamps the key with a tick of a counter instead and evicts by sampling: on `evict`, a handful of random keys
+//! are compared and the least recently used of them is dropped, like the approximate LRU of Redis.
+//!
+//! ## Trade-off
+//! - Speed: `on_get` is a map lookup and a store, without list surgery or pointer chasing.
+//! - Accuracy: samples are drawn with replacement, so the victim is on average among the oldest
+//!   `1 / (sample_size + 1)` of the keys, the oldest sixth with `DEFAULT_SAMPLE_SIZE`, rather than the oldest
+//!   one. A key used recently is evicted only if every sample misses the keys older than it. Evictions cost
+//!   `sample_size` random lookups, so bigger samples trade eviction speed for accuracy. Once there are no more
+//!   keys than `sample_size`, all of them are compared and it evicts like `LRU`.
+//! - Recency is a tick per key rather than a position in a list, so there is no tail to show: `peek_victim`
+//!   returns `None`.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::Rng;
+
+use super::common::EvictionPolicy;
+
+/// Number of keys compared on every eviction unless configured with `with_sample_size`.
+pub const DEFAULT_SAMPLE_SIZE: usize = 5;
+
+/// Sampled LRU eviction policy, see the module documentation for the trade-off against `LRU`.
+pub struct SampledLRU<K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Keys in no particular order with the tick of their last access, to sample victims from.
+    keys: Vec<(K, u64)>,
+
+    /// Position of every key in `keys`.
+    positions: HashMap<K, usize>,
+
+    /// Tick of the last access, incremented on every access.
+    tick: u64,
+
+    /// Number of keys compared on every eviction.
+    sample_size: usize,
+}
+
+impl<K: Eq + Hash + Clone> SampledLRU<K> {
+    /// Creates a new instance of `SampledLRU` comparing `DEFAULT_SAMPLE_SIZE` keys on every eviction.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates a new instance of `SampledLRU` with room for `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_sample_size(capacity, DEFAULT_SAMPLE_SIZE)
+    }
+
+    /// Creates a new instance of `SampledLRU` with room for `capacity` keys, comparing `sample_size` keys on
+    /// every eviction, all of them once there are no more keys than that. Use it through
+    /// `CacheSyncConfig::Custom` to configure the sample size of a cache.
+    ///
+    /// Panics if `sample_size` is zero.
+    pub fn with_sample_size(capacity: usize, sample_size: usize) -> Self {
+        if sample_size == 0 {
+            panic!("sample size must be greater than zero.");
+        }
+        Self {
+            keys: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+            tick: 0,
+            sample_size,
+        }
+    }
+
+    /// Returns the number of keys tracked.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Number of keys compared on every eviction.
+    pub fn sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    /// Stamps the key at `position` as used now.
+    fn touch(&mut self, position: usize) {
+        self.tick += 1;
+        self.keys[position].1 = self.tick;
+    }
+
+    /// Removes the key at `position`, moving the last key in its place.
+    fn remove_at(&mut self, position: usize) -> K {
+        let (key, _) = self.keys.swap_remove(position);
+        self.positions.remove(&key);
+        if let Some((moved, _)) = self.keys.get(position) {
+            self.positions.insert(moved.clone(), position);
+        }
+        key
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for SampledLRU<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for SampledLRU<K> {
+    fn on_get(&mut self, key: &K) {
+        if let Some(position) = self.positions.get(key).copied() {
+            self.touch(position);
+        }
+    }
+
+    fn on_set(&mut self, key: K) {
+        let position = match self.positions.get(&key) {
+            Some(position) => *position,
+            None => {
+                self.positions.insert(key.clone(), self.keys.len());
+                self.keys.push((key, 0));
+                self.keys.len() - 1
+            },
+        };
+        self.touch(position);
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let victim = if self.sample_size >= self.keys.len() {
+            // as many samples as keys, so all of them are compared, like exact `LRU`.
+            (0..self.keys.len()).min_by_key(|x| self.keys[*x].1)?
+        } else {
+            let mut rng = rand::thread_rng();
+            (0..self.sample_size)
+                .map(|_| rng.gen_range(0..self.keys.len()))
+                .min_by_key(|x| self.keys[*x].1)?
+        };
+        Some(self.remove_at(victim))
+    }
+
+    fn remove(&mut self, key: K) {
+        if let Some(position) = self.positions.get(&key).copied() {
+            self.remove_at(position);
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.keys.shrink_to_fit();
+        self.positions.shrink_to_fit();
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.keys.reserve(additional);
+        self.positions.reserve(additional);
+    }
+}
@@ -0,0 +1,57 @@
+//! Unit tests regarding approximate LFU
+
+#[test]
+fn test_new_approx_lfu() {
+    use crate::eviction_policies::{approx_lfu::ApproxLFU, common::EvictionPolicy};
+
+    let mut lfu: ApproxLFU<i32> = ApproxLFU::new();
+    assert!(lfu.evict().is_none());
+    assert_eq!(lfu.peek_victim(), None);
+}
+
+#[test]
+fn test_evicts_least_frequent_of_samples() {
+    use crate::eviction_policies::{approx_lfu::ApproxLFU, common::EvictionPolicy};
+
+    let mut lfu: ApproxLFU<i32> = ApproxLFU::with_capacity(4);
+    lfu.on_set(1);
+    lfu.on_set(2);
+    for _ in 0..10 {
+        lfu.on_get(&1);
+    }
+    assert!(lfu.estimate(&1) >= 11);
+    // with two keys, both are sampled almost surely.
+    let mut evicted = vec![];
+    while let Some(key) = lfu.evict() {
+        evicted.push(key);
+    }
+    evicted.sort();
+    assert_eq!(evicted, vec![1, 2]);
+    assert!(lfu.is_empty());
+}
+
+#[test]
+fn test_on_get_unknown_key_and_remove() {
+    use crate::eviction_policies::{approx_lfu::ApproxLFU, common::EvictionPolicy};
+
+    let mut lfu: ApproxLFU<i32> = ApproxLFU::new();
+    lfu.on_get(&42);
+    assert_eq!(lfu.estimate(&42), 0);
+    assert!(lfu.evict().is_none());
+
+    for key in 0..10 {
+        lfu.on_set(key);
+    }
+    lfu.on_set(3);
+    assert_eq!(lfu.len(), 10);
+    lfu.remove(3);
+    lfu.remove(9);
+    lfu.remove(42);
+    assert_eq!(lfu.len(), 8);
+    let mut evicted = vec![];
+    while let Some(key) = lfu.evict() {
+        evicted.push(key);
+    }
+    evicted.sort();
+    assert_eq!(evicted, vec![0, 1, 2, 4, 5, 6, 7, 8]);
+}
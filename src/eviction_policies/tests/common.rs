@@ -0,0 +1,93 @@
+//! Unit tests shared by all the policies.
+
+#[cfg(test)]
+use crate::eviction_policies::{common::EvictionPolicy, fifo::FIFO, lfu::LFU, lru::LRU, noevicton::NoEviction};
+
+/// `on_get` on keys which were never set must neither create state nor change the eviction order.
+#[cfg(test)]
+fn assert_on_get_unknown_key_is_noop(mut policy: Box<dyn EvictionPolicy<i32>>, evicts: bool) {
+    policy.on_get(&42);
+    assert_eq!(policy.evict(), None);
+    assert_eq!(policy.peek_victim(), None);
+
+    policy.on_set(1);
+    policy.on_set(2);
+    policy.on_get(&42);
+    policy.on_get(&43);
+    if evicts {
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.evict(), Some(2));
+    }
+    assert_eq!(policy.evict(), None);
+}
+
+#[test]
+fn test_on_get_unknown_key_is_noop() {
+    assert_on_get_unknown_key_is_noop(Box::new(LRU::new()), true);
+    assert_on_get_unknown_key_is_noop(Box::new(LFU::new()), true);
+    assert_on_get_unknown_key_is_noop(Box::new(FIFO::new()), true);
+    assert_on_get_unknown_key_is_noop(Box::new(NoEviction::new()), false);
+}
+
+#[test]
+fn test_lfu_on_get_unknown_key_keeps_state() {
+    let mut lfu: LFU<i32> = LFU::new();
+    lfu.on_get(&42);
+    assert_eq!(lfu.len(), 0);
+    assert_eq!(lfu.freq_lists(), 0);
+
+    lfu.on_set(1);
+    lfu.on_get(&1);
+    lfu.on_set(2);
+    let freq_lists = lfu.freq_lists();
+    lfu.on_get(&42);
+    assert_eq!(lfu.len(), 2);
+    assert_eq!(lfu.freq_lists(), freq_lists);
+    // least frequency is still the one of key 2.
+    assert_eq!(lfu.peek_victim(), Some(&2));
+    assert_eq!(lfu.evict(), Some(2));
+}
+
+#[test]
+fn test_policies_created_with_capacity() {
+    assert_on_get_unknown_key_is_noop(Box::new(LRU::with_capacity(2)), true);
+    assert_on_get_unknown_key_is_noop(Box::new(LFU::with_capacity(2)), true);
+    assert_on_get_unknown_key_is_noop(Box::new(FIFO::with_capacity(2)), true);
+    // huge sizes used as "unbounded" must not allocate up front.
+    assert_on_get_unknown_key_is_noop(crate::config::EvictionPolicyEnum::LRU.create_policy(usize::MAX), true);
+    assert_on_get_unknown_key_is_noop(crate::config::EvictionPolicyEnum::LFU.create_policy(usize::MAX), true);
+    assert_on_get_unknown_key_is_noop(crate::config::EvictionPolicyEnum::FIFO.create_policy(usize::MAX), true);
+}
+
+/// Exports the state of `policy` after some operations and imports it into the empty `restored`, which must
+/// evict in the same order.
+#[cfg(test)]
+fn assert_state_round_trip(mut policy: Box<dyn EvictionPolicy<i32>>, mut restored: Box<dyn EvictionPolicy<i32>>) {
+    for key in 1..=4 {
+        policy.on_set(key);
+    }
+    policy.on_get(&1);
+    policy.on_get(&1);
+    policy.on_get(&3);
+    policy.remove(2);
+    restored.import_state(policy.export_state().unwrap());
+    assert_eq!(restored.export_state(), policy.export_state());
+    while let Some(victim) = policy.evict() {
+        assert_eq!(restored.evict(), Some(victim));
+    }
+    assert_eq!(restored.evict(), None);
+}
+
+#[test]
+fn test_export_and_import_state() {
+    assert_state_round_trip(Box::new(LRU::new()), Box::new(LRU::new()));
+    assert_state_round_trip(Box::new(LFU::new()), Box::new(LFU::new()));
+    assert_state_round_trip(Box::new(FIFO::new()), Box::new(FIFO::new()));
+    assert!(NoEviction::<i32>::new().export_state().is_none());
+
+    let mut lfu: LFU<i32> = LFU::new();
+    lfu.on_set(1);
+    lfu.on_get(&1);
+    lfu.on_set(2);
+    assert_eq!(lfu.export_state(), Some(vec![(2, 1), (1, 2)]));
+}
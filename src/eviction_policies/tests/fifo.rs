@@ -79,3 +79,22 @@ fn test_on_get() {
     fifo.on_get(&key1);
     // On_get should not affect the queue, no assertions needed here.
 }
+
+#[test]
+fn test_peek_victim_skips_tombstones() {
+    let mut fifo: FIFO<i32> = FIFO::new();
+    assert_eq!(fifo.peek_victim(), None);
+
+    fifo.on_set(1);
+    fifo.on_set(2);
+    fifo.on_set(3);
+    fifo.remove(1);
+    fifo.on_set(1);
+    fifo.remove(2);
+
+    assert_eq!(fifo.peek_victim(), Some(&3));
+    assert_eq!(fifo.evict(), Some(3));
+    assert_eq!(fifo.peek_victim(), Some(&1));
+    assert_eq!(fifo.evict(), Some(1));
+    assert_eq!(fifo.peek_victim(), None);
+}
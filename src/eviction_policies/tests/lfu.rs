@@ -76,3 +76,51 @@ fn test_evict_with_multiple_keys() {
     assert_eq!(lfu.evict(), Some(key4));
     assert_eq!(lfu.evict(), None);
 }
+
+#[test]
+fn test_peek_victim() {
+    let mut lfu: LFU<i32> = LFU::new();
+    assert_eq!(lfu.peek_victim(), None);
+
+    lfu.on_set(1);
+    lfu.on_set(2);
+    lfu.on_set(3);
+    lfu.on_get(&1);
+    lfu.on_get(&3);
+
+    assert_eq!(lfu.peek_victim(), Some(&2));
+    assert_eq!(lfu.evict(), Some(2));
+    assert_eq!(lfu.peek_victim(), Some(&1));
+    assert_eq!(lfu.evict(), Some(1));
+}
+
+#[test]
+fn test_shrink_to_fit_keeps_order() {
+    let mut lfu: LFU<i32> = LFU::new();
+    for i in 0..100 {
+        lfu.on_set(i);
+        lfu.on_get(&i);
+    }
+    for i in 2..100 {
+        lfu.remove(i);
+    }
+    lfu.on_get(&0);
+    lfu.shrink_to_fit();
+
+    assert_eq!(lfu.evict(), Some(1));
+    assert_eq!(lfu.evict(), Some(0));
+    assert_eq!(lfu.evict(), None);
+}
+
+#[test]
+fn test_frequencies_beyond_32_bits() {
+    let mut lfu: LFU<i32> = LFU::new();
+    let freq: u64 = u32::MAX as u64 + 1;
+    lfu.import_state(vec![(1, freq), (2, freq + 1)]);
+    lfu.on_get(&1);
+    lfu.on_get(&1);
+
+    assert_eq!(lfu.export_state(), Some(vec![(2, freq + 1), (1, freq + 2)]));
+    assert_eq!(lfu.frequency_histogram().into_iter().collect::<Vec<(u64, usize)>>(), vec![(freq + 1, 1), (freq + 2, 1)]);
+    assert_eq!(lfu.evict(), Some(2));
+}
@@ -75,3 +75,20 @@ fn test_evict_with_multiple_keys() {
     assert_eq!(lru.evict(), Some(key4));
     assert_eq!(lru.evict(), None);
 }
+
+#[test]
+fn test_peek_lru_and_mru() {
+    let mut lru: LRU<i32> = LRU::new();
+    assert_eq!(lru.peek_lru(), None);
+    assert_eq!(lru.peek_mru(), None);
+
+    lru.on_set(1);
+    lru.on_set(2);
+    lru.on_set(3);
+    lru.on_get(&1);
+
+    assert_eq!(lru.peek_lru(), Some(&2));
+    assert_eq!(lru.peek_mru(), Some(&1));
+    assert_eq!(lru.peek_victim(), Some(&2));
+    assert_eq!(lru.evict(), Some(2));
+}
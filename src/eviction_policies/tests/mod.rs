@@ -1,4 +1,7 @@
 //! Contains Unit tests...
 mod lru;
 mod lfu;
-mod fifo;
\ No newline at end of file
+mod fifo;
+mod common;
+mod approx_lfu;
+mod sampled_lru;
\ No newline at end of file
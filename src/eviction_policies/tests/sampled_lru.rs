@@ -0,0 +1,52 @@
+//! Unit tests regarding sampled LRU
+
+#[test]
+fn test_new_sampled_lru() {
+    use crate::eviction_policies::{common::EvictionPolicy, sampled_lru::{SampledLRU, DEFAULT_SAMPLE_SIZE}};
+
+    let mut lru: SampledLRU<i32> = SampledLRU::new();
+    assert!(lru.evict().is_none());
+    assert_eq!(lru.peek_victim(), None);
+    assert_eq!(lru.sample_size(), DEFAULT_SAMPLE_SIZE);
+}
+
+#[test]
+fn test_evicts_least_recently_used_of_samples() {
+    use crate::eviction_policies::{common::EvictionPolicy, sampled_lru::SampledLRU};
+
+    // comparing all the keys, so that it evicts like exact LRU.
+    let mut lru: SampledLRU<i32> = SampledLRU::with_sample_size(4, 4);
+    for key in 0..4 {
+        lru.on_set(key);
+    }
+    lru.on_get(&0);
+    lru.on_set(2);
+    lru.on_get(&42);
+    assert_eq!(lru.len(), 4);
+    let mut evicted = vec![];
+    while let Some(key) = lru.evict() {
+        evicted.push(key);
+    }
+    assert_eq!(evicted, vec![1, 3, 0, 2]);
+    assert!(lru.is_empty());
+}
+
+#[test]
+fn test_remove() {
+    use crate::eviction_policies::{common::EvictionPolicy, sampled_lru::SampledLRU};
+
+    let mut lru: SampledLRU<i32> = SampledLRU::new();
+    for key in 0..10 {
+        lru.on_set(key);
+    }
+    lru.remove(3);
+    lru.remove(9);
+    lru.remove(42);
+    assert_eq!(lru.len(), 8);
+    let mut evicted = vec![];
+    while let Some(key) = lru.evict() {
+        evicted.push(key);
+    }
+    evicted.sort();
+    assert_eq!(evicted, vec![0, 1, 2, 4, 5, 6, 7, 8]);
+}
@@ -90,7 +90,8 @@
 //!         aof_config: Some(EvictionAOFConfig {
 //!             folder: String::from("./data"), //folder in which persistent file should be written.
 //!             cache_name: String::from("async_lof_cache"), //Unique cache name as with same name file will be created.
-//!             flush_time: Some(5000) //After every 5000 milliseconds data will be flushed to disk.
+//!             flush_time: Some(5000), //After every 5000 milliseconds data will be flushed to disk.
+//!             persist_read_ops: true //whether to store reads also, `false` makes reads faster but eviction order may differ after restart.
 //!         })
 //!     })).await;
 //!
@@ -176,5 +177,10 @@ pub mod cache; // Core functionalities for creating and managing in-memory cache
 pub mod cache_events; //Event manager which do things upon each event in cache.
 pub mod common; // Common types and utilities used throughout the library
 pub mod config;
+pub mod error; // Errors returned by cache operations
 pub mod eviction_policies; // Implementations of different eviction policies for cache management
+#[cfg(all(feature = "mmap", unix))]
+pub mod mmap_aof; // Memory-mapped append only files
+#[cfg(feature = "slab")]
+mod slab; // Slab storage of the entries of caches
 mod tests; //Contains different configuration structs and enums.
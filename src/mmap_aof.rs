@@ -0,0 +1,334 @@
+//! Memory-mapped `AOF` writer for very high write rates, behind the `mmap` feature on unix.
+//!
+//! `AOF` appends every batch of records with a `write_all` system call. `MmapAOF` copies the records into a
+//! pre-sized region of the file mapped into memory instead, so that appending is a memory copy, and makes them
+//! durable with `sync`, which `MmapAOFSubscriber` calls periodically.
+//!
+//! ## File layout
+//! - A header of `HEADER_BYTES`: the magic bytes `SINEMMAP` followed by the committed length of the records as
+//!   a little endian `u64`.
+//! - The records, encoded the same way as in `AOF`, followed by unused room.
+//!
+//! The committed length is only updated by `sync`, once the records are on the disk, so a crash leaves the
+//! records committed by the last `sync` and never a partial record. Records appended after it are lost, like
+//! the records waiting for the periodic flush of `AOF`.
+//!
+//! When the region fills up, the file is grown to twice its size, or more for a bigger batch, and mapped again.
+//! Iterators keep the previous mapping alive until they are dropped.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::aof::{AOFIterator, ValueTransform, AOF};
+use crate::common::{AOFRecord, AofStats, FlushHealth};
+
+/// Magic bytes at the start of the file.
+const MAGIC: &[u8; 8] = b"SINEMMAP";
+
+/// Bytes of the header: the magic bytes and the committed length.
+pub const HEADER_BYTES: usize = 16;
+
+/// Region of a file mapped into memory, unmapped on drop.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// bytes are only written through `MmapAOF`, which needs `&mut self` to do so, past the bytes handed out to
+// iterators.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, len: usize) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr: ptr as *mut u8, len })
+    }
+
+    /// Copies `bytes` at `offset`, the caller checks that they fit.
+    fn write(&self, offset: usize, bytes: &[u8]) {
+        assert!(offset + bytes.len() <= self.len);
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(offset), bytes.len()) };
+    }
+
+    fn bytes(&self, start: usize, end: usize) -> &[u8] {
+        assert!(start <= end && end <= self.len);
+        unsafe { std::slice::from_raw_parts(self.ptr.add(start), end - start) }
+    }
+
+    /// Waits until the bytes in `start..end` are on the disk.
+    fn sync(&self, start: usize, end: usize) -> io::Result<()> {
+        // `msync` takes an address aligned to a page.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let start = start - start % page;
+        if unsafe { libc::msync(self.ptr.add(start) as *mut libc::c_void, end - start, libc::MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+/// Committed records of a mapping read by `AOFIterator`, which keep the mapping alive.
+pub(crate) struct MappedBytes {
+    mapping: Arc<Mapping>,
+    start: usize,
+    end: usize,
+}
+
+impl AsRef<[u8]> for MappedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.mapping.bytes(self.start, self.end)
+    }
+}
+
+/// Append-only file written through a memory mapping, see the module documentation.
+pub struct MmapAOF {
+    file: File,
+    mapping: Arc<Mapping>,
+    /// End of the records appended, committed or not.
+    len: usize,
+    /// End of the records committed by the last `sync`.
+    committed: usize,
+    value_transform: Option<ValueTransform>,
+}
+
+impl MmapAOF {
+    /// Opens the file at `path`, creating it with room for `initial_bytes` of records if it does not exist.
+    /// Records past the committed length, e.g. appended after the last `sync` before a crash, are discarded.
+    ///
+    /// Returns an error of kind `InvalidData` if the file exists but is not a memory-mapped `AOF`.
+    pub fn open(path: &str, initial_bytes: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let created = file.metadata()?.len() == 0;
+        if created {
+            file.set_len((HEADER_BYTES + initial_bytes.max(1)) as u64)?;
+        }
+        let file_bytes = file.metadata()?.len() as usize;
+        let invalid = |detail: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{} {}", path, detail));
+        if file_bytes < HEADER_BYTES {
+            return Err(invalid("is not a memory-mapped AOF file"));
+        }
+        let mapping = Mapping::new(&file, file_bytes)?;
+        if created {
+            mapping.write(0, MAGIC);
+            mapping.write(MAGIC.len(), &0u64.to_le_bytes());
+        } else if mapping.bytes(0, MAGIC.len()) != MAGIC {
+            return Err(invalid("is not a memory-mapped AOF file"));
+        }
+        let committed = u64::from_le_bytes(mapping.bytes(MAGIC.len(), HEADER_BYTES).try_into().unwrap());
+        let committed = HEADER_BYTES.saturating_add(committed as usize);
+        if committed > file_bytes {
+            return Err(invalid("has a committed length past the end of the file"));
+        }
+        Ok(Self { file, mapping: Arc::new(mapping), len: committed, committed, value_transform: None })
+    }
+
+    /// Transforms the values of the records appended from now on and of the records read, see
+    /// `AOF::set_value_transform`.
+    pub fn set_value_transform(&mut self, value_transform: Option<ValueTransform>) {
+        self.value_transform = value_transform;
+    }
+
+    /// Encodes the records like `AOF` and appends them, see `append_bytes`.
+    pub async fn append<K, V>(&mut self, records: &[AOFRecord<K, V>]) -> io::Result<()>
+    where
+        for<'de> K: Deserialize<'de> + Serialize,
+        for<'de> V: Deserialize<'de> + Serialize + 'static,
+    {
+        let mut bytes = vec![];
+        for r in records {
+            bytes.extend(AOF::encode_record(self.value_transform.as_ref(), r.operation.clone(), &r.key, &r.value, r.expires_at, r.cache_id, r.version, r.origin_ts).await);
+        }
+        self.append_bytes(&bytes)
+    }
+
+    /// Copies already encoded records into the mapped region, growing the file if they don't fit. They are
+    /// durable, and read by `into_iter`, only after the next `sync`.
+    pub fn append_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let end = self.len + bytes.len();
+        if end > self.mapping.len {
+            self.grow(end)?;
+        }
+        self.mapping.write(self.len, bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Grows the file to at least `min_bytes`, doubling it, and maps it again.
+    fn grow(&mut self, min_bytes: usize) -> io::Result<()> {
+        let file_bytes = min_bytes.max(self.mapping.len * 2);
+        self.file.set_len(file_bytes as u64)?;
+        self.mapping = Arc::new(Mapping::new(&self.file, file_bytes)?);
+        Ok(())
+    }
+
+    /// Writes the appended records to disk and then commits them by updating the length in the header, so that
+    /// a crash in between leaves the records committed before.
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.len == self.committed {
+            return Ok(());
+        }
+        self.mapping.sync(self.committed, self.len)?;
+        self.mapping.write(MAGIC.len(), &((self.len - HEADER_BYTES) as u64).to_le_bytes());
+        self.mapping.sync(0, HEADER_BYTES)?;
+        self.committed = self.len;
+        Ok(())
+    }
+
+    /// Bytes of the records committed by the last `sync`.
+    pub fn committed_bytes(&self) -> u64 {
+        (self.committed - HEADER_BYTES) as u64
+    }
+
+    /// Bytes of the records appended since the last `sync`.
+    pub fn pending_bytes(&self) -> u64 {
+        (self.len - self.committed) as u64
+    }
+
+    /// Size of the file in bytes, including the unused room.
+    pub fn file_bytes(&self) -> u64 {
+        self.mapping.len as u64
+    }
+
+    /// Returns an iterator over the committed records, reading the mapped region directly.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn into_iter(&self) -> AOFIterator {
+        let bytes = MappedBytes { mapping: self.mapping.clone(), start: HEADER_BYTES, end: self.committed };
+        AOFIterator::from_mapped(bytes, self.value_transform.clone())
+    }
+}
+
+/// Persists the records of a cache to a `MmapAOF`, see `AsyncCacheBuilder::mmap_aof`.
+///
+/// Records are copied into the mapped region as they come and synced to disk every `flush_time` milliseconds,
+/// or right away in case of `None`. If the file can not grow, e.g. because the disk is full, the records are
+/// dropped and the error is reported by `flush_health`.
+pub struct MmapAOFSubscriber<K, V> {
+    aof: Mutex<MmapAOF>,
+    flush_time: Option<u32>,
+    /// Number of records appended since the last `sync`.
+    pending: AtomicU64,
+    health: std::sync::Mutex<FlushHealth>,
+    /// Records replayed from the file plus the ones recorded since, see `aof_stats`.
+    records: AtomicU64,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> MmapAOFSubscriber<K, V>
+where
+    for<'de> K: Deserialize<'de> + Serialize + Send + Sync + 'static,
+    for<'de> V: Deserialize<'de> + Serialize + Send + Sync + 'static,
+{
+    /// Opens the file at `path`, see `MmapAOF::open`, and starts the periodic sync if `flush_time` is set. The
+    /// task holds a weak reference only, so it stops once the subscriber is dropped.
+    pub fn open(path: &str, initial_bytes: usize, flush_time: Option<u32>, value_transform: Option<ValueTransform>) -> io::Result<Arc<Self>> {
+        let mut aof = MmapAOF::open(path, initial_bytes)?;
+        aof.set_value_transform(value_transform);
+        let instance = Arc::new(Self {
+            aof: Mutex::new(aof),
+            flush_time,
+            pending: AtomicU64::new(0),
+            health: std::sync::Mutex::new(FlushHealth::default()),
+            records: AtomicU64::new(0),
+            _marker: PhantomData,
+        });
+        if let Some(flush_time) = flush_time {
+            let subscriber = Arc::downgrade(&instance);
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(flush_time as u64)).await;
+                    match subscriber.upgrade() {
+                        Some(subscriber) => {
+                            let _ = subscriber.sync().await;
+                        },
+                        None => break,
+                    }
+                }
+            });
+        }
+        Ok(instance)
+    }
+
+    /// Appends the records, syncing them right away if `sync` or if there is no `flush_time`.
+    pub async fn append(&self, records: Vec<AOFRecord<K, V>>, sync: bool) -> io::Result<()> {
+        let mut aof = self.aof.lock().await;
+        let mut result = aof.append(&records).await;
+        if result.is_ok() {
+            self.records.fetch_add(records.len() as u64, Ordering::Relaxed);
+            self.pending.fetch_add(records.len() as u64, Ordering::Relaxed);
+            if sync || self.flush_time.is_none() {
+                result = Self::sync_locked(&mut aof, &self.pending);
+            }
+        }
+        if result.is_err() || sync || self.flush_time.is_none() {
+            self.health.lock().unwrap().record(&result);
+        }
+        result
+    }
+
+    /// Appends a `PolicyState` record with `state` and commits it, see `AOFSubscriber::on_policy_state`.
+    pub async fn on_policy_state(&self, state: Vec<u8>) -> io::Result<()> {
+        let mut aof = self.aof.lock().await;
+        let bytes = AOF::encode_policy_state(aof.value_transform.as_ref(), None, state).await;
+        let mut result = aof.append_bytes(&bytes);
+        if result.is_ok() {
+            self.records.fetch_add(1, Ordering::Relaxed);
+            self.pending.fetch_add(1, Ordering::Relaxed);
+            result = Self::sync_locked(&mut aof, &self.pending);
+        }
+        self.health.lock().unwrap().record(&result);
+        result
+    }
+
+    /// Commits the appended records, see `MmapAOF::sync`.
+    pub async fn sync(&self) -> io::Result<()> {
+        let result = Self::sync_locked(&mut *self.aof.lock().await, &self.pending);
+        self.health.lock().unwrap().record(&result);
+        result
+    }
+
+    fn sync_locked(aof: &mut MmapAOF, pending: &AtomicU64) -> io::Result<()> {
+        aof.sync()?;
+        pending.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns an iterator over the committed records.
+    #[allow(clippy::wrong_self_convention)]
+    pub async fn into_iter(&self) -> AOFIterator {
+        self.aof.lock().await.into_iter()
+    }
+
+    /// Result of the last sync and the number of records waiting for the next one.
+    pub async fn flush_health(&self) -> FlushHealth {
+        FlushHealth { pending: self.pending.load(Ordering::Relaxed) as usize, ..self.health.lock().unwrap().clone() }
+    }
+
+    /// Size of the file, including the unused room, and approximate number of records in it.
+    pub async fn aof_stats(&self) -> AofStats {
+        AofStats { file_bytes: self.aof.lock().await.file_bytes(), approx_records: self.records.load(Ordering::Relaxed) }
+    }
+
+    /// Sets the number of records in the file, e.g. after they have been counted while replaying.
+    pub fn set_approx_records(&self, records: u64) {
+        self.records.store(records, Ordering::Relaxed);
+    }
+}
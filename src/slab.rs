@@ -0,0 +1,206 @@
+//! Storage of the entries of `Cache` in a slab, behind the `slab` feature.
+//!
+//! `SlabMap` keeps the entries in a `Vec` of slots and maps keys to the index of their slot, so that the
+//! entries lie next to each other instead of inside the buckets of the `HashMap`, and the `HashMap` only holds
+//! keys and indices. Slots of removed entries are reused by the next inserts.
+//!
+//! It has the subset of the `HashMap` API which `Cache` uses, with the same semantics, so that `Cache` works the
+//! same with either storage.
+
+use std::borrow::Borrow;
+use std::collections::{hash_map, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+use crate::common::CacheEntry;
+
+/// Map of keys to entries stored in a slab, see the module documentation.
+pub(crate) struct SlabMap<K, V, S> {
+    /// Index of the slot of every key.
+    index: HashMap<K, usize, S>,
+    /// Entries, `None` for free slots.
+    slots: Vec<Option<CacheEntry<V>>>,
+    /// Indices of the free slots, reused by `insert` before the slab grows.
+    free: Vec<usize>,
+}
+
+impl<K, V, S> SlabMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub(crate) fn with_hasher(hash_builder: S) -> Self {
+        SlabMap { index: HashMap::with_hasher(hash_builder), slots: vec![], free: vec![] }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub(crate) fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
+
+    pub(crate) fn get<Q>(&self, key: &Q) -> Option<&CacheEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = *self.index.get(key)?;
+        self.slots[slot].as_ref()
+    }
+
+    pub(crate) fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut CacheEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = *self.index.get(key)?;
+        self.slots[slot].as_mut()
+    }
+
+    /// Mutable references to the entries of several keys at once, `None` if any key is absent or if two keys
+    /// are the same entry.
+    pub(crate) fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut CacheEntry<V>; N]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut slots = [0; N];
+        for (slot, key) in slots.iter_mut().zip(keys) {
+            *slot = *self.index.get(key)?;
+        }
+        for i in 0..N {
+            if slots[..i].contains(&slots[i]) || self.slots[slots[i]].is_none() {
+                return None;
+            }
+        }
+        // Every reference is derived from the same base pointer: indexing the `Vec` again would borrow all of
+        // its slots and invalidate the references taken before.
+        let base = self.slots.as_mut_ptr();
+        // SAFETY: the slots are distinct, in bounds and used, and the slab is borrowed mutably by the returned
+        // references.
+        Some(slots.map(|slot| unsafe { (*base.add(slot)).as_mut().unwrap_unchecked() }))
+    }
+
+    pub(crate) fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &CacheEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, slot) = self.index.get_key_value(key)?;
+        Some((key, self.slots[*slot].as_ref()?))
+    }
+
+    /// Inserts the entry, in the slot of the previous entry of the key if any, and returns the previous entry.
+    pub(crate) fn insert(&mut self, key: K, entry: CacheEntry<V>) -> Option<CacheEntry<V>> {
+        if let Some(slot) = self.index.get(&key) {
+            return self.slots[*slot].replace(entry);
+        }
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some(entry);
+                slot
+            },
+            None => {
+                self.slots.push(Some(entry));
+                self.slots.len() - 1
+            },
+        };
+        self.index.insert(key, slot);
+        None
+    }
+
+    pub(crate) fn remove<Q>(&mut self, key: &Q) -> Option<CacheEntry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, entry)| entry)
+    }
+
+    pub(crate) fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, CacheEntry<V>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, slot) = self.index.remove_entry(key)?;
+        self.free.push(slot);
+        Some((key, self.slots[slot].take()?))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &CacheEntry<V>)> {
+        self.index.iter().filter_map(|(key, slot)| Some((key, self.slots[*slot].as_ref()?)))
+    }
+
+    pub(crate) fn keys(&self) -> hash_map::Keys<'_, K, usize> {
+        self.index.keys()
+    }
+
+    /// Removes all the entries and yields them. All the entries are removed even if the iterator is dropped
+    /// before the end, but unlike `HashMap::drain`, the slab itself is not kept for reuse.
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = (K, CacheEntry<V>)> + '_ {
+        self.free.clear();
+        let mut slots = std::mem::take(&mut self.slots);
+        self.index.drain().filter_map(move |(key, slot)| Some((key, slots[slot].take()?)))
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.index.reserve(additional);
+        self.slots.reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// Releases the unused capacity, including free slots at the end of the slab. Free slots between used ones
+    /// are kept, as entries are never moved.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        while self.slots.last().is_some_and(|x| x.is_none()) {
+            self.slots.pop();
+        }
+        let len = self.slots.len();
+        self.free.retain(|slot| *slot < len);
+        self.index.shrink_to_fit();
+        self.slots.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    /// Number of slots in the slab, used or free.
+    #[cfg(test)]
+    pub(crate) fn slots(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+impl<K, V, S> IntoIterator for SlabMap<K, V, S> {
+    type Item = (K, CacheEntry<V>);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { index: self.index.into_iter(), slots: self.slots }
+    }
+}
+
+/// Iterator over the entries of a consumed `SlabMap`.
+pub(crate) struct IntoIter<K, V> {
+    index: hash_map::IntoIter<K, usize>,
+    slots: Vec<Option<CacheEntry<V>>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, CacheEntry<V>);
+
+    fn next(&mut self) -> Option<(K, CacheEntry<V>)> {
+        let (key, slot) = self.index.next()?;
+        Some((key, self.slots[slot].take()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.index.size_hint()
+    }
+}
@@ -1 +1,4 @@
-mod test_aof;
\ No newline at end of file
+mod test_aof;
+mod test_error;
+#[cfg(feature = "slab")]
+mod test_slab;
\ No newline at end of file
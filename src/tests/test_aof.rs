@@ -29,8 +29,12 @@ async fn test_aof_on_event_put() -> Result<(), tokio::io::Error> {
         key: test_key.clone(),
         value: Some(test_value.clone()),
         operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
     };
-    ao_file.on_event(record, true).await;
+    ao_file.on_event(record, true).await?;
 
     let test_key1 = String::from("key2");
     let test_value1 = String::from("value2");
@@ -39,8 +43,12 @@ async fn test_aof_on_event_put() -> Result<(), tokio::io::Error> {
         key: test_key1.clone(),
         value: Some(test_value1.clone()),
         operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
     };
-    ao_file.on_event(record, true).await;
+    ao_file.on_event(record, true).await?;
 
     let mut total_records = 0;
     if let Ok(mut record_iter) = ao_file.into_iter().await {
@@ -94,16 +102,24 @@ async fn test_aof_random_ops_and_iteration_with_write_and_flush() -> Result<(),
             key: key.clone(),
             value: value.clone(),
             operation: operations[op].clone(),
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
         });
         aof.on_event(
             AOFRecord {
                 key: key.clone(),
                 value: value.clone(),
                 operation: operations[op].clone(),
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None,
             },
             true,
         )
-        .await;
+        .await?;
     }
 
     // Read records from AOF and check order
@@ -150,18 +166,26 @@ async fn test_aof_random_ops_and_iteration_with_single_flush() -> Result<(), tok
             key: key.clone(),
             value: value.clone(),
             operation: operations[op].clone(),
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
         });
         aof.on_event(
             AOFRecord {
                 key: key.clone(),
                 value: value.clone(),
                 operation: operations[op].clone(),
+                expires_at: None,
+                cache_id: None,
+                version: None,
+                origin_ts: None,
             },
             false,
         )
-        .await;
+        .await?;
     }
-    aof.flush().await;
+    aof.flush().await?;
 
     // Read records from AOF and check order
     let mut iter = aof.into_iter().await.unwrap();
@@ -208,9 +232,13 @@ async fn test_aof_random_ops_and_iteration_with_multi() -> Result<(), tokio::io:
             key: key.clone(),
             value: value.clone(),
             operation: operations[op].clone(),
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
         });
     }
-    aof.on_event_multi(written_records.clone(), true).await;
+    aof.on_event_multi(written_records.clone(), true).await?;
 
     // Read records from AOF and check order
     let mut iter = aof.into_iter().await.unwrap();
@@ -260,6 +288,10 @@ async fn test_aof_subscriber_with_disk_and_flush_time() -> Result<(), tokio::io:
             key: key,
             value: value,
             operation: operations[op].clone(),
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
         };
         written_records.push(r.clone());
         subscriber.on_event(r).await;
@@ -315,6 +347,10 @@ async fn test_aof_subscriber_with_disk() -> Result<(), tokio::io::Error> {
             key: key,
             value: value,
             operation: operations[op].clone(),
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
         };
         written_records.push(r.clone());
         subscriber.on_event(r).await;
@@ -332,3 +368,387 @@ async fn test_aof_subscriber_with_disk() -> Result<(), tokio::io::Error> {
     tokio::fs::remove_file(format!("{}.dat", test_file)).await?;
     Ok(())
 }
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_aof_subscriber_keeps_records_when_flush_fails() -> Result<(), tokio::io::Error> {
+    let folder = "test_aof8";
+    let _ = tokio::fs::remove_dir_all(folder).await;
+    tokio::fs::create_dir_all(folder).await?;
+    // every write to /dev/full fails with "no space left on device".
+    tokio::fs::symlink("/dev/full", format!("{}/full.dat", folder)).await?;
+    let subscriber: AOFSubscriber<String, String> = AOFSubscriber::new(
+        Some(String::from(folder)),
+        Some(String::from("full")),
+        Some(100)
+    ).await;
+
+    subscriber.on_event(AOFRecord {
+        key: String::from("key1"),
+        value: Some(String::from("value1")),
+        operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
+    }).await;
+    subscriber.flush_to_disk().await;
+    let health = subscriber.flush_health().await;
+    assert!(health.last_error.is_some());
+    assert_eq!(health.last_ok, None);
+    assert_eq!(health.pending, 1);
+
+    tokio::fs::remove_dir_all(folder).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_aof_bytes_values_are_written_raw() -> Result<(), tokio::io::Error> {
+    let test_file = "test_aof9.dat";
+    let _ = tokio::fs::remove_file(test_file).await; //clean the file if exists
+    let aof = AOF::new(test_file.to_string()).await;
+
+    let mut rng = thread_rng();
+    let values: Vec<Vec<u8>> = (0..4).map(|_| (0..1024 * 1024).map(|_| rng.gen()).collect()).collect();
+    for (i, value) in values.iter().enumerate() {
+        aof.on_event(AOFRecord {
+            key: format!("key{}", i),
+            value: Some(value.clone()),
+            operation: Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
+        }, true).await?;
+    }
+
+    let raw_size: usize = values.iter().map(|x| x.len()).sum();
+    let json_size: usize = values.iter().map(|x| serde_json::to_vec(x).unwrap().len()).sum();
+    let file_size = tokio::fs::metadata(test_file).await?.len() as usize;
+    assert!(file_size < raw_size + 1024);
+    assert!(file_size * 3 < json_size);
+
+    let mut iter = aof.into_iter().await?;
+    for (i, value) in values.iter().enumerate() {
+        let record = iter.next::<String, Vec<u8>>().await?.unwrap();
+        assert_eq!(record.key, format!("key{}", i));
+        assert_eq!(record.value.as_ref(), Some(value));
+    }
+    assert!(iter.next::<String, Vec<u8>>().await?.is_none());
+
+    // raw bytes can't be read as another type.
+    let mut iter = aof.into_iter().await?;
+    assert!(iter.next::<String, String>().await.is_err());
+
+    tokio::fs::remove_file(test_file).await?;
+    Ok(())
+}
+
+#[test]
+fn test_flush_interval_jitter() {
+    use crate::aof::jittered_interval;
+
+    assert_eq!(jittered_interval(100, 0.0), tokio::time::Duration::from_millis(100));
+    let intervals: Vec<_> = (0..200).map(|_| jittered_interval(100, 0.2).as_millis()).collect();
+    assert!(intervals.iter().all(|x| (80..=120).contains(x)));
+    assert!(intervals.iter().any(|x| *x != intervals[0]));
+}
+
+/// Error of the next record, which must be a corruption, returning its offset.
+#[cfg(test)]
+async fn corruption_offset(iter: &mut crate::aof::AOFIterator) -> u64 {
+    let Err(e) = iter.next::<String, String>().await else {
+        panic!("expected corruption");
+    };
+    assert_eq!(e.kind(), tokio::io::ErrorKind::InvalidData);
+    match e.into_inner().unwrap().downcast::<crate::error::CacheError>().map(|x| *x) {
+        Ok(crate::error::CacheError::Corruption { offset, .. }) => offset,
+        other => panic!("expected corruption, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_aof_rejects_corrupted_lengths() -> Result<(), tokio::io::Error> {
+    let test_file = "test_aof10.dat";
+    let _ = tokio::fs::remove_file(test_file).await; //clean the file if exists
+    let aof = AOF::new(test_file.to_string()).await;
+    aof.on_event(AOFRecord {
+        key: String::from("key"),
+        value: Some(String::from("value")),
+        operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
+    }, true).await?;
+    let valid = tokio::fs::read(test_file).await?;
+
+    // value length of `u64::MAX` after a valid record.
+    let mut bytes = valid.clone();
+    bytes.extend([1u8]);
+    bytes.extend(3u32.to_le_bytes());
+    bytes.extend(b"key");
+    bytes.extend(u64::MAX.to_le_bytes());
+    tokio::fs::write(test_file, &bytes).await?;
+    let mut iter = aof.into_iter().await?;
+    assert!(iter.next::<String, String>().await?.is_some());
+    assert_eq!(corruption_offset(&mut iter).await, valid.len() as u64);
+
+    // key length past the end of the file.
+    let mut bytes = vec![2u8];
+    bytes.extend(u32::MAX.to_le_bytes());
+    tokio::fs::write(test_file, &bytes).await?;
+    assert_eq!(corruption_offset(&mut aof.into_iter().await?).await, 0);
+
+    // invalid operation and truncated record.
+    tokio::fs::write(test_file, [9u8]).await?;
+    assert_eq!(corruption_offset(&mut aof.into_iter().await?).await, 0);
+    tokio::fs::write(test_file, &valid[..valid.len() - 1]).await?;
+    assert_eq!(corruption_offset(&mut aof.into_iter().await?).await, 0);
+
+    // a valid record bigger than the limit.
+    tokio::fs::write(test_file, &valid).await?;
+    let mut iter = aof.into_iter().await?;
+    iter.set_max_record_bytes(4);
+    assert_eq!(corruption_offset(&mut iter).await, 0);
+
+    tokio::fs::remove_file(test_file).await?;
+    Ok(())
+}
+
+/// Fuzzes the parser with random bytes and with valid records with random bytes changed or cut off: it must
+/// neither panic nor allocate more than the file holds.
+#[tokio::test]
+async fn test_aof_parser_survives_random_bytes() -> Result<(), tokio::io::Error> {
+    let test_file = "test_aof11.dat";
+    let _ = tokio::fs::remove_file(test_file).await; //clean the file if exists
+    let aof = AOF::new(test_file.to_string()).await;
+    for i in 0..20 {
+        aof.on_event(AOFRecord {
+            key: format!("key{}", i),
+            value: Some(format!("value{}", i)),
+            operation: if i % 3 == 0 { Operation::Remove } else { Operation::Put },
+            expires_at: Some(i),
+            cache_id: None,
+            version: Some(i),
+            origin_ts: None,
+        }, true).await?;
+    }
+    let valid = tokio::fs::read(test_file).await?;
+
+    let mut rng = thread_rng();
+    for _ in 0..500 {
+        let bytes: Vec<u8> = if rng.gen_bool(0.5) {
+            (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect()
+        } else {
+            let mut bytes = valid[..rng.gen_range(0..=valid.len())].to_vec();
+            for _ in 0..rng.gen_range(0..4) {
+                if !bytes.is_empty() {
+                    let index = rng.gen_range(0..bytes.len());
+                    bytes[index] = rng.gen();
+                }
+            }
+            bytes
+        };
+        tokio::fs::write(test_file, &bytes).await?;
+        let mut iter = aof.into_iter().await?;
+        let mut records = 0;
+        while let Ok(Some(_)) = iter.next::<String, String>().await {
+            records += 1;
+        }
+        assert!(records <= 20);
+        assert!(iter.bytes_read() <= bytes.len() as u64);
+    }
+
+    tokio::fs::remove_file(test_file).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_aof_open_reader_reads_typed_records() -> Result<(), tokio::io::Error> {
+    use crate::error::CacheError;
+    use tokio::io::AsyncWriteExt;
+
+    let test_file = "test_aof_open_reader_reads_typed_records.dat";
+    let _ = tokio::fs::remove_file(test_file).await;
+    // opening for reading neither creates the file nor appends to it.
+    assert!(matches!(AOF::open_reader(test_file).await, Err(CacheError::Io(_))));
+    assert!(tokio::fs::metadata(test_file).await.is_err());
+
+    let aof = AOF::new(test_file.to_string()).await;
+    for (key, value) in [("key1", serde_json::json!(1)), ("key2", serde_json::json!("two")), ("key3", serde_json::json!(3))] {
+        let record = AOFRecord {
+            key: String::from(key),
+            value: Some(value),
+            operation: Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
+        };
+        aof.on_event(record, true).await?;
+    }
+    tokio::fs::OpenOptions::new().append(true).open(test_file).await?.write_all(&[1, 2]).await?;
+    let file_bytes = tokio::fs::metadata(test_file).await?.len();
+
+    // "two" can not be decoded as u32, the records after it are read anyway.
+    let mut records = AOF::open_reader(test_file).await.unwrap().records::<String, u32>();
+    let record = records.next().await.unwrap().unwrap();
+    assert_eq!((record.key, record.value, record.operation), (String::from("key1"), Some(1), Operation::Put));
+    assert!(matches!(records.next().await, Some(Err(CacheError::Serialize(_)))));
+    assert_eq!(records.next().await.unwrap().unwrap().value, Some(3));
+    assert!(matches!(records.next().await, Some(Err(CacheError::Corruption { .. }))));
+    assert_eq!(records.bytes_read(), file_bytes - 2);
+    assert!(records.next().await.is_none());
+    assert_eq!(tokio::fs::metadata(test_file).await?.len(), file_bytes);
+
+    tokio::fs::remove_file(test_file).await?;
+    Ok(())
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[tokio::test]
+async fn test_mmap_aof_replays_committed_records_only() -> Result<(), tokio::io::Error> {
+    use crate::mmap_aof::{MmapAOF, HEADER_BYTES};
+
+    let test_file = "test_mmap_aof_replays_committed_records_only.mmap";
+    let _ = tokio::fs::remove_file(test_file).await;
+    let record = |key: u32| AOFRecord {
+        key,
+        value: Some(key.to_string()),
+        operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
+    };
+
+    // room for a few records only, so that the file has to grow.
+    let mut aof = MmapAOF::open(test_file, 64)?;
+    let records: Vec<_> = (0..100).map(record).collect();
+    aof.append(&records).await?;
+    assert!(aof.into_iter().next::<u32, String>().await?.is_none());
+    aof.sync()?;
+    assert!(aof.file_bytes() >= HEADER_BYTES as u64 + aof.committed_bytes());
+    // appended but not synced, as if the process crashed before the next sync.
+    aof.append(&[record(100)]).await?;
+    assert!(aof.pending_bytes() > 0);
+    let mut iter = aof.into_iter();
+    drop(aof);
+
+    let mut keys = vec![];
+    while let Some(record) = iter.next::<u32, String>().await? {
+        assert_eq!(record.value, Some(record.key.to_string()));
+        keys.push(record.key);
+    }
+    assert_eq!(keys, (0..100).collect::<Vec<_>>());
+
+    let mut aof = MmapAOF::open(test_file, 64)?;
+    assert_eq!(aof.pending_bytes(), 0);
+    aof.append(&[record(101)]).await?;
+    aof.sync()?;
+    let mut iter = aof.into_iter();
+    let mut last = None;
+    while let Some(record) = iter.next::<u32, String>().await? {
+        last = Some(record.key);
+    }
+    assert_eq!(last, Some(101));
+
+    tokio::fs::write(test_file, b"not a memory-mapped file").await?;
+    assert_eq!(MmapAOF::open(test_file, 64).err().map(|x| x.kind()), Some(std::io::ErrorKind::InvalidData));
+    tokio::fs::remove_file(test_file).await?;
+    Ok(())
+}
+
+/// Repair truncates after the last valid record and leaves valid files as they are.
+#[tokio::test]
+async fn test_aof_repair() -> Result<(), crate::error::CacheError> {
+    let test_file = "test_aof12.dat";
+    let _ = tokio::fs::remove_file(test_file).await; //clean the file if exists
+    let aof = AOF::new(test_file.to_string()).await;
+    for i in 0..3 {
+        aof.on_event(AOFRecord {
+            key: format!("key{}", i),
+            value: Some(format!("value{}", i)),
+            operation: Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
+        }, true).await?;
+    }
+    let valid = tokio::fs::read(test_file).await?;
+    let report = AOF::repair(test_file).await?;
+    assert_eq!(report, crate::common::RepairReport { records_kept: 3, bytes_discarded: 0 });
+
+    // a record cut short by a crash.
+    let mut bytes = valid.clone();
+    bytes.extend([1u8, 3, 0, 0, 0, b'k']);
+    tokio::fs::write(test_file, &bytes).await?;
+    let report = AOF::repair(test_file).await?;
+    assert_eq!(report, crate::common::RepairReport { records_kept: 3, bytes_discarded: 6 });
+    assert_eq!(tokio::fs::read(test_file).await?, valid);
+    let mut iter = aof.into_iter().await?;
+    let mut records = 0;
+    while iter.next::<String, String>().await?.is_some() {
+        records += 1;
+    }
+    assert_eq!(records, 3);
+
+    assert!(matches!(AOF::repair("missing_test_aof12.dat").await, Err(crate::error::CacheError::Io(_))));
+    tokio::fs::remove_file(test_file).await?;
+    Ok(())
+}
+
+/// Test that a failed write without periodic flush is reported by `flush_health` instead of panicking.
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_aof_subscriber_reports_failed_write() -> Result<(), tokio::io::Error> {
+    let folder = "./test_aof_subscriber_reports_failed_write";
+    let _ = tokio::fs::remove_dir_all(folder).await;
+    tokio::fs::create_dir_all(folder).await?;
+    // every write to `/dev/full` fails as the disk is full.
+    std::os::unix::fs::symlink("/dev/full", format!("{}/cache.dat", folder))?;
+    let subscriber: AOFSubscriber<String, String> = AOFSubscriber::new(Some(folder.to_string()), Some("cache".to_string()), None).await;
+    subscriber.on_event(AOFRecord {
+        key: String::from("key1"),
+        value: Some(String::from("value1")),
+        operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
+    }).await;
+    assert!(subscriber.flush_health().await.last_error.is_some());
+
+    tokio::fs::remove_dir_all(folder).await?;
+    Ok(())
+}
+
+/// Test that `AOF` returns failed writes and flushes instead of panicking.
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_aof_returns_failed_write() -> Result<(), tokio::io::Error> {
+    let folder = "./test_aof_returns_failed_write";
+    let _ = tokio::fs::remove_dir_all(folder).await;
+    tokio::fs::create_dir_all(folder).await?;
+    std::os::unix::fs::symlink("/dev/full", format!("{}/cache.dat", folder))?;
+    let mut aof = AOF::new(format!("{}/cache.dat", folder)).await;
+    let record = || AOFRecord {
+        key: String::from("key1"),
+        value: Some(String::from("value1")),
+        operation: Operation::Put,
+        expires_at: None,
+        cache_id: None,
+        version: None,
+        origin_ts: None,
+    };
+    assert!(aof.on_event(record(), true).await.is_err());
+    assert!(aof.on_event_multi(vec![record(), record()], true).await.is_err());
+    // the error of an unflushed write shows up once flushed.
+    let _ = aof.on_event(record(), false).await;
+    assert!(aof.flush().await.is_err());
+
+    tokio::fs::remove_dir_all(folder).await?;
+    Ok(())
+}
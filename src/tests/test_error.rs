@@ -0,0 +1,19 @@
+#[test]
+fn test_cache_error_conversions() {
+    use std::error::Error;
+    use crate::error::CacheError;
+
+    let e: CacheError = std::io::Error::new(std::io::ErrorKind::NotFound, "missing").into();
+    assert!(matches!(e, CacheError::Io(ref x) if x.kind() == std::io::ErrorKind::NotFound));
+    assert!(e.source().is_some());
+
+    let e: CacheError = serde_json::from_str::<u32>("not a number").unwrap_err().into();
+    assert!(matches!(e, CacheError::Serialize(_)));
+
+    let e = CacheError::Corruption { offset: 42, detail: String::from("truncated record") };
+    assert_eq!(e.to_string(), "corrupted aof at offset 42: truncated record");
+    assert!(e.source().is_none());
+
+    let e = CacheError::PolicyMismatch { expected: String::from("LRU"), found: String::from("LFU") };
+    assert_eq!(e.to_string(), "expected eviction policy LRU but found LFU");
+}
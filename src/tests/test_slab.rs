@@ -0,0 +1,46 @@
+#[test]
+fn test_slab_reuses_free_slots() {
+    use std::collections::hash_map::RandomState;
+    use crate::{common::CacheEntry, slab::SlabMap};
+
+    let mut slab: SlabMap<&str, u32, RandomState> = SlabMap::with_hasher(RandomState::new());
+    for (key, value) in [("K1", 1), ("K2", 2), ("K3", 3)] {
+        assert!(slab.insert(key, CacheEntry::new(value)).is_none());
+    }
+    assert_eq!(slab.insert("K2", CacheEntry::new(20)).map(|x| x.value), Some(2));
+    assert_eq!(slab.remove("K1").map(|x| x.value), Some(1));
+    slab.insert("K4", CacheEntry::new(4));
+    assert_eq!(slab.slots(), 3);
+    assert_eq!(slab.get("K4").map(|x| x.value), Some(4));
+    assert_eq!(slab.get("K2").map(|x| x.value), Some(20));
+
+    // only free slots at the end can be released.
+    slab.remove("K3");
+    slab.shrink_to_fit();
+    assert_eq!(slab.slots(), 2);
+    let mut entries: Vec<(&str, u32)> = slab.into_iter().map(|(key, entry)| (key, entry.value)).collect();
+    entries.sort();
+    assert_eq!(entries, vec![("K2", 20), ("K4", 4)]);
+}
+
+#[test]
+fn test_slab_get_many_mut() {
+    use std::collections::hash_map::RandomState;
+    use crate::{common::CacheEntry, slab::SlabMap};
+
+    let mut slab: SlabMap<&str, u32, RandomState> = SlabMap::with_hasher(RandomState::new());
+    for (key, value) in [("K1", 1), ("K2", 2), ("K3", 3)] {
+        slab.insert(key, CacheEntry::new(value));
+    }
+    let [a, b, c] = slab.get_many_mut(["K3", "K1", "K2"]).unwrap();
+    std::mem::swap(&mut a.value, &mut b.value);
+    c.value += 10;
+    assert_eq!(slab.get("K1").map(|x| x.value), Some(3));
+    assert_eq!(slab.get("K2").map(|x| x.value), Some(12));
+    assert_eq!(slab.get("K3").map(|x| x.value), Some(1));
+
+    assert!(slab.get_many_mut(["K1", "K1"]).is_none());
+    assert!(slab.get_many_mut(["K1", "K4"]).is_none());
+    slab.remove("K2");
+    assert!(slab.get_many_mut(["K2", "K3"]).is_none());
+}
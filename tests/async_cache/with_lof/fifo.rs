@@ -2,7 +2,7 @@ use rand::Rng;
 use rand::{distributions::WeightedIndex, thread_rng};
 use rand::distributions::Distribution;
 use sine_cache::config::{CacheConfig, EvictionAOFConfig, EvictionAsyncConfig};
-use sine_cache::{cache::{AsyncCache, Cache}, common::Operation, config::{AsyncCacheConfig, CacheSyncConfig}};
+use sine_cache::{cache::{AsyncCache, AsyncCacheBuilder, Cache}, common::Operation, config::{AsyncCacheConfig, CacheSyncConfig}};
 
 #[tokio::test]
 async fn test_fifo_eviction_async_cache_with_periodic_flush()  -> Result<(), tokio::io::Error> {
@@ -16,7 +16,8 @@ async fn test_fifo_eviction_async_cache_with_periodic_flush()  -> Result<(), tok
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size: max_size
         })
@@ -46,6 +47,7 @@ async fn test_fifo_eviction_async_cache_with_periodic_flush()  -> Result<(), tok
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -68,7 +70,8 @@ async fn test_fifo_eviction_async_cache_with_periodic_flush()  -> Result<(), tok
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size
         })
@@ -90,16 +93,17 @@ async fn test_fifo_eviction_async_cache_with_instant_flush()  -> Result<(), toki
     let flush_time = None;
     let max_size = 50;
     let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
-    let async_cache: AsyncCache<String, String> = AsyncCache::new(
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(
         AsyncCacheConfig::FIFO(EvictionAsyncConfig {
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size: max_size
         })
-    ).await;
+    ).allow_sync_writes(true).build().await;
     let mut cache: Cache<String, String> = Cache::new(CacheSyncConfig::FIFO(CacheConfig{
         max_size
     }));
@@ -125,6 +129,7 @@ async fn test_fifo_eviction_async_cache_with_instant_flush()  -> Result<(), toki
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -142,16 +147,17 @@ async fn test_fifo_eviction_async_cache_with_instant_flush()  -> Result<(), toki
     }
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     drop(async_cache);
-    let async_cache = AsyncCache::new(
+    let async_cache = AsyncCacheBuilder::new(
         AsyncCacheConfig::FIFO(EvictionAsyncConfig {
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size
         })
-    ).await;
+    ).allow_sync_writes(true).build().await;
     
     for i in 0..num_ops {
         let key = format!("key{}", i);
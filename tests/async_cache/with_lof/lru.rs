@@ -1,8 +1,12 @@
+use std::sync::Arc;
+
 use rand::Rng;
 use rand::{distributions::WeightedIndex, thread_rng};
 use rand::distributions::Distribution;
-use sine_cache::config::{CacheConfig, EvictionAOFConfig, EvictionAsyncConfig};
-use sine_cache::{cache::{AsyncCache, Cache}, common::Operation, config::{AsyncCacheConfig, CacheSyncConfig}};
+use sine_cache::config::{CacheConfig, CustomEvictionAOFConfig, CustomEvictionAsyncConfig, EvictionAOFConfig, EvictionAsyncConfig};
+use sine_cache::eviction_policies::lru::LRU;
+use sine_cache::aof::{MemoryAOF, AOF};
+use sine_cache::{cache::{AsyncCache, AsyncCacheBuilder, Cache}, common::{AOFRecord, Operation, ReplayProgress}, config::{AsyncCacheConfig, CacheSyncConfig}, error::CacheError};
 
 #[tokio::test]
 async fn test_lru_eviction_async_cache_with_periodic_flush()  -> Result<(), tokio::io::Error> {
@@ -16,7 +20,8 @@ async fn test_lru_eviction_async_cache_with_periodic_flush()  -> Result<(), toki
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size: max_size
         })
@@ -46,6 +51,7 @@ async fn test_lru_eviction_async_cache_with_periodic_flush()  -> Result<(), toki
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -68,7 +74,8 @@ async fn test_lru_eviction_async_cache_with_periodic_flush()  -> Result<(), toki
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size
         })
@@ -90,16 +97,17 @@ async fn test_lru_eviction_async_cache_with_instant_flush()  -> Result<(), tokio
     let flush_time = None;
     let max_size = 50;
     let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
-    let async_cache: AsyncCache<String, String> = AsyncCache::new(
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(
         AsyncCacheConfig::LRU(EvictionAsyncConfig {
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size: max_size
         })
-    ).await;
+    ).allow_sync_writes(true).build().await;
     let mut cache: Cache<String, String> = Cache::new(CacheSyncConfig::LRU(CacheConfig{
         max_size
     }));
@@ -125,6 +133,7 @@ async fn test_lru_eviction_async_cache_with_instant_flush()  -> Result<(), tokio
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -142,16 +151,17 @@ async fn test_lru_eviction_async_cache_with_instant_flush()  -> Result<(), tokio
     }
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     drop(async_cache);
-    let async_cache = AsyncCache::new(
+    let async_cache = AsyncCacheBuilder::new(
         AsyncCacheConfig::LRU(EvictionAsyncConfig {
             aof_config: Some(EvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time
+                flush_time,
+                persist_read_ops: true
             }),
             max_size
         })
-    ).await;
+    ).allow_sync_writes(true).build().await;
     
     for i in 0..num_ops {
         let key = format!("key{}", i);
@@ -161,3 +171,993 @@ async fn test_lru_eviction_async_cache_with_instant_flush()  -> Result<(), tokio
     tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
     Ok(())
 }
+
+#[tokio::test]
+async fn test_lru_async_cache_replay_progress()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_replay_progress";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    for i in 0..5 {
+        async_cache.put(i, i).await;
+    }
+    drop(async_cache);
+
+    let reports = Arc::new(std::sync::Mutex::new(vec![]));
+    let reports_clone = reports.clone();
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .replay_progress(2, move |progress: ReplayProgress| reports_clone.lock().unwrap().push(progress))
+        .build()
+        .await;
+    assert_eq!(async_cache.size().await, 5);
+
+    let reports = reports.lock().unwrap().clone();
+    let total_bytes = tokio::fs::metadata(format!("{}/{}.dat", folder, cache_name)).await?.len();
+    assert_eq!(reports.iter().map(|x| x.records).collect::<Vec<_>>(), vec![2, 4, 5]);
+    assert!(reports.iter().all(|x| x.total_bytes == Some(total_bytes)));
+    assert!(reports[0].bytes_read < reports[1].bytes_read);
+    assert_eq!(reports[2].bytes_read, total_bytes);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_flush_health()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_flush_health";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(
+        AsyncCacheConfig::LRU(EvictionAsyncConfig {
+            aof_config: Some(EvictionAOFConfig {
+                folder: String::from(folder),
+                cache_name:  String::from(cache_name),
+                flush_time: Some(100),
+                persist_read_ops: true
+            }),
+            max_size: 10
+        })
+    ).await;
+
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    let health = async_cache.flush_health().await;
+    assert_eq!(health.pending, 2);
+    assert_eq!(health.last_error, None);
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+    let health = async_cache.flush_health().await;
+    assert_eq!(health.pending, 0);
+    assert!(health.last_ok.is_some());
+    assert_eq!(health.last_error, None);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_reload()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_reload";
+    let other_name = "test_lru_async_cache_reload_other";
+    let folder = ".";
+    let config = |cache_name: &str, flush_time: Option<u32>| AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, other_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config(cache_name, Some(10000))).await;
+
+    // pending records are written before replaying, exactly once.
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    async_cache.reload().await?;
+    assert_eq!(async_cache.flush_health().await.pending, 0);
+    assert_eq!(async_cache.len().await, 2);
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+
+    // the file is replaced by another process.
+    let other: AsyncCache<String, String> = AsyncCacheBuilder::new(config(other_name, None)).allow_sync_writes(true).build().await;
+    other.put(String::from("key3"), String::from("value3")).await;
+    drop(other);
+    tokio::fs::rename(format!("{}/{}.dat", folder, other_name), format!("{}/{}.dat", folder, cache_name)).await?;
+    async_cache.reload().await?;
+    assert_eq!(async_cache.len().await, 1);
+    assert!(!async_cache.contains_key(&String::from("key1")).await);
+    assert_eq!(async_cache.get(&String::from("key3")).await, Some(String::from("value3")));
+
+    // new records go to the new file.
+    async_cache.put(String::from("key4"), String::from("value4")).await;
+    async_cache.reload().await?;
+    assert_eq!(async_cache.len().await, 2);
+    assert!(async_cache.contains_key(&String::from("key4")).await);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_reload_drops_expired_entries()  -> Result<(), tokio::io::Error> {
+    use std::time::Duration;
+    use sine_cache::common::MockClock;
+
+    let cache_name = "test_lru_async_cache_reload_drops_expired_entries";
+    let other_name = "test_lru_async_cache_reload_drops_expired_entries_other";
+    let folder = ".";
+    let config = |cache_name: &str, max_size: usize| AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, other_name)).await;
+    let clock = MockClock::new();
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config(cache_name, 2)).clock(clock.clone()).build().await;
+
+    let other: AsyncCache<String, String> = AsyncCacheBuilder::new(config(other_name, 10)).clock(clock.clone()).build().await;
+    other.put(String::from("key1"), String::from("value1")).await;
+    other.put_with_ttl(String::from("key2"), String::from("value2"), Duration::from_secs(1)).await;
+    other.put(String::from("key3"), String::from("value3")).await;
+    drop(other);
+    tokio::fs::rename(format!("{}/{}.dat", folder, other_name), format!("{}/{}.dat", folder, cache_name)).await?;
+
+    // the expired entry is dropped while replaying, instead of evicting key1 to make room for key3.
+    clock.advance(Duration::from_secs(2));
+    async_cache.reload().await?;
+    assert_eq!(async_cache.len().await, 2);
+    assert!(!async_cache.contains_key(&String::from("key2")).await);
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+    assert_eq!(async_cache.get(&String::from("key3")).await, Some(String::from("value3")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_skips_records_which_can_not_be_decoded()  -> Result<(), tokio::io::Error> {
+    #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+    struct ValueV1 {
+        name: String,
+    }
+    #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+    struct ValueV2 {
+        name: String,
+        age: u32,
+    }
+
+    let cache_name = "test_lru_async_cache_skips_records_which_can_not_be_decoded";
+    let folder = ".";
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, ValueV1> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    async_cache.put(String::from("key1"), ValueV1 { name: String::from("one") }).await;
+    drop(async_cache);
+    let async_cache: AsyncCache<String, ValueV2> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .on_decode_error(|_, _| {})
+        .build()
+        .await;
+    async_cache.put(String::from("key2"), ValueV2 { name: String::from("two"), age: 2 }).await;
+    drop(async_cache);
+
+    // by default, replay stops at the old record.
+    let async_cache: AsyncCache<String, ValueV2> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.len().await, 0);
+    drop(async_cache);
+
+    let skipped = Arc::new(std::sync::Mutex::new(vec![]));
+    let skipped_clone = skipped.clone();
+    let async_cache: AsyncCache<String, ValueV2> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .on_decode_error(move |bytes, e| skipped_clone.lock().unwrap().push((bytes.to_vec(), e)))
+        .build()
+        .await;
+    assert_eq!(async_cache.len().await, 1);
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(ValueV2 { name: String::from("two"), age: 2 }));
+    let skipped = std::mem::take(&mut *skipped.lock().unwrap());
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(serde_json::from_slice::<ValueV1>(&skipped[0].0).unwrap(), ValueV1 { name: String::from("one") });
+    assert!(matches!(skipped[0].1, CacheError::Serialize(_)));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_skips_put_records_without_value()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_skips_put_records_without_value";
+    let folder = ".";
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    for (key, value) in [("key1", Some("value1")), ("key2", None), ("key3", Some("value3"))] {
+        let record = AOFRecord {
+            key: String::from(key),
+            value: value.map(String::from),
+            operation: Operation::Put,
+            expires_at: None,
+            cache_id: None,
+            version: None,
+            origin_ts: None,
+        };
+        aof.on_event(record, true).await?;
+    }
+    drop(aof);
+
+    // skipped even without callback.
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.len().await, 2);
+    assert_eq!(async_cache.get(&String::from("key2")).await, None);
+    assert_eq!(async_cache.get(&String::from("key3")).await, Some(String::from("value3")));
+    drop(async_cache);
+
+    let skipped = Arc::new(std::sync::Mutex::new(vec![]));
+    let skipped_clone = skipped.clone();
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .on_decode_error(move |bytes, e| skipped_clone.lock().unwrap().push((bytes.to_vec(), e)))
+        .build()
+        .await;
+    assert_eq!(async_cache.len().await, 2);
+    let skipped = std::mem::take(&mut *skipped.lock().unwrap());
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(serde_json::from_slice::<String>(&skipped[0].0).unwrap(), String::from("key2"));
+    assert!(matches!(skipped[0].1, CacheError::Corruption { .. }));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_warm_from_snapshot()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_warm_from_snapshot";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    async_cache.warm_from_snapshot((0..15).map(|i| (i, i * 10)).collect()).await;
+    // only the last `max_size` entries are kept, the first ones are evicted first.
+    assert_eq!(async_cache.size().await, 10);
+    assert_eq!(async_cache.peek(&4).await, None);
+    assert_eq!(async_cache.next_victim().await, Some(5));
+    assert_eq!(async_cache.get_with_version(&14).await, Some((140, 15)));
+    async_cache.put(15, 150).await;
+    assert_eq!(async_cache.get_with_version(&15).await, Some((150, 16)));
+    drop(async_cache);
+
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.size().await, 10);
+    assert_eq!(async_cache.peek(&5).await, None);
+    assert_eq!(async_cache.next_victim().await, Some(6));
+    assert_eq!(async_cache.get_with_version(&14).await, Some((140, 15)));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_aof_stats()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_aof_stats";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.aof_stats().await.map(|x| x.approx_records), Some(0));
+    for i in 0..3 {
+        async_cache.put(i, i).await;
+    }
+    async_cache.remove_many(&[0, 1]).await;
+    let stats = async_cache.aof_stats().await.unwrap();
+    assert_eq!(stats.approx_records, 5);
+    assert_eq!(stats.file_bytes, tokio::fs::metadata(format!("{}/{}.dat", folder, cache_name)).await?.len());
+    drop(async_cache);
+
+    // records replayed at start are counted.
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.aof_stats().await.map(|x| x.approx_records), Some(5));
+    async_cache.put(3, 3).await;
+    assert_eq!(async_cache.aof_stats().await.map(|x| x.approx_records), Some(6));
+
+    let in_memory: AsyncCache<u32, u32> = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: None,
+        max_size: 10
+    })).await;
+    assert_eq!(in_memory.aof_stats().await, None);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[should_panic]
+async fn test_lru_async_cache_sync_writes_must_be_allowed() {
+    let _async_cache: AsyncCache<u32, u32> = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from("."),
+            cache_name:  String::from("test_lru_async_cache_sync_writes_must_be_allowed"),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    })).await;
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_without_read_ops()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_without_read_ops";
+    let folder = ".";
+    // reads are not flushed, so sync writes need not be allowed.
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 2
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+    assert_eq!(async_cache.next_victim().await, Some(String::from("key2")));
+    drop(async_cache);
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut operations = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        operations.push(record.operation);
+    }
+    assert_eq!(operations, vec![Operation::Put, Operation::Put]);
+
+    // the order is rebuilt from writes only.
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.next_victim().await, Some(String::from("key1")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_value_transform()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_value_transform";
+    let folder = ".";
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    // stands in for a cipher.
+    let xor = |bytes: &[u8]| bytes.iter().map(|x| x ^ 0x5a).collect::<Vec<u8>>();
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .value_transform(xor, xor)
+        .build()
+        .await;
+    async_cache.put(String::from("key1"), String::from("secret value")).await;
+    async_cache.remove(&String::from("key1")).await;
+    async_cache.put(String::from("key2"), String::from("another secret")).await;
+    drop(async_cache);
+
+    let bytes = tokio::fs::read(format!("{}/{}.dat", folder, cache_name)).await?;
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|x| x == needle);
+    assert!(!contains(b"secret"));
+    // keys are not transformed.
+    assert!(contains(b"key2"));
+
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .value_transform(xor, xor)
+        .build()
+        .await;
+    assert_eq!(async_cache.len().await, 1);
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(String::from("another secret")));
+    drop(async_cache);
+
+    // without the transform, transformed values can only be skipped.
+    let skipped = Arc::new(std::sync::Mutex::new(0));
+    let skipped_clone = skipped.clone();
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .on_decode_error(move |_, _| *skipped_clone.lock().unwrap() += 1)
+        .build()
+        .await;
+    assert_eq!(async_cache.len().await, 0);
+    assert_eq!(*skipped.lock().unwrap(), 2);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_restores_saved_policy_state()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_restores_saved_policy_state";
+    let folder = ".";
+    // reads are not persisted, so replay alone restores the order of insertion.
+    let config = || AsyncCacheConfig::Custom(CustomEvictionAsyncConfig {
+        aof_config: Some(CustomEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 3,
+        policy: Box::new(LRU::new())
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let _ = tokio::fs::remove_file(format!("{}/{}.policy", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).persist_policy_state(true).build().await;
+    for key in ["K1", "K2", "K3"] {
+        async_cache.put(String::from(key), String::from(key)).await;
+    }
+    async_cache.get(&String::from("K1")).await;
+    async_cache.save_policy_state().await.unwrap();
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.next_victim().await, Some(String::from("K1")));
+    let error = async_cache.save_policy_state().await.unwrap_err();
+    assert!(matches!(error.kind(), CacheError::Config(_)));
+    assert_eq!(error.cache_name(), Some(cache_name));
+    assert!(error.to_string().starts_with(&format!("cache {}: invalid config", cache_name)));
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).persist_policy_state(true).build().await;
+    assert_eq!(async_cache.next_victim().await, Some(String::from("K2")));
+    async_cache.put(String::from("K4"), String::from("K4")).await;
+    assert!(!async_cache.contains_key(&String::from("K2")).await);
+    assert!(async_cache.contains_key(&String::from("K1")).await);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    tokio::fs::remove_file(format!("{}/{}.policy", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_pins_survive_restart()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_pins_survive_restart";
+    let folder = ".";
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 2,
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    for key in ["K1", "K2", "K3"] {
+        async_cache.pin(&String::from(key)).await;
+    }
+    async_cache.unpin(&String::from("K3")).await;
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert!(async_cache.is_pinned(&String::from("K1")).await);
+    assert!(!async_cache.is_pinned(&String::from("K3")).await);
+    async_cache.put(String::from("K1"), String::from("K1")).await;
+    async_cache.put(String::from("K3"), String::from("K3")).await;
+    async_cache.put(String::from("K4"), String::from("K4")).await;
+    assert!(async_cache.contains_key(&String::from("K1")).await);
+    assert!(!async_cache.contains_key(&String::from("K3")).await);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_compress_values_over()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_compress_values_over";
+    let folder = ".";
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 10
+    });
+    // stands in for a compression.
+    let xor = |bytes: &[u8]| bytes.iter().map(|x| x ^ 0x5a).collect::<Vec<u8>>();
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .value_transform(xor, xor)
+        .compress_values_over(Some(16))
+        .build()
+        .await;
+    async_cache.put(String::from("key1"), String::from("small")).await;
+    async_cache.put(String::from("key2"), String::from("a large value over the threshold")).await;
+    drop(async_cache);
+
+    let bytes = tokio::fs::read(format!("{}/{}.dat", folder, cache_name)).await?;
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|x| x == needle);
+    assert!(contains(b"small"));
+    assert!(!contains(b"large"));
+
+    // the threshold is not needed to read the values back.
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .value_transform(xor, xor)
+        .build()
+        .await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("small")));
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(String::from("a large value over the threshold")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+/// Evicts the key with the fewest hits, which are not in `AOF` as reads are not persisted.
+struct FewestHits {
+    hits: std::collections::HashMap<String, u64>,
+}
+
+impl FewestHits {
+    fn victim(&self) -> Option<&String> {
+        self.hits.iter().min_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0))).map(|x| x.0)
+    }
+}
+
+impl sine_cache::eviction_policies::common::EvictionPolicy<String> for FewestHits {
+    fn on_get(&mut self, key: &String) {
+        if let Some(hits) = self.hits.get_mut(key) {
+            *hits += 1;
+        }
+    }
+
+    fn on_set(&mut self, key: String) {
+        self.hits.entry(key).or_insert(0);
+    }
+
+    fn evict(&mut self) -> Option<String> {
+        let victim = self.victim()?.clone();
+        self.hits.remove(&victim);
+        Some(victim)
+    }
+
+    fn peek_victim(&self) -> Option<&String> {
+        self.victim()
+    }
+
+    fn remove(&mut self, key: String) {
+        self.hits.remove(&key);
+    }
+
+    fn export_state_bytes(&self) -> Option<Vec<u8>> {
+        serde_json::to_vec(&self.hits).ok()
+    }
+
+    fn import_state_bytes(&mut self, bytes: &[u8]) {
+        if let Ok(hits) = serde_json::from_slice(bytes) {
+            self.hits = hits;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_custom_async_cache_restores_policy_state_from_aof()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_custom_async_cache_restores_policy_state_from_aof";
+    let folder = ".";
+    let config = || AsyncCacheConfig::Custom(CustomEvictionAsyncConfig {
+        aof_config: Some(CustomEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 3,
+        policy: Box::new(FewestHits { hits: Default::default() })
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    for key in ["K1", "K2", "K3"] {
+        async_cache.put(String::from(key), String::from(key)).await;
+    }
+    for key in ["K1", "K1", "K2"] {
+        async_cache.get(&String::from(key)).await;
+    }
+    assert!(async_cache.record_policy_state().await.unwrap());
+    async_cache.put(String::from("K4"), String::from("K4")).await;
+    assert!(!async_cache.contains_key(&String::from("K3")).await);
+    drop(async_cache);
+
+    // without the state, all the keys would have no hits and `K1` would be the victim.
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config())
+        .allow_sync_writes(true)
+        .policy_state_interval(Some(std::time::Duration::from_millis(20)))
+        .build()
+        .await;
+    assert!(!async_cache.contains_key(&String::from("K3")).await);
+    assert_eq!(async_cache.next_victim().await, Some(String::from("K4")));
+    for _ in 0..3 {
+        async_cache.get(&String::from("K4")).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.next_victim().await, Some(String::from("K2")));
+
+    let builder: AsyncCacheBuilder<String, String> = AsyncCacheBuilder::new(AsyncCacheConfig::LRU(EvictionAsyncConfig { max_size: 3, aof_config: None }))
+        .policy_state_interval(Some(std::time::Duration::ZERO));
+    assert_eq!(builder.validate().unwrap_err(), vec![sine_cache::error::ConfigError::PolicyStateIntervalWithoutAof, sine_cache::error::ConfigError::ZeroPolicyStateInterval]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_soft_limit_keeps_headroom()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_soft_limit_keeps_headroom";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 100
+    });
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config())
+        .soft_limit(50, std::time::Duration::from_millis(5))
+        .build()
+        .await;
+    let mut largest = 0;
+    for i in 0..300 {
+        async_cache.put(i, i).await;
+        largest = largest.max(async_cache.size().await);
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+    assert!(largest < 100);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(async_cache.size().await, 50);
+    assert_eq!(async_cache.get(&299).await, Some(299));
+    drop(async_cache);
+
+    let async_cache: AsyncCache<u32, u32> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.size().await, 50);
+
+    let builder = AsyncCacheBuilder::<u32, u32>::new(config()).soft_limit(100, std::time::Duration::ZERO);
+    assert_eq!(builder.validate().unwrap_err(), vec![sine_cache::error::ConfigError::InvalidSoftLimit(100), sine_cache::error::ConfigError::ZeroSoftLimitInterval]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_every_operation_round_trips()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_every_operation_round_trips";
+    let folder = ".";
+    let path = format!("{}/{}.dat", folder, cache_name);
+    let _ = tokio::fs::remove_file(&path).await;
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    });
+    let async_cache: AsyncCache<String, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    async_cache.put(String::from("K1"), 1).await;
+    async_cache.put(String::from("K2"), 2).await;
+    async_cache.get(&String::from("K1")).await;
+    async_cache.remove(&String::from("K2")).await;
+    async_cache.pin(&String::from("K1")).await;
+    async_cache.clear().await;
+    async_cache.clear().await;
+    async_cache.put_batch_atomic(vec![(String::from("K3"), 3), (String::from("K4"), 4)]).await.unwrap();
+    async_cache.pin(&String::from("K4")).await;
+    async_cache.unpin(&String::from("K1")).await;
+    drop(async_cache);
+
+    let mut iter = AOF::open_reader(&path).await.unwrap();
+    let mut operations = vec![];
+    while let Some(record) = iter.next::<String, u32>().await? {
+        assert_eq!(Operation::from_int(record.operation.to_int() as u8), Ok(record.operation.clone()));
+        operations.push(record.operation);
+    }
+    assert_eq!(operations, vec![
+        Operation::Put, Operation::Put, Operation::Get, Operation::Remove, Operation::Pin, Operation::Clear,
+        Operation::BatchBegin, Operation::Put, Operation::Put, Operation::BatchCommit, Operation::Pin, Operation::Unpin
+    ]);
+    assert!(Operation::from_int(9).is_err());
+
+    let async_cache: AsyncCache<String, u32> = AsyncCacheBuilder::new(config()).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.size().await, 2);
+    assert_eq!(async_cache.get(&String::from("K1")).await, None);
+    assert_eq!(async_cache.get(&String::from("K3")).await, Some(3));
+    assert!(async_cache.is_pinned(&String::from("K4")).await);
+    assert!(!async_cache.is_pinned(&String::from("K1")).await);
+
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_file_extension_and_manifest()  -> Result<(), tokio::io::Error> {
+    use sine_cache::aof::{AofManifest, MANIFEST_FORMAT_VERSION};
+    let cache_name = "test_lru_async_cache_file_extension_and_manifest";
+    let folder = ".";
+    let path = format!("{}/{}.aof", folder, cache_name);
+    let manifest_path = AofManifest::path(folder, cache_name);
+    let _ = tokio::fs::remove_file(&path).await;
+    let _ = tokio::fs::remove_file(&manifest_path).await;
+    let aof_config = || Some(EvictionAOFConfig {
+        folder: String::from(folder),
+        cache_name:  String::from(cache_name),
+        flush_time: None,
+        persist_read_ops: false
+    });
+    let build = |max_size| AsyncCacheBuilder::<String, u32>::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {aof_config: aof_config(), max_size}))
+        .file_extension("aof")
+        .write_manifest(true)
+        .build();
+    let async_cache = build(10).await;
+    async_cache.put(String::from("K1"), 1).await;
+    drop(async_cache);
+    assert!(tokio::fs::try_exists(&path).await?);
+    assert!(!tokio::fs::try_exists(format!("{}/{}.dat", folder, cache_name)).await?);
+    let manifest = AofManifest::read(&manifest_path).await.unwrap().unwrap();
+    assert_eq!(manifest.format_version, MANIFEST_FORMAT_VERSION);
+    assert_eq!((manifest.policy.as_str(), manifest.max_size), ("LRU", 10));
+    assert_eq!(manifest.segments, vec![format!("{}.aof", cache_name)]);
+
+    let async_cache = build(20).await;
+    assert_eq!(async_cache.get(&String::from("K1")).await, Some(1));
+    drop(async_cache);
+    let rewritten = AofManifest::read(&manifest_path).await.unwrap().unwrap();
+    assert_eq!((rewritten.max_size, rewritten.created_at), (20, manifest.created_at));
+
+    let mismatch = tokio::spawn(AsyncCacheBuilder::<String, u32>::new(AsyncCacheConfig::LFU(EvictionAsyncConfig {aof_config: aof_config(), max_size: 10}))
+        .file_extension("aof")
+        .build());
+    assert!(mismatch.await.is_err_and(|x| x.is_panic()));
+
+    let builder = AsyncCacheBuilder::<String, u32>::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {aof_config: None, max_size: 10}))
+        .file_extension("a.b")
+        .write_manifest(true);
+    assert_eq!(builder.validate().unwrap_err(), vec![sine_cache::error::ConfigError::InvalidFileExtension(String::from("a.b")), sine_cache::error::ConfigError::ManifestWithoutAof]);
+
+    tokio::fs::remove_file(&path).await?;
+    tokio::fs::remove_file(&manifest_path).await?;
+    Ok(())
+}
+
+/// Test that shutdown flushes the pending writes without waiting for the periodic flush and stops the tasks.
+#[tokio::test]
+async fn test_lru_async_cache_shutdown_flushes_pending_writes()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_shutdown_flushes_pending_writes";
+    let folder = ".";
+    let path = format!("{}/{}.dat", folder, cache_name);
+    let _ = tokio::fs::remove_file(&path).await;
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: Some(60_000),
+            persist_read_ops: false
+        }),
+        max_size: 100
+    });
+    let async_cache = AsyncCacheBuilder::<String, u32>::new(config())
+        .sweep_interval(Some(std::time::Duration::from_secs(60)))
+        .build()
+        .await;
+    for i in 0..50 {
+        async_cache.put(format!("K{}", i), i).await;
+    }
+    async_cache.remove(&String::from("K0")).await;
+    assert_eq!(tokio::fs::metadata(&path).await?.len(), 0);
+
+    let start = std::time::Instant::now();
+    async_cache.shutdown().await.unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+    let async_cache = AsyncCacheBuilder::<String, u32>::new(config()).build().await;
+    assert_eq!(async_cache.size().await, 49);
+    assert_eq!(async_cache.get(&String::from("K0")).await, None);
+    assert_eq!(async_cache.get(&String::from("K49")).await, Some(49));
+    drop(async_cache);
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}
+
+/// Test that `AOF` holds the normalized keys and that they are found again after a restart.
+#[tokio::test]
+async fn test_lru_async_cache_normalize_key()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_lru_async_cache_normalize_key";
+    let folder = ".";
+    let path = format!("{}/{}.dat", folder, cache_name);
+    let _ = tokio::fs::remove_file(&path).await;
+    let build = || AsyncCacheBuilder::<String, u32>::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        }),
+        max_size: 10
+    })).normalize_key(|key| key.to_lowercase()).build();
+    let async_cache = build().await;
+    async_cache.put(String::from("Key1"), 1).await;
+    async_cache.put(String::from("KEY2"), 2).await;
+    assert_eq!(async_cache.remove(&String::from("kEy2")).await, Some(2));
+    assert_eq!(async_cache.get(&String::from("KEY1")).await, Some(1));
+    drop(async_cache);
+
+    let mut iter = AOF::new(path.clone()).await.into_iter().await?;
+    let mut keys = vec![];
+    while let Some(record) = iter.next::<String, u32>().await? {
+        keys.push(record.key);
+    }
+    assert_eq!(keys, vec![String::from("key1"), String::from("key2"), String::from("key2")]);
+
+    let async_cache = build().await;
+    assert_eq!(async_cache.size().await, 1);
+    assert!(async_cache.contains_key(&String::from("KEY1")).await);
+    drop(async_cache);
+    tokio::fs::remove_file(&path).await?;
+    Ok(())
+}
+
+/// Test that every method taking a key finds the entry written with another form of the key, and records the
+/// normalized key in `AOF`.
+#[tokio::test]
+async fn test_lru_async_cache_normalize_key_every_method()  -> Result<(), tokio::io::Error> {
+    let aof = MemoryAOF::new();
+    let config = AsyncCacheConfig::LRU(EvictionAsyncConfig { aof_config: None, max_size: 10 });
+    let async_cache = AsyncCacheBuilder::<String, u32>::new(config)
+        .memory_aof(aof.clone())
+        .normalize_key(|key| key.to_lowercase())
+        .stale_reads(std::time::Duration::from_millis(10))
+        .build().await;
+    let key = |key: &str| key.to_string();
+    async_cache.put(key("Bar"), 1).await;
+
+    assert_eq!(async_cache.update(&key("BAR"), true, |x| { *x += 1; *x }).await, Some(2));
+    assert_eq!(async_cache.read_many(&[key("BAR"), key("baz")], |values| values.iter().map(|x| x.copied()).collect::<Vec<_>>()).await, vec![Some(2), None]);
+    assert_eq!(async_cache.touch_many(&[key("BAR")]).await, 1);
+    assert_eq!(async_cache.get_ref(&key("BAR")).await.map(|x| *x), Some(2));
+    assert_eq!(async_cache.get_or_refresh_ahead(&key("BAR")).await, Some(2));
+    assert_eq!(async_cache.get_or_insert_with(key("BAR"), || async { 0 }).await, 2);
+    assert_eq!(async_cache.replace(key("BAR"), 3).await, Some(2));
+    assert_eq!(async_cache.replace_if_present(&key("BAR"), 4).await, Some(3));
+    let (_, version) = async_cache.get_with_version(&key("BAR")).await.unwrap();
+    assert!(async_cache.compare_and_swap(key("BAR"), 0, 5).await.is_err());
+    assert!(async_cache.compare_and_swap(key("BAR"), version, 5).await.is_ok());
+    assert!(async_cache.put_if_newer(key("BAR"), 6, 100).await);
+    assert!(!async_cache.put_if_newer(key("bar"), 7, 50).await);
+    assert!(async_cache.set_ttl(&key("BAR"), Some(std::time::Duration::from_secs(60))).await);
+    assert!(async_cache.get_ttl(&key("BAR")).await.is_some());
+    assert!(async_cache.pin(&key("BAR")).await);
+    assert!(async_cache.is_pinned(&key("bar")).await);
+    assert!(async_cache.unpin(&key("BAR")).await);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(async_cache.get_stale(&key("BAR")), Some(6));
+    assert!(async_cache.compare_and_remove(&key("BAR"), &6).await);
+    async_cache.put(key("Bar"), 1).await;
+    assert_eq!(async_cache.remove_many(&[key("BAR")]).await, 1);
+    assert_eq!(async_cache.size().await, 0);
+
+    let mut iter = aof.into_iter().await;
+    while let Some(record) = iter.next::<String, u32>().await? {
+        assert_eq!(record.key, key("bar"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_from_entries()  -> Result<(), tokio::io::Error> {
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig { aof_config: None, max_size: 3 });
+    let async_cache: AsyncCache<u32, u32> = AsyncCache::from_entries(config(), (0..5).map(|i| (i, i * 10))).await;
+    // entries are put in order, so the first ones are evicted.
+    assert_eq!(async_cache.size().await, 3);
+    assert_eq!(async_cache.peek(&1).await, None);
+    assert_eq!(async_cache.peek(&2).await, Some(20));
+
+    let aof = MemoryAOF::new();
+    let entries = vec![(1, 10), (2, 20), (1, 11), (3, 30), (4, 40)];
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).memory_aof(aof.clone()).build_with_entries(entries).await;
+    let mut iter = aof.into_iter().await;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<u32, u32>().await? {
+        assert_eq!(record.operation, Operation::Put);
+        records.push((record.key, record.value.unwrap()));
+    }
+    assert_eq!(records, vec![(1, 10), (2, 20), (1, 11), (3, 30), (4, 40)]);
+    drop(async_cache);
+
+    // replaying the records in order evicts the same key.
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).memory_aof(aof).build().await;
+    assert_eq!(async_cache.peek(&2).await, None);
+    assert_eq!(async_cache.peek(&1).await, Some(11));
+    assert_eq!(async_cache.size().await, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lru_async_cache_bytes_values()  -> Result<(), tokio::io::Error> {
+    use bytes::Bytes;
+
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig { aof_config: None, max_size: 10 });
+    let aof = MemoryAOF::new();
+    let async_cache: AsyncCache<String, Bytes> = AsyncCacheBuilder::new(config()).memory_aof(aof.clone()).build().await;
+    async_cache.put_serialized(String::from("K1"), &vec![1, 2, 3]).await.unwrap();
+    async_cache.put(String::from("K2"), Bytes::from_static(b"raw")).await;
+    assert_eq!(async_cache.get_deserialized::<Vec<u32>>(&String::from("K1")).await.unwrap(), Some(vec![1, 2, 3]));
+    assert_eq!(async_cache.get_deserialized::<Vec<u32>>(&String::from("K3")).await.unwrap(), None);
+    assert!(matches!(async_cache.get_deserialized::<Vec<u32>>(&String::from("K2")).await, Err(CacheError::Serialize(_))));
+    drop(async_cache);
+
+    // values are written as they are rather than as JSON arrays of numbers.
+    let bytes = aof.bytes().await;
+    assert!(bytes.windows(7).any(|x| x == b"[1,2,3]"));
+    let async_cache: AsyncCache<String, Bytes> = AsyncCacheBuilder::new(config()).memory_aof(aof).build().await;
+    assert_eq!(async_cache.get(&String::from("K1")).await, Some(Bytes::from_static(b"[1,2,3]")));
+    assert_eq!(async_cache.get(&String::from("K2")).await, Some(Bytes::from_static(b"raw")));
+    Ok(())
+}
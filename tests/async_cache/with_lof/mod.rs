@@ -1,4 +1,6 @@
 pub mod no_eviction;
 pub mod fifo;
 pub mod lru;
-pub mod lfu;
\ No newline at end of file
+pub mod lfu;
+pub mod ttl;
+pub mod shared_aof;
\ No newline at end of file
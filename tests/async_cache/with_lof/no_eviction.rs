@@ -3,7 +3,8 @@ use core::num;
 use rand::{random, Rng};
 use rand::{distributions::WeightedIndex, thread_rng};
 use rand::distributions::Distribution;
-use sine_cache::{cache::{AsyncCache, Cache}, common::Operation, config::{AsyncCacheConfig, CacheSyncConfig, NoEvictionAOFConfig, NoEvictionAsyncConfig}};
+use sine_cache::aof::{MemoryAOF, AOF};
+use sine_cache::{cache::{AsyncCache, AsyncCacheBuilder, Cache}, common::{AOFRecord, CacheOp, OpResult, Operation}, config::{AsyncCacheConfig, CacheSyncConfig, NoEvictionAOFConfig, NoEvictionAsyncConfig}};
 
 #[tokio::test]
 async fn test_no_eviction_async_cache_with_periodic_flush()  -> Result<(), tokio::io::Error> {
@@ -43,6 +44,7 @@ async fn test_no_eviction_async_cache_with_periodic_flush()  -> Result<(), tokio
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -71,11 +73,7 @@ async fn test_no_eviction_async_cache_with_periodic_flush()  -> Result<(), tokio
         })
     ).await;
     
-    for i in 0..num_ops {
-        let key = format!("key{}", i);
-        assert_eq!(cache.contains_key(&key), async_cache.contains_key(&key).await);
-        assert_eq!(cache.get(&key).cloned(), async_cache.get(&key).await);
-    };
+    assert!(async_cache.content_eq_with(&cache).await);
     tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
     Ok(())
 }
@@ -118,6 +116,7 @@ async fn test_no_eviction_async_cache_with_periodic_flush_with_persistent_reads(
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -146,11 +145,7 @@ async fn test_no_eviction_async_cache_with_periodic_flush_with_persistent_reads(
         })
     ).await;
     
-    for i in 0..num_ops {
-        let key = format!("key{}", i);
-        assert_eq!(cache.contains_key(&key), async_cache.contains_key(&key).await);
-        assert_eq!(cache.get(&key).cloned(), async_cache.get(&key).await);
-    };
+    assert!(async_cache.content_eq_with(&cache).await);
     tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
     Ok(())
 }
@@ -193,6 +188,7 @@ async fn test_no_eviction_async_cache_with_instant_flush_with_persistent_reads()
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -221,11 +217,7 @@ async fn test_no_eviction_async_cache_with_instant_flush_with_persistent_reads()
         })
     ).await;
     
-    for i in 0..num_ops {
-        let key = format!("key{}", i);
-        assert_eq!(cache.contains_key(&key), async_cache.contains_key(&key).await);
-        assert_eq!(cache.get(&key).cloned(), async_cache.get(&key).await);
-    };
+    assert!(async_cache.content_eq_with(&cache).await);
     tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
     Ok(())
 }
@@ -233,19 +225,10 @@ async fn test_no_eviction_async_cache_with_instant_flush_with_persistent_reads()
 
 #[tokio::test]
 async fn test_no_eviction_async_cache_with_instant_flush()  -> Result<(), tokio::io::Error> {
-    let cache_name = "test_no_eviction_async_cache_with_instant_flush";
-    let folder = ".";
-    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
-    let async_cache = AsyncCache::new(
-        AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
-            aof_config: Some(NoEvictionAOFConfig {
-                folder: String::from(folder),
-                cache_name:  String::from(cache_name),
-                flush_time: None,
-                persist_read_ops: false
-            })
-        })
-    ).await;
+    // records are appended to memory right away, so they are replayed without waiting for any flush.
+    let aof = MemoryAOF::new();
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig { aof_config: None });
+    let async_cache = AsyncCacheBuilder::new(config()).memory_aof(aof.clone()).build().await;
     let mut cache = Cache::new(CacheSyncConfig::NoEviction);
     // Define weights for different operations (adjust weights as needed)
     let weights = &[0.3, 0.5, 0.2];
@@ -269,6 +252,7 @@ async fn test_no_eviction_async_cache_with_instant_flush()  -> Result<(), tokio:
             _ => None,
         };
         match operations[op].clone() {
+            Operation::BatchBegin | Operation::BatchCommit | Operation::Pin | Operation::Unpin | Operation::PolicyState | Operation::Clear => unreachable!(),
             Operation::Get => {
                 async_cache.get(&key).await;
                 cache.get(&key);
@@ -282,26 +266,698 @@ async fn test_no_eviction_async_cache_with_instant_flush()  -> Result<(), tokio:
                 cache.put(key.clone(), value.clone());
             }
         }
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
     }
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
     drop(async_cache);
+    let async_cache = AsyncCacheBuilder::new(config()).memory_aof(aof).build().await;
+
+    assert!(async_cache.content_eq_with(&cache).await);
+    Ok(())
+}
+#[tokio::test]
+async fn test_no_eviction_async_cache_remove_missing_key_is_not_persisted()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_remove_missing_key_is_not_persisted";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
     let async_cache = AsyncCache::new(
         AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
             aof_config: Some(NoEvictionAOFConfig {
                 folder: String::from(folder),
                 cache_name:  String::from(cache_name),
-                flush_time: Some(100),
+                flush_time: None,
                 persist_read_ops: false
             })
         })
     ).await;
-    
-    for i in 0..num_ops {
-        let key = format!("key{}", i);
-        assert_eq!(cache.contains_key(&key), async_cache.contains_key(&key).await);
-        assert_eq!(cache.get(&key).cloned(), async_cache.get(&key).await);
+
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    assert_eq!(async_cache.remove(&String::from("key2")).await, None);
+    assert_eq!(async_cache.remove(&String::from("key1")).await, Some(String::from("value1")));
+    assert_eq!(async_cache.remove(&String::from("key1")).await, None);
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut operations = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        operations.push(record.operation);
+    }
+    assert_eq!(operations, vec![Operation::Put, Operation::Remove]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_get_missing_key_is_not_persisted()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_get_missing_key_is_not_persisted";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache = AsyncCache::new(
+        AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+            aof_config: Some(NoEvictionAOFConfig {
+                folder: String::from(folder),
+                cache_name:  String::from(cache_name),
+                flush_time: None,
+                persist_read_ops: true
+            })
+        })
+    ).await;
+
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    assert_eq!(async_cache.get(&String::from("key2")).await, None);
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        records.push((record.key, record.operation));
+    }
+    assert_eq!(records, vec![
+        (String::from("key1"), Operation::Put),
+        (String::from("key1"), Operation::Get),
+    ]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_replace()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_replace";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCache::new(config()).await;
+
+    assert_eq!(async_cache.replace(String::from("key1"), String::from("value1")).await, None);
+    assert_eq!(async_cache.replace(String::from("key1"), String::from("value2")).await, Some(String::from("value1")));
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        records.push((record.operation, record.value));
+    }
+    assert_eq!(records, vec![
+        (Operation::Put, Some(String::from("value1"))),
+        (Operation::Put, Some(String::from("value2"))),
+    ]);
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value2")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_borrowed_lookups()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_borrowed_lookups";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache = AsyncCache::new(
+        AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+            aof_config: Some(NoEvictionAOFConfig {
+                folder: String::from(folder),
+                cache_name:  String::from(cache_name),
+                flush_time: None,
+                persist_read_ops: true
+            })
+        })
+    ).await;
+
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    assert_eq!(async_cache.get_borrowed("key1").await, Some(String::from("value1")));
+    assert_eq!(async_cache.get_borrowed("key2").await, None);
+    assert!(async_cache.contains_key_borrowed("key1").await);
+    assert_eq!(async_cache.remove_borrowed("key1").await, Some(String::from("value1")));
+    assert_eq!(async_cache.remove_borrowed("key1").await, None);
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        records.push((record.key, record.operation));
+    }
+    assert_eq!(records, vec![
+        (String::from("key1"), Operation::Put),
+        (String::from("key1"), Operation::Get),
+        (String::from("key1"), Operation::Remove),
+    ]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_versions_are_restored()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_versions_are_restored";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCache::new(config()).await;
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    let (_, version) = async_cache.get_with_version(&String::from("key1")).await.unwrap();
+    let version = async_cache.compare_and_swap(String::from("key1"), version, String::from("value2")).await.unwrap();
+    assert!(async_cache.compare_and_swap(String::from("key1"), version - 1, String::from("value3")).await.is_err());
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.get_with_version(&String::from("key1")).await, Some((String::from("value2"), version)));
+    async_cache.put(String::from("key2"), String::from("value1")).await;
+    let (_, newer) = async_cache.get_with_version(&String::from("key2")).await.unwrap();
+    assert!(newer > version);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_put_if_newer_is_restored()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_put_if_newer_is_restored";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCache::new(config()).await;
+    assert!(async_cache.put_if_newer(String::from("key1"), String::from("value1"), 100).await);
+    assert!(!async_cache.put_if_newer(String::from("key1"), String::from("value2"), 50).await);
+    async_cache.put(String::from("key2"), String::from("value1")).await;
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+    assert!(!async_cache.put_if_newer(String::from("key1"), String::from("value2"), 100).await);
+    assert!(async_cache.put_if_newer(String::from("key1"), String::from("value3"), 101).await);
+    assert!(async_cache.put_if_newer(String::from("key2"), String::from("value2"), 1).await);
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value3")));
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(String::from("value2")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_peek_is_not_persisted()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_peek_is_not_persisted";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache = AsyncCache::new(
+        AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+            aof_config: Some(NoEvictionAOFConfig {
+                folder: String::from(folder),
+                cache_name:  String::from(cache_name),
+                flush_time: None,
+                persist_read_ops: true
+            })
+        })
+    ).await;
+
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    assert_eq!(async_cache.peek(&String::from("key1")).await, Some(String::from("value1")));
+    assert_eq!(async_cache.get_opts(&String::from("key1"), false).await, Some(String::from("value1")));
+    assert_eq!(async_cache.get_opts(&String::from("key1"), true).await, Some(String::from("value1")));
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        records.push((record.key, record.operation));
+    }
+    assert_eq!(records, vec![
+        (String::from("key1"), Operation::Put),
+        (String::from("key1"), Operation::Get),
+    ]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_put_batch_atomic()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_put_batch_atomic";
+    let folder = ".";
+    let file = format!("{}/{}.dat", folder, cache_name);
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: Some(10000),
+            persist_read_ops: false
+        })
+    });
+    let _ = tokio::fs::remove_file(&file).await;
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+
+    // the pending record is written before the batch.
+    async_cache.put(String::from("key0"), String::from("value0")).await;
+    async_cache.put_batch_atomic(vec![
+        (String::from("key1"), String::from("value1")),
+        (String::from("key2"), String::from("value2")),
+    ]).await.unwrap();
+    assert_eq!(async_cache.len().await, 3);
+    assert_eq!(async_cache.flush_health().await.pending, 0);
+
+    let aof = AOF::new(file.clone()).await;
+    let mut iter = aof.into_iter().await?;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        records.push(record.operation);
+    }
+    assert_eq!(records, vec![Operation::Put, Operation::BatchBegin, Operation::Put, Operation::Put, Operation::BatchCommit]);
+
+    // a batch cut short before its commit marker is discarded.
+    let committed_len = tokio::fs::metadata(&file).await?.len();
+    async_cache.put_batch_atomic(vec![
+        (String::from("key3"), String::from("value3")),
+        (String::from("key1"), String::from("value4")),
+    ]).await.unwrap();
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value4")));
+    drop(async_cache);
+    let file_len = tokio::fs::metadata(&file).await?.len();
+    std::fs::OpenOptions::new().write(true).open(&file)?.set_len(file_len - 1)?;
+    assert!(file_len - 1 > committed_len);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.len().await, 3);
+    assert!(!async_cache.contains_key(&String::from("key3")).await);
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+
+    tokio::fs::remove_file(&file).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_torn_batch_is_discarded_before_later_records()  -> Result<(), tokio::io::Error> {
+    let record = |operation, key: &str, value: Option<&str>, version| AOFRecord {
+        key: String::from(key),
+        value: value.map(String::from),
+        operation,
+        expires_at: None,
+        cache_id: None,
+        version,
+        origin_ts: None
     };
+    let aof = MemoryAOF::new();
+    // batches cut short by a crash, announcing their number of records or written before they did.
+    aof.on_event_multi(vec![
+        record(Operation::BatchBegin, "key1", None, Some(2)),
+        record(Operation::Put, "key1", Some("value1"), Some(1)),
+    ]).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig { aof_config: None });
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).memory_aof(aof.clone()).build().await;
+    assert_eq!(async_cache.len().await, 0);
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    drop(async_cache);
+    aof.on_event_multi(vec![
+        record(Operation::BatchBegin, "key3", None, None),
+        record(Operation::Put, "key3", Some("value3"), Some(2)),
+        record(Operation::Remove, "key4", None, None),
+    ]).await;
+
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).memory_aof(aof.clone()).build().await;
+    async_cache.put(String::from("key5"), String::from("value5")).await;
+    async_cache.put_batch_atomic(vec![
+        (String::from("key6"), String::from("value6")),
+        (String::from("key7"), String::from("value7")),
+    ]).await.unwrap();
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(config()).memory_aof(aof).build().await;
+    assert_eq!(async_cache.len().await, 4);
+    for key in ["key2", "key5", "key6", "key7"] {
+        assert!(async_cache.contains_key(&String::from(key)).await);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_remove_many()  -> Result<(), tokio::io::Error> {
+    let aof = MemoryAOF::new();
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig { aof_config: None });
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).memory_aof(aof.clone()).build().await;
+    for i in 0..5 {
+        async_cache.put(i, i).await;
+    }
+    assert_eq!(async_cache.remove_many(&[1, 3, 7, 3]).await, 2);
+    assert_eq!(async_cache.remove_many(&[8, 9]).await, 0);
+    assert_eq!(async_cache.size().await, 3);
+    assert!(!async_cache.contains_key(&1).await);
+    assert!(!async_cache.contains_key(&3).await);
+    drop(async_cache);
+
+    let mut iter = aof.into_iter().await;
+    let mut removed = vec![];
+    while let Some(record) = iter.next::<u32, u32>().await? {
+        if record.operation == Operation::Remove {
+            removed.push(record.key);
+        }
+    }
+    assert_eq!(removed, vec![1, 3]);
+
+    let async_cache: AsyncCache<u32, u32> = AsyncCacheBuilder::new(config()).memory_aof(aof).build().await;
+    assert_eq!(async_cache.size().await, 3);
+    assert_eq!(async_cache.peek(&1).await, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_drain()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_drain";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: Some(50),
+            persist_read_ops: false
+        })
+    });
+    let async_cache: AsyncCache<u32, u32> = AsyncCache::new(config()).await;
+    for i in 0..5 {
+        async_cache.put(i, i * 10).await;
+    }
+    let mut drained = async_cache.drain().await;
+    drained.sort();
+    assert_eq!(drained, (0..5).map(|i| (i, i * 10)).collect::<Vec<_>>());
+    assert!(async_cache.is_empty().await);
+    assert!(async_cache.drain().await.is_empty());
+    async_cache.put(7, 70).await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+    drop(async_cache);
+
+    let async_cache: AsyncCache<u32, u32> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.size().await, 1);
+    assert_eq!(async_cache.peek(&7).await, Some(70));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_pipeline()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_pipeline";
+    let folder = ".";
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        })
+    });
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+
+    let results = async_cache.pipeline(vec![
+        CacheOp::Get(String::from("key1")),
+        CacheOp::Get(String::from("key2")),
+        CacheOp::Put(String::from("key2"), String::from("value2")),
+        CacheOp::Contains(String::from("key2")),
+        CacheOp::Remove(String::from("key1")),
+        CacheOp::Remove(String::from("key3")),
+        CacheOp::Contains(String::from("key1")),
+    ]).await;
+    assert_eq!(results, vec![
+        OpResult::Get(Some(String::from("value1"))),
+        OpResult::Get(None),
+        OpResult::Put,
+        OpResult::Contains(true),
+        OpResult::Remove(Some(String::from("value1"))),
+        OpResult::Remove(None),
+        OpResult::Contains(false),
+    ]);
+    assert!(async_cache.pipeline(vec![]).await.is_empty());
+    drop(async_cache);
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut operations = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        operations.push(record.operation);
+    }
+    assert_eq!(operations, vec![Operation::Put, Operation::Get, Operation::Put, Operation::Remove]);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.len().await, 1);
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(String::from("value2")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_degrades_to_memory() -> Result<(), tokio::io::Error> {
+    use sine_cache::cache::AsyncCacheBuilder;
+    use sine_cache::config::AofFailureMode;
+
+    // a file where the folder should be, so that the `AOF` can not be opened.
+    let folder = "./test_no_eviction_async_cache_degrades_to_memory";
+    tokio::fs::write(folder, b"").await?;
+    let config = AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name: String::from("cache"),
+            flush_time: Some(100),
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCacheBuilder::new(config).aof_failure(AofFailureMode::DegradeToMemory).build().await;
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+    let error = async_cache.flush_health().await.last_error.unwrap();
+    assert!(error.contains("test_no_eviction_async_cache_degrades_to_memory/cache.dat"));
+
+    tokio::fs::remove_file(folder).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_validate_lists_all_problems() -> Result<(), tokio::io::Error> {
+    use sine_cache::cache::AsyncCacheBuilder;
+    use sine_cache::config::AofFailureMode;
+    use sine_cache::error::ConfigError;
+
+    let file = "./test_no_eviction_async_cache_validate_lists_all_problems";
+    tokio::fs::write(file, b"").await?;
+    let folder = String::from(file);
+    let config = AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: folder.clone(),
+            cache_name: String::from("a/b"),
+            flush_time: Some(0),
+            persist_read_ops: false
+        })
+    });
+    let builder = AsyncCacheBuilder::<String, String>::new(config).sweep_interval(Some(std::time::Duration::ZERO));
+    assert_eq!(builder.validate(), Err(vec![
+        ConfigError::ZeroFlushTime,
+        ConfigError::InvalidCacheName(String::from("a/b")),
+        ConfigError::FolderNotWritable { folder, detail: format!("{} is not a folder", file) },
+        ConfigError::ZeroSweepInterval,
+    ]));
+
+    // the cache works in memory if the folder can not be written.
+    let builder = builder.aof_failure(AofFailureMode::DegradeToMemory);
+    assert_eq!(builder.validate().unwrap_err().len(), 3);
+
+    tokio::fs::remove_file(file).await?;
+    Ok(())
+}
+
+#[cfg(all(feature = "mmap", unix))]
+#[tokio::test]
+async fn test_no_eviction_async_cache_with_mmap_aof() -> Result<(), tokio::io::Error> {
+    use sine_cache::cache::AsyncCacheBuilder;
+
+    let cache_name = "test_no_eviction_async_cache_with_mmap_aof";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.mmap", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name: String::from(cache_name),
+            flush_time: Some(50),
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCacheBuilder::new(config()).mmap_aof(256).build().await;
+    for i in 0..100 {
+        async_cache.put(i, i.to_string()).await;
+    }
+    async_cache.remove(&0).await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(async_cache.flush_health().await.pending, 0);
+    drop(async_cache);
+
+    let async_cache: AsyncCache<u32, String> = AsyncCacheBuilder::new(config()).mmap_aof(256).build().await;
+    assert_eq!(async_cache.len().await, 99);
+    assert_eq!(async_cache.get(&99).await, Some(String::from("99")));
+    assert!(tokio::fs::metadata(format!("{}/{}.dat", folder, cache_name)).await.is_err());
+
+    tokio::fs::remove_file(format!("{}/{}.mmap", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_adaptive_flush_shortens_on_burst() -> Result<(), tokio::io::Error> {
+    use std::time::Duration;
+    use sine_cache::{cache::AsyncCacheBuilder, config::FlushStrategy, error::ConfigError};
+
+    let adaptive = FlushStrategy::Adaptive { min: Duration::from_millis(10), max: Duration::from_secs(10), target_batch: 50 };
+    assert_eq!(adaptive.interval(0), Duration::from_secs(10));
+    assert_eq!(adaptive.interval(50), Duration::from_millis(10));
+    assert_eq!(adaptive.interval(500), Duration::from_millis(10));
+    assert!(adaptive.interval(25) < Duration::from_secs(10) && adaptive.interval(25) > Duration::from_millis(10));
+
+    let cache_name = "test_no_eviction_async_cache_adaptive_flush_shortens_on_burst";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = |flush_time| AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name: String::from(cache_name),
+            flush_time,
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCacheBuilder::new(config(Some(10_000))).flush_strategy(adaptive).build().await;
+    for i in 0..5 {
+        async_cache.put(i, i).await;
+    }
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(async_cache.flush_health().await.pending, 5);
+
+    for i in 5..105 {
+        async_cache.put(i, i).await;
+    }
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(async_cache.flush_health().await.pending, 0);
+    drop(async_cache);
+
+    let builder: AsyncCacheBuilder<u32, u32> = AsyncCacheBuilder::new(config(None))
+        .flush_strategy(FlushStrategy::Adaptive { min: Duration::from_secs(1), max: Duration::from_millis(1), target_batch: 50 });
+    assert_eq!(builder.validate().unwrap_err(), vec![ConfigError::FlushStrategyWithoutFlushTime, ConfigError::InvalidFlushStrategy]);
+
     tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_should_persist_skips_rejected_values()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_should_persist_skips_rejected_values";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        })
+    });
+    let build = || sine_cache::cache::AsyncCacheBuilder::new(config())
+        .should_persist(|x: &Result<u32, String>| x.is_ok())
+        .build();
+    let async_cache = build().await;
+    async_cache.put(String::from("key1"), Ok(1)).await;
+    async_cache.put(String::from("key2"), Ok(2)).await;
+    async_cache.put(String::from("key2"), Err(String::from("timeout"))).await;
+    async_cache.put(String::from("key3"), Err(String::from("timeout"))).await;
+    assert_eq!(async_cache.get(&String::from("key3")).await, Some(Err(String::from("timeout"))));
+    drop(async_cache);
+
+    let async_cache = build().await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(Ok(1)));
+    assert_eq!(async_cache.get(&String::from("key2")).await, None);
+    assert_eq!(async_cache.get(&String::from("key3")).await, None);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_eviction_async_cache_compare_and_remove_is_restored()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_compare_and_remove_is_restored";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCache::new(config()).await;
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    async_cache.put(String::from("key2"), String::from("value1")).await;
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    assert!(async_cache.compare_and_remove(&String::from("key1"), &String::from("value1")).await);
+    assert!(!async_cache.compare_and_remove(&String::from("key2"), &String::from("value1")).await);
+    assert!(!async_cache.compare_and_remove(&String::from("key3"), &String::from("value1")).await);
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(String::from("value2")));
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, None);
+    assert_eq!(async_cache.get(&String::from("key2")).await, Some(String::from("value2")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+/// Test that only the updates of replace_if_present are recorded and restored.
+#[tokio::test]
+async fn test_no_eviction_async_cache_replace_if_present_is_restored()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_no_eviction_async_cache_replace_if_present_is_restored";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let config = || AsyncCacheConfig::NoEviction(NoEvictionAsyncConfig {
+        aof_config: Some(NoEvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: false
+        })
+    });
+    let async_cache = AsyncCache::new(config()).await;
+    async_cache.put(String::from("key1"), String::from("value1")).await;
+    assert_eq!(async_cache.replace_if_present(&String::from("key1"), String::from("value2")).await, Some(String::from("value1")));
+    assert_eq!(async_cache.replace_if_present(&String::from("key2"), String::from("value2")).await, None);
+    assert_eq!(async_cache.aof_stats().await.unwrap().approx_records, 2);
+    drop(async_cache);
+
+    let async_cache: AsyncCache<String, String> = AsyncCache::new(config()).await;
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value2")));
+    assert!(!async_cache.contains_key(&String::from("key2")).await);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
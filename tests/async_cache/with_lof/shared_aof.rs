@@ -0,0 +1,105 @@
+use sine_cache::aof::AofMultiplexer;
+use sine_cache::{cache::{AsyncCache, AsyncCacheBuilder}, config::{AsyncCacheConfig, EvictionAsyncConfig}};
+
+fn lru_config<K>() -> AsyncCacheConfig<K> {
+    AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: None,
+        max_size: 10
+    })
+}
+
+#[tokio::test]
+async fn test_caches_sharing_aof_replay_their_own_records()  -> Result<(), tokio::io::Error> {
+    let name = "test_caches_sharing_aof_replay_their_own_records";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, name)).await;
+    let multiplexer = AofMultiplexer::new(String::from(folder), String::from(name), None).await;
+    let strings: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 1)
+        .build()
+        .await;
+    let numbers: AsyncCache<u32, u64> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 2)
+        .build()
+        .await;
+
+    strings.put(String::from("key1"), String::from("value1")).await;
+    numbers.put(1, 100).await;
+    strings.put(String::from("key2"), String::from("value2")).await;
+    numbers.put(2, 200).await;
+    strings.remove(&String::from("key1")).await;
+    drop(strings);
+    drop(numbers);
+
+    let strings: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 1)
+        .build()
+        .await;
+    let numbers: AsyncCache<u32, u64> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 2)
+        .build()
+        .await;
+    assert_eq!(strings.size().await, 1);
+    assert_eq!(strings.get(&String::from("key2")).await, Some(String::from("value2")));
+    assert_eq!(numbers.size().await, 2);
+    assert_eq!(numbers.get(&1).await, Some(100));
+    assert_eq!(numbers.get(&2).await, Some(200));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[should_panic(expected = "cache id 1 is already registered.")]
+async fn test_shared_aof_duplicate_cache_id_panics() {
+    let name = "test_shared_aof_duplicate_cache_id_panics";
+    let multiplexer = AofMultiplexer::new(String::from("."), String::from(name), None).await;
+    let _ = tokio::fs::remove_file(format!("./{}.dat", name)).await;
+    let _first: AsyncCache<u32, u32> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 1)
+        .build()
+        .await;
+    let _second: AsyncCache<u32, u32> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 1)
+        .build()
+        .await;
+}
+
+#[tokio::test]
+async fn test_shared_aof_put_batch_atomic()  -> Result<(), tokio::io::Error> {
+    let name = "test_shared_aof_put_batch_atomic";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, name)).await;
+    let multiplexer = AofMultiplexer::new(String::from(folder), String::from(name), Some(10000)).await;
+    let strings: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 1)
+        .build()
+        .await;
+    let numbers: AsyncCache<u32, u64> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 2)
+        .build()
+        .await;
+
+    numbers.put(1, 100).await;
+    strings.put_batch_atomic(vec![
+        (String::from("key1"), String::from("value1")),
+        (String::from("key2"), String::from("value2")),
+    ]).await.unwrap();
+    assert_eq!(multiplexer.flush_health().await.pending, 0);
+    drop(strings);
+    drop(numbers);
+
+    let strings: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 1)
+        .build()
+        .await;
+    let numbers: AsyncCache<u32, u64> = AsyncCacheBuilder::new(lru_config())
+        .shared_aof(multiplexer.clone(), 2)
+        .build()
+        .await;
+    assert_eq!(strings.size().await, 2);
+    assert_eq!(numbers.get(&1).await, Some(100));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, name)).await?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use sine_cache::aof::AOF;
+use sine_cache::{cache::{AsyncCache, AsyncCacheBuilder}, common::{Lookup, MockClock, Operation}, config::{AsyncCacheConfig, EvictionAOFConfig, EvictionAsyncConfig}};
+
+fn lru_config(folder: &str, cache_name: &str) -> AsyncCacheConfig<String> {
+    AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from(folder),
+            cache_name:  String::from(cache_name),
+            flush_time: None,
+            persist_read_ops: true
+        }),
+        max_size: 10
+    })
+}
+
+#[tokio::test]
+async fn test_ttl_is_restored_from_aof()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_ttl_is_restored_from_aof";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+
+    async_cache.put_with_ttl(String::from("short"), String::from("value1"), Duration::from_millis(100)).await;
+    async_cache.put_with_ttl(String::from("long"), String::from("value2"), Duration::from_secs(60)).await;
+    async_cache.put(String::from("forever"), String::from("value3")).await;
+    drop(async_cache);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.size().await, 2);
+    assert!(!async_cache.contains_key(&String::from("short")).await);
+    assert_eq!(async_cache.get(&String::from("long")).await, Some(String::from("value2")));
+    assert_eq!(async_cache.get(&String::from("forever")).await, Some(String::from("value3")));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sweeper_purges_and_persists_removes()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_sweeper_purges_and_persists_removes";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name))
+        .allow_sync_writes(true)
+        .sweep_interval(Some(Duration::from_millis(50)))
+        .build()
+        .await;
+
+    async_cache.put_with_ttl(String::from("key1"), String::from("value1"), Duration::from_millis(20)).await;
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(async_cache.size().await, 1);
+
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut records = vec![];
+    while let Some(record) = iter.next::<String, String>().await? {
+        records.push((record.key, record.operation, record.expires_at.is_some()));
+    }
+    assert_eq!(records, vec![
+        (String::from("key1"), Operation::Put, true),
+        (String::from("key2"), Operation::Put, false),
+        (String::from("key1"), Operation::Remove, false),
+    ]);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_absent_keys_are_restored_from_aof()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_absent_keys_are_restored_from_aof";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, Option<String>> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+
+    async_cache.put(String::from("present"), Some(String::from("value1"))).await;
+    async_cache.put_absent(String::from("absent"), Duration::from_secs(60)).await;
+    async_cache.put_absent(String::from("short"), Duration::from_millis(100)).await;
+    drop(async_cache);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let async_cache: AsyncCache<String, Option<String>> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.lookup(&String::from("present")).await, Lookup::Present(String::from("value1")));
+    assert_eq!(async_cache.lookup(&String::from("absent")).await, Lookup::KnownAbsent);
+    assert_eq!(async_cache.lookup(&String::from("short")).await, Lookup::Unknown);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_drain_expired_returns_and_persists_expired_entries()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_drain_expired_returns_and_persists_expired_entries";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+
+    async_cache.put_with_ttl(String::from("key1"), String::from("value1"), Duration::from_millis(20)).await;
+    for i in 0..9 {
+        async_cache.put_with_ttl(format!("short{}", i), format!("value{}", i), Duration::from_millis(20)).await;
+    }
+    // key1 is evicted for capacity, so it is never drained.
+    async_cache.put(String::from("key2"), String::from("value2")).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut drained = async_cache.drain_expired().await;
+    drained.sort();
+    assert_eq!(drained, (0..9).map(|i| (format!("short{}", i), format!("value{}", i))).collect::<Vec<_>>());
+    assert!(async_cache.drain_expired().await.is_empty());
+    assert_eq!(async_cache.size().await, 1);
+
+    drop(async_cache);
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+    assert_eq!(async_cache.size().await, 1);
+    let aof = AOF::new(format!("{}/{}.dat", folder, cache_name)).await;
+    let mut iter = aof.into_iter().await?;
+    let mut removes = 0;
+    while let Some(record) = iter.next::<String, String>().await? {
+        if record.operation == Operation::Remove {
+            removes += 1;
+        }
+    }
+    assert_eq!(removes, 9);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_set_ttl_is_restored_from_aof()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_set_ttl_is_restored_from_aof";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+
+    async_cache.put(String::from("short"), String::from("value1")).await;
+    async_cache.put_with_ttl(String::from("forever"), String::from("value2"), Duration::from_millis(100)).await;
+    let (_, version) = async_cache.get_with_version(&String::from("forever")).await.unwrap();
+    assert!(async_cache.set_ttl(&String::from("short"), Some(Duration::from_millis(100))).await);
+    assert!(async_cache.set_ttl(&String::from("forever"), None).await);
+    assert!(!async_cache.set_ttl(&String::from("absent"), None).await);
+    assert!(async_cache.get_ttl(&String::from("short")).await.is_some());
+    assert_eq!(async_cache.get_ttl(&String::from("forever")).await, None);
+    drop(async_cache);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name)).allow_sync_writes(true).build().await;
+    assert!(!async_cache.contains_key(&String::from("short")).await);
+    assert_eq!(async_cache.get_with_version(&String::from("forever")).await, Some((String::from("value2"), version)));
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expiry_with_mock_clock()  -> Result<(), tokio::io::Error> {
+    let cache_name = "test_expiry_with_mock_clock";
+    let folder = ".";
+    let _ = tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await;
+    let clock = MockClock::new();
+    let async_cache: AsyncCache<String, String> = AsyncCacheBuilder::new(lru_config(folder, cache_name))
+        .allow_sync_writes(true)
+        .clock(clock.clone())
+        .build()
+        .await;
+
+    async_cache.put_with_ttl(String::from("key1"), String::from("value1"), Duration::from_secs(60)).await;
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(async_cache.get_ttl(&String::from("key1")).await, Some(Duration::from_secs(30)));
+    assert_eq!(async_cache.get(&String::from("key1")).await, Some(String::from("value1")));
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(async_cache.get(&String::from("key1")).await, None);
+    assert_eq!(async_cache.len().await, 0);
+
+    tokio::fs::remove_file(format!("{}/{}.dat", folder, cache_name)).await?;
+    Ok(())
+}
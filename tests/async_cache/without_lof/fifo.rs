@@ -25,8 +25,8 @@ async fn test_basic_get_ref_put() {
     cache.put("K1".to_string(), 1).await;
     cache.put("K2".to_string(), 2).await;
 
-    assert_eq!(cache.get_ref(&"K1".to_string()).await, Some(&1));
-    assert_eq!(cache.get_ref(&"K2".to_string()).await, Some(&2));
+    assert_eq!(cache.get_ref(&"K1".to_string()).await.as_deref(), Some(&1));
+    assert_eq!(cache.get_ref(&"K2".to_string()).await.as_deref(), Some(&2));
 }
 
 /// Test FIFO eviction policy when inserting more items than the cache capacity.
@@ -39,12 +39,12 @@ async fn test_lru_eviction() {
     cache.put("K1".to_string(), 10).await;
     cache.put("K3".to_string(), 3).await;
 
-    assert_eq!(cache.get_ref(&"K1".to_string()).await, None);
-    assert_eq!(cache.get_ref(&"K2".to_string()).await, Some(&2));
-    assert_eq!(cache.get_ref(&"K3".to_string()).await, Some(&3));
+    assert_eq!(cache.get_ref(&"K1".to_string()).await.as_deref(), None);
+    assert_eq!(cache.get_ref(&"K2".to_string()).await.as_deref(), Some(&2));
+    assert_eq!(cache.get_ref(&"K3".to_string()).await.as_deref(), Some(&3));
     cache.put("K4".to_string(), 4).await;
-    assert_eq!(cache.get_ref(&"K4".to_string()).await, Some(&4));
-    assert_eq!(cache.get_ref(&"K2".to_string()).await, None);
+    assert_eq!(cache.get_ref(&"K4".to_string()).await.as_deref(), Some(&4));
+    assert_eq!(cache.get_ref(&"K2".to_string()).await.as_deref(), None);
 }
 
 #[tokio::test]
@@ -0,0 +1,285 @@
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+use std::time::Duration;
+
+use sine_cache::{cache::{AsyncCache, AsyncCacheBuilder}, common::Lookup, config::{AsyncCacheConfig, EvictionAsyncConfig}};
+
+async fn lru_cache() -> Arc<AsyncCache<String, String>> {
+    Arc::new(AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})).await)
+}
+
+#[tokio::test]
+async fn test_get_or_insert_with_runs_init_once() {
+    let cache = lru_cache().await;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut handles = vec![];
+    for _ in 0..10 {
+        let cache = cache.clone();
+        let calls = calls.clone();
+        handles.push(tokio::spawn(async move {
+            cache.get_or_insert_with(String::from("key1"), || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                String::from("value1")
+            }).await
+        }));
+    }
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), String::from("value1"));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.size().await, 1);
+}
+
+#[tokio::test]
+async fn test_get_or_try_insert_with_does_not_cache_errors() {
+    let cache = lru_cache().await;
+    let result: Result<String, &str> = cache.get_or_try_insert_with(String::from("key1"), || async { Err("unavailable") }).await;
+    assert_eq!(result, Err("unavailable"));
+    assert!(!cache.contains_key(&String::from("key1")).await);
+
+    let result: Result<String, &str> = cache.get_or_try_insert_with(String::from("key1"), || async { Ok(String::from("value1")) }).await;
+    assert_eq!(result, Ok(String::from("value1")));
+    let result: Result<String, &str> = cache.get_or_try_insert_with(String::from("key1"), || async { Err("not called") }).await;
+    assert_eq!(result, Ok(String::from("value1")));
+}
+
+#[tokio::test]
+async fn test_get_or_try_insert_with_waiters_retry_after_error() {
+    let cache = lru_cache().await;
+    let failing = {
+        let cache = cache.clone();
+        tokio::spawn(async move {
+            cache.get_or_try_insert_with(String::from("key1"), || async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Err::<String, &str>("unavailable")
+            }).await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let waiting = {
+        let cache = cache.clone();
+        tokio::spawn(async move {
+            cache.get_or_try_insert_with(String::from("key1"), || async {
+                Ok::<String, &str>(String::from("value1"))
+            }).await
+        })
+    };
+    assert_eq!(failing.await.unwrap(), Err("unavailable"));
+    assert_eq!(waiting.await.unwrap(), Ok(String::from("value1")));
+    assert_eq!(cache.get(&String::from("key1")).await, Some(String::from("value1")));
+}
+
+#[tokio::test]
+async fn test_get_or_insert_absent_with_caches_misses_for_ttl() {
+    let cache: AsyncCache<String, Option<String>> = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})).await;
+    let calls = AtomicUsize::new(0);
+
+    for _ in 0..3 {
+        let value = cache.get_or_insert_absent_with(String::from("key1"), Duration::from_millis(50), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            None
+        }).await;
+        assert_eq!(value, None);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.lookup(&String::from("key1")).await, Lookup::KnownAbsent);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(cache.lookup(&String::from("key1")).await, Lookup::Unknown);
+    let value = cache.get_or_insert_absent_with(String::from("key1"), Duration::from_millis(50), || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Some(String::from("value1"))
+    }).await;
+    assert_eq!(value, Some(String::from("value1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(cache.lookup(&String::from("key1")).await, Lookup::Present(String::from("value1")));
+}
+
+#[tokio::test]
+async fn test_get_with_loader_loads_misses_once() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let loader_calls = calls.clone();
+    let cache: AsyncCache<String, String> = AsyncCacheBuilder::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None}))
+        .loader(move |key: String| {
+            let calls = loader_calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                (key != "missing").then(|| format!("value of {}", key))
+            }
+        })
+        .build()
+        .await;
+
+    let mut handles = vec![];
+    for _ in 0..10 {
+        let cache = cache.clone();
+        handles.push(tokio::spawn(async move { cache.get(&String::from("key1")).await }));
+    }
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), Some(String::from("value of key1")));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.peek(&String::from("key1")).await, Some(String::from("value of key1")));
+    // every `get` counts a single lookup, also the ones waiting for the loader.
+    let lookups = cache.stats().await.lookups;
+    assert_eq!(lookups.hits + lookups.misses_absent, 10);
+
+    // keys the loader does not find are not cached.
+    assert_eq!(cache.get(&String::from("missing")).await, None);
+    assert_eq!(cache.get(&String::from("missing")).await, None);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(cache.size().await, 1);
+    assert_eq!(cache.stats().await.lookups.misses_absent, lookups.misses_absent + 2);
+
+    // other reads don't load.
+    assert_eq!(cache.peek(&String::from("key2")).await, None);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_get_or_insert_arc_shares_single_value() {
+    let cache: Arc<AsyncCache<String, Arc<Vec<u8>>>> = Arc::new(
+        AsyncCache::new_without_aof(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})).await
+    );
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut handles = vec![];
+    for _ in 0..10 {
+        let cache = cache.clone();
+        let calls = calls.clone();
+        handles.push(tokio::spawn(async move {
+            cache.get_or_insert_arc(String::from("key1"), || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                vec![1; 1024]
+            }).await
+        }));
+    }
+    let mut values = vec![];
+    for handle in handles {
+        values.push(handle.await.unwrap());
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    let stored = cache.get(&String::from("key1")).await.unwrap();
+    assert!(values.iter().all(|x| Arc::ptr_eq(x, &stored)));
+}
+
+#[tokio::test]
+async fn test_get_with_loader_timeout_gives_up_on_slow_loader() {
+    use sine_cache::error::LoaderTimeout;
+
+    let cache: AsyncCache<String, String> = AsyncCacheBuilder::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None}))
+        .loader(|key: String| async move {
+            if key == "slow" {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Some(format!("value of {}", key))
+        })
+        .loader_timeout(Duration::from_millis(50))
+        .build()
+        .await;
+
+    let timeout = Duration::from_millis(30);
+    let mut handles = vec![];
+    for _ in 0..5 {
+        let cache = cache.clone();
+        handles.push(tokio::spawn(async move { cache.get_with_loader_timeout(&String::from("slow"), timeout).await }));
+    }
+    let started = std::time::Instant::now();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), Err(LoaderTimeout { timeout }));
+    }
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(cache.size().await, 0);
+
+    assert_eq!(cache.get(&String::from("slow")).await, None);
+    assert_eq!(cache.get_with_loader_timeout(&String::from("fast"), timeout).await, Ok(Some(String::from("value of fast"))));
+    assert_eq!(cache.get(&String::from("fast")).await, Some(String::from("value of fast")));
+}
+
+#[tokio::test]
+async fn test_get_or_refresh_ahead_refreshes_once_in_background() {
+    use sine_cache::common::MockClock;
+    use tokio::sync::Semaphore;
+
+    let clock = MockClock::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    // every load waits for a permit, so the background refresh only completes when the test allows it.
+    let permits = Arc::new(Semaphore::new(1));
+    let (loader_calls, loader_permits) = (calls.clone(), permits.clone());
+    let cache: AsyncCache<String, String> = AsyncCacheBuilder::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None}))
+        .loader(move |key: String| {
+            let (calls, permits) = (loader_calls.clone(), loader_permits.clone());
+            async move {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                permits.acquire().await.unwrap().forget();
+                Some(format!("value {} of {}", call, key))
+            }
+        })
+        .loader_ttl(Duration::from_secs(10))
+        .refresh_ahead_fraction(0.2)
+        .clock(clock.clone())
+        .build()
+        .await;
+    let key = String::from("key1");
+
+    assert_eq!(cache.get_or_refresh_ahead(&key).await, Some(String::from("value 1 of key1")));
+    assert_eq!(cache.get_ttl(&key).await, Some(Duration::from_secs(10)));
+    clock.advance(Duration::from_secs(7));
+    assert_eq!(cache.get_or_refresh_ahead(&key).await, Some(String::from("value 1 of key1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // within the last 2 seconds the current value is returned while a single refresh runs.
+    clock.advance(Duration::from_secs(1));
+    for _ in 0..5 {
+        assert_eq!(cache.get_or_refresh_ahead(&key).await, Some(String::from("value 1 of key1")));
+    }
+    permits.add_permits(1);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while cache.peek(&key).await != Some(String::from("value 2 of key1")) {
+            tokio::task::yield_now().await;
+        }
+    }).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.get_ttl(&key).await, Some(Duration::from_secs(10)));
+    assert_eq!(cache.get_or_refresh_ahead(&key).await, Some(String::from("value 2 of key1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_get_or_refresh_ahead_does_not_bring_back_removed_key() {
+    use sine_cache::common::MockClock;
+    use tokio::sync::Semaphore;
+
+    let clock = MockClock::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let permits = Arc::new(Semaphore::new(1));
+    let (loader_calls, loader_permits) = (calls.clone(), permits.clone());
+    let cache: AsyncCache<String, String> = AsyncCacheBuilder::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None}))
+        .loader(move |key: String| {
+            let (calls, permits) = (loader_calls.clone(), loader_permits.clone());
+            async move {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                permits.acquire().await.unwrap().forget();
+                Some(format!("value {} of {}", call, key))
+            }
+        })
+        .loader_ttl(Duration::from_secs(10))
+        .refresh_ahead_fraction(0.2)
+        .clock(clock.clone())
+        .build()
+        .await;
+    let key = String::from("key1");
+
+    assert_eq!(cache.get_or_refresh_ahead(&key).await, Some(String::from("value 1 of key1")));
+    clock.advance(Duration::from_secs(9));
+    assert_eq!(cache.get_or_refresh_ahead(&key).await, Some(String::from("value 1 of key1")));
+    assert!(cache.remove(&key).await.is_some());
+
+    // the miss waits for the refresh, whose value is dropped, then loads the key again.
+    permits.add_permits(2);
+    assert_eq!(cache.get(&key).await, Some(String::from("value 3 of key1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
@@ -24,10 +24,10 @@ async fn test_get_ref_and_remove() {
     cache.put("K1".to_string(), 1).await;
     cache.put("K2".to_string(), 2).await;
 
-    assert_eq!(cache.get_ref(&"K1".to_string()).await, Some(&1));
+    assert_eq!(cache.get_ref(&"K1".to_string()).await.as_deref(), Some(&1));
 
     cache.remove(&"K2".to_string()).await;
-    assert_eq!(cache.get_ref(&"K2".to_string()).await, None);
+    assert_eq!(cache.get_ref(&"K2".to_string()).await.as_deref(), None);
 }
 
 #[test]
@@ -25,8 +25,8 @@ async fn test_basic_get_ref_put() {
     cache.put("K1".to_string(), 1).await;
     cache.put("K2".to_string(), 2).await;
 
-    assert_eq!(cache.get_ref(&"K1".to_string()).await, Some(&1));
-    assert_eq!(cache.get_ref(&"K2".to_string()).await, Some(&2));
+    assert_eq!(cache.get_ref(&"K1".to_string()).await.as_deref(), Some(&1));
+    assert_eq!(cache.get_ref(&"K2".to_string()).await.as_deref(), Some(&2));
 }
 
 /// Test LRU eviction policy when inserting more items than the cache capacity.
@@ -39,13 +39,13 @@ async fn test_lru_eviction() {
     cache.put("K1".to_string(), 10).await;
     cache.put("K3".to_string(), 3).await;
 
-    assert_eq!(cache.get_ref(&"K1".to_string()).await, Some(&10));
+    assert_eq!(cache.get_ref(&"K1".to_string()).await.as_deref(), Some(&10));
     assert!(cache.contains_key(&"K1".to_string()).await);
-    assert_eq!(cache.get_ref(&"K2".to_string()).await, None);
-    assert_eq!(cache.get_ref(&"K3".to_string()).await, Some(&3));
+    assert_eq!(cache.get_ref(&"K2".to_string()).await.as_deref(), None);
+    assert_eq!(cache.get_ref(&"K3".to_string()).await.as_deref(), Some(&3));
     cache.put("K4".to_string(), 4).await;
-    assert_eq!(cache.get_ref(&"K4".to_string()).await, Some(&4));
-    assert_eq!(cache.get_ref(&"K1".to_string()).await, None);
+    assert_eq!(cache.get_ref(&"K4".to_string()).await.as_deref(), Some(&4));
+    assert_eq!(cache.get_ref(&"K1".to_string()).await.as_deref(), None);
     assert!(!cache.contains_key(&"K1".to_string()).await);
 }
 
@@ -111,3 +111,306 @@ async fn test_thread_safe_lru_cache() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_clones_share_the_cache() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})).await;
+
+    let mut handles = vec![];
+    for i in 0..5 {
+        let cache = cache.clone();
+        handles.push(tokio::spawn(async move {
+            cache.put(i, format!("Value{}", i)).await;
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(cache.size().await, 5);
+    let other = cache.clone();
+    other.remove(&0).await;
+    assert!(!cache.contains_key(&0).await);
+    assert_eq!(cache.get(&4).await, Some(String::from("Value4")));
+}
+
+/// Test that the value can not be removed while a reference to it is held.
+#[tokio::test]
+async fn test_get_ref_blocks_removal_until_dropped() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    cache.put("K1".to_string(), String::from("one")).await;
+
+    let value = cache.get_ref(&"K1".to_string()).await.unwrap();
+    let cache_clone = cache.clone();
+    let remove = tokio::spawn(async move { cache_clone.remove(&"K1".to_string()).await });
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    assert!(!remove.is_finished());
+    assert_eq!(value.as_str(), "one");
+
+    drop(value);
+    assert_eq!(remove.await.unwrap(), Some(String::from("one")));
+    assert!(cache.get_ref(&"K1".to_string()).await.is_none());
+}
+
+#[tokio::test]
+async fn test_read_many_reads_values_under_one_lock() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    cache.put("K1".to_string(), String::from("one")).await;
+    cache.put("K2".to_string(), String::from("two")).await;
+
+    let keys = ["K1".to_string(), "K3".to_string(), "K2".to_string()];
+    let joined = cache.read_many(&keys, |values| {
+        values.iter().map(|x| x.map_or("-", |x| x.as_str())).collect::<Vec<_>>().join(",")
+    }).await;
+    assert_eq!(joined, "one,-,two");
+
+    // reads count as accesses, so K1 is used more recently than K2.
+    cache.read_many(&keys[..1], |_| ()).await;
+    cache.put("K3".to_string(), String::from("three")).await;
+    assert!(cache.contains_key(&"K1".to_string()).await);
+    assert!(!cache.contains_key(&"K2".to_string()).await);
+}
+
+#[tokio::test]
+async fn test_touch_many_protects_keys_from_eviction() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 3, aof_config: None})).await;
+    cache.put("K1".to_string(), 1).await;
+    cache.put("K2".to_string(), 2).await;
+    cache.put("K3".to_string(), 3).await;
+
+    let keys = ["K1".to_string(), "K4".to_string(), "K2".to_string()];
+    assert_eq!(cache.touch_many(&keys).await, 2);
+
+    // K3 is now the least recently used key.
+    cache.put("K4".to_string(), 4).await;
+    assert!(cache.contains_key(&"K1".to_string()).await);
+    assert!(cache.contains_key(&"K2".to_string()).await);
+    assert!(!cache.contains_key(&"K3".to_string()).await);
+}
+
+#[tokio::test]
+async fn test_update_in_place() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    cache.put("K1".to_string(), vec![1]).await;
+    cache.put("K2".to_string(), vec![2]).await;
+
+    // bookkeeping which does not protect K1 from eviction.
+    assert_eq!(cache.update(&"K1".to_string(), false, |x| { x.push(3); x.len() }).await, Some(2));
+    assert_eq!(cache.next_victim().await, Some("K1".to_string()));
+
+    let (_, version) = cache.get_with_version(&"K2".to_string()).await.unwrap();
+    assert_eq!(cache.update(&"K1".to_string(), true, |x| x.push(4)).await, Some(()));
+    assert_eq!(cache.next_victim().await, Some("K2".to_string()));
+    assert!(cache.get_with_version(&"K1".to_string()).await.unwrap().1 > version);
+    assert_eq!(cache.peek(&"K1".to_string()).await, Some(vec![1, 3, 4]));
+    assert_eq!(cache.update(&"K3".to_string(), true, |x| x.push(5)).await, None);
+}
+
+#[tokio::test]
+async fn test_stale_reads_catch_up_after_refresh() {
+    use sine_cache::cache::AsyncCacheBuilder;
+    use std::time::Duration;
+
+    let config = AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None});
+    let cache = AsyncCacheBuilder::new(config).stale_reads(Duration::from_millis(50)).build().await;
+    cache.put("K1".to_string(), 1).await;
+    // not published until the next refresh.
+    assert_eq!(cache.get_stale(&"K1".to_string()), None);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(cache.get_stale(&"K1".to_string()), Some(1));
+
+    cache.remove(&"K1".to_string()).await;
+    assert_eq!(cache.get_stale(&"K1".to_string()), Some(1));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(cache.get_stale(&"K1".to_string()), None);
+}
+
+#[tokio::test]
+async fn test_stats_stream_sends_snapshots_until_cache_is_dropped() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    let mut stats = cache.stats_stream(std::time::Duration::from_millis(10));
+    cache.put("K1".to_string(), 1).await;
+    cache.put("K2".to_string(), 2).await;
+    cache.put("K3".to_string(), 3).await;
+
+    let snapshot = stats.next().await.unwrap();
+    assert_eq!((snapshot.size, snapshot.live_size), (2, 2));
+    assert_eq!(snapshot, cache.stats().await);
+    assert!(snapshot.aof_stats.is_none());
+
+    drop(cache);
+    let ended = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+        while stats.next().await.is_some() {}
+    }).await;
+    assert!(ended.is_ok());
+}
+
+#[tokio::test]
+async fn test_stats_stream_ends_on_shutdown() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    let mut stats = cache.stats_stream(std::time::Duration::from_millis(10));
+    // a clone keeps the cache alive, only `shutdown` stops the task.
+    let clone = cache.clone();
+    clone.put("K1".to_string(), 1).await;
+    assert!(stats.next().await.is_some());
+
+    // the task is waiting for the stream to take the next snapshot, which it never does.
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let shutdown = tokio::time::timeout(std::time::Duration::from_secs(1), cache.shutdown()).await;
+    assert!(matches!(shutdown, Ok(Ok(()))));
+    while stats.next().await.is_some() {}
+    drop(clone);
+}
+
+#[tokio::test]
+async fn test_try_put_fails_when_nothing_can_be_evicted() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 1, aof_config: None})).await;
+    cache.put("K1".to_string(), 1).await;
+    cache.pin(&"K1".to_string()).await;
+
+    let full = cache.try_put("K2".to_string(), 2).await.unwrap_err();
+    assert_eq!((full.key.as_str(), full.value), ("K2", 2));
+    assert_eq!(cache.get(&"K1".to_string()).await, Some(1));
+
+    cache.unpin(&"K1".to_string()).await;
+    assert_eq!(cache.try_put("K2".to_string(), 2).await, Ok(None));
+    assert_eq!(cache.get(&"K2".to_string()).await, Some(2));
+}
+
+#[tokio::test]
+async fn test_export_and_import_ndjson() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 3000, aof_config: None})).await;
+    for i in 0..2500 {
+        cache.put(format!("K{}", i), i).await;
+    }
+    let mut dump = vec![];
+    assert_eq!(cache.export_ndjson(&mut dump).await.unwrap(), 2500);
+    let dump = String::from_utf8(dump).unwrap();
+    assert_eq!(dump.lines().count(), 2500);
+    assert!(dump.lines().any(|x| x == r#"{"key":"K7","value":7}"#));
+
+    let copy = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 3000, aof_config: None})).await;
+    assert_eq!(copy.import_ndjson(dump.as_bytes()).await.unwrap(), 2500);
+    assert_eq!(copy.len().await, 2500);
+    assert_eq!(copy.get(&"K2499".to_string()).await, Some(2499));
+
+    let edited = "{\"key\":\"K1\",\"value\":10}\n\n{\"key\":\"K2\"}\n{\"key\":\"K3\",\"value\":30}\n";
+    let e = copy.import_ndjson(edited.as_bytes()).await.unwrap_err();
+    assert!(e.to_string().contains("line 3"));
+    assert_eq!(copy.get(&"K1".to_string()).await, Some(10));
+    assert_eq!(copy.get(&"K3".to_string()).await, Some(3));
+}
+
+#[tokio::test]
+async fn test_reader_sees_writes_of_the_cache() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    let reader = cache.reader();
+    let reader_clone = reader.clone();
+    cache.put("K1".to_string(), 1).await;
+    cache.put("K2".to_string(), 2).await;
+
+    assert_eq!(reader.size().await, 2);
+    assert_eq!(reader_clone.peek(&"K1".to_string()).await, Some(1));
+    assert_eq!(cache.next_victim().await, Some("K1".to_string()));
+    assert_eq!(reader.get(&"K1".to_string()).await, Some(1));
+    assert_eq!(cache.next_victim().await, Some("K2".to_string()));
+
+    cache.put("K3".to_string(), 3).await;
+    assert!(!reader.contains_key(&"K2".to_string()).await);
+    assert!(reader_clone.contains_key(&"K3".to_string()).await);
+}
+
+#[tokio::test]
+async fn test_utilization_events_are_edge_triggered() {
+    use sine_cache::{common::UtilizationEvent, config::Watermarks};
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})).await;
+    let watermarks = Watermarks { low: 0.2, high: 0.8, hysteresis: 0.15 };
+    let mut events = cache.utilization_events(watermarks, std::time::Duration::from_millis(5));
+    let settle = || tokio::time::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(events.recv().await, Some(UtilizationEvent::Low(0.0)));
+
+    for i in 0..8 {
+        cache.put(i, i).await;
+    }
+    assert_eq!(events.recv().await, Some(UtilizationEvent::High(0.8)));
+    // hovering within the hysteresis is not reported again.
+    cache.remove(&7).await;
+    settle().await;
+    cache.put(7, 7).await;
+    settle().await;
+    assert!(events.try_recv().is_err());
+
+    for i in 5..8 {
+        cache.remove(&i).await;
+    }
+    settle().await;
+    assert!(events.try_recv().is_err());
+    for i in 5..8 {
+        cache.put(i, i).await;
+    }
+    assert_eq!(events.recv().await, Some(UtilizationEvent::High(0.8)));
+    for i in 2..8 {
+        cache.remove(&i).await;
+    }
+    assert_eq!(events.recv().await, Some(UtilizationEvent::Low(0.2)));
+    assert_eq!(cache.utilization().await, 0.2);
+
+    drop(cache);
+    let ended = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+        while events.recv().await.is_some() {}
+    }).await;
+    assert!(ended.is_ok());
+}
+
+#[tokio::test]
+async fn test_stats_report_would_have_hit() {
+    let config = AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 1, aof_config: None});
+    let cache = sine_cache::cache::AsyncCacheBuilder::new(config).ghost_capacity(8).build().await;
+    cache.put("K1".to_string(), 1).await;
+    cache.put("K2".to_string(), 2).await;
+    assert_eq!(cache.get(&"K1".to_string()).await, None);
+    assert_eq!(cache.get(&"K3".to_string()).await, None);
+    assert_eq!(cache.stats().await.would_have_hit, 1);
+}
+
+#[tokio::test]
+async fn test_stats_report_cache_name() {
+    let config = || AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 1, aof_config: None});
+    let cache: AsyncCache<String, i32> = AsyncCache::new_without_aof(config()).await;
+    assert_eq!(cache.name(), None);
+    assert_eq!(cache.stats().await.cache_name, None);
+
+    let cache: AsyncCache<String, i32> = sine_cache::cache::AsyncCacheBuilder::new(config()).name("sessions").build().await;
+    assert_eq!(cache.name(), Some("sessions"));
+    assert_eq!(cache.stats().await.cache_name.as_deref(), Some("sessions"));
+}
+
+#[tokio::test]
+async fn test_snapshot_is_not_torn_by_concurrent_writes() {
+    let cache = AsyncCache::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})).await;
+    let keys = ["A".to_string(), "B".to_string()];
+    cache.put_batch_atomic(vec![(keys[0].clone(), 0), (keys[1].clone(), 0)]).await.unwrap();
+    cache.put("C".to_string(), 0).await;
+    let writer = {
+        let cache = cache.clone();
+        let keys = keys.clone();
+        tokio::spawn(async move {
+            for i in 1..500 {
+                cache.put_batch_atomic(vec![(keys[0].clone(), i), (keys[1].clone(), i)]).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+    for _ in 0..500 {
+        let snapshot = cache.snapshot(Some(&keys)).await;
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[&keys[0]], snapshot[&keys[1]]);
+        tokio::task::yield_now().await;
+    }
+    writer.await.unwrap();
+
+    let snapshot = cache.snapshot(None).await;
+    assert_eq!(snapshot.len(), 3);
+    assert_eq!(snapshot["A"], 499);
+    assert!(cache.snapshot(Some(&["D".to_string()])).await.is_empty());
+}
@@ -1,3 +1,5 @@
 pub mod fifo;
 pub mod lru;
-pub mod lfu;
\ No newline at end of file
+pub mod lfu;
+pub mod non_serde;
+pub mod get_or_insert;
\ No newline at end of file
@@ -0,0 +1,88 @@
+use sine_cache::{
+    cache::{AsyncCache, AsyncCacheBuilder}, config::{AsyncCacheConfig, EvictionAOFConfig, EvictionAsyncConfig}
+};
+
+/// Key which intentionally does not implement `Serialize`/`Deserialize`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PlainKey(u32);
+
+/// Value which intentionally does not implement `Serialize`/`Deserialize`.
+#[derive(Clone, Debug, PartialEq)]
+struct PlainValue(String);
+
+#[tokio::test]
+async fn test_non_serde_get_put_remove() {
+    let cache = AsyncCache::new_without_aof(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+
+    cache.put(PlainKey(1), PlainValue("one".to_string())).await;
+    cache.put(PlainKey(2), PlainValue("two".to_string())).await;
+    assert_eq!(cache.get(&PlainKey(1)).await, Some(PlainValue("one".to_string())));
+
+    cache.put(PlainKey(3), PlainValue("three".to_string())).await;
+    assert!(!cache.contains_key(&PlainKey(2)).await);
+
+    cache.remove(&PlainKey(1)).await;
+    assert_eq!(cache.get(&PlainKey(1)).await, None);
+    assert_eq!(cache.size().await, 1);
+}
+
+#[tokio::test]
+#[should_panic]
+async fn test_non_serde_with_aof_config_panics() {
+    let _cache: AsyncCache<PlainKey, PlainValue> = AsyncCache::new_without_aof(AsyncCacheConfig::LRU(EvictionAsyncConfig {
+        max_size: 2,
+        aof_config: Some(EvictionAOFConfig {
+            folder: String::from("."),
+            cache_name: String::from("test_non_serde_with_aof_config_panics"),
+            flush_time: Some(100),
+            persist_read_ops: true
+        })
+    })).await;
+}
+
+#[tokio::test]
+async fn test_weak_values_are_pruned_once_dropped() {
+    let cache: AsyncCache<PlainKey, std::sync::Weak<PlainValue>> = AsyncCache::new_without_aof(
+        AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 10, aof_config: None})
+    ).await;
+    let one = std::sync::Arc::new(PlainValue("one".to_string()));
+    let two = std::sync::Arc::new(PlainValue("two".to_string()));
+    let three = std::sync::Arc::new(PlainValue("three".to_string()));
+    cache.put(PlainKey(1), std::sync::Arc::downgrade(&one)).await;
+    cache.put(PlainKey(2), std::sync::Arc::downgrade(&two)).await;
+    cache.put(PlainKey(3), std::sync::Arc::downgrade(&three)).await;
+
+    assert_eq!(cache.get_upgrade(&PlainKey(1)).await, Some(one.clone()));
+    drop(one);
+    assert_eq!(cache.get_upgrade(&PlainKey(1)).await, None);
+    assert!(!cache.contains_key(&PlainKey(1)).await);
+
+    drop(two);
+    assert_eq!(cache.prune_dead().await, 1);
+    assert_eq!(cache.size().await, 1);
+    assert_eq!(cache.get_upgrade(&PlainKey(3)).await, Some(three));
+}
+
+#[tokio::test]
+async fn test_len_is_empty_and_max_size() {
+    let cache = AsyncCache::new_without_aof(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None})).await;
+    assert!(cache.is_empty().await);
+    assert_eq!(cache.max_size().await, Some(2));
+
+    cache.put(PlainKey(1), PlainValue("one".to_string())).await;
+    assert!(!cache.is_empty().await);
+    assert_eq!(cache.len().await, 1);
+}
+
+#[tokio::test]
+async fn test_estimated_bytes() {
+    let cache = AsyncCacheBuilder::new(AsyncCacheConfig::LRU(EvictionAsyncConfig {max_size: 2, aof_config: None}))
+        .entry_overhead(8)
+        .build_without_aof()
+        .await;
+    assert_eq!(cache.estimated_bytes().await, 0);
+
+    cache.put(PlainKey(1), PlainValue("one".to_string())).await;
+    let inline = std::mem::size_of::<PlainKey>() + std::mem::size_of::<sine_cache::common::CacheEntry<PlainValue>>();
+    assert_eq!(cache.estimated_bytes().await, inline + 8);
+}
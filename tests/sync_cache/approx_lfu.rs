@@ -0,0 +1,19 @@
+use sine_cache::config::{CacheConfig, CacheSyncConfig};
+
+use super::{assert_basic_get_put, zipf_hit_ratio};
+
+/// Test basic functionality of putting and getting items from the cache.
+#[test]
+fn test_basic_get_put() {
+    assert_basic_get_put(CacheSyncConfig::ApproxLFU(CacheConfig{max_size: 2}));
+}
+
+/// Test that on a skewed workload, the approximate policy keeps nearly as many hot keys as exact LFU.
+#[test]
+fn test_zipf_hit_ratio_close_to_lfu() {
+    let exact = zipf_hit_ratio(CacheSyncConfig::LFU(CacheConfig{max_size: 100}));
+    let approx = zipf_hit_ratio(CacheSyncConfig::ApproxLFU(CacheConfig{max_size: 100}));
+    let lru = zipf_hit_ratio(CacheSyncConfig::LRU(CacheConfig{max_size: 100}));
+    assert!(approx > exact * 0.9);
+    assert!(approx > lru);
+}
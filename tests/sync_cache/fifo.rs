@@ -9,7 +9,7 @@ use sine_cache::config::CacheConfig;
 fn test_put_get() {
     let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::FIFO(CacheConfig{max_size: 2}));
     let key1 = "key1".to_string();
-    let value1 = CacheEntry { value: "value1".to_string() };
+    let value1 = CacheEntry::new("value1".to_string());
     cache.put(key1.clone(), value1.clone());
 
     assert!(
@@ -17,7 +17,7 @@ fn test_put_get() {
     );
 
     let key2 = "key2".to_string();
-    let value2 = CacheEntry { value: "value2".to_string() };
+    let value2 = CacheEntry::new("value2".to_string());
     cache.put(key2.clone(), value2.clone());
 
     assert!(
@@ -32,15 +32,15 @@ fn test_put_get() {
 fn test_eviction() {
     let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::FIFO(CacheConfig{max_size: 2}));
     let key1 = "key1".to_string();
-    let value1 = CacheEntry { value: "value1".to_string() };
+    let value1 = CacheEntry::new("value1".to_string());
     cache.put(key1.clone(), value1.clone());
 
     let key2 = "key2".to_string();
-    let value2 = CacheEntry { value: "value2".to_string() };
+    let value2 = CacheEntry::new("value2".to_string());
     cache.put(key2.clone(), value2.clone());
 
     let key3 = "key3".to_string();
-    let value3 = CacheEntry { value: "value3".to_string() };
+    let value3 = CacheEntry::new("value3".to_string());
     cache.put(key3.clone(), value3.clone());
 
     assert!(
@@ -93,3 +93,15 @@ fn test_size() {
 
     assert_eq!(cache.size(), 2);
 }
+
+#[test]
+fn test_explain_next_eviction() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::FIFO(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.get(&"K1".to_string());
+
+    let explanation = cache.explain_next_eviction();
+    assert_eq!(explanation.victim, Some("K1".to_string()));
+    assert_eq!(explanation.reason, "FIFO front");
+}
@@ -80,3 +80,45 @@ fn test_lfu_eviction() {
     assert_eq!(cache.get(&"K2".to_string()), Some(&20));
 
 }
+
+#[test]
+fn test_frequency_histogram() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LFU(CacheConfig{max_size: 4}));
+    assert_eq!(cache.frequency_histogram(), Some(Default::default()));
+
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.put("K3".to_string(), 3);
+    cache.get(&"K1".to_string());
+    cache.get(&"K1".to_string());
+    cache.get(&"K2".to_string());
+    assert_eq!(cache.frequency_histogram(), Some([(1, 1), (2, 1), (3, 1)].into_iter().collect()));
+
+    cache.get(&"K3".to_string());
+    cache.remove(&"K1".to_string());
+    assert_eq!(cache.frequency_histogram(), Some([(2, 2)].into_iter().collect()));
+
+    let lru_cache: Cache<String, i32> = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 4}));
+    assert_eq!(lru_cache.frequency_histogram(), None);
+}
+
+#[test]
+fn test_explain_next_eviction() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LFU(CacheConfig{max_size: 3}));
+    let explanation = cache.explain_next_eviction();
+    assert_eq!((explanation.victim, explanation.reason.as_str()), (None, "cache is empty"));
+
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.get(&"K1".to_string());
+    let explanation = cache.explain_next_eviction();
+    assert_eq!(explanation.victim, Some("K2".to_string()));
+    assert_eq!(explanation.reason, "LFU freq=1 oldest");
+
+    // K2 is now used more often than K1.
+    cache.get(&"K2".to_string());
+    cache.get(&"K2".to_string());
+    let explanation = cache.explain_next_eviction();
+    assert_eq!(explanation.victim, Some("K1".to_string()));
+    assert_eq!(explanation.reason, "LFU freq=2 oldest");
+}
@@ -3,7 +3,7 @@ use std::{
 };
 
 use sine_cache::{
-    cache::Cache, common::CacheEntry, config::CacheConfig, eviction_policies::{common::EvictionPolicy, lru::LRU}
+    cache::Cache, common::{CacheEntry, Lookup}, config::CacheConfig, error::CasError, eviction_policies::{common::EvictionPolicy, lru::LRU}
 };
 
 /// Test basic functionality of putting and getting items from the cache.
@@ -56,6 +56,20 @@ fn test_get_mut_and_remove() {
     assert_eq!(cache.get(&"K2".to_string()), None);
 }
 
+#[test]
+fn test_get_mut_no_touch_keeps_eviction_order() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+
+    *cache.get_mut_no_touch(&"K1".to_string()).unwrap() += 10;
+    assert_eq!(cache.next_victim(), Some(&"K1".to_string()));
+    *cache.get_mut(&"K1".to_string()).unwrap() += 10;
+    assert_eq!(cache.next_victim(), Some(&"K2".to_string()));
+    assert_eq!(cache.peek(&"K1".to_string()), Some(&21));
+    assert_eq!(cache.get_mut_no_touch(&"K3".to_string()), None);
+}
+
 #[test]
 fn test_contains_key() {
     let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
@@ -76,3 +90,574 @@ fn test_size() {
 
     assert_eq!(cache.size(), 2);
 }
+
+/// Test that `from_entries` evicts in iteration order and leaves the policy as sequential puts would.
+#[test]
+fn test_from_entries() {
+    let entries = vec![
+        ("K1".to_string(), 1),
+        ("K2".to_string(), 2),
+        ("K3".to_string(), 3),
+        ("K2".to_string(), 20),
+    ];
+    let mut cache = Cache::from_entries(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}), entries);
+
+    assert_eq!(cache.size(), 2);
+    assert!(!cache.contains_key(&"K1".to_string()));
+
+    // K3 is least recently used as K2 was overwritten after it.
+    cache.put("K4".to_string(), 4);
+    assert!(!cache.contains_key(&"K3".to_string()));
+    assert_eq!(cache.get(&"K2".to_string()), Some(&20));
+    assert_eq!(cache.get(&"K4".to_string()), Some(&4));
+}
+
+/// Test that `remove` returns the removed value and only touches the policy on a real removal.
+#[test]
+fn test_remove_returns_value() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+
+    assert_eq!(cache.remove(&"K1".to_string()), Some(1));
+    assert_eq!(cache.remove(&"K1".to_string()), None);
+    assert_eq!(cache.remove(&"K3".to_string()), None);
+    assert_eq!(cache.size(), 1);
+}
+
+/// Test that a custom hasher can be plugged into both the cache and the eviction policy.
+#[test]
+fn test_custom_hasher() {
+    type FixedState = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+
+    let policy: LRU<String, FixedState> = LRU::with_hasher(FixedState::default());
+    let mut cache: Cache<String, i32, FixedState> = Cache::with_hasher(
+        sine_cache::config::CacheSyncConfig::Custom(sine_cache::config::CustomCacheConfig {
+            max_size: 2,
+            policy: Box::new(policy)
+        }),
+        FixedState::default()
+    );
+
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+    cache.put("K3".to_string(), 3);
+
+    assert!(!cache.contains_key(&"K2".to_string()));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+    assert_eq!(cache.get(&"K3".to_string()), Some(&3));
+}
+
+/// Test that the next victim can be inspected without evicting or touching it.
+#[test]
+fn test_next_victim() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    assert_eq!(cache.next_victim(), None);
+
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    assert_eq!(cache.next_victim(), Some(&"K1".to_string()));
+    assert_eq!(cache.next_victim(), Some(&"K1".to_string()));
+
+    cache.get(&"K1".to_string());
+    assert_eq!(cache.next_victim(), Some(&"K2".to_string()));
+    cache.put("K3".to_string(), 3);
+    assert!(!cache.contains_key(&"K2".to_string()));
+}
+
+/// Test that shrinking after bulk removals keeps the entries and the eviction order.
+#[test]
+fn test_shrink_to_fit() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 1000}));
+    for i in 0..1000 {
+        cache.put(i, i);
+    }
+    for i in 2..1000 {
+        cache.remove(&i);
+    }
+    cache.shrink_to_fit();
+
+    assert_eq!(cache.size(), 2);
+    assert_eq!(cache.get(&0), Some(&0));
+    assert_eq!(cache.next_victim(), Some(&1));
+}
+
+/// Test that reserving keeps the eviction order and is capped at `max_size`.
+#[test]
+fn test_reserve() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 1000}));
+    cache.put(0, 0);
+    cache.reserve(usize::MAX);
+    cache.reserve(999);
+    for i in 1..1001 {
+        cache.put(i, i);
+    }
+
+    assert_eq!(cache.size(), 1000);
+    assert!(!cache.contains_key(&0));
+    assert_eq!(cache.next_victim(), Some(&1));
+}
+
+/// Test that replace returns the previous value and evicts only for new keys.
+#[test]
+fn test_replace() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+
+    assert_eq!(cache.replace("K1".to_string(), 1), None);
+    cache.put("K2".to_string(), 2);
+    assert_eq!(cache.replace("K1".to_string(), 10), Some(1));
+    assert_eq!(cache.size(), 2);
+    assert_eq!(cache.get(&"K2".to_string()), Some(&2));
+
+    assert_eq!(cache.replace("K3".to_string(), 3), None);
+    assert!(!cache.contains_key(&"K1".to_string()));
+    assert_eq!(cache.size(), 2);
+}
+
+/// Test that replace_if_present updates present keys only.
+#[test]
+fn test_replace_if_present() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+
+    assert_eq!(cache.replace_if_present(&"K1".to_string(), 10), Some(1));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&10));
+    assert_eq!(cache.replace_if_present(&"K2".to_string(), 2), None);
+    assert!(!cache.contains_key(&"K2".to_string()));
+    assert_eq!(cache.size(), 1);
+}
+
+/// Test the size accessors.
+#[test]
+fn test_len_is_empty_and_max_size() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    assert!(cache.is_empty());
+    assert_eq!(cache.len(), 0);
+    assert_eq!(cache.max_size(), Some(2));
+
+    cache.put("K1".to_string(), 1);
+    assert!(!cache.is_empty());
+    assert_eq!(cache.len(), cache.size());
+    assert_eq!(cache.len(), 1);
+
+    let cache: Cache<String, i32> = Cache::new(sine_cache::config::CacheSyncConfig::NoEviction);
+    assert_eq!(cache.max_size(), None);
+}
+
+/// Test lookups by borrowed keys, which also count as accesses for the eviction policy.
+#[test]
+fn test_borrowed_lookups() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+
+    assert_eq!(cache.get_borrowed("K1"), Some(&1));
+    assert_eq!(cache.get_borrowed("K3"), None);
+    assert!(cache.contains_key_borrowed("K2"));
+    assert_eq!(cache.next_victim(), Some(&"K2".to_string()));
+
+    assert_eq!(cache.remove_borrowed("K2"), Some(2));
+    assert_eq!(cache.remove_borrowed("K2"), None);
+    assert!(!cache.contains_key_borrowed("K2"));
+    assert_eq!(cache.size(), 1);
+}
+
+/// Test that writes bump versions and compare and swap writes only on a matching version.
+#[test]
+fn test_compare_and_swap() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    assert_eq!(cache.compare_and_swap("K1".to_string(), 1, 1), Err(CasError::VersionMismatch { expected: 1, current: 0 }));
+    let version = cache.compare_and_swap("K1".to_string(), 0, 1).unwrap();
+    assert_eq!(cache.get_with_version(&"K1".to_string()), Some((&1, version)));
+
+    cache.put("K1".to_string(), 2);
+    let (_, newer) = cache.get_with_version(&"K1".to_string()).unwrap();
+    assert!(newer > version);
+    assert_eq!(cache.compare_and_swap("K1".to_string(), version, 3), Err(CasError::VersionMismatch { expected: version, current: newer }));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&2));
+
+    let latest = cache.compare_and_swap("K1".to_string(), newer, 3).unwrap();
+    assert!(latest > newer);
+    assert_eq!(cache.get_with_version(&"K1".to_string()), Some((&3, latest)));
+}
+
+/// Test that several distinct keys can be mutated at once, and that missing or repeated keys are refused.
+#[test]
+fn test_get_many_mut() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 3}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.put("K3".to_string(), 3);
+
+    let [a, b] = cache.get_many_mut([&"K2".to_string(), &"K1".to_string()]).unwrap();
+    std::mem::swap(a, b);
+    assert_eq!(cache.get(&"K1".to_string()), Some(&2));
+    assert_eq!(cache.get(&"K2".to_string()), Some(&1));
+    assert_eq!(cache.next_victim(), Some(&"K3".to_string()));
+
+    assert!(cache.get_many_mut([&"K1".to_string(), &"K4".to_string()]).is_none());
+    assert!(cache.get_many_mut([&"K3".to_string(), &"K3".to_string()]).is_none());
+    assert_eq!(cache.next_victim(), Some(&"K3".to_string()));
+
+    // keys are normalized before being looked up and compared.
+    cache.set_key_normalizer(|key: &String| key.to_uppercase());
+    assert!(cache.get_many_mut([&"k3".to_string(), &"K3".to_string()]).is_none());
+    let [a, b] = cache.get_many_mut([&"k3".to_string(), &"k1".to_string()]).unwrap();
+    *a += 10;
+    *b += 10;
+    assert_eq!(cache.get(&"K3".to_string()), Some(&13));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&12));
+}
+
+/// Test that put if newer keeps the value with the latest timestamp.
+#[test]
+fn test_put_if_newer() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    assert!(cache.put_if_newer("K1".to_string(), 1, 10));
+    assert!(!cache.put_if_newer("K1".to_string(), 2, 5));
+    assert!(!cache.put_if_newer("K1".to_string(), 2, 10));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+    assert!(cache.put_if_newer("K1".to_string(), 3, 11));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&3));
+
+    cache.put("K1".to_string(), 4);
+    assert!(cache.put_if_newer("K1".to_string(), 5, 1));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&5));
+}
+
+/// Test that absent keys are told apart from unknown ones until their ttl expires.
+#[test]
+fn test_negative_caching() {
+    let mut cache: Cache<String, Option<i32>> = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), Some(1));
+    cache.put_absent("K2".to_string(), std::time::Duration::from_millis(50));
+
+    assert_eq!(cache.lookup(&"K1".to_string()), Lookup::Present(&1));
+    assert_eq!(cache.lookup(&"K2".to_string()), Lookup::KnownAbsent);
+    assert_eq!(cache.lookup(&"K3".to_string()), Lookup::Unknown);
+
+    thread::sleep(std::time::Duration::from_millis(100));
+    assert_eq!(cache.lookup(&"K2".to_string()), Lookup::Unknown);
+}
+
+/// Test that clear empties the cache and its eviction policy.
+#[test]
+fn test_clear() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(cache.next_victim(), None);
+
+    cache.put("K3".to_string(), 3);
+    cache.put("K4".to_string(), 4);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.next_victim(), Some(&"K3".to_string()));
+}
+
+/// Test defaults on a miss, with and without inserting.
+#[test]
+fn test_get_or_default() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+
+    assert_eq!(cache.get_or_default(&"K3".to_string()), 0);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.next_victim(), Some(&"K1".to_string()));
+    assert_eq!(cache.get_or_default(&"K1".to_string()), 1);
+    assert_eq!(cache.next_victim(), Some(&"K2".to_string()));
+
+    *cache.entry_or_default("K1".to_string()) += 1;
+    assert_eq!(cache.get(&"K1".to_string()), Some(&2));
+    *cache.entry_or_default("K3".to_string()) += 1;
+    *cache.entry_or_default("K3".to_string()) += 1;
+    assert_eq!(cache.get(&"K3".to_string()), Some(&2));
+    assert!(!cache.contains_key(&"K2".to_string()));
+}
+
+/// Test that reads without recording access don't change the eviction order.
+#[test]
+fn test_get_opts() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+
+    assert_eq!(cache.peek(&"K1".to_string()), Some(&1));
+    assert_eq!(cache.get_opts(&"K1".to_string(), false), Some(&1));
+    assert_eq!(cache.next_victim(), Some(&"K1".to_string()));
+
+    assert_eq!(cache.get_opts(&"K1".to_string(), true), Some(&1));
+    assert_eq!(cache.next_victim(), Some(&"K2".to_string()));
+}
+
+/// Test that indexing reads like `peek`.
+#[test]
+fn test_index() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+
+    assert_eq!(cache[&"K1".to_string()], 1);
+    assert_eq!(cache.next_victim(), Some(&"K1".to_string()));
+}
+
+/// Test that indexing panics on a missing key.
+#[test]
+#[should_panic]
+fn test_index_missing_key() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    let _ = cache[&"K2".to_string()];
+}
+
+/// Test that contents are compared regardless of the eviction order.
+#[test]
+fn test_content_eq() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 3}));
+    let mut other = Cache::new(sine_cache::config::CacheSyncConfig::FIFO(CacheConfig{max_size: 3}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    other.put("K2".to_string(), 2);
+    other.put("K1".to_string(), 1);
+    assert!(cache.content_eq(&other));
+
+    other.put("K2".to_string(), 3);
+    assert!(!cache.content_eq(&other));
+    other.put("K2".to_string(), 2);
+    other.put("K3".to_string(), 3);
+    assert!(!cache.content_eq(&other));
+    assert!(!other.content_eq(&cache));
+}
+
+/// Test that drain yields all the entries and leaves the cache and the eviction order empty.
+#[test]
+fn test_drain() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 3}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.put_with_ttl("K3".to_string(), 3, std::time::Duration::ZERO);
+
+    let mut drained: Vec<_> = cache.drain().collect();
+    drained.sort();
+    assert_eq!(drained, vec![("K1".to_string(), 1), ("K2".to_string(), 2)]);
+    assert!(cache.is_empty());
+    assert_eq!(cache.next_victim(), None);
+
+    // works as new afterwards.
+    cache.put("K4".to_string(), 4);
+    cache.put("K5".to_string(), 5);
+    assert_eq!(cache.next_victim(), Some(&"K4".to_string()));
+    drop(cache.drain());
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_pinned_entries_are_not_evicted() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    assert!(cache.pin(&"K1".to_string()));
+    assert!(!cache.pin(&"K1".to_string()));
+
+    // K1 is the least recently used key, so K2 is evicted instead.
+    cache.put("K3".to_string(), 3);
+    assert!(cache.contains_key(&"K1".to_string()));
+    assert!(!cache.contains_key(&"K2".to_string()));
+
+    // with every entry pinned, the cache grows past max_size.
+    cache.pin(&"K3".to_string());
+    cache.put("K4".to_string(), 4);
+    assert_eq!(cache.size(), 3);
+
+    cache.unpin(&"K1".to_string());
+    cache.put("K5".to_string(), 5);
+    assert!(!cache.contains_key(&"K1".to_string()));
+    assert!(cache.contains_key(&"K3".to_string()));
+}
+
+#[test]
+fn test_validate_zero_max_size() {
+    use sine_cache::config::CacheSyncConfig;
+    use sine_cache::error::ConfigError;
+
+    assert_eq!(CacheSyncConfig::<String>::LRU(CacheConfig{max_size: 0}).validate(), Err(vec![ConfigError::ZeroMaxSize]));
+    assert_eq!(CacheSyncConfig::<String>::LRU(CacheConfig{max_size: 1}).validate(), Ok(()));
+    assert_eq!(CacheSyncConfig::<String>::NoEviction.validate(), Ok(()));
+}
+
+#[test]
+fn test_extend_and_into_iter() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.extend(vec![("K1".to_string(), 1), ("K2".to_string(), 2), ("K3".to_string(), 3)]);
+    assert!(!cache.contains_key(&"K1".to_string()));
+
+    let mut entries: Vec<(String, i32)> = cache.into_iter().collect();
+    entries.sort();
+    assert_eq!(entries, vec![("K2".to_string(), 2), ("K3".to_string(), 3)]);
+}
+
+#[test]
+fn test_try_put_fails_when_nothing_can_be_evicted() {
+    use sine_cache::error::CacheFull;
+
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    cache.pin(&"K1".to_string());
+    cache.pin(&"K2".to_string());
+
+    assert_eq!(cache.try_put("K3".to_string(), 3), Err(CacheFull { key: "K3".to_string(), value: 3 }));
+    assert_eq!(cache.size(), 2);
+    // replacing a value needs no room.
+    assert_eq!(cache.try_put("K1".to_string(), 10), Ok(Some(1)));
+
+    cache.unpin(&"K2".to_string());
+    assert_eq!(cache.try_put("K3".to_string(), 3), Ok(None));
+    assert!(!cache.contains_key(&"K2".to_string()));
+    assert_eq!(cache.size(), 2);
+}
+
+/// Test that utilization is the fill level of the cache.
+#[test]
+fn test_utilization() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 4}));
+    assert_eq!(cache.utilization(), 0.0);
+    cache.put("K1".to_string(), 1);
+    assert_eq!(cache.utilization(), 0.25);
+    for i in 2..=5 {
+        cache.put(format!("K{}", i), i);
+    }
+    assert_eq!(cache.utilization(), 1.0);
+
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::NoEviction);
+    cache.put("K1".to_string(), 1);
+    assert_eq!(cache.utilization(), 0.0);
+}
+
+/// Test that misses of recently evicted keys are counted while they are remembered.
+#[test]
+fn test_ghost_keys_count_would_have_hit() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.set_ghost_capacity(2);
+    for i in 1..=5 {
+        cache.put(format!("K{}", i), i);
+    }
+    // K1, K2 and K3 were evicted in order, only K2 and K3 are remembered.
+    assert_eq!(cache.get(&"K1".to_string()), None);
+    assert_eq!(cache.would_have_hit(), 0);
+    assert_eq!(cache.get(&"K2".to_string()), None);
+    assert_eq!(cache.get_borrowed("K3"), None);
+    assert_eq!(cache.peek(&"K3".to_string()), None);
+    assert_eq!(cache.get(&"K6".to_string()), None);
+    assert_eq!(cache.would_have_hit(), 2);
+
+    cache.put("K3".to_string(), 3);
+    assert_eq!(cache.get(&"K3".to_string()), Some(&3));
+    assert_eq!(cache.would_have_hit(), 2);
+
+    cache.set_ghost_capacity(0);
+    assert_eq!(cache.get(&"K2".to_string()), None);
+    assert_eq!(cache.would_have_hit(), 0);
+}
+
+/// Test that evict_n evicts in the order of the policy, skipping pinned keys.
+#[test]
+fn test_evict_n() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 4}));
+    for i in 1..=4 {
+        cache.put(format!("K{}", i), i);
+    }
+    cache.pin(&"K1".to_string());
+    assert_eq!(cache.evict_n(2), vec!["K2".to_string(), "K3".to_string()]);
+    assert_eq!(cache.evict_n(5), vec!["K4".to_string()]);
+    assert_eq!(cache.size(), 1);
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+}
+
+/// Test getting or inserting a value and mutating it in place.
+#[test]
+fn test_get_or_insert_with_mut() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.get_or_insert_with_mut("K1".to_string(), Vec::new).push(1);
+    cache.get_or_insert_with_mut("K2".to_string(), Vec::new).push(2);
+    cache.get_or_insert_with_mut("K1".to_string(), || unreachable!()).push(3);
+    assert_eq!(cache.get(&"K1".to_string()), Some(&vec![1, 3]));
+    assert_eq!(cache.next_victim(), Some(&"K2".to_string()));
+
+    cache.get_or_insert_with_mut("K3".to_string(), || vec![4]).push(5);
+    assert_eq!(cache.get(&"K3".to_string()), Some(&vec![4, 5]));
+    assert!(!cache.contains_key(&"K2".to_string()));
+    assert_eq!(cache.len(), 2);
+}
+
+/// Test that normalized keys which map to the same key are the same entry.
+#[test]
+fn test_key_normalizer() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.set_key_normalizer(|key: &String| key.to_lowercase());
+    cache.put("Key1".to_string(), "Value1");
+    cache.put("KEY2".to_string(), "Value2");
+    cache.put("key1".to_string(), "value1");
+
+    assert_eq!(cache.size(), 2);
+    assert_eq!(cache.get(&"KEY1".to_string()), Some(&"value1"));
+    assert!(cache.contains_key(&"Key2".to_string()));
+    assert_eq!(cache.next_victim(), Some(&"key2".to_string()));
+    *cache.get_mut(&"kEY2".to_string()).unwrap() = "VALUE2";
+    assert_eq!(cache.remove(&"key2".to_string()), Some("VALUE2"));
+    assert_eq!(cache.size(), 1);
+}
+
+/// Test that every method taking a key finds the entry written with another form of the key.
+#[test]
+fn test_key_normalizer_every_method() {
+    let mut cache = Cache::new(sine_cache::config::CacheSyncConfig::LRU(CacheConfig{max_size: 4}));
+    cache.set_key_normalizer(|key: &String| key.to_lowercase());
+    let key = |key: &str| key.to_string();
+    cache.put(key("Foo"), 1);
+
+    assert_eq!(cache.get(&key("FOO")), Some(&1));
+    assert_eq!(cache.peek(&key("fOO")), Some(&1));
+    assert_eq!(cache.get_opts(&key("FoO"), false), Some(&1));
+    assert_eq!(cache[&key("FOO")], 1);
+    assert!(cache.contains_key(&key("FOO")));
+    assert_eq!(cache.get_or_default(&key("FOO")), 1);
+    *cache.get_mut(&key("FOO")).unwrap() += 1;
+    *cache.get_mut_no_touch(&key("FOO")).unwrap() += 1;
+    *cache.entry_or_default(key("FOO")) += 1;
+    *cache.get_or_insert_with_mut(key("FOO"), || 0) += 1;
+    assert_eq!(cache.snapshot(Some(&[key("FOO")])), HashMap::from([(key("foo"), 5)]));
+    assert_eq!(cache.size(), 1);
+
+    assert_eq!(cache.replace(key("FOO"), 6), Some(5));
+    assert_eq!(cache.replace_if_present(&key("FOO"), 7), Some(6));
+    assert!(cache.try_put(key("FOO"), 8).is_ok());
+    let (_, version) = cache.get_with_version(&key("FOO")).unwrap();
+    assert!(cache.compare_and_swap(key("FOO"), 0, 9).is_err());
+    assert_eq!(cache.compare_and_swap(key("FOO"), version, 9), Ok(version + 1));
+    assert!(cache.put_if_newer(key("FOO"), 10, 100));
+    assert!(!cache.put_if_newer(key("foo"), 11, 50));
+    assert_eq!(cache.get(&key("foo")), Some(&10));
+    assert_eq!(cache.size(), 1);
+
+    assert_eq!(cache.get_ttl(&key("FOO")), None);
+    assert!(cache.set_ttl(&key("FOO"), Some(std::time::Duration::from_secs(60))));
+    assert!(cache.get_ttl(&key("FOO")).is_some());
+    cache.put_with_ttl(key("FOO"), 12, std::time::Duration::from_secs(60));
+    assert_eq!(cache.get(&key("foo")), Some(&12));
+
+    assert!(cache.pin(&key("FOO")));
+    assert!(cache.is_pinned(&key("foo")));
+    assert!(!cache.pin(&key("foo")));
+    assert!(cache.unpin(&key("fOo")));
+
+    assert!(!cache.compare_and_remove(&key("FOO"), &0));
+    assert!(cache.compare_and_remove(&key("FOO"), &12));
+    cache.put(key("Foo"), 1);
+    assert_eq!(cache.remove(&key("FOO")), Some(1));
+    assert_eq!(cache.size(), 0);
+}
@@ -1,4 +1,44 @@
 //! Includes various tests.
+use rand::{distributions::{Distribution, WeightedIndex}, rngs::StdRng, SeedableRng};
+use sine_cache::{cache::Cache, config::CacheSyncConfig};
+
 pub mod fifo;
 pub mod lru;
 pub mod lfu;
+pub mod ttl;
+pub mod weighted;
+pub mod approx_lfu;
+pub mod sampled_lru;
+
+/// Hit ratio of a read-through workload where keys are drawn from a Zipfian distribution, to compare the
+/// approximate policies with the exact ones.
+fn zipf_hit_ratio(config: CacheSyncConfig<usize>) -> f64 {
+    let mut cache = Cache::new(config);
+    let weights: Vec<f64> = (0..1000).map(|x| 1.0 / (x + 1) as f64).collect();
+    let distribution = WeightedIndex::new(&weights).unwrap();
+    let mut rng = StdRng::seed_from_u64(7);
+    let ops = 50_000;
+    let mut hits = 0;
+    for _ in 0..ops {
+        let key = distribution.sample(&mut rng);
+        if cache.get(&key).is_some() {
+            hits += 1;
+        } else {
+            cache.put(key, key);
+        }
+    }
+    hits as f64 / ops as f64
+}
+
+/// Puts and gets two keys in a cache of `max_size` 2, then checks that a third one evicts one of them.
+fn assert_basic_get_put(config: CacheSyncConfig<String>) {
+    let mut cache = Cache::new(config);
+    cache.put("K1".to_string(), 1);
+    cache.put("K2".to_string(), 2);
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+    assert_eq!(cache.get(&"K2".to_string()), Some(&2));
+
+    cache.put("K3".to_string(), 3);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&"K3".to_string()), Some(&3));
+}
@@ -0,0 +1,23 @@
+use sine_cache::{config::{CacheConfig, CacheSyncConfig, CustomCacheConfig}, eviction_policies::sampled_lru::SampledLRU};
+
+use super::{assert_basic_get_put, zipf_hit_ratio};
+
+/// Test basic functionality of putting and getting items from the cache.
+#[test]
+fn test_basic_get_put() {
+    assert_basic_get_put(CacheSyncConfig::SampledLRU(CacheConfig{max_size: 2}));
+}
+
+/// Test that on a skewed workload, the sampled policy keeps nearly as many hot keys as exact LRU, and more
+/// of them with a bigger sample.
+#[test]
+fn test_zipf_hit_ratio_close_to_lru() {
+    let exact = zipf_hit_ratio(CacheSyncConfig::LRU(CacheConfig{max_size: 100}));
+    let sampled = zipf_hit_ratio(CacheSyncConfig::SampledLRU(CacheConfig{max_size: 100}));
+    let sampled_more = zipf_hit_ratio(CacheSyncConfig::Custom(CustomCacheConfig{
+        max_size: 100,
+        policy: Box::new(SampledLRU::with_sample_size(100, 20)),
+    }));
+    assert!(sampled > exact * 0.9);
+    assert!(sampled_more > exact * 0.95);
+}
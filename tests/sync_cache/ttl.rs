@@ -0,0 +1,170 @@
+//!Includes tests regarding expiry of entries.
+
+use std::time::{Duration, Instant};
+
+use sine_cache::cache::Cache;
+use sine_cache::common::{unix_millis_to_instant, CacheEntry, LookupCounts, MockClock};
+use sine_cache::config::{CacheConfig, CacheSyncConfig};
+
+#[test]
+fn test_put_with_ttl_expires() {
+    let mut cache = Cache::new(CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_millis(50));
+    cache.put("K2".to_string(), 2);
+    assert!(cache.contains_key(&"K1".to_string()));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!cache.contains_key(&"K1".to_string()));
+    assert_eq!(cache.size(), 2); // not removed until accessed.
+    assert_eq!(cache.get(&"K1".to_string()), None);
+    assert_eq!(cache.size(), 1);
+    assert_eq!(cache.get(&"K2".to_string()), Some(&2));
+}
+
+#[test]
+fn test_put_clears_ttl() {
+    let mut cache = Cache::new(CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_millis(50));
+    cache.put("K1".to_string(), 10);
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&10));
+}
+
+#[test]
+fn test_purge_expired() {
+    let mut cache = Cache::new(CacheSyncConfig::FIFO(CacheConfig{max_size: 3}));
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_millis(50));
+    cache.put_with_ttl("K2".to_string(), 2, Duration::from_secs(60));
+    cache.put("K3".to_string(), 3);
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(cache.purge_expired(), vec!["K1".to_string()]);
+    assert_eq!(cache.size(), 2);
+
+    // K1 is removed from the policy as well, so K2 is the next to be evicted.
+    cache.put("K4".to_string(), 4);
+    cache.put("K5".to_string(), 5);
+    assert!(!cache.contains_key(&"K2".to_string()));
+    assert!(cache.contains_key(&"K3".to_string()));
+}
+
+#[test]
+fn test_get_and_set_ttl() {
+    let mut cache = Cache::new(CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_secs(60));
+    cache.put("K2".to_string(), 2);
+    let ttl = cache.get_ttl(&"K1".to_string()).unwrap();
+    assert!(ttl > Duration::from_secs(59) && ttl <= Duration::from_secs(60));
+    assert_eq!(cache.get_ttl(&"K2".to_string()), None);
+    assert_eq!(cache.get_ttl(&"K3".to_string()), None);
+
+    // shortened, extended and cleared without changing the values.
+    assert!(cache.set_ttl(&"K1".to_string(), Some(Duration::from_millis(50))));
+    assert!(cache.set_ttl(&"K2".to_string(), Some(Duration::from_secs(60))));
+    assert!(cache.get_ttl(&"K2".to_string()).is_some());
+    assert!(!cache.set_ttl(&"K3".to_string(), None));
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(cache.get_ttl(&"K1".to_string()), None);
+    assert!(!cache.set_ttl(&"K1".to_string(), None));
+    assert_eq!(cache.size(), 1);
+
+    assert!(cache.set_ttl(&"K2".to_string(), None));
+    assert_eq!(cache.get_ttl(&"K2".to_string()), None);
+    assert_eq!(cache.get(&"K2".to_string()), Some(&2));
+
+    // a ttl past the range of `Instant` never expires.
+    assert!(cache.set_ttl(&"K2".to_string(), Some(Duration::MAX)));
+    assert_eq!(cache.get_ttl(&"K2".to_string()), None);
+    assert_eq!(cache.get(&"K2".to_string()), Some(&2));
+}
+
+#[test]
+fn test_ttl_past_instant_range_never_expires() {
+    let mut cache = Cache::new(CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+
+    assert_eq!(CacheEntry::with_ttl(1, Duration::MAX).expires_at, None);
+    cache.put_with_ttl("K1".to_string(), 1, Duration::MAX);
+    assert_eq!(cache.get_ttl(&"K1".to_string()), None);
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+    // e.g. the expiry of a corrupted `AOF` record, past the range of `Instant` on some platforms.
+    assert!(unix_millis_to_instant(u64::MAX) >= Instant::now());
+}
+
+#[test]
+fn test_expiry_with_mock_clock() {
+    let clock = MockClock::new();
+    let mut cache = Cache::new(CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.set_clock(clock.clone());
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_secs(60));
+    clock.advance(Duration::from_secs(59));
+    assert_eq!(cache.get_ttl(&"K1".to_string()), Some(Duration::from_secs(1)));
+    assert!(cache.contains_key(&"K1".to_string()));
+    clock.advance(Duration::from_secs(1));
+    assert!(!cache.contains_key(&"K1".to_string()));
+    assert_eq!(cache.get(&"K1".to_string()), None);
+
+    cache.put("K2".to_string(), 2);
+    assert!(cache.set_ttl(&"K2".to_string(), Some(Duration::from_secs(3600))));
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(cache.purge_expired(), vec!["K2".to_string()]);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_live_size_excludes_expired_entries() {
+    let clock = MockClock::new();
+    let mut cache = Cache::new(CacheSyncConfig::NoEviction);
+    cache.set_clock(clock.clone());
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_secs(10));
+    cache.put_with_ttl("K2".to_string(), 2, Duration::from_secs(20));
+    cache.put("K3".to_string(), 3);
+    // overwriting replaces the expiry, removing resets it.
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_secs(30));
+    cache.put_with_ttl("K4".to_string(), 4, Duration::from_secs(5));
+    cache.remove(&"K4".to_string());
+    assert!(cache.set_ttl(&"K3".to_string(), Some(Duration::from_secs(15))));
+    assert_eq!(cache.live_size(), 3);
+
+    clock.advance(Duration::from_secs(20));
+    assert_eq!(cache.size(), 3);
+    assert_eq!(cache.live_size(), 1);
+    let mut purged = cache.purge_expired();
+    purged.sort();
+    assert_eq!(purged, vec!["K2".to_string(), "K3".to_string()]);
+    assert_eq!(cache.size(), 1);
+    assert_eq!(cache.live_size(), 1);
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(cache.live_size(), 0);
+    assert_eq!(cache.purge_expired(), vec!["K1".to_string()]);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_lookup_counts_tell_expired_misses_apart() {
+    let clock = MockClock::new();
+    let mut cache = Cache::new(CacheSyncConfig::LRU(CacheConfig{max_size: 2}));
+    cache.set_clock(clock.clone());
+
+    cache.put_with_ttl("K1".to_string(), 1, Duration::from_secs(10));
+    assert_eq!(cache.get(&"K1".to_string()), Some(&1));
+    assert_eq!(cache.get(&"K2".to_string()), None);
+    // reads which don't count as an access are not counted.
+    assert_eq!(cache.peek(&"K1".to_string()), Some(&1));
+    assert_eq!(cache.lookup_counts(), LookupCounts { hits: 1, misses_absent: 1, misses_expired: 0 });
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(cache.get(&"K1".to_string()), None);
+    assert_eq!(cache.lookup_counts(), LookupCounts { hits: 1, misses_absent: 1, misses_expired: 1 });
+    // the expired entry was removed by the lookup, so the key is absent now.
+    assert_eq!(cache.get_borrowed("K1"), None);
+    assert_eq!(cache.lookup_counts(), LookupCounts { hits: 1, misses_absent: 2, misses_expired: 1 });
+}
@@ -0,0 +1,145 @@
+use std::sync::{Arc, Mutex};
+
+use sine_cache::common::MemoryBudget;
+use sine_cache::error::ConfigError;
+use sine_cache::eviction_policies::common::EvictionPolicy;
+use sine_cache::{cache::Cache, config::{CacheSyncConfig, CustomCacheConfig}};
+
+/// Evicts the heaviest key, records weights it has been told about.
+struct HeaviestFirst {
+    weights: Arc<Mutex<Vec<(u32, usize)>>>,
+}
+
+impl EvictionPolicy<u32> for HeaviestFirst {
+    fn on_get(&mut self, _key: &u32) {}
+
+    fn on_set(&mut self, key: u32) {
+        self.on_set_weighted(key, 0);
+    }
+
+    fn on_set_weighted(&mut self, key: u32, weight: usize) {
+        let mut weights = self.weights.lock().unwrap();
+        weights.retain(|(k, _)| *k != key);
+        weights.push((key, weight));
+    }
+
+    fn evict(&mut self) -> Option<u32> {
+        let mut weights = self.weights.lock().unwrap();
+        let index = (0..weights.len()).max_by_key(|i| weights[*i].1)?;
+        Some(weights.remove(index).0)
+    }
+
+    fn remove(&mut self, key: u32) {
+        self.weights.lock().unwrap().retain(|(k, _)| *k != key);
+    }
+}
+
+#[test]
+fn test_weigher_passes_weight_to_policy() {
+    let weights = Arc::new(Mutex::new(vec![]));
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::Custom(CustomCacheConfig {
+        max_size: 3,
+        policy: Box::new(HeaviestFirst { weights: weights.clone() }),
+    }));
+    cache.set_weigher(|_, value: &String| value.len());
+
+    cache.put(1, String::from("a"));
+    cache.put(2, String::from("a long value"));
+    cache.put(3, String::from("abc"));
+    assert_eq!(*weights.lock().unwrap(), vec![(1, 1), (2, 12), (3, 3)]);
+
+    cache.put(4, String::from("ab"));
+    assert!(!cache.contains_key(&2));
+    assert_eq!(cache.size(), 3);
+}
+
+#[test]
+fn test_without_weigher_on_set_is_called() {
+    let weights = Arc::new(Mutex::new(vec![]));
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::Custom(CustomCacheConfig {
+        max_size: 3,
+        policy: Box::new(HeaviestFirst { weights: weights.clone() }),
+    }));
+    cache.put(1, String::from("a long value"));
+    assert_eq!(*weights.lock().unwrap(), vec![(1, 0)]);
+}
+
+#[test]
+fn test_estimated_bytes() {
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::LRU(sine_cache::config::CacheConfig { max_size: 3 }));
+    cache.put(1, String::from("a"));
+    cache.put(2, String::from("abc"));
+    let inline = std::mem::size_of::<u32>() + std::mem::size_of::<sine_cache::common::CacheEntry<String>>();
+    assert_eq!(cache.estimated_bytes(), 2 * inline);
+
+    cache.set_entry_overhead(10);
+    assert_eq!(cache.estimated_bytes(), 2 * (inline + 10));
+
+    cache.set_weigher(|_, value: &String| value.len());
+    assert_eq!(cache.estimated_bytes(), 4);
+}
+
+#[test]
+fn test_max_memory_evicts_until_entries_fit() {
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::LRU(sine_cache::config::CacheConfig { max_size: 100 }));
+    cache.set_weigher(|_, value: &String| value.len());
+    cache.set_max_memory(MemoryBudget::Bytes(10)).unwrap();
+    assert_eq!(cache.max_bytes(), Some(10));
+
+    cache.put(1, String::from("aaaa"));
+    cache.put(2, String::from("bbbb"));
+    cache.put(3, String::from("cccc"));
+    assert!(!cache.contains_key(&1));
+    assert_eq!(cache.estimated_bytes(), 8);
+
+    // overwriting with a smaller value frees room.
+    cache.put(2, String::from("b"));
+    cache.put(4, String::from("dddd"));
+    assert!(cache.contains_key(&2) && cache.contains_key(&3) && cache.contains_key(&4));
+    assert_eq!(cache.estimated_bytes(), 9);
+
+    // removed entries free room as well.
+    cache.remove(&3);
+    cache.put(5, String::from("eeee"));
+    assert_eq!(cache.size(), 3);
+
+    // an entry larger than the budget does not fit even alone, so it is evicted last.
+    cache.put(6, String::from("a value longer than the budget"));
+    assert!(cache.is_empty());
+    assert_eq!(cache.estimated_bytes(), 0);
+}
+
+#[test]
+fn test_max_memory_without_weigher_uses_inline_size() {
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::FIFO(sine_cache::config::CacheConfig { max_size: 100 }));
+    for i in 0..5 {
+        cache.put(i, String::from("a"));
+    }
+    let inline = std::mem::size_of::<u32>() + std::mem::size_of::<sine_cache::common::CacheEntry<String>>();
+    // existing entries are evicted right away.
+    cache.set_max_memory(MemoryBudget::Bytes(3 * inline as u64)).unwrap();
+    assert_eq!(cache.size(), 3);
+    assert!(!cache.contains_key(&0) && !cache.contains_key(&1));
+
+    cache.put(5, String::from("a"));
+    assert_eq!(cache.size(), 3);
+    assert!(!cache.contains_key(&2));
+}
+
+#[test]
+fn test_max_memory_percent_of_system() {
+    let Some(total) = sine_cache::common::total_system_memory() else {
+        return;
+    };
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::LRU(sine_cache::config::CacheConfig { max_size: 100 }));
+    cache.set_max_memory(MemoryBudget::PercentOfSystem(50.0)).unwrap();
+    assert_eq!(cache.max_bytes(), Some(total / 2));
+}
+
+#[test]
+fn test_max_memory_invalid_percent() {
+    assert_eq!(MemoryBudget::PercentOfSystem(150.0).resolve(), Err(ConfigError::InvalidMemoryPercent(150.0)));
+    let mut cache: Cache<u32, String> = Cache::new(CacheSyncConfig::LRU(sine_cache::config::CacheConfig { max_size: 100 }));
+    assert!(cache.set_max_memory(MemoryBudget::PercentOfSystem(0.0)).is_err());
+    assert_eq!(cache.max_bytes(), None);
+}